@@ -0,0 +1,60 @@
+// Shadow call stack built from observed call and return instructions
+// Measures call depth for stepping and gives backtraces for code without call frame information
+
+use crate::riscv::Instruction;
+use serde::{Deserialize, Serialize};
+
+/// One active call, recorded when its call instruction executed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackEntry {
+    /// Address of the call instruction in the caller.
+    pub call_site: u32,
+    /// Address the callee was entered at.
+    pub target: u32,
+    pub return_address: u32,
+    /// Caller registers just after the call. Callee-saved registers (sp, s0-s11)
+    /// hold these values again once the callee returns, so they are good enough
+    /// to locate the caller's variables.
+    pub registers: [u32; 32],
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CallStack {
+    entries: Vec<StackEntry>,
+}
+
+impl CallStack {
+    /// Updates the stack after `instruction` at `pc` executed and moved the PC to `next_pc`.
+    pub fn observe(&mut self, pc: u32, instruction: &Instruction, next_pc: u32, registers: &[u32; 32]) {
+        if instruction.is_call() {
+            self.entries.push(StackEntry {
+                call_site: pc,
+                target: next_pc,
+                return_address: pc.wrapping_add(4),
+                registers: *registers,
+            });
+        } else if instruction.is_return() {
+            // Unwind to the matching call. Returns that match nothing (for
+            // example into code reached by a tail call) leave the stack alone.
+            if let Some(index) = self.entries.iter().rposition(|entry| entry.return_address == next_pc) {
+                self.entries.truncate(index);
+            }
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// `pc` followed by the call site of every active call, innermost first.
+    pub fn frame_addresses(&self, pc: u32) -> Vec<u32> {
+        std::iter::once(pc)
+            .chain(self.entries.iter().rev().map(|entry| entry.call_site))
+            .collect()
+    }
+
+    /// Active calls, outermost first.
+    pub fn entries(&self) -> &[StackEntry] {
+        &self.entries
+    }
+}
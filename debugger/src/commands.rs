@@ -0,0 +1,551 @@
+// Debugger command interpreter
+// Shared by the interactive TUI console and other front-ends that accept typed commands
+
+use crate::debuginfo;
+use crate::emulator::{StopReason, MAX_MEMORY_READ};
+use crate::riscv::{Instruction, REGISTER_NAMES};
+use crate::expression::Expression;
+use crate::sessions::SessionStore;
+use crate::{Breakpoint, CallFrame, Checkpoint, PicoDebugger};
+use std::path::Path;
+
+pub const HELP: &[&str] = &[
+    "step, s              - Execute one instruction",
+    "next, n              - Step over calls",
+    "finish               - Run until the selected frame returns",
+    "continue, c          - Continue execution",
+    "break, b <addr> [if <expr>] - Set a breakpoint, optionally conditional",
+    "delete, d <addr>     - Remove breakpoint",
+    "condition <addr> [expr] - Set or clear a breakpoint condition",
+    "ignore <addr> <n>    - Pass the next n hits of a breakpoint",
+    "commands <addr> [cmd; ...] - Commands to run when a breakpoint stops",
+    "breakpoints          - List breakpoints with hit counts",
+    "print, p <expr>      - Evaluate e.g. a0 + 4, *(u8*)(sp + 8), cycle",
+    "set <reg|pc|*(uN*)addr> = <expr> - Write a register or memory",
+    "watch <expr> / unwatch <expr> - Add or remove a watch expression",
+    "watches              - Show the value of every watch expression",
+    "checkpoint save|restore|delete <name> - Snapshot or roll back the VM",
+    "checkpoint list      - List checkpoints",
+    "checkpoint write <name> <file> / load <file> - Store or read a checkpoint on disk",
+    "session save [checkpoint] - Save the session to resume with `sessions resume`",
+    "registers, r         - Show register state",
+    "backtrace, bt        - Show the call stack",
+    "frame, f [n]         - Select frame n or describe the selected frame",
+    "up [n] / down [n]    - Select an outer / inner frame",
+    "memory, m <addr> [n] - Show n memory words at address",
+    "list, l [addr]       - Show source around the selected frame or address",
+    "locals               - Show local variables of the selected frame",
+    "syscalls [n]         - Show the last n ecalls (default 20)",
+    "syscalls inputs      - Show how the input stream was read",
+    "syscalls public      - Show public-values writes and the committed stream",
+    "panic [n]            - Show the panic message, backtrace and last n instructions",
+    "quit, q              - Exit debugger",
+];
+
+/// Source lines shown either side of the current line by `list`.
+const LIST_CONTEXT: u32 = 5;
+
+/// Ecalls listed by `syscalls` without a count.
+const DEFAULT_SYSCALL_COUNT: usize = 20;
+
+/// Instructions listed by `panic` and after a failed run when no count is given.
+pub const DEFAULT_HISTORY: usize = 20;
+
+/// Lines of guest stderr shown in a diagnosis.
+const STDERR_TAIL: usize = 10;
+
+/// Result of executing one command line.
+#[derive(Debug, Default)]
+pub struct CommandOutcome {
+    pub lines: Vec<String>,
+    /// Address the front-end should show in its memory view, if the command asked for one.
+    pub view_address: Option<u32>,
+    pub quit: bool,
+}
+
+impl CommandOutcome {
+    fn lines(lines: Vec<String>) -> Self {
+        Self {
+            lines,
+            ..Default::default()
+        }
+    }
+}
+
+pub fn execute(debugger: &mut PicoDebugger, line: &str) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return Ok(CommandOutcome::default());
+    };
+    let args: Vec<&str> = words.collect();
+
+    if let Some(stop) = resume(debugger, command) {
+        return report_stop(debugger, stop?);
+    }
+
+    match command {
+        "backtrace" | "bt" | "where" => Ok(CommandOutcome::lines(backtrace(debugger)?)),
+        "frame" | "f" => {
+            if let Some(index) = args.first() {
+                debugger.select_frame(index.parse()?)?;
+            }
+            Ok(CommandOutcome::lines(vec![describe_frame(
+                debugger.selected_frame(),
+                &debugger.current_frame()?,
+            )]))
+        }
+        "up" | "down" => {
+            let count: usize = args.first().map(|count| count.parse()).transpose()?.unwrap_or(1);
+            let selected = debugger.selected_frame();
+            let index = if command == "up" {
+                selected + count
+            } else {
+                selected.checked_sub(count).ok_or("Bottom (innermost) frame selected; you cannot go down.")?
+            };
+            debugger.select_frame(index)?;
+            Ok(CommandOutcome::lines(vec![describe_frame(index, &debugger.current_frame()?)]))
+        }
+        "break" | "b" => {
+            let address = parse_address(args.first().ok_or("Usage: break <address> [if <condition>]")?)?;
+            let condition = match args.get(1) {
+                Some(&"if") => Some(args[2..].join(" ")),
+                Some(_) => return Err("Usage: break <address> [if <condition>]".into()),
+                None => None,
+            };
+            debugger.add_breakpoint(address as u64)?;
+            if condition.is_some() {
+                debugger.set_breakpoint_condition(address as u64, condition.as_deref())?;
+            }
+            Ok(CommandOutcome::lines(vec![format!("Added breakpoint at address: 0x{:x}", address)]))
+        }
+        "condition" => {
+            let address = parse_address(args.first().ok_or("Usage: condition <address> [expression]")?)?;
+            let condition = args[1..].join(" ");
+            let condition = (!condition.is_empty()).then_some(condition);
+            debugger.set_breakpoint_condition(address as u64, condition.as_deref())?;
+            Ok(CommandOutcome::lines(vec![match condition {
+                Some(condition) => format!("Breakpoint at 0x{:x} stops when {}", address, condition),
+                None => format!("Breakpoint at 0x{:x} is now unconditional", address),
+            }]))
+        }
+        "ignore" => {
+            let [address, count] = args[..] else {
+                return Err("Usage: ignore <address> <count>".into());
+            };
+            let address = parse_address(address)?;
+            let count = count.parse()?;
+            debugger.set_breakpoint_ignore_count(address as u64, count)?;
+            Ok(CommandOutcome::lines(vec![format!(
+                "Will ignore next {} hits of breakpoint at 0x{:x}",
+                count, address
+            )]))
+        }
+        "commands" => {
+            let address = parse_address(args.first().ok_or("Usage: commands <address> [command; command ...]")?)?;
+            let commands: Vec<String> = args[1..]
+                .join(" ")
+                .split(';')
+                .map(|command| command.trim().to_string())
+                .filter(|command| !command.is_empty())
+                .collect();
+            let summary = if commands.is_empty() {
+                format!("Cleared commands of breakpoint at 0x{:x}", address)
+            } else {
+                format!("Breakpoint at 0x{:x} will run: {}", address, commands.join("; "))
+            };
+            debugger.set_breakpoint_commands(address as u64, commands)?;
+            Ok(CommandOutcome::lines(vec![summary]))
+        }
+        "breakpoints" => {
+            let breakpoints = debugger.breakpoints();
+            let mut lines = Vec::new();
+            if breakpoints.is_empty() {
+                lines.push("No breakpoints".to_string());
+            }
+            for breakpoint in breakpoints {
+                lines.push(describe_breakpoint(debugger, breakpoint));
+            }
+            Ok(CommandOutcome::lines(lines))
+        }
+        "print" | "p" => {
+            let expression = Expression::parse(&args.join(" "))?;
+            let value = expression
+                .evaluate(debugger.emulator()?)
+                .ok_or("Division by zero")?;
+            Ok(CommandOutcome::lines(vec![format!("0x{:x} ({})", value, value)]))
+        }
+        "watch" => {
+            let expression = args.join(" ");
+            if expression.is_empty() {
+                return Err("Usage: watch <expression>".into());
+            }
+            debugger.add_watch(&expression)?;
+            Ok(CommandOutcome::lines(vec![format!("Watching {}", expression)]))
+        }
+        "unwatch" => {
+            let expression = args.join(" ");
+            debugger.remove_watch(&expression)?;
+            Ok(CommandOutcome::lines(vec![format!("No longer watching {}", expression)]))
+        }
+        "watches" => {
+            let session = debugger.session().ok_or("No active debugging session")?;
+            let emulator = debugger.emulator()?;
+            let mut lines = Vec::new();
+            if session.watch_variables.is_empty() {
+                lines.push("No watch expressions".to_string());
+            }
+            for watch in &session.watch_variables {
+                let value = Expression::parse(watch)?
+                    .evaluate(emulator)
+                    .map_or("division by zero".to_string(), |value| format!("0x{:x} ({})", value, value));
+                lines.push(format!("  {} = {}", watch, value));
+            }
+            Ok(CommandOutcome::lines(lines))
+        }
+        "session" => {
+            let checkpoint = match args[..] {
+                ["save"] => None,
+                ["save", checkpoint] => Some(checkpoint),
+                _ => return Err("Usage: session save [checkpoint]".into()),
+            };
+            let path = SessionStore::new(debugger.sessions_dir()).save(debugger, checkpoint)?;
+            let session_id = &debugger.session().ok_or("No active debugging session")?.session_id;
+            Ok(CommandOutcome::lines(vec![
+                format!("Saved session {} to {}", session_id, path.display()),
+                format!("Resume with: pico-debug sessions resume {}", session_id),
+            ]))
+        }
+        "delete" | "d" => {
+            let address = parse_address(args.first().ok_or("Usage: delete <address>")?)?;
+            debugger.remove_breakpoint(address as u64)?;
+            Ok(CommandOutcome::lines(vec![format!("Removed breakpoint at address: 0x{:x}", address)]))
+        }
+        "registers" | "r" => {
+            let emulator = debugger.emulator()?;
+            let mut lines = vec![format!("  pc:   0x{:08x}", emulator.pc)];
+            for (name, value) in REGISTER_NAMES.iter().zip(emulator.registers) {
+                lines.push(format!("  {:<5} 0x{:08x}", format!("{}:", name), value));
+            }
+            Ok(CommandOutcome::lines(lines))
+        }
+        "memory" | "m" | "x" => {
+            let address = parse_address(args.first().ok_or("Usage: memory <address> [count]")?)?;
+            let count = args.get(1).map(|count| count.parse::<u32>()).transpose()?.unwrap_or(1);
+            if count as usize > MAX_MEMORY_READ / 4 {
+                return Err(format!("Can read at most {} words at once", MAX_MEMORY_READ / 4).into());
+            }
+            let emulator = debugger.emulator()?;
+            let lines = (0..count)
+                .map(|i| {
+                    let word_address = address.wrapping_add(i * 4);
+                    format!("Memory[0x{:x}] = 0x{:08x}", word_address, emulator.memory.read_u32(word_address))
+                })
+                .collect();
+            Ok(CommandOutcome {
+                lines,
+                view_address: Some(address),
+                quit: false,
+            })
+        }
+        "list" | "l" => {
+            let pc = match args.first() {
+                Some(address) => parse_address(address)?,
+                None => debugger.current_frame()?.pc as u32,
+            };
+            let debug_info = debugger.debug_info().ok_or("Program has no debug information")?;
+            let location = debug_info
+                .location(pc)
+                .ok_or_else(|| format!("No source line information for PC 0x{:x}", pc))?;
+            let function = debug_info.function_at(pc).unwrap_or_else(|| "??".to_string());
+            let mut lines = vec![format!("{} in {}", location, function)];
+            for (line, text) in debuginfo::source_lines(&location, LIST_CONTEXT)? {
+                let marker = if line == location.line { "=>" } else { "  " };
+                lines.push(format!("{} {:>5}  {}", marker, line, text));
+            }
+            Ok(CommandOutcome::lines(lines))
+        }
+        "locals" => {
+            let frame = debugger.current_frame()?;
+            let mut variables: Vec<_> = frame.local_variables.into_iter().collect();
+            variables.sort();
+            let mut lines = vec![format!("Locals of {}:", frame.function_name)];
+            if variables.is_empty() {
+                lines.push("  (none described by debug info)".to_string());
+            }
+            for (name, value) in variables {
+                lines.push(format!("  {} = 0x{:x} ({})", name, value, value));
+            }
+            Ok(CommandOutcome::lines(lines))
+        }
+        "syscalls" => {
+            let io_log = debugger.io_log();
+            let debug_info = debugger.debug_info();
+            let lines = match args.first().copied() {
+                Some("inputs") => io_log.input_lines(debug_info),
+                Some("public") => io_log.public_values_lines(debug_info),
+                count => {
+                    let count = count.map(|count| count.parse()).transpose()?.unwrap_or(DEFAULT_SYSCALL_COUNT);
+                    io_log.syscall_lines(debug_info, Some(count))
+                }
+            };
+            Ok(CommandOutcome::lines(lines))
+        }
+        "panic" => {
+            let count = args.first().map(|count| count.parse()).transpose()?.unwrap_or(DEFAULT_HISTORY);
+            Ok(CommandOutcome::lines(diagnosis(debugger, count)?))
+        }
+        "set" => {
+            let text = args.join(" ");
+            let (target, value) = text.split_once('=').ok_or("Usage: set <register|pc|*(uN*)address> = <expression>")?;
+            let emulator = debugger.emulator()?;
+            let value = Expression::parse(value)?.evaluate(emulator).ok_or("Division by zero")?;
+            let description = match Expression::parse(target)? {
+                Expression::Pc => {
+                    debugger.set_register_value("pc", value.bits())?;
+                    format!("pc = 0x{:x}", value)
+                }
+                Expression::Register(index) => {
+                    debugger.set_register_value(REGISTER_NAMES[index], value.bits())?;
+                    format!("{} = 0x{:x}", REGISTER_NAMES[index], value)
+                }
+                Expression::Load { width, address, .. } => {
+                    let address = address.evaluate(emulator).ok_or("Division by zero")?.bits() as u32;
+                    if !value.fits(width) {
+                        return Err(format!("Value {} does not fit in {} bytes", value, width).into());
+                    }
+                    let bytes = value.bits().to_le_bytes();
+                    debugger.emulator_mut()?.memory.write_bytes(address, &bytes[..width as usize]);
+                    format!("Memory[0x{:x}] = 0x{:x} ({} bytes)", address, value, width)
+                }
+                _ => return Err("Can only set a register, pc or *(uN*)address".into()),
+            };
+            Ok(CommandOutcome::lines(vec![description]))
+        }
+        "checkpoint" => {
+            let usage = "Usage: checkpoint save|restore|delete <name>, checkpoint list, checkpoint write <name> <file>, checkpoint load <file>";
+            let lines = match args[..] {
+                ["save", name] => vec![format!("Saved {}", describe_checkpoint(debugger.save_checkpoint(name)?))],
+                ["restore", name] => vec![format!("Restored {}", describe_checkpoint(debugger.restore_checkpoint(name)?))],
+                ["delete", name] => {
+                    debugger.delete_checkpoint(name)?;
+                    vec![format!("Deleted checkpoint '{}'", name)]
+                }
+                ["list"] => {
+                    let mut lines: Vec<String> = debugger.checkpoints().map(describe_checkpoint).collect();
+                    if lines.is_empty() {
+                        lines.push("No checkpoints".to_string());
+                    }
+                    lines
+                }
+                ["write", name, path] => {
+                    let checkpoint = debugger.checkpoint(name).ok_or_else(|| format!("No checkpoint named '{}'", name))?;
+                    checkpoint.save(Path::new(path))?;
+                    vec![format!("Wrote checkpoint '{}' to {}", name, path)]
+                }
+                ["load", path] => {
+                    let checkpoint = Checkpoint::load(Path::new(path))?;
+                    vec![format!("Loaded {}", describe_checkpoint(debugger.import_checkpoint(checkpoint)?))]
+                }
+                _ => return Err(usage.into()),
+            };
+            Ok(CommandOutcome::lines(lines))
+        }
+        "help" | "h" => Ok(CommandOutcome::lines(HELP.iter().map(|line| line.to_string()).collect())),
+        "quit" | "q" => Ok(CommandOutcome {
+            lines: vec!["Exiting debugger...".to_string()],
+            view_address: None,
+            quit: true,
+        }),
+        _ => Err(format!("Unknown command: {}", command).into()),
+    }
+}
+
+/// Runs `command` if it resumes execution, returning None for any other command.
+fn resume(debugger: &mut PicoDebugger, command: &str) -> Option<Result<StopReason, Box<dyn std::error::Error>>> {
+    Some(match command {
+        "step" | "s" => debugger.step_instruction(),
+        "next" | "n" => debugger.step_over(),
+        "finish" => debugger.step_out(),
+        "continue" | "c" => debugger.continue_execution(),
+        _ => return None,
+    })
+}
+
+/// Describes a stop, adding a diagnosis when the guest panicked, trapped or
+/// exited with a failure code so the path to the fault is visible. A breakpoint
+/// stop runs the breakpoint's commands; if one of them resumes, the next stop
+/// is reported the same way.
+pub fn report_stop(debugger: &mut PicoDebugger, stop: StopReason) -> Result<CommandOutcome, Box<dyn std::error::Error>> {
+    let mut stop = stop;
+    let mut lines = Vec::new();
+    loop {
+        lines.push(describe_stop(debugger, &stop)?);
+        if is_failure(&stop) {
+            lines.extend(diagnosis(debugger, DEFAULT_HISTORY)?);
+        }
+        let StopReason::Breakpoint(address) = stop else {
+            break;
+        };
+        let commands = debugger
+            .breakpoint(address as u64)
+            .map(|breakpoint| breakpoint.commands.clone())
+            .unwrap_or_default();
+        let mut resumed = None;
+        for command in &commands {
+            let name = command.split_whitespace().next().unwrap_or_default();
+            if let Some(next) = resume(debugger, name) {
+                resumed = Some(next?);
+                break;
+            }
+            lines.extend(execute(debugger, command)?.lines);
+        }
+        match resumed {
+            Some(next) => stop = next,
+            None => break,
+        }
+    }
+    Ok(CommandOutcome::lines(lines))
+}
+
+/// `checkpoint 'name' at cycle N, PC 0x.. (created ...)`.
+fn describe_checkpoint(checkpoint: &Checkpoint) -> String {
+    format!(
+        "checkpoint '{}' at cycle {}, PC 0x{:08x} (taken {})",
+        checkpoint.name,
+        checkpoint.cycle(),
+        checkpoint.pc(),
+        checkpoint.created_at.format("%Y-%m-%d %H:%M:%S")
+    )
+}
+
+/// `0xADDR if condition, hit N times, ignore next N, then: commands`.
+fn describe_breakpoint(debugger: &PicoDebugger, breakpoint: &Breakpoint) -> String {
+    let mut text = debuginfo::describe_address(debugger.debug_info(), breakpoint.address as u32);
+    if let Some(condition) = &breakpoint.condition {
+        text.push_str(&format!(" if {}", condition));
+    }
+    text.push_str(&format!(", hit {} times", breakpoint.hit_count));
+    if breakpoint.ignore_count > 0 {
+        text.push_str(&format!(", ignore next {}", breakpoint.ignore_count));
+    }
+    if !breakpoint.commands.is_empty() {
+        text.push_str(&format!(", then: {}", breakpoint.commands.join("; ")));
+    }
+    text
+}
+
+pub fn is_failure(stop: &StopReason) -> bool {
+    matches!(stop, StopReason::Panicked | StopReason::Trapped(_)) || matches!(stop, StopReason::Halted(code) if *code != 0)
+}
+
+/// The panic message and location if the guest panicked, the tail of its
+/// stderr, a symbolised backtrace and the last `history` instructions executed.
+pub fn diagnosis(debugger: &PicoDebugger, history: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let emulator = debugger.emulator()?;
+    let debug_info = debugger.debug_info();
+    let monitor = debugger.panic_monitor();
+    let mut lines = Vec::new();
+
+    match monitor.report() {
+        Some(report) => {
+            lines.push(format!("Guest {} (cycle {})", report, report.cycle));
+            lines.push("Backtrace at panic:".to_string());
+            for (index, &address) in report.frames.iter().enumerate() {
+                lines.push(format!("  #{:<2} {}", index, debuginfo::describe_address(debug_info, address)));
+            }
+        }
+        None => {
+            lines.push("Backtrace:".to_string());
+            lines.extend(backtrace(debugger)?.into_iter().map(|line| format!(" {}", line)));
+        }
+    }
+
+    let stderr = String::from_utf8_lossy(&emulator.stderr);
+    let stderr: Vec<&str> = stderr.lines().collect();
+    if !stderr.is_empty() {
+        lines.push("Guest stderr:".to_string());
+        for line in &stderr[stderr.len().saturating_sub(STDERR_TAIL)..] {
+            lines.push(format!("  {}", line));
+        }
+    }
+
+    let recent: Vec<_> = monitor.recent_instructions(history).collect();
+    lines.push(format!("Last {} instructions:", recent.len()));
+    for executed in recent {
+        let mut line = format!(
+            "  {:>10}  0x{:08x}  {:<28}",
+            executed.cycle,
+            executed.pc,
+            Instruction::decode(executed.word).disassemble(executed.pc)
+        );
+        if let Some(function) = debug_info.and_then(|info| info.function_at(executed.pc)) {
+            line.push_str(&format!(" in {}", function));
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    Ok(lines)
+}
+
+pub fn backtrace(debugger: &PicoDebugger) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let selected = debugger.selected_frame();
+    Ok(debugger
+        .backtrace()?
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            let marker = if index == selected { "*" } else { " " };
+            format!("{}{}", marker, describe_frame(index, frame))
+        })
+        .collect())
+}
+
+/// `#N 0xPC in function (file:line) -> return address`, as printed by `bt` and `frame`.
+pub fn describe_frame(index: usize, frame: &CallFrame) -> String {
+    let mut text = format!("#{:<2} 0x{:08x} in {}", index, frame.pc, frame.function_name);
+    if let Some(location) = &frame.source_location {
+        text.push_str(&format!(" ({})", location));
+    }
+    if frame.return_address != 0 {
+        text.push_str(&format!(", returns to 0x{:08x}", frame.return_address));
+    }
+    text
+}
+
+/// One-line description of where and why execution stopped.
+pub fn describe_stop(debugger: &PicoDebugger, stop: &StopReason) -> Result<String, Box<dyn std::error::Error>> {
+    let emulator = debugger.emulator()?;
+    let pc = emulator.pc;
+    let mut location = format!(
+        "Cycle {}: {} at PC 0x{:x}",
+        emulator.cycle,
+        Instruction::decode(emulator.memory.read_u32(pc)).disassemble(pc),
+        pc
+    );
+    if let Some(debug_info) = debugger.debug_info() {
+        if let Some(function) = debug_info.function_at(pc) {
+            location.push_str(&format!(" in {}", function));
+        }
+        if let Some(source) = debug_info.location(pc) {
+            location.push_str(&format!(" ({})", source));
+        }
+    }
+    Ok(match stop {
+        StopReason::Halted(code) => format!("Program halted with exit code {}", code),
+        StopReason::Trapped(trap) => format!("Program trapped: {}", trap),
+        StopReason::Panicked => format!("Guest entered its panic handler. {}", location),
+        StopReason::Breakpoint(address) => {
+            let hits = debugger.breakpoint(*address as u64).map(|breakpoint| breakpoint.hit_count).unwrap_or(0);
+            format!("Breakpoint hit at PC 0x{:x} (hit {}). {}", address, hits, location)
+        }
+        StopReason::Stepped | StopReason::CycleLimit => location,
+    })
+}
+
+pub fn parse_address(text: &str) -> Result<u32, Box<dyn std::error::Error>> {
+    let text = text.trim();
+    let value = match text.strip_prefix("0x") {
+        Some(hex_digits) => u32::from_str_radix(hex_digits, 16)?,
+        None => text.parse()?,
+    };
+    Ok(value)
+}
@@ -0,0 +1,633 @@
+// Debug Adapter Protocol server
+// Speaks DAP over stdio so VS Code and other DAP clients can drive a PicoDebugger
+
+use crate::emulator::{StopReason, MAX_MEMORY_READ};
+use crate::expression::Expression;
+use crate::parse_inputs;
+use crate::riscv::{Instruction, REGISTER_NAMES};
+use crate::{PicoDebugger, RunGoal};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+const THREAD_ID: i64 = 1;
+const REGISTERS_REFERENCE: i64 = 1;
+const STACK_REFERENCE: i64 = 2;
+/// Locals of frame N use reference `LOCALS_REFERENCE + N`.
+const LOCALS_REFERENCE: i64 = 1000;
+/// Number of words shown in the stack scope, starting at sp.
+const STACK_WORDS: u32 = 32;
+/// Cycles executed between checks for requests (such as pause) while the guest runs.
+const RUN_SLICE_CYCLES: u64 = 200_000;
+
+/// Requests that start execution, which cannot be served while the guest is already running.
+const RESUME_REQUESTS: [&str; 5] = ["configurationDone", "continue", "next", "stepIn", "stepOut"];
+
+pub struct DapServer<W: Write> {
+    /// Messages read by a separate thread, so requests arrive while the guest runs.
+    requests: Receiver<Result<Value, String>>,
+    writer: W,
+    debugger: PicoDebugger,
+    seq: i64,
+    stop_on_entry: bool,
+    /// Where the run started by the current request stops, see [`RunGoal`].
+    goal: Option<RunGoal>,
+    instruction_breakpoints: Vec<u64>,
+    /// Addresses of the line breakpoints set in each source file.
+    source_breakpoints: HashMap<String, Vec<u64>>,
+    stdout_sent: usize,
+    stderr_sent: usize,
+}
+
+impl DapServer<io::Stdout> {
+    pub fn stdio() -> Self {
+        Self::new(io::BufReader::new(io::stdin()), io::stdout())
+    }
+}
+
+impl<W: Write> DapServer<W> {
+    pub fn new<R: BufRead + Send + 'static>(mut reader: R, writer: W) -> Self {
+        let (sender, requests) = mpsc::channel();
+        std::thread::spawn(move || {
+            while let Some(message) = read_message(&mut reader).map_err(|e| e.to_string()).transpose() {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            writer,
+            debugger: PicoDebugger::new(),
+            seq: 1,
+            stop_on_entry: false,
+            goal: None,
+            instruction_breakpoints: Vec::new(),
+            source_breakpoints: HashMap::new(),
+            stdout_sent: 0,
+            stderr_sent: 0,
+        }
+    }
+
+    /// Serves requests until the client disconnects.
+    pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        while let Ok(request) = self.requests.recv() {
+            if !self.dispatch(&request?)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers one request and runs any execution it starts. Returns false once the
+    /// client ends the session.
+    fn dispatch(&mut self, request: &Value) -> Result<bool, Box<dyn std::error::Error>> {
+        let command = request["command"].as_str().unwrap_or_default();
+        self.answer(request)?;
+        match command {
+            "launch" => self.send_event("initialized", Value::Null)?,
+            "configurationDone" if self.stop_on_entry => self.send_stopped("entry", None)?,
+            "disconnect" | "terminate" => return Ok(false),
+            _ => {}
+        }
+        match self.goal.take() {
+            Some(goal) => self.resume(goal),
+            None => Ok(true),
+        }
+    }
+
+    /// Sends the response to `request`.
+    fn answer(&mut self, request: &Value) -> io::Result<()> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = request.get("arguments").cloned().unwrap_or(Value::Null);
+        let request_seq = request["seq"].as_i64().unwrap_or(0);
+        match self.handle_request(command, &arguments) {
+            Ok(body) => self.respond(request_seq, command, true, None, body),
+            Err(e) => self.respond(request_seq, command, false, Some(e.to_string()), Value::Null),
+        }
+    }
+
+    fn handle_request(&mut self, command: &str, arguments: &Value) -> Result<Value, Box<dyn std::error::Error>> {
+        match command {
+            "initialize" => Ok(json!({
+                "supportsConditionalBreakpoints": true,
+                "supportsConfigurationDoneRequest": true,
+                "supportsHitConditionalBreakpoints": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsReadMemoryRequest": true,
+                "supportsSteppingGranularity": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => {
+                let program = arguments["program"]
+                    .as_str()
+                    .ok_or("launch requires a 'program' argument")?;
+                // "input" is one chunk or a list of chunks, as for --input on the command line
+                let inputs: Vec<String> = match &arguments["input"] {
+                    Value::Null => Vec::new(),
+                    Value::String(input) => vec![input.clone()],
+                    Value::Array(inputs) => inputs
+                        .iter()
+                        .map(|input| input.as_str().map(str::to_string).ok_or("launch 'input' entries must be strings"))
+                        .collect::<Result<_, _>>()?,
+                    _ => return Err("launch 'input' must be a string or a list of strings".into()),
+                };
+                self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+                self.debugger.start_session(PathBuf::from(program), parse_inputs(&inputs)?)?;
+                Ok(Value::Null)
+            }
+            "setBreakpoints" => {
+                let path = arguments["source"]["path"]
+                    .as_str()
+                    .ok_or("setBreakpoints requires a source path")?
+                    .to_string();
+                let previous = self.source_breakpoints.remove(&path).unwrap_or_default();
+                self.remove_breakpoints(previous)?;
+                let mut addresses = Vec::new();
+                let mut breakpoints = Vec::new();
+                for requested in arguments["breakpoints"].as_array().into_iter().flatten() {
+                    let line = requested["line"].as_u64().unwrap_or(0) as u32;
+                    // The line table gives one address per function that has code for the line.
+                    let Some((line, found)) = self.debugger.debug_info().and_then(|info| info.line_addresses(&path, line))
+                    else {
+                        breakpoints.push(json!({
+                            "verified": false,
+                            "message": format!("No code for {}:{} in the program's line table", path, line),
+                        }));
+                        continue;
+                    };
+                    let mut breakpoint = json!({ "verified": true, "line": line, "source": { "path": path } });
+                    for address in found {
+                        addresses.push(address as u64);
+                        if let Err(e) = self.add_breakpoint(address as u64, requested) {
+                            breakpoint["verified"] = json!(false);
+                            breakpoint["message"] = json!(e.to_string());
+                        }
+                    }
+                    breakpoints.push(breakpoint);
+                }
+                self.source_breakpoints.insert(path, addresses);
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "setInstructionBreakpoints" => {
+                let previous = std::mem::take(&mut self.instruction_breakpoints);
+                self.remove_breakpoints(previous)?;
+                let mut breakpoints = Vec::new();
+                for requested in arguments["breakpoints"].as_array().into_iter().flatten() {
+                    let reference = requested["instructionReference"].as_str().unwrap_or_default();
+                    let offset = requested["offset"].as_i64().unwrap_or(0);
+                    match parse_reference(reference) {
+                        Some(address) => {
+                            let address = (address as i64 + offset) as u64;
+                            self.instruction_breakpoints.push(address);
+                            match self.add_breakpoint(address, requested) {
+                                Ok(()) => breakpoints.push(json!({
+                                    "verified": true,
+                                    "instructionReference": format!("0x{:x}", address),
+                                })),
+                                Err(e) => breakpoints.push(json!({
+                                    "verified": false,
+                                    "instructionReference": format!("0x{:x}", address),
+                                    "message": e.to_string(),
+                                })),
+                            }
+                        }
+                        None => breakpoints.push(json!({
+                            "verified": false,
+                            "message": format!("Invalid instruction reference '{}'", reference),
+                        })),
+                    }
+                }
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+            "configurationDone" | "continue" => {
+                if command == "continue" || !self.stop_on_entry {
+                    self.goal = Some(RunGoal::Continue);
+                }
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                self.goal = Some(match arguments["granularity"].as_str() {
+                    Some("instruction") => self.debugger.step_over_goal()?,
+                    _ => self.debugger.step_line_goal(false)?,
+                });
+                Ok(Value::Null)
+            }
+            "stepIn" => {
+                self.goal = Some(match arguments["granularity"].as_str() {
+                    Some("instruction") => RunGoal::Step,
+                    _ => self.debugger.step_line_goal(true)?,
+                });
+                Ok(Value::Null)
+            }
+            "stepOut" => {
+                self.goal = Some(self.debugger.step_out_goal()?);
+                Ok(Value::Null)
+            }
+            // Pausing a running guest happens between run slices; a stopped guest stays stopped.
+            "pause" | "disconnect" | "terminate" => Ok(Value::Null),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "guest" }] })),
+            "stackTrace" => {
+                let emulator = self.debugger.emulator()?;
+                let frames = self.debugger.backtrace()?;
+                let stack_frames: Vec<Value> = frames
+                    .iter()
+                    .enumerate()
+                    .map(|(id, frame)| {
+                        let pc = frame.pc as u32;
+                        let instruction = Instruction::decode(emulator.memory.read_u32(pc));
+                        let mut stack_frame = json!({
+                            "id": id,
+                            "name": format!("{} (0x{:08x}: {})", frame.function_name, pc, instruction.disassemble(pc)),
+                            "line": 0,
+                            "column": 0,
+                            "instructionPointerReference": format!("0x{:x}", pc),
+                        });
+                        if let Some(location) = &frame.source_location {
+                            stack_frame["source"] = json!({ "path": location.file });
+                            stack_frame["line"] = json!(location.line);
+                            stack_frame["column"] = json!(location.column.unwrap_or(0));
+                        }
+                        stack_frame
+                    })
+                    .collect();
+                Ok(json!({
+                    "totalFrames": stack_frames.len(),
+                    "stackFrames": stack_frames,
+                }))
+            }
+            "scopes" => {
+                let frame_id = arguments["frameId"].as_i64().unwrap_or(0);
+                Ok(json!({
+                    "scopes": [
+                        { "name": "Locals", "variablesReference": LOCALS_REFERENCE + frame_id, "expensive": false },
+                        { "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+                        { "name": "Stack", "variablesReference": STACK_REFERENCE, "expensive": false },
+                    ]
+                }))
+            }
+            "variables" => {
+                let emulator = self.debugger.emulator()?;
+                let variables: Vec<Value> = match arguments["variablesReference"].as_i64() {
+                    Some(REGISTERS_REFERENCE) => std::iter::once(("pc".to_string(), emulator.pc))
+                        .chain(
+                            REGISTER_NAMES
+                                .iter()
+                                .zip(emulator.registers)
+                                .map(|(name, value)| (name.to_string(), value)),
+                        )
+                        .map(|(name, value)| variable(&name, value, None))
+                        .collect(),
+                    Some(STACK_REFERENCE) => {
+                        let stack_pointer = emulator.registers[2];
+                        (0..STACK_WORDS)
+                            .map(|i| {
+                                let address = stack_pointer.wrapping_add(i * 4);
+                                let value = emulator.memory.read_u32(address);
+                                variable(&format!("sp+0x{:x}", i * 4), value, Some(address))
+                            })
+                            .collect()
+                    }
+                    Some(reference) if reference >= LOCALS_REFERENCE => {
+                        let frame = self.debugger.frame((reference - LOCALS_REFERENCE) as usize)?;
+                        let mut locals: Vec<(String, u64)> = frame.local_variables.into_iter().collect();
+                        locals.sort();
+                        locals
+                            .into_iter()
+                            .map(|(name, value)| {
+                                json!({
+                                    "name": name,
+                                    "value": format!("0x{:x} ({})", value, value),
+                                    "variablesReference": 0,
+                                })
+                            })
+                            .collect()
+                    }
+                    _ => Vec::new(),
+                };
+                Ok(json!({ "variables": variables }))
+            }
+            "readMemory" => {
+                let reference = arguments["memoryReference"].as_str().unwrap_or_default();
+                let address = parse_reference(reference).ok_or("Invalid memory reference")?;
+                let address = (address as i64 + arguments["offset"].as_i64().unwrap_or(0)) as u32;
+                // Longer reads return a prefix; the client asks again from where the data ends
+                let count = (arguments["count"].as_u64().unwrap_or(0) as usize).min(MAX_MEMORY_READ);
+                let bytes = self.debugger.emulator()?.memory.read_bytes(address, count);
+                Ok(json!({
+                    "address": format!("0x{:x}", address),
+                    "data": base64_encode(&bytes),
+                }))
+            }
+            "evaluate" => {
+                let expression = arguments["expression"].as_str().unwrap_or_default().trim();
+                let value = Expression::parse(expression)?
+                    .evaluate(self.debugger.emulator()?)
+                    .ok_or("Division by zero")?;
+                Ok(json!({ "result": format!("0x{:08x} ({})", value, value), "variablesReference": 0 }))
+            }
+            other => Err(format!("Unsupported request '{}'", other).into()),
+        }
+    }
+
+    /// Runs toward `goal` in slices, serving the requests that arrive in between,
+    /// until execution stops. Returns false if the client ended the session meanwhile.
+    fn resume(&mut self, goal: RunGoal) -> Result<bool, Box<dyn std::error::Error>> {
+        loop {
+            let stop = self.debugger.run_to(&goal, Some(RUN_SLICE_CYCLES));
+            if !matches!(stop, Ok(StopReason::CycleLimit)) {
+                self.report_stop(stop)?;
+                return Ok(true);
+            }
+            self.flush_output()?;
+            loop {
+                let request = match self.requests.try_recv() {
+                    Ok(request) => request?,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(false),
+                };
+                let command = request["command"].as_str().unwrap_or_default();
+                if RESUME_REQUESTS.contains(&command) {
+                    let request_seq = request["seq"].as_i64().unwrap_or(0);
+                    self.respond(request_seq, command, false, Some("The guest is already running".to_string()), Value::Null)?;
+                    continue;
+                }
+                self.answer(&request)?;
+                match command {
+                    "pause" => {
+                        self.send_stopped("pause", None)?;
+                        return Ok(true);
+                    }
+                    "disconnect" | "terminate" => return Ok(false),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn report_stop(&mut self, stop: Result<StopReason, Box<dyn std::error::Error>>) -> Result<(), Box<dyn std::error::Error>> {
+        self.flush_output()?;
+        match stop? {
+            StopReason::Halted(code) => {
+                self.send_event("exited", json!({ "exitCode": code }))?;
+                self.send_event("terminated", Value::Null)?;
+            }
+            StopReason::Breakpoint(address) => {
+                let from_source = self.source_breakpoints.values().any(|addresses| addresses.contains(&(address as u64)));
+                self.send_stopped(if from_source { "breakpoint" } else { "instruction breakpoint" }, None)?
+            }
+            StopReason::Trapped(trap) => self.send_stopped("exception", Some(trap.to_string()))?,
+            StopReason::Panicked => {
+                let text = self.debugger.panic_monitor().report().map(|report| report.to_string());
+                self.send_stopped("exception", text)?
+            }
+            StopReason::Stepped | StopReason::CycleLimit => self.send_stopped("step", None)?,
+        }
+        Ok(())
+    }
+
+    /// Adds a breakpoint with the hit condition and condition of a DAP breakpoint request.
+    fn add_breakpoint(&mut self, address: u64, requested: &Value) -> Result<(), Box<dyn std::error::Error>> {
+        self.debugger.add_breakpoint(address)?;
+        // hitCondition N stops on the Nth hit.
+        let ignore_count = requested["hitCondition"]
+            .as_str()
+            .and_then(|hits| hits.trim().parse::<u64>().ok())
+            .map_or(0, |hits| hits.saturating_sub(1));
+        self.debugger.set_breakpoint_ignore_count(address, ignore_count)?;
+        self.debugger
+            .set_breakpoint_condition(address, requested["condition"].as_str().filter(|condition| !condition.trim().is_empty()))
+    }
+
+    /// Removes breakpoints that were replaced, keeping addresses another request still sets.
+    fn remove_breakpoints(&mut self, addresses: Vec<u64>) -> Result<(), Box<dyn std::error::Error>> {
+        for address in addresses {
+            let still_set = self.instruction_breakpoints.contains(&address)
+                || self.source_breakpoints.values().any(|addresses| addresses.contains(&address));
+            if !still_set {
+                self.debugger.remove_breakpoint(address)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_output(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let emulator = self.debugger.emulator()?;
+        let stdout = String::from_utf8_lossy(&emulator.stdout[self.stdout_sent..]).into_owned();
+        let stderr = String::from_utf8_lossy(&emulator.stderr[self.stderr_sent..]).into_owned();
+        self.stdout_sent = emulator.stdout.len();
+        self.stderr_sent = emulator.stderr.len();
+        if !stdout.is_empty() {
+            self.send_event("output", json!({ "category": "stdout", "output": stdout }))?;
+        }
+        if !stderr.is_empty() {
+            self.send_event("output", json!({ "category": "stderr", "output": stderr }))?;
+        }
+        Ok(())
+    }
+
+    fn send_stopped(&mut self, reason: &str, text: Option<String>) -> io::Result<()> {
+        self.send_event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+                "text": text,
+            }),
+        )
+    }
+
+    fn respond(
+        &mut self,
+        request_seq: i64,
+        command: &str,
+        success: bool,
+        message: Option<String>,
+        body: Value,
+    ) -> io::Result<()> {
+        let seq = self.next_seq();
+        self.write_message(&json!({
+            "seq": seq,
+            "type": "response",
+            "request_seq": request_seq,
+            "command": command,
+            "success": success,
+            "message": message,
+            "body": body,
+        }))
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        let seq = self.next_seq();
+        self.write_message(&json!({
+            "seq": seq,
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+
+    fn next_seq(&mut self) -> i64 {
+        let seq = self.seq;
+        self.seq += 1;
+        seq
+    }
+
+    fn write_message(&mut self, message: &Value) -> io::Result<()> {
+        let payload = message.to_string();
+        write!(self.writer, "Content-Length: {}\r\n\r\n{}", payload.len(), payload)?;
+        self.writer.flush()
+    }
+}
+
+fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Value>, Box<dyn std::error::Error>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(length) = line.strip_prefix("Content-Length:") {
+            content_length = Some(length.trim().parse::<usize>()?);
+        }
+    }
+    let length = content_length.ok_or("DAP message without Content-Length header")?;
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload)?;
+    Ok(Some(serde_json::from_slice(&payload)?))
+}
+
+fn variable(name: &str, value: u32, memory_reference: Option<u32>) -> Value {
+    json!({
+        "name": name,
+        "value": format!("0x{:08x}", value),
+        "type": "u32",
+        "variablesReference": 0,
+        "memoryReference": memory_reference.map(|address| format!("0x{:x}", address)),
+    })
+}
+
+fn parse_reference(reference: &str) -> Option<u32> {
+    let reference = reference.trim();
+    match reference.strip_prefix("0x") {
+        Some(hex_digits) => u32::from_str_radix(hex_digits, 16).ok(),
+        None => reference.parse().ok(),
+    }
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (chunk.get(1).copied().unwrap_or(0) as u32) << 8
+            | chunk.get(2).copied().unwrap_or(0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves `requests` in order and returns every message the server sent.
+    fn exchange(requests: &[(&str, Value)]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for (seq, (command, arguments)) in requests.iter().enumerate() {
+            let request = json!({ "seq": seq + 1, "type": "request", "command": command, "arguments": arguments }).to_string();
+            input.extend_from_slice(format!("Content-Length: {}\r\n\r\n{}", request.len(), request).as_bytes());
+        }
+        let mut server = DapServer::new(io::Cursor::new(input), Vec::new());
+        server.run().unwrap();
+        let mut output = io::Cursor::new(server.writer);
+        std::iter::from_fn(|| read_message(&mut output).unwrap()).collect()
+    }
+
+    fn launch() -> (&'static str, Value) {
+        let program = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/frames.elf");
+        ("launch", json!({ "program": program }))
+    }
+
+    fn events<'a>(messages: &'a [Value], event: &str) -> Vec<&'a Value> {
+        messages.iter().filter(|message| message["event"] == event).collect()
+    }
+
+    #[test]
+    fn source_breakpoints_resolve_through_the_line_table() {
+        let messages = exchange(&[
+            launch(),
+            ("setBreakpoints", json!({ "source": { "path": "tests/fixtures/frames.s" }, "breakpoints": [{ "line": 59 }, { "line": 14 }, { "line": 900 }] })),
+            ("configurationDone", Value::Null),
+            ("stackTrace", json!({ "threadId": THREAD_ID })),
+            ("next", json!({ "threadId": THREAD_ID })),
+            ("stackTrace", json!({ "threadId": THREAD_ID })),
+            ("next", json!({ "threadId": THREAD_ID })),
+            ("stackTrace", json!({ "threadId": THREAD_ID })),
+            ("disconnect", Value::Null),
+        ]);
+        let breakpoints = &messages.iter().find(|message| message["command"] == "setBreakpoints").unwrap()["body"]["breakpoints"];
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(breakpoints[0]["line"], 59);
+        // A label has no code of its own, so the breakpoint moves to the next line
+        assert_eq!(breakpoints[1]["line"], 15);
+        assert_eq!(breakpoints[2]["verified"], false);
+
+        assert_eq!(events(&messages, "stopped")[0]["body"]["reason"], "breakpoint");
+        let traces: Vec<&Value> = messages.iter().filter(|message| message["command"] == "stackTrace").collect();
+        let frames = &traces[0]["body"]["stackFrames"];
+        assert_eq!(frames[0]["line"], 59);
+        assert_eq!(frames[1]["line"], 47);
+        assert_eq!(frames.as_array().unwrap().len(), 4);
+        assert_eq!(traces[1]["body"]["stackFrames"][0]["line"], 60);
+        // Stepping a line from the last line of leaf returns into inner
+        assert_eq!(traces[2]["body"]["stackFrames"][0]["line"], 48);
+    }
+
+    #[test]
+    fn instruction_breakpoints_report_their_own_stop_reason() {
+        let messages = exchange(&[
+            launch(),
+            // leaf in frames.elf
+            ("setInstructionBreakpoints", json!({ "breakpoints": [{ "instructionReference": "0x20086c" }] })),
+            ("configurationDone", Value::Null),
+            ("disconnect", Value::Null),
+        ]);
+        let breakpoints = &messages.iter().find(|message| message["command"] == "setInstructionBreakpoints").unwrap()["body"]["breakpoints"];
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(events(&messages, "stopped")[0]["body"]["reason"], "instruction breakpoint");
+    }
+
+    #[test]
+    fn pause_stops_a_running_guest() {
+        let messages = exchange(&[
+            launch(),
+            ("configurationDone", Value::Null),
+            ("continue", Value::Null),
+            ("pause", Value::Null),
+            ("evaluate", json!({ "expression": "s1" })),
+            ("disconnect", Value::Null),
+        ]);
+        let continued = messages.iter().find(|message| message["command"] == "continue").unwrap();
+        assert_eq!(continued["success"], false);
+        assert_eq!(events(&messages, "stopped")[0]["body"]["reason"], "pause");
+        assert!(events(&messages, "exited").is_empty());
+        // The spin loop was interrupted part way through
+        let result = &messages.iter().find(|message| message["command"] == "evaluate").unwrap()["body"]["result"];
+        assert_ne!(result, "0x00000000 (0)");
+    }
+}
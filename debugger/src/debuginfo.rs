@@ -0,0 +1,548 @@
+// DWARF and symbol table lookups for guest programs
+// Maps program counters to functions, source lines and local variables
+
+use crate::emulator::Memory;
+use gimli::{EndianArcSlice, LittleEndian, Reader as _, Section, UnwindSection};
+use object::{Object, ObjectSection, ObjectSymbol, SymbolKind};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+type Reader = EndianArcSlice<LittleEndian>;
+
+/// A source position resolved from the DWARF line table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: Option<u32>,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/// One level of the (possibly inlined) function nesting at a PC, innermost first.
+#[derive(Debug, Clone)]
+pub struct SourceFrame {
+    pub function: Option<String>,
+    pub location: Option<SourceLocation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u32,
+    pub size: u32,
+}
+
+/// Where DWARF says a variable lives at a particular PC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableLocation {
+    Register(u16),
+    Memory(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalVariable {
+    pub name: String,
+    pub location: VariableLocation,
+    /// Size in bytes of the variable's type, when DWARF records one.
+    pub size: Option<u64>,
+}
+
+impl LocalVariable {
+    /// Reads the variable's current value. Values wider than 8 bytes are not
+    /// representable and yield `None`.
+    pub fn value(&self, registers: &[u32; 32], memory: &Memory) -> Option<u64> {
+        match self.location {
+            VariableLocation::Register(register) => registers.get(register as usize).map(|&value| value as u64),
+            VariableLocation::Memory(address) => {
+                let size = self.size.unwrap_or(4);
+                if size > 8 {
+                    return None;
+                }
+                let bytes = memory.read_bytes(address, size as usize);
+                Some(bytes.iter().rev().fold(0u64, |value, &byte| (value << 8) | byte as u64))
+            }
+        }
+    }
+}
+
+/// Debug information for one guest ELF. Programs built without `-g` still get
+/// function names from the symbol table.
+pub struct DebugInfo {
+    context: Option<addr2line::Context<Reader>>,
+    debug_frame: Option<gimli::DebugFrame<Reader>>,
+    eh_frame: Option<gimli::EhFrame<Reader>>,
+    bases: gimli::BaseAddresses,
+    symbols: Vec<Symbol>,
+    /// Data objects and address markers such as `_end`, by address.
+    data_symbols: Vec<Symbol>,
+}
+
+impl DebugInfo {
+    pub fn load(elf: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = object::File::parse(elf)?;
+        let section_data = |name: &str| -> Option<Reader> {
+            let section = file.section_by_name(name)?;
+            let data = section.uncompressed_data().ok()?;
+            Some(Reader::new(Arc::from(&*data), LittleEndian))
+        };
+
+        let dwarf = gimli::Dwarf::load(|id| -> Result<Reader, gimli::Error> {
+            Ok(section_data(id.name()).unwrap_or_else(|| Reader::new(Arc::from(&[][..]), LittleEndian)))
+        })?;
+        let context = if dwarf.debug_info.reader().is_empty() {
+            None
+        } else {
+            Some(addr2line::Context::from_dwarf(dwarf)?)
+        };
+
+        let address_size = if file.is_64() { 8 } else { 4 };
+        let debug_frame = section_data(".debug_frame").map(|data| {
+            let mut debug_frame = gimli::DebugFrame::from(data);
+            debug_frame.set_address_size(address_size);
+            debug_frame
+        });
+        let eh_frame = section_data(".eh_frame").map(|data| {
+            let mut eh_frame = gimli::EhFrame::from(data);
+            eh_frame.set_address_size(address_size);
+            eh_frame
+        });
+        let mut bases = gimli::BaseAddresses::default();
+        if let Some(section) = file.section_by_name(".eh_frame") {
+            bases = bases.set_eh_frame(section.address());
+        }
+        if let Some(section) = file.section_by_name(".text") {
+            bases = bases.set_text(section.address());
+        }
+
+        let mut symbols: Vec<Symbol> = file
+            .symbols()
+            .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.address() != 0)
+            .filter_map(|symbol| {
+                Some(Symbol {
+                    name: demangle(symbol.name().ok()?),
+                    address: symbol.address() as u32,
+                    size: symbol.size() as u32,
+                })
+            })
+            .collect();
+        symbols.sort_by_key(|symbol| symbol.address);
+        symbols.dedup_by_key(|symbol| symbol.address);
+
+        let mut data_symbols: Vec<Symbol> = file
+            .symbols()
+            .filter(|symbol| matches!(symbol.kind(), SymbolKind::Data | SymbolKind::Unknown) && symbol.address() != 0)
+            .filter_map(|symbol| {
+                Some(Symbol {
+                    name: demangle(symbol.name().ok().filter(|name| !name.is_empty())?),
+                    address: symbol.address() as u32,
+                    size: symbol.size() as u32,
+                })
+            })
+            .collect();
+        data_symbols.sort_by_key(|symbol| symbol.address);
+
+        Ok(Self {
+            context,
+            debug_frame,
+            eh_frame,
+            bases,
+            symbols,
+            data_symbols,
+        })
+    }
+
+    pub fn has_dwarf(&self) -> bool {
+        self.context.is_some()
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// The symbol table entry covering `pc`.
+    pub fn symbol_at(&self, pc: u32) -> Option<&Symbol> {
+        let index = self.symbols.partition_point(|symbol| symbol.address <= pc).checked_sub(1)?;
+        let symbol = &self.symbols[index];
+        if symbol.size == 0 || pc - symbol.address < symbol.size {
+            Some(symbol)
+        } else {
+            None
+        }
+    }
+
+    pub fn data_symbols(&self) -> &[Symbol] {
+        &self.data_symbols
+    }
+
+    /// The sized data object covering `address`.
+    pub fn data_symbol_at(&self, address: u32) -> Option<&Symbol> {
+        let end = self.data_symbols.partition_point(|symbol| symbol.address <= address);
+        let symbol = self.data_symbols[..end].iter().rev().find(|symbol| symbol.size != 0)?;
+        (address - symbol.address < symbol.size).then_some(symbol)
+    }
+
+    /// Address of a data symbol, matching `name` exactly or as the last path segment.
+    pub fn data_symbol_address(&self, name: &str) -> Option<u32> {
+        self.data_symbols
+            .iter()
+            .find(|symbol| symbol.name == name || symbol.name.rsplit("::").next() == Some(name))
+            .map(|symbol| symbol.address)
+    }
+
+    pub fn symbol_address(&self, name: &str) -> Option<u32> {
+        self.symbols.iter().find(|symbol| symbol.name == name).map(|symbol| symbol.address)
+    }
+
+    /// Name of the innermost function at `pc`, including inlined functions.
+    pub fn function_at(&self, pc: u32) -> Option<String> {
+        self.frames(pc)
+            .into_iter()
+            .find_map(|frame| frame.function)
+            .or_else(|| self.symbol_at(pc).map(|symbol| symbol.name.clone()))
+    }
+
+    pub fn location(&self, pc: u32) -> Option<SourceLocation> {
+        let location = self.context.as_ref()?.find_location(pc as u64).ok()??;
+        source_location(&location)
+    }
+
+    /// Function and source position for every inlining level at `pc`, innermost first.
+    pub fn frames(&self, pc: u32) -> Vec<SourceFrame> {
+        let mut frames = Vec::new();
+        let Some(context) = &self.context else {
+            return frames;
+        };
+        let Ok(mut iter) = context.find_frames(pc as u64).skip_all_loads() else {
+            return frames;
+        };
+        while let Ok(Some(frame)) = iter.next() {
+            frames.push(SourceFrame {
+                function: frame
+                    .function
+                    .as_ref()
+                    .and_then(|function| function.raw_name().ok().map(|name| demangle(&name))),
+                location: frame.location.as_ref().and_then(source_location),
+            });
+        }
+        frames
+    }
+
+    /// Distinct source lines covered by the named function, in ascending order.
+    pub fn function_lines(&self, name: &str) -> Vec<u32> {
+        let (Some(context), Some(symbol)) = (&self.context, self.symbols.iter().find(|symbol| symbol.name == name))
+        else {
+            return Vec::new();
+        };
+        let end = symbol.address as u64 + symbol.size.max(1) as u64;
+        let mut lines: Vec<u32> = match context.find_location_range(symbol.address as u64, end) {
+            Ok(ranges) => ranges.filter_map(|(_, _, location)| location.line).collect(),
+            Err(_) => Vec::new(),
+        };
+        lines.sort_unstable();
+        lines.dedup();
+        lines
+    }
+
+    /// Breakpoint addresses for `line` of `file`: where each function with code for the
+    /// line first reaches it. A line without code moves to the next one that has some, as
+    /// in gdb, so the line actually used is returned alongside the addresses. `file` may be
+    /// a suffix of the path in the line table or the other way round.
+    pub fn line_addresses(&self, file: &str, line: u32) -> Option<(u32, Vec<u32>)> {
+        let ranges = self.context.as_ref()?.find_location_range(0, u64::MAX).ok()?;
+        let rows: Vec<(u32, u32)> = ranges
+            .filter(|(_, _, location)| location.file.is_some_and(|path| same_file(path, file)))
+            .filter_map(|(address, _, location)| Some((location.line.filter(|&found| found >= line)?, address as u32)))
+            .collect();
+        let line = rows.iter().map(|&(found, _)| found).min()?;
+        let mut addresses: Vec<u32> = rows.into_iter().filter(|&(found, _)| found == line).map(|(_, address)| address).collect();
+        addresses.sort_unstable();
+        let mut functions = std::collections::HashSet::new();
+        addresses.retain(|&address| functions.insert(self.symbol_at(address).map_or(address, |symbol| symbol.address)));
+        Some((line, addresses))
+    }
+
+    /// Canonical frame address at `pc` computed from the call frame information.
+    pub fn canonical_frame_address(&self, pc: u32, registers: &[u32; 32]) -> Option<u32> {
+        match self.unwind_row(pc)?.cfa() {
+            gimli::CfaRule::RegisterAndOffset { register, offset } => {
+                let base = *registers.get(register.0 as usize)?;
+                Some((base as i64).wrapping_add(*offset) as u32)
+            }
+            gimli::CfaRule::Expression(_) => None,
+        }
+    }
+
+    /// Registers of the caller of the frame at `pc`, recovered from the call frame
+    /// information: sp becomes the canonical frame address and every register the
+    /// CFI saved is reloaded. The caller resumes at the returned `ra`.
+    pub fn unwind(&self, pc: u32, registers: &[u32; 32], memory: &Memory) -> Option<[u32; 32]> {
+        let row = self.unwind_row(pc)?;
+        let cfa = match row.cfa() {
+            gimli::CfaRule::RegisterAndOffset { register, offset } => {
+                (*registers.get(register.0 as usize)? as i64).wrapping_add(*offset) as u32
+            }
+            gimli::CfaRule::Expression(_) => return None,
+        };
+        let mut caller = *registers;
+        for (register, rule) in row.registers() {
+            let value = match rule {
+                gimli::RegisterRule::SameValue => continue,
+                gimli::RegisterRule::Offset(offset) => memory.read_u32((cfa as i64).wrapping_add(*offset) as u32),
+                gimli::RegisterRule::ValOffset(offset) => (cfa as i64).wrapping_add(*offset) as u32,
+                gimli::RegisterRule::Register(other) => *registers.get(other.0 as usize)?,
+                _ => return None,
+            };
+            *caller.get_mut(register.0 as usize)? = value;
+        }
+        caller[2] = cfa;
+        Some(caller)
+    }
+
+    fn unwind_row(&self, pc: u32) -> Option<gimli::UnwindTableRow<usize>> {
+        let mut context = gimli::UnwindContext::new();
+        if let Some(debug_frame) = &self.debug_frame {
+            if let Ok(row) = debug_frame.unwind_info_for_address(
+                &self.bases,
+                &mut context,
+                pc as u64,
+                gimli::DebugFrame::cie_from_offset,
+            ) {
+                return Some(row.clone());
+            }
+        }
+        let eh_frame = self.eh_frame.as_ref()?;
+        eh_frame
+            .unwind_info_for_address(&self.bases, &mut context, pc as u64, gimli::EhFrame::cie_from_offset)
+            .ok()
+            .cloned()
+    }
+
+    /// Parameters and locals visible at `pc` whose location DWARF describes.
+    /// Inner scopes shadow outer ones, so a name appears at most once.
+    pub fn local_variables(&self, pc: u32, registers: &[u32; 32], memory: &Memory) -> Vec<LocalVariable> {
+        let Some(context) = &self.context else {
+            return Vec::new();
+        };
+        let Some(unit) = context.find_dwarf_and_unit(pc as u64).skip_all_loads() else {
+            return Vec::new();
+        };
+        let Ok(mut tree) = unit.entries_tree(None) else {
+            return Vec::new();
+        };
+        let Ok(root) = tree.root() else {
+            return Vec::new();
+        };
+
+        let scope = Scope {
+            debug_info: self,
+            unit,
+            pc,
+            registers,
+            memory,
+        };
+        let mut variables: Vec<LocalVariable> = Vec::new();
+        // Errors part way through leave whatever was collected so far, which is
+        // more useful to a debugger user than nothing.
+        let _ = scope.visit(root, None, &mut variables);
+        variables
+    }
+}
+
+/// State shared while walking the DIE tree for [`DebugInfo::local_variables`].
+struct Scope<'a> {
+    debug_info: &'a DebugInfo,
+    unit: gimli::UnitRef<'a, Reader>,
+    pc: u32,
+    registers: &'a [u32; 32],
+    memory: &'a Memory,
+}
+
+impl Scope<'_> {
+    fn visit(
+        &self,
+        node: gimli::EntriesTreeNode<'_, '_, '_, Reader>,
+        frame_base: Option<u64>,
+        variables: &mut Vec<LocalVariable>,
+    ) -> Result<(), gimli::Error> {
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            match entry.tag() {
+                gimli::DW_TAG_namespace | gimli::DW_TAG_compile_unit => self.visit(child, frame_base, variables)?,
+                gimli::DW_TAG_subprogram | gimli::DW_TAG_lexical_block | gimli::DW_TAG_inlined_subroutine => {
+                    if !self.contains_pc(entry)? {
+                        continue;
+                    }
+                    let frame_base = match entry.attr_value(gimli::DW_AT_frame_base)? {
+                        Some(gimli::AttributeValue::Exprloc(expression)) => self.frame_base(expression).or(frame_base),
+                        _ => frame_base,
+                    };
+                    self.visit(child, frame_base, variables)?;
+                }
+                gimli::DW_TAG_variable | gimli::DW_TAG_formal_parameter if frame_base.is_some() => {
+                    if let Some(variable) = self.variable(entry, frame_base)? {
+                        variables.retain(|existing| existing.name != variable.name);
+                        variables.push(variable);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn contains_pc(&self, entry: &gimli::DebuggingInformationEntry<'_, '_, Reader>) -> Result<bool, gimli::Error> {
+        let mut ranges = self.unit.die_ranges(entry)?;
+        while let Some(range) = ranges.next()? {
+            if (range.begin..range.end).contains(&(self.pc as u64)) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn variable(
+        &self,
+        entry: &gimli::DebuggingInformationEntry<'_, '_, Reader>,
+        frame_base: Option<u64>,
+    ) -> Result<Option<LocalVariable>, gimli::Error> {
+        let Some(name) = entry.attr_value(gimli::DW_AT_name)? else {
+            return Ok(None);
+        };
+        let name = self.unit.attr_string(name)?.to_string_lossy()?.into_owned();
+        let Some(location) = entry.attr_value(gimli::DW_AT_location)? else {
+            return Ok(None);
+        };
+        let expression = match location {
+            gimli::AttributeValue::Exprloc(expression) => Some(expression),
+            other => {
+                let mut expression = None;
+                if let Some(mut locations) = self.unit.attr_locations(other)? {
+                    while let Some(entry) = locations.next()? {
+                        if (entry.range.begin..entry.range.end).contains(&(self.pc as u64)) {
+                            expression = Some(entry.data);
+                            break;
+                        }
+                    }
+                }
+                expression
+            }
+        };
+        let Some(location) = expression.and_then(|expression| self.evaluate(expression, frame_base)) else {
+            return Ok(None);
+        };
+        let size = match entry.attr_value(gimli::DW_AT_type)? {
+            Some(gimli::AttributeValue::UnitRef(offset)) => self.type_size(offset)?,
+            _ => None,
+        };
+        Ok(Some(LocalVariable { name, location, size }))
+    }
+
+    /// Byte size of a type, looking through typedefs and qualifiers.
+    fn type_size(&self, mut offset: gimli::UnitOffset<usize>) -> Result<Option<u64>, gimli::Error> {
+        for _ in 0..16 {
+            let entry = self.unit.entry(offset)?;
+            if let Some(size) = entry.attr_value(gimli::DW_AT_byte_size)?.and_then(|value| value.udata_value()) {
+                return Ok(Some(size));
+            }
+            match entry.attr_value(gimli::DW_AT_type)? {
+                Some(gimli::AttributeValue::UnitRef(next)) => offset = next,
+                _ => return Ok(None),
+            }
+        }
+        Ok(None)
+    }
+
+    fn frame_base(&self, expression: gimli::Expression<Reader>) -> Option<u64> {
+        match self.evaluate(expression, None)? {
+            VariableLocation::Register(register) => self.registers.get(register as usize).map(|&value| value as u64),
+            VariableLocation::Memory(address) => Some(address as u64),
+        }
+    }
+
+    fn evaluate(&self, expression: gimli::Expression<Reader>, frame_base: Option<u64>) -> Option<VariableLocation> {
+        let mut evaluation = expression.evaluation(self.unit.encoding());
+        let mut result = evaluation.evaluate().ok()?;
+        loop {
+            result = match result {
+                gimli::EvaluationResult::Complete => break,
+                gimli::EvaluationResult::RequiresRegister { register, .. } => {
+                    let value = *self.registers.get(register.0 as usize)?;
+                    evaluation.resume_with_register(gimli::Value::Generic(value as u64)).ok()?
+                }
+                gimli::EvaluationResult::RequiresFrameBase => evaluation.resume_with_frame_base(frame_base?).ok()?,
+                gimli::EvaluationResult::RequiresCallFrameCfa => {
+                    let cfa = self.debug_info.canonical_frame_address(self.pc, self.registers)?;
+                    evaluation.resume_with_call_frame_cfa(cfa as u64).ok()?
+                }
+                gimli::EvaluationResult::RequiresMemory { address, size, .. } => {
+                    let bytes = self.memory.read_bytes(address as u32, size as usize);
+                    let value = bytes.iter().rev().fold(0u64, |value, &byte| (value << 8) | byte as u64);
+                    evaluation.resume_with_memory(gimli::Value::Generic(value)).ok()?
+                }
+                gimli::EvaluationResult::RequiresRelocatedAddress(address) => {
+                    evaluation.resume_with_relocated_address(address).ok()?
+                }
+                _ => return None,
+            };
+        }
+        match evaluation.result().first()?.location {
+            gimli::Location::Register { register } => Some(VariableLocation::Register(register.0)),
+            gimli::Location::Address { address } => Some(VariableLocation::Memory(address as u32)),
+            _ => None,
+        }
+    }
+}
+
+fn source_location(location: &addr2line::Location<'_>) -> Option<SourceLocation> {
+    Some(SourceLocation {
+        file: location.file?.to_string(),
+        line: location.line?,
+        column: location.column,
+    })
+}
+
+fn same_file(line_table_path: &str, requested: &str) -> bool {
+    let (line_table_path, requested) = (std::path::Path::new(line_table_path), std::path::Path::new(requested));
+    line_table_path.ends_with(requested) || requested.ends_with(line_table_path)
+}
+
+/// `0x00200830 in function (file:line)`, with as much as `debug_info` can tell.
+pub fn describe_address(debug_info: Option<&DebugInfo>, address: u32) -> String {
+    let mut text = format!("0x{:08x}", address);
+    if let Some(function) = debug_info.and_then(|info| info.function_at(address)) {
+        text.push_str(&format!(" in {}", function));
+    }
+    if let Some(location) = debug_info.and_then(|info| info.location(address)) {
+        text.push_str(&format!(" ({})", location));
+    }
+    text
+}
+
+/// Demangles a Rust symbol, dropping the trailing hash; other names pass through.
+pub fn demangle(name: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(name))
+}
+
+/// Reads `context` lines either side of `location` from the source file on disk.
+pub fn source_lines(
+    location: &SourceLocation,
+    context: u32,
+) -> Result<Vec<(u32, String)>, Box<dyn std::error::Error>> {
+    let source = std::fs::read_to_string(&location.file)
+        .map_err(|e| format!("cannot read source file {}: {}", location.file, e))?;
+    let first = location.line.saturating_sub(context).max(1);
+    let last = location.line.saturating_add(context);
+    Ok(source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| (index as u32 + 1, text.to_string()))
+        .filter(|(line, _)| (first..=last).contains(line))
+        .collect())
+}
@@ -0,0 +1,286 @@
+// Differential execution
+// Runs two programs in lockstep and reports the first cycle where they disagree
+
+use crate::callstack::CallStack;
+use crate::debuginfo::{self, DebugInfo};
+use crate::emulator::{Emulator, Program, StepEvent, StopReason, Trap};
+use crate::riscv::{Instruction, REGISTER_NAMES};
+use crate::MemoryAccessType;
+use std::collections::VecDeque;
+
+/// Instructions of each run kept for context.
+const HISTORY_LENGTH: usize = 64;
+
+/// One side of a comparison: an emulator plus what is needed to symbolise it.
+pub struct DiffRun {
+    pub label: String,
+    pub emulator: Emulator,
+    debug_info: Option<DebugInfo>,
+    call_stack: CallStack,
+    /// (cycle, pc, instruction word) of the most recent steps.
+    history: VecDeque<(u64, u32, u32)>,
+}
+
+impl DiffRun {
+    pub fn new(label: String, program: &Program, inputs: Vec<Vec<u8>>) -> Self {
+        Self {
+            label,
+            emulator: Emulator::new(program, inputs),
+            // Missing or malformed debug info only costs symbolisation.
+            debug_info: DebugInfo::load(&program.elf).ok(),
+            call_stack: CallStack::default(),
+            history: VecDeque::with_capacity(HISTORY_LENGTH),
+        }
+    }
+
+    fn step(&mut self) -> Result<StepEvent, Trap> {
+        let event = self.emulator.step()?;
+        self.call_stack
+            .observe(event.pc, &event.instruction, event.next_pc, &self.emulator.registers);
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back((event.cycle, event.pc, event.word));
+        Ok(event)
+    }
+
+    /// The last `count` instructions, the next PC and the backtrace.
+    pub fn context_lines(&self, count: usize) -> Vec<String> {
+        let debug_info = self.debug_info.as_ref();
+        let mut lines = vec![format!("{}:", self.label)];
+        for &(cycle, pc, word) in self.history.iter().skip(self.history.len().saturating_sub(count)) {
+            lines.push(format!(
+                "  {:>10}  {:<28} at {}",
+                cycle,
+                Instruction::decode(word).disassemble(pc),
+                debuginfo::describe_address(debug_info, pc)
+            ));
+        }
+        lines.push("  backtrace:".to_string());
+        for (index, &address) in self.call_stack.frame_addresses(self.emulator.pc).iter().enumerate() {
+            lines.push(format!("    #{:<2} {}", index, debuginfo::describe_address(debug_info, address)));
+        }
+        lines
+    }
+}
+
+/// How two runs first disagreed.
+#[derive(Debug, Clone)]
+pub enum Difference {
+    /// The runs are about to execute different instructions.
+    Pc { a: u32, b: u32 },
+    /// Registers that differ after the step, as (index, a, b).
+    Registers(Vec<(usize, u32, u32)>),
+    /// The step wrote different (address, value) pairs.
+    MemoryWrites { a: Vec<(u32, u32)>, b: Vec<(u32, u32)> },
+    /// One run stopped and the other did not, or they stopped differently.
+    Stop { a: String, b: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub cycle: u64,
+    pub difference: Difference,
+}
+
+impl Divergence {
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Runs diverge at cycle {}", self.cycle)];
+        match &self.difference {
+            Difference::Pc { a, b } => lines.push(format!("  next PC: a 0x{:08x}, b 0x{:08x}", a, b)),
+            Difference::Registers(registers) => {
+                for &(index, a, b) in registers {
+                    lines.push(format!("  {:<4} a 0x{:08x}, b 0x{:08x}", REGISTER_NAMES[index], a, b));
+                }
+            }
+            Difference::MemoryWrites { a, b } => {
+                let describe = |writes: &[(u32, u32)]| {
+                    if writes.is_empty() {
+                        return "no writes".to_string();
+                    }
+                    writes
+                        .iter()
+                        .map(|(address, value)| format!("[0x{:08x}] = 0x{:x}", address, value))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                lines.push(format!("  memory writes: a {}", describe(a)));
+                lines.push(format!("                 b {}", describe(b)));
+            }
+            Difference::Stop { a, b } => {
+                lines.push(format!("  a: {}", a));
+                lines.push(format!("  b: {}", b));
+            }
+        }
+        lines
+    }
+}
+
+/// Result of a lockstep run.
+#[derive(Debug, Clone)]
+pub enum DiffOutcome {
+    Diverged(Divergence),
+    /// Both runs agreed until they stopped the same way.
+    Identical(StopReason),
+}
+
+/// Steps both runs together until they disagree, both stop, or `max_cycles` elapse.
+pub fn run_lockstep(a: &mut DiffRun, b: &mut DiffRun, max_cycles: Option<u64>) -> DiffOutcome {
+    loop {
+        let cycle = a.emulator.cycle;
+        if max_cycles.is_some_and(|max| cycle >= max) {
+            return DiffOutcome::Identical(StopReason::CycleLimit);
+        }
+        if a.emulator.pc != b.emulator.pc {
+            return DiffOutcome::Diverged(Divergence {
+                cycle,
+                difference: Difference::Pc {
+                    a: a.emulator.pc,
+                    b: b.emulator.pc,
+                },
+            });
+        }
+
+        let difference = match (a.step(), b.step()) {
+            (Ok(event_a), Ok(event_b)) => {
+                let writes_a = memory_writes(&event_a);
+                let writes_b = memory_writes(&event_b);
+                let registers: Vec<(usize, u32, u32)> = (0..32)
+                    .filter(|&index| a.emulator.registers[index] != b.emulator.registers[index])
+                    .map(|index| (index, a.emulator.registers[index], b.emulator.registers[index]))
+                    .collect();
+                if writes_a != writes_b {
+                    Some(Difference::MemoryWrites { a: writes_a, b: writes_b })
+                } else if !registers.is_empty() {
+                    Some(Difference::Registers(registers))
+                } else {
+                    match (a.emulator.exit_code(), b.emulator.exit_code()) {
+                        (Some(code_a), Some(code_b)) if code_a == code_b => {
+                            return DiffOutcome::Identical(StopReason::Halted(code_a))
+                        }
+                        (None, None) => None,
+                        (code_a, code_b) => Some(Difference::Stop {
+                            a: describe_exit(code_a),
+                            b: describe_exit(code_b),
+                        }),
+                    }
+                }
+            }
+            (Err(trap_a), Err(trap_b)) if trap_a == trap_b => return DiffOutcome::Identical(StopReason::Trapped(trap_a)),
+            (result_a, result_b) => Some(Difference::Stop {
+                a: describe_step(&result_a, &a.emulator),
+                b: describe_step(&result_b, &b.emulator),
+            }),
+        };
+
+        if let Some(difference) = difference {
+            return DiffOutcome::Diverged(Divergence { cycle, difference });
+        }
+    }
+}
+
+fn memory_writes(event: &StepEvent) -> Vec<(u32, u32)> {
+    event
+        .memory
+        .iter()
+        .filter(|access| access.access_type == MemoryAccessType::Write)
+        .map(|access| (access.address as u32, access.value as u32))
+        .collect()
+}
+
+fn describe_exit(code: Option<u32>) -> String {
+    match code {
+        Some(code) => format!("halted with exit code {}", code),
+        None => "still running".to_string(),
+    }
+}
+
+fn describe_step(result: &Result<StepEvent, Trap>, emulator: &Emulator) -> String {
+    match result {
+        Ok(_) => describe_exit(emulator.exit_code()),
+        Err(trap) => format!("trapped: {}", trap),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::Segment;
+
+    /// addi a0, zero, 1; addi a1, zero, 2; sw a1, 0x100(zero); addi t0, zero, 0; addi a0, zero, 0; ecall
+    const BASE: [u32; 6] = [0x0010_0513, 0x0020_0593, 0x10b0_2023, 0x0000_0293, 0x0000_0513, 0x0000_0073];
+
+    fn run(label: &str, code: &[u32]) -> DiffRun {
+        let data: Vec<u8> = code.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let program = Program {
+            entry: 0,
+            segments: vec![Segment { vaddr: 0, mem_size: data.len() as u32, data, flags: 5 }],
+            elf: Vec::new(),
+        };
+        DiffRun::new(label.to_string(), &program, Vec::new())
+    }
+
+    /// Runs BASE against a copy with `word` at `index`.
+    fn diverge(index: usize, word: u32) -> Divergence {
+        let mut b = BASE;
+        b[index] = word;
+        match run_lockstep(&mut run("a", &BASE), &mut run("b", &b), Some(100)) {
+            DiffOutcome::Diverged(divergence) => divergence,
+            DiffOutcome::Identical(stop) => panic!("runs did not diverge: {:?}", stop),
+        }
+    }
+
+    #[test]
+    fn identical_runs_stop_the_same_way() {
+        let outcome = run_lockstep(&mut run("a", &BASE), &mut run("b", &BASE), None);
+        assert!(matches!(outcome, DiffOutcome::Identical(StopReason::Halted(0))));
+        let outcome = run_lockstep(&mut run("a", &BASE), &mut run("b", &BASE), Some(2));
+        assert!(matches!(outcome, DiffOutcome::Identical(StopReason::CycleLimit)));
+    }
+
+    #[test]
+    fn a_different_register_value_is_reported_at_its_step() {
+        // addi a1, zero, 3
+        let divergence = diverge(1, 0x0030_0593);
+        assert_eq!(divergence.cycle, 1);
+        assert!(matches!(divergence.difference, Difference::Registers(ref registers) if registers == &[(11, 2, 3)]));
+        assert_eq!(divergence.lines(), ["Runs diverge at cycle 1", "  a1   a 0x00000002, b 0x00000003"]);
+    }
+
+    #[test]
+    fn a_different_store_is_reported_before_registers() {
+        // sw a0, 0x100(zero)
+        let divergence = diverge(2, 0x10a0_2023);
+        assert_eq!(divergence.cycle, 2);
+        assert!(matches!(
+            divergence.difference,
+            Difference::MemoryWrites { ref a, ref b } if a == &[(0x100, 2)] && b == &[(0x100, 1)]
+        ));
+    }
+
+    #[test]
+    fn a_different_control_flow_is_reported_before_the_next_step() {
+        // Compared with the addi a1 it replaces, j 8 changes nothing but the PC
+        let mut a = BASE;
+        a[1] = 0x0000_0013;
+        let mut b = BASE;
+        b[1] = 0x0080_006f;
+        let DiffOutcome::Diverged(divergence) = run_lockstep(&mut run("a", &a), &mut run("b", &b), None) else {
+            panic!("runs did not diverge");
+        };
+        assert_eq!(divergence.cycle, 2);
+        assert!(matches!(divergence.difference, Difference::Pc { a: 8, b: 12 }));
+    }
+
+    #[test]
+    fn stopping_differently_is_a_divergence() {
+        // An all-zero word is illegal where a halted with exit code 0
+        let divergence = diverge(5, 0);
+        assert_eq!(divergence.cycle, 5);
+        let Difference::Stop { a, b } = &divergence.difference else {
+            panic!("expected a stop difference, got {:?}", divergence.difference);
+        };
+        assert_eq!(a, "halted with exit code 0");
+        assert!(b.starts_with("trapped: "));
+    }
+}
@@ -0,0 +1,791 @@
+// RV32IM emulator for Pico guest programs
+// Loads the guest ELF, executes it instruction by instruction and reports every step
+
+use crate::riscv::{AluOp, BranchOp, Instruction, LoadOp, StoreOp};
+use crate::syscalls::{self, WeierstrassCurve};
+use crate::{MemoryAccess, MemoryAccessType};
+use object::{Object, ObjectSegment};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::path::Path;
+
+/// Initial stack pointer of a Pico guest.
+pub const STACK_TOP: u32 = 0x0020_0400;
+pub const PAGE_SIZE: u32 = 4096;
+
+/// Most bytes a front end (gdb, DAP or the `memory` command) reads from guest memory in one request.
+pub const MAX_MEMORY_READ: usize = 0x2000;
+
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub vaddr: u32,
+    pub mem_size: u32,
+    pub data: Vec<u8>,
+    pub flags: u32,
+}
+
+/// A guest program loaded from a 32-bit RISC-V ELF file.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub entry: u32,
+    pub segments: Vec<Segment>,
+    pub elf: Vec<u8>,
+}
+
+impl Program {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let elf = std::fs::read(path)
+            .map_err(|e| format!("failed to read program {}: {}", path.display(), e))?;
+        Self::from_elf(elf)
+    }
+
+    pub fn from_elf(elf: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = object::File::parse(&*elf)?;
+        if file.architecture() != object::Architecture::Riscv32 {
+            return Err(format!("expected a riscv32 ELF, found {:?}", file.architecture()).into());
+        }
+
+        let mut segments = Vec::new();
+        for segment in file.segments() {
+            let flags = match segment.flags() {
+                object::SegmentFlags::Elf { p_flags } => p_flags,
+                _ => 0,
+            };
+            segments.push(Segment {
+                vaddr: segment.address() as u32,
+                mem_size: segment.size() as u32,
+                data: segment.data()?.to_vec(),
+                flags,
+            });
+        }
+
+        Ok(Self {
+            entry: file.entry() as u32,
+            segments,
+            elf,
+        })
+    }
+}
+
+/// Sparse byte-addressed guest memory split into fixed-size pages.
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    pages: HashMap<u32, Vec<u8>>,
+}
+
+impl Memory {
+    pub fn read_u8(&self, address: u32) -> u8 {
+        self.pages
+            .get(&(address / PAGE_SIZE))
+            .map(|page| page[(address % PAGE_SIZE) as usize])
+            .unwrap_or(0)
+    }
+
+    pub fn write_u8(&mut self, address: u32, value: u8) {
+        let page = self
+            .pages
+            .entry(address / PAGE_SIZE)
+            .or_insert_with(|| vec![0; PAGE_SIZE as usize]);
+        page[(address % PAGE_SIZE) as usize] = value;
+    }
+
+    pub fn read_u32(&self, address: u32) -> u32 {
+        if address.is_multiple_of(4) {
+            if let Some(page) = self.pages.get(&(address / PAGE_SIZE)) {
+                let offset = (address % PAGE_SIZE) as usize;
+                return u32::from_le_bytes(page[offset..offset + 4].try_into().unwrap());
+            }
+            return 0;
+        }
+        u32::from_le_bytes([
+            self.read_u8(address),
+            self.read_u8(address.wrapping_add(1)),
+            self.read_u8(address.wrapping_add(2)),
+            self.read_u8(address.wrapping_add(3)),
+        ])
+    }
+
+    pub fn write_u32(&mut self, address: u32, value: u32) {
+        for (i, byte) in value.to_le_bytes().into_iter().enumerate() {
+            self.write_u8(address.wrapping_add(i as u32), byte);
+        }
+    }
+
+    pub fn read_bytes(&self, address: u32, len: usize) -> Vec<u8> {
+        (0..len as u32)
+            .map(|i| self.read_u8(address.wrapping_add(i)))
+            .collect()
+    }
+
+    pub fn write_bytes(&mut self, address: u32, bytes: &[u8]) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.write_u8(address.wrapping_add(i as u32), byte);
+        }
+    }
+
+    /// Page numbers that hold any data, in ascending order.
+    pub fn page_numbers(&self) -> Vec<u32> {
+        let mut pages: Vec<u32> = self.pages.keys().copied().collect();
+        pages.sort_unstable();
+        pages
+    }
+}
+
+/// Pages are written as hex strings keyed by page number, which keeps
+/// checkpoints far smaller than a JSON array per byte.
+impl Serialize for Memory {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let pages: BTreeMap<u32, String> = self.pages.iter().map(|(&number, page)| (number, hex::encode(page))).collect();
+        pages.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Memory {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut pages = HashMap::new();
+        for (number, page) in BTreeMap::<u32, String>::deserialize(deserializer)? {
+            let page = hex::decode(page).map_err(D::Error::custom)?;
+            if page.len() != PAGE_SIZE as usize {
+                return Err(D::Error::custom(format!("page {} has {} bytes", number, page.len())));
+            }
+            pages.insert(number, page);
+        }
+        Ok(Self { pages })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trap {
+    Halted,
+    IllegalInstruction { pc: u32, word: u32 },
+    MisalignedAccess { pc: u32, address: u32 },
+    MisalignedJump { pc: u32, target: u32 },
+    Breakpoint { pc: u32 },
+    UnsupportedSyscall { pc: u32, code: u32 },
+    /// A precompile was called with arguments it has no result for, e.g. a point not on the curve.
+    PrecompileFailed { pc: u32, code: u32, reason: String },
+    /// HINT_READ asked for a length other than that of the next input chunk (`None` when none is left).
+    HintLengthMismatch { pc: u32, requested: u32, available: Option<u32> },
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trap::Halted => write!(f, "program has already halted"),
+            Trap::IllegalInstruction { pc, word } => {
+                write!(f, "illegal instruction 0x{:08x} at PC 0x{:x}", word, pc)
+            }
+            Trap::MisalignedAccess { pc, address } => {
+                write!(f, "misaligned memory access to 0x{:x} at PC 0x{:x}", address, pc)
+            }
+            Trap::MisalignedJump { pc, target } => {
+                write!(f, "misaligned jump to 0x{:x} at PC 0x{:x}", target, pc)
+            }
+            Trap::Breakpoint { pc } => write!(f, "ebreak at PC 0x{:x}", pc),
+            Trap::UnsupportedSyscall { pc, code } => write!(
+                f,
+                "syscall {} is not supported by the emulator (PC 0x{:x})",
+                syscalls::syscall_name(*code),
+                pc
+            ),
+            Trap::PrecompileFailed { pc, code, reason } => {
+                write!(f, "{} failed at PC 0x{:x}: {}", syscalls::syscall_name(*code), pc, reason)
+            }
+            Trap::HintLengthMismatch { pc, requested, available: Some(available) } => write!(
+                f,
+                "hint read of {} bytes at PC 0x{:x}, but the next input chunk has {} bytes",
+                requested, pc, available
+            ),
+            Trap::HintLengthMismatch { pc, requested, available: None } => {
+                write!(f, "hint read of {} bytes at PC 0x{:x}, but no input chunks are left", requested, pc)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Trap {}
+
+#[derive(Debug, Clone)]
+pub struct SyscallEvent {
+    pub code: u32,
+    /// Values of a0, a1 and a2 when the ecall was issued.
+    pub arguments: [u32; 3],
+    pub result: Option<u32>,
+}
+
+/// Everything observable about one executed instruction.
+#[derive(Debug, Clone)]
+pub struct StepEvent {
+    pub cycle: u64,
+    pub pc: u32,
+    pub word: u32,
+    pub instruction: Instruction,
+    pub next_pc: u32,
+    pub register_write: Option<(u8, u32)>,
+    pub memory: Vec<MemoryAccess>,
+    pub syscall: Option<SyscallEvent>,
+}
+
+/// Why [`Emulator::run`] returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    Halted(u32),
+    /// Only produced by the debugger, which checks breakpoints between steps.
+    Breakpoint(u32),
+    /// A debugger step, step-over or step-out completed normally.
+    Stepped,
+    /// Only produced by the debugger, when the guest enters its panic handler.
+    Panicked,
+    Trapped(Trap),
+    CycleLimit,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UnconstrainedState {
+    registers: [u32; 32],
+    pc: u32,
+    overwritten: HashMap<u32, u8>,
+    /// Output lengths and digest on entry; output written inside the block is dropped on exit.
+    public_values_len: usize,
+    stdout_len: usize,
+    stderr_len: usize,
+    committed_digest: [u32; 8],
+    /// Input chunks read inside the block, oldest first, put back on exit.
+    inputs_read: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Emulator {
+    pub registers: [u32; 32],
+    pub pc: u32,
+    pub memory: Memory,
+    pub cycle: u64,
+    pub public_values: Vec<u8>,
+    pub committed_digest: [u32; 8],
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    inputs: VecDeque<Vec<u8>>,
+    exit_code: Option<u32>,
+    unconstrained: Option<UnconstrainedState>,
+}
+
+impl Emulator {
+    /// Creates an emulator with the program image loaded and `inputs` queued as hint chunks.
+    pub fn new(program: &Program, inputs: Vec<Vec<u8>>) -> Self {
+        let mut memory = Memory::default();
+        for segment in &program.segments {
+            memory.write_bytes(segment.vaddr, &segment.data);
+        }
+
+        let mut registers = [0u32; 32];
+        registers[2] = STACK_TOP;
+
+        Self {
+            registers,
+            pc: program.entry,
+            memory,
+            cycle: 0,
+            public_values: Vec::new(),
+            committed_digest: [0; 8],
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            inputs: inputs.into(),
+            exit_code: None,
+            unconstrained: None,
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.exit_code.is_some()
+    }
+
+    pub fn exit_code(&self) -> Option<u32> {
+        self.exit_code
+    }
+
+    /// Runs until the guest halts, traps or `max_cycles` more cycles have executed,
+    /// passing every step to `on_step`.
+    pub fn run<E>(
+        &mut self,
+        max_cycles: Option<u64>,
+        mut on_step: impl FnMut(&StepEvent) -> Result<(), E>,
+    ) -> Result<StopReason, E> {
+        let limit = max_cycles.map(|max| self.cycle.saturating_add(max));
+        loop {
+            if let Some(code) = self.exit_code {
+                return Ok(StopReason::Halted(code));
+            }
+            if limit.is_some_and(|limit| self.cycle >= limit) {
+                return Ok(StopReason::CycleLimit);
+            }
+            match self.step() {
+                Ok(event) => on_step(&event)?,
+                Err(trap) => return Ok(StopReason::Trapped(trap)),
+            }
+        }
+    }
+
+    pub fn step(&mut self) -> Result<StepEvent, Trap> {
+        if self.is_halted() {
+            return Err(Trap::Halted);
+        }
+
+        let pc = self.pc;
+        if !pc.is_multiple_of(4) {
+            return Err(Trap::MisalignedJump { pc, target: pc });
+        }
+        let word = self.memory.read_u32(pc);
+        let instruction = Instruction::decode(word);
+        let mut event = StepEvent {
+            cycle: self.cycle,
+            pc,
+            word,
+            instruction,
+            next_pc: pc.wrapping_add(4),
+            register_write: None,
+            memory: Vec::new(),
+            syscall: None,
+        };
+
+        match instruction {
+            Instruction::Lui { rd, imm } => self.write_register(&mut event, rd, imm),
+            Instruction::Auipc { rd, imm } => self.write_register(&mut event, rd, pc.wrapping_add(imm)),
+            Instruction::Jal { rd, offset } => {
+                let target = pc.wrapping_add(offset as u32);
+                self.jump(&mut event, target)?;
+                self.write_register(&mut event, rd, pc.wrapping_add(4));
+            }
+            Instruction::Jalr { rd, rs1, offset } => {
+                let target = self.registers[rs1 as usize].wrapping_add(offset as u32) & !1;
+                self.jump(&mut event, target)?;
+                self.write_register(&mut event, rd, pc.wrapping_add(4));
+            }
+            Instruction::Branch { op, rs1, rs2, offset } => {
+                let a = self.registers[rs1 as usize];
+                let b = self.registers[rs2 as usize];
+                let taken = match op {
+                    BranchOp::Beq => a == b,
+                    BranchOp::Bne => a != b,
+                    BranchOp::Blt => (a as i32) < (b as i32),
+                    BranchOp::Bge => (a as i32) >= (b as i32),
+                    BranchOp::Bltu => a < b,
+                    BranchOp::Bgeu => a >= b,
+                };
+                if taken {
+                    self.jump(&mut event, pc.wrapping_add(offset as u32))?;
+                }
+            }
+            Instruction::Load { op, rd, rs1, offset } => {
+                let address = self.registers[rs1 as usize].wrapping_add(offset as u32);
+                let value = match op {
+                    LoadOp::Lb => self.load(&mut event, address, 1)? as u8 as i8 as i32 as u32,
+                    LoadOp::Lh => self.load(&mut event, address, 2)? as u16 as i16 as i32 as u32,
+                    LoadOp::Lw => self.load(&mut event, address, 4)?,
+                    LoadOp::Lbu => self.load(&mut event, address, 1)?,
+                    LoadOp::Lhu => self.load(&mut event, address, 2)?,
+                };
+                self.write_register(&mut event, rd, value);
+            }
+            Instruction::Store { op, rs1, rs2, offset } => {
+                let address = self.registers[rs1 as usize].wrapping_add(offset as u32);
+                let value = self.registers[rs2 as usize];
+                match op {
+                    StoreOp::Sb => self.store(&mut event, address, value & 0xff, 1)?,
+                    StoreOp::Sh => self.store(&mut event, address, value & 0xffff, 2)?,
+                    StoreOp::Sw => self.store(&mut event, address, value, 4)?,
+                }
+            }
+            Instruction::OpImm { op, rd, rs1, imm } => {
+                let value = alu(op, self.registers[rs1 as usize], imm as u32);
+                self.write_register(&mut event, rd, value);
+            }
+            Instruction::Op { op, rd, rs1, rs2 } => {
+                let value = alu(op, self.registers[rs1 as usize], self.registers[rs2 as usize]);
+                self.write_register(&mut event, rd, value);
+            }
+            Instruction::Fence => {}
+            Instruction::Ecall => self.ecall(&mut event)?,
+            Instruction::Ebreak => return Err(Trap::Breakpoint { pc }),
+            Instruction::Unknown(word) => return Err(Trap::IllegalInstruction { pc, word }),
+        }
+
+        self.pc = event.next_pc;
+        self.cycle += 1;
+        Ok(event)
+    }
+
+    fn write_register(&mut self, event: &mut StepEvent, rd: u8, value: u32) {
+        if rd != 0 {
+            self.registers[rd as usize] = value;
+            event.register_write = Some((rd, value));
+        }
+    }
+
+    fn jump(&mut self, event: &mut StepEvent, target: u32) -> Result<(), Trap> {
+        if !target.is_multiple_of(4) {
+            return Err(Trap::MisalignedJump { pc: event.pc, target });
+        }
+        event.next_pc = target;
+        Ok(())
+    }
+
+    fn load(&mut self, event: &mut StepEvent, address: u32, size: u32) -> Result<u32, Trap> {
+        if !address.is_multiple_of(size) {
+            return Err(Trap::MisalignedAccess { pc: event.pc, address });
+        }
+        let value = match size {
+            1 => self.memory.read_u8(address) as u32,
+            2 => u16::from_le_bytes([self.memory.read_u8(address), self.memory.read_u8(address + 1)]) as u32,
+            _ => self.memory.read_u32(address),
+        };
+        event.memory.push(MemoryAccess {
+            address: address as u64,
+            value: value as u64,
+            access_type: MemoryAccessType::Read,
+            cycle: event.cycle,
+        });
+        Ok(value)
+    }
+
+    fn store(&mut self, event: &mut StepEvent, address: u32, value: u32, size: u32) -> Result<(), Trap> {
+        if !address.is_multiple_of(size) {
+            return Err(Trap::MisalignedAccess { pc: event.pc, address });
+        }
+        for (i, byte) in value.to_le_bytes().into_iter().take(size as usize).enumerate() {
+            self.write_byte(address + i as u32, byte);
+        }
+        event.memory.push(MemoryAccess {
+            address: address as u64,
+            value: value as u64,
+            access_type: MemoryAccessType::Write,
+            cycle: event.cycle,
+        });
+        Ok(())
+    }
+
+    fn write_byte(&mut self, address: u32, value: u8) {
+        if let Some(state) = &mut self.unconstrained {
+            let previous = self.memory.read_u8(address);
+            state.overwritten.entry(address).or_insert(previous);
+        }
+        self.memory.write_u8(address, value);
+    }
+
+    fn read_words(&mut self, event: &mut StepEvent, address: u32, count: usize) -> Result<Vec<u32>, Trap> {
+        (0..count as u32)
+            .map(|i| self.load(event, address.wrapping_add(i * 4), 4))
+            .collect()
+    }
+
+    fn write_words(&mut self, event: &mut StepEvent, address: u32, words: &[u32]) -> Result<(), Trap> {
+        for (i, &word) in words.iter().enumerate() {
+            self.store(event, address.wrapping_add(i as u32 * 4), word, 4)?;
+        }
+        Ok(())
+    }
+
+    fn ecall(&mut self, event: &mut StepEvent) -> Result<(), Trap> {
+        let code = self.registers[5];
+        let arguments = [self.registers[10], self.registers[11], self.registers[12]];
+        let [a0, a1, a2] = arguments;
+        let mut result = None;
+
+        match code {
+            syscalls::HALT => {
+                self.exit_code = Some(a0);
+            }
+            syscalls::WRITE => {
+                let bytes = self.memory.read_bytes(a1, a2 as usize);
+                match a0 {
+                    syscalls::FD_STDOUT => self.stdout.extend_from_slice(&bytes),
+                    syscalls::FD_STDERR => self.stderr.extend_from_slice(&bytes),
+                    syscalls::FD_PUBLIC_VALUES => self.public_values.extend_from_slice(&bytes),
+                    syscalls::FD_HINT => self.inputs.push_back(bytes),
+                    _ => {}
+                }
+            }
+            syscalls::ENTER_UNCONSTRAINED => {
+                self.unconstrained = Some(UnconstrainedState {
+                    registers: self.registers,
+                    pc: event.pc,
+                    overwritten: HashMap::new(),
+                    public_values_len: self.public_values.len(),
+                    stdout_len: self.stdout.len(),
+                    stderr_len: self.stderr.len(),
+                    committed_digest: self.committed_digest,
+                    inputs_read: Vec::new(),
+                });
+                result = Some(1);
+            }
+            syscalls::EXIT_UNCONSTRAINED => {
+                if let Some(state) = self.unconstrained.take() {
+                    for (address, byte) in state.overwritten {
+                        self.memory.write_u8(address, byte);
+                    }
+                    self.registers = state.registers;
+                    self.public_values.truncate(state.public_values_len);
+                    self.stdout.truncate(state.stdout_len);
+                    self.stderr.truncate(state.stderr_len);
+                    self.committed_digest = state.committed_digest;
+                    // Hints the block wrote for later reads (FD_HINT) stay queued behind the ones it consumed
+                    for chunk in state.inputs_read.into_iter().rev() {
+                        self.inputs.push_front(chunk);
+                    }
+                    event.next_pc = state.pc.wrapping_add(4);
+                    result = Some(0);
+                }
+            }
+            syscalls::COMMIT => {
+                if let Some(slot) = self.committed_digest.get_mut(a0 as usize) {
+                    *slot = a1;
+                }
+            }
+            syscalls::HINT_LEN => {
+                result = Some(self.inputs.front().map(|chunk| chunk.len() as u32).unwrap_or(0));
+            }
+            syscalls::HINT_READ => {
+                if !a0.is_multiple_of(4) {
+                    return Err(Trap::MisalignedAccess { pc: event.pc, address: a0 });
+                }
+                // The SDK reads each chunk whole, with the length HINT_LEN returned
+                let available = self.inputs.front().map(|chunk| chunk.len() as u32);
+                if available.unwrap_or(0) != a1 {
+                    return Err(Trap::HintLengthMismatch { pc: event.pc, requested: a1, available });
+                }
+                let chunk = self.inputs.pop_front().unwrap_or_default();
+                if let Some(state) = &mut self.unconstrained {
+                    state.inputs_read.push(chunk.clone());
+                }
+                for (i, word) in chunk.chunks(4).enumerate() {
+                    let mut bytes = [0u8; 4];
+                    bytes[..word.len()].copy_from_slice(word);
+                    // A buffer running past the top of memory wraps, as the guest's own stores would
+                    let address = a0.wrapping_add(i as u32 * 4);
+                    if word.len() == 4 {
+                        self.store(event, address, u32::from_le_bytes(bytes), 4)?;
+                    } else {
+                        for (j, &byte) in word.iter().enumerate() {
+                            self.store(event, address.wrapping_add(j as u32), byte as u32, 1)?;
+                        }
+                    }
+                }
+            }
+            syscalls::SHA_EXTEND => {
+                let mut w: [u32; 64] = self.read_words(event, a0, 64)?.try_into().unwrap();
+                syscalls::sha_extend(&mut w);
+                self.write_words(event, a0.wrapping_add(64), &w[16..])?;
+            }
+            syscalls::SHA_COMPRESS => {
+                let w: [u32; 64] = self.read_words(event, a0, 64)?.try_into().unwrap();
+                let mut h: [u32; 8] = self.read_words(event, a1, 8)?.try_into().unwrap();
+                syscalls::sha_compress(&w, &mut h);
+                self.write_words(event, a1, &h)?;
+            }
+            syscalls::KECCAK_PERMUTE => {
+                let words = self.read_words(event, a0, 50)?;
+                let mut state = [0u64; 25];
+                for (lane, pair) in state.iter_mut().zip(words.chunks(2)) {
+                    *lane = pair[0] as u64 | ((pair[1] as u64) << 32);
+                }
+                syscalls::keccak_permute(&mut state);
+                let words: Vec<u32> = state
+                    .iter()
+                    .flat_map(|lane| [*lane as u32, (*lane >> 32) as u32])
+                    .collect();
+                self.write_words(event, a0, &words)?;
+            }
+            syscalls::ED_ADD => {
+                let p = self.read_words(event, a0, 16)?;
+                let q = self.read_words(event, a1, 16)?;
+                let sum = syscalls::ed_add(&p, &q).map_err(|reason| precompile_failed(event, code, reason))?;
+                self.write_words(event, a0, &sum)?;
+            }
+            syscalls::ED_DECOMPRESS => {
+                // The compressed y is in the upper half of the buffer; its top bit may carry the sign
+                let mut y = self.read_words(event, a0.wrapping_add(32), 8)?;
+                let sign = a1 != 0 || y[7] >> 31 != 0;
+                y[7] &= 0x7fff_ffff;
+                let x = syscalls::ed_decompress(&y, sign).map_err(|reason| precompile_failed(event, code, reason))?;
+                self.write_words(event, a0, &x)?;
+            }
+            syscalls::SECP256K1_ADD | syscalls::BN254_ADD | syscalls::BLS12381_ADD => {
+                let curve = WeierstrassCurve::for_syscall(code).unwrap();
+                let p = self.read_words(event, a0, 2 * curve.coordinate_words())?;
+                let q = self.read_words(event, a1, 2 * curve.coordinate_words())?;
+                let sum = curve.add(&p, &q).map_err(|reason| precompile_failed(event, code, reason))?;
+                self.write_words(event, a0, &sum)?;
+            }
+            syscalls::SECP256K1_DOUBLE | syscalls::BN254_DOUBLE | syscalls::BLS12381_DOUBLE => {
+                let curve = WeierstrassCurve::for_syscall(code).unwrap();
+                let p = self.read_words(event, a0, 2 * curve.coordinate_words())?;
+                let double = curve.double(&p).map_err(|reason| precompile_failed(event, code, reason))?;
+                self.write_words(event, a0, &double)?;
+            }
+            syscalls::SECP256K1_DECOMPRESS | syscalls::BLS12381_DECOMPRESS => {
+                // x is in the upper half of the buffer and y is written to the lower half
+                let curve = WeierstrassCurve::for_syscall(code).unwrap();
+                let words = curve.coordinate_words();
+                let x = self.read_words(event, a0.wrapping_add(words as u32 * 4), words)?;
+                let y = curve.decompress(&x, a1 != 0).map_err(|reason| precompile_failed(event, code, reason))?;
+                self.write_words(event, a0, &y)?;
+            }
+            syscalls::UINT256_MUL => {
+                // y is followed by the modulus
+                let x = self.read_words(event, a0, 8)?;
+                let y = self.read_words(event, a1, 8)?;
+                let modulus = self.read_words(event, a1.wrapping_add(32), 8)?;
+                self.write_words(event, a0, &syscalls::uint256_mul(&x, &y, &modulus))?;
+            }
+            _ => return Err(Trap::UnsupportedSyscall { pc: event.pc, code }),
+        }
+
+        if let Some(value) = result {
+            self.registers[5] = value;
+            event.register_write = Some((5, value));
+        }
+        event.syscall = Some(SyscallEvent {
+            code,
+            arguments,
+            result,
+        });
+        Ok(())
+    }
+}
+
+fn precompile_failed(event: &StepEvent, code: u32, reason: String) -> Trap {
+    Trap::PrecompileFailed { pc: event.pc, code, reason }
+}
+
+fn alu(op: AluOp, a: u32, b: u32) -> u32 {
+    match op {
+        AluOp::Add => a.wrapping_add(b),
+        AluOp::Sub => a.wrapping_sub(b),
+        AluOp::Sll => a.wrapping_shl(b & 0x1f),
+        AluOp::Slt => ((a as i32) < (b as i32)) as u32,
+        AluOp::Sltu => (a < b) as u32,
+        AluOp::Xor => a ^ b,
+        AluOp::Srl => a.wrapping_shr(b & 0x1f),
+        AluOp::Sra => ((a as i32).wrapping_shr(b & 0x1f)) as u32,
+        AluOp::Or => a | b,
+        AluOp::And => a & b,
+        AluOp::Mul => a.wrapping_mul(b),
+        AluOp::Mulh => (((a as i32 as i64) * (b as i32 as i64)) >> 32) as u32,
+        AluOp::Mulhsu => (((a as i32 as i64) * (b as i64)) >> 32) as u32,
+        AluOp::Mulhu => (((a as u64) * (b as u64)) >> 32) as u32,
+        AluOp::Div => match (a as i32, b as i32) {
+            (_, 0) => u32::MAX,
+            (i32::MIN, -1) => a,
+            (x, y) => (x / y) as u32,
+        },
+        AluOp::Divu => a.checked_div(b).unwrap_or(u32::MAX),
+        AluOp::Rem => match (a as i32, b as i32) {
+            (_, 0) => a,
+            (i32::MIN, -1) => 0,
+            (x, y) => (x % y) as u32,
+        },
+        AluOp::Remu => a.checked_rem(b).unwrap_or(a),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A program with `code` at address 0.
+    fn program(code: &[u32]) -> Program {
+        let data: Vec<u8> = code.iter().flat_map(|word| word.to_le_bytes()).collect();
+        Program {
+            entry: 0,
+            segments: vec![Segment { vaddr: 0, mem_size: data.len() as u32, data, flags: 5 }],
+            elf: Vec::new(),
+        }
+    }
+
+    const ECALL: u32 = 0x0000_0073;
+    /// addi t0, zero, 0; addi a0, zero, 0; ecall
+    const HALT: [u32; 3] = [0x0000_0293, 0x0000_0513, ECALL];
+
+    #[test]
+    fn secp256k1_double_through_ecall() {
+        // lui a0, 0x2; addi t0, zero, SECP256K1_DOUBLE; ecall
+        let code = [&[0x0000_2537, 0x10b0_0293, ECALL][..], &HALT].concat();
+        let mut emulator = Emulator::new(&program(&code), Vec::new());
+        let g = [
+            0x16f81798, 0x59f2815b, 0x2dce28d9, 0x029bfcdb, 0xce870b07, 0x55a06295, 0xf9dcbbac, 0x79be667e,
+            0xfb10d4b8, 0x9c47d08f, 0xa6855419, 0xfd17b448, 0x0e1108a8, 0x5da4fbfc, 0x26a3c465, 0x483ada77,
+        ];
+        for (i, word) in g.into_iter().enumerate() {
+            emulator.memory.write_u32(0x2000 + i as u32 * 4, word);
+        }
+        assert_eq!(emulator.run(Some(100), |_| Ok::<_, ()>(())), Ok(StopReason::Halted(0)));
+        assert_eq!(emulator.memory.read_u32(0x2000), 0x5c709ee5);
+        assert_eq!(emulator.memory.read_u32(0x201c), 0xc6047f94);
+        assert_eq!(emulator.memory.read_u32(0x2020), 0x50cfe52a);
+    }
+
+    #[test]
+    fn precompile_without_a_result_traps() {
+        // lui a0, 0x2; addi t0, zero, BN254_DOUBLE; ecall, with the point (1, 0) at 0x2000
+        let mut emulator = Emulator::new(&program(&[0x0000_2537, 0x10f0_0293, ECALL]), Vec::new());
+        emulator.memory.write_u32(0x2000, 1);
+        match emulator.run(Some(100), |_| Ok::<_, ()>(())) {
+            Ok(StopReason::Trapped(Trap::PrecompileFailed { pc: 8, code: syscalls::BN254_DOUBLE, .. })) => {}
+            other => panic!("expected a precompile trap, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn hint_read_wraps_at_the_top_of_memory() {
+        // addi a0, zero, -4; addi a1, zero, 6; addi t0, zero, HINT_READ; ecall
+        let code = [&[0xffc0_0513, 0x0060_0593, 0x0f10_0293, ECALL][..], &HALT].concat();
+        let mut emulator = Emulator::new(&program(&code), vec![vec![1, 2, 3, 4, 5, 6]]);
+        let stop = emulator.run(Some(5), |_| Ok::<_, ()>(()));
+        assert_eq!(stop, Ok(StopReason::CycleLimit));
+        assert_eq!(emulator.memory.read_u32(0xffff_fffc), 0x0403_0201);
+        assert_eq!(emulator.memory.read_bytes(0, 2), [5, 6]);
+    }
+
+    #[test]
+    fn hint_read_must_take_the_whole_chunk() {
+        // addi a0, zero, 0x100; addi a1, zero, 4; addi t0, zero, HINT_READ; ecall
+        let code = [0x1000_0513, 0x0040_0593, 0x0f10_0293, ECALL];
+        let mut emulator = Emulator::new(&program(&code), vec![vec![1, 2, 3, 4, 5, 6]]);
+        let stop = emulator.run(Some(10), |_| Ok::<_, ()>(()));
+        assert_eq!(stop, Ok(StopReason::Trapped(Trap::HintLengthMismatch { pc: 12, requested: 4, available: Some(6) })));
+        assert_eq!(emulator.memory.read_u32(0x100), 0);
+
+        let mut emulator = Emulator::new(&program(&code), Vec::new());
+        let stop = emulator.run(Some(10), |_| Ok::<_, ()>(()));
+        assert_eq!(stop, Ok(StopReason::Trapped(Trap::HintLengthMismatch { pc: 12, requested: 4, available: None })));
+    }
+
+    #[test]
+    fn unconstrained_block_rolls_back_outputs_and_inputs() {
+        let code = [
+            0x0030_0293, ECALL, // enter_unconstrained
+            0x0030_0513, 0x0000_25b7, 0x0040_0613, 0x0020_0293, ECALL, // write 4 bytes at 0x2000 to public values
+            0x0010_0513, ECALL, // ... to stdout
+            0x0040_0513, ECALL, // ... as a hint for later
+            0x0000_3537, 0x0020_0593, 0x0f10_0293, ECALL, // hint_read 2 bytes to 0x3000
+            0x0040_0293, ECALL, // exit_unconstrained
+        ];
+        let mut emulator = Emulator::new(&program(&code), vec![vec![7, 8], vec![9]]);
+        emulator.memory.write_u32(0x2000, 0x6463_6261);
+        emulator.public_values = vec![1];
+        emulator.committed_digest[0] = 5;
+        assert_eq!(emulator.run(Some(code.len() as u64), |_| Ok::<_, ()>(())), Ok(StopReason::CycleLimit));
+
+        // Back after enter_unconstrained, which now returns 0
+        assert_eq!(emulator.pc, 8);
+        assert_eq!(emulator.registers[10], 0);
+        assert_eq!(emulator.registers[11], 0);
+        assert_eq!(emulator.memory.read_u32(0x3000), 0);
+        assert_eq!(emulator.public_values, [1]);
+        assert!(emulator.stdout.is_empty());
+        assert_eq!(emulator.committed_digest[0], 5);
+        // The chunk read inside the block is read again; the one it wrote follows the rest
+        assert_eq!(Vec::from(emulator.inputs.clone()), [vec![7, 8], vec![9], b"abcd".to_vec()]);
+    }
+}
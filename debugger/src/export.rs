@@ -0,0 +1,173 @@
+// Profile export formats
+// Collapsed stacks for flamegraph tools, speedscope JSON and pprof protobuf
+
+use crate::ProfilerResult;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// One `frame;frame;frame cycles` line per stack, as read by `flamegraph.pl` and inferno.
+pub fn collapsed_stacks(result: &ProfilerResult) -> String {
+    let mut output = String::new();
+    for stack in &result.stacks {
+        output.push_str(&stack.frames.join(";"));
+        output.push_str(&format!(" {}\n", stack.cycles));
+    }
+    output
+}
+
+/// A sampled profile in the speedscope file format, weighted by cycles.
+pub fn speedscope(result: &ProfilerResult, name: &str) -> Value {
+    let mut frame_indices: HashMap<&str, usize> = HashMap::new();
+    let mut frames = Vec::new();
+    let mut samples = Vec::new();
+    let mut weights = Vec::new();
+    for stack in &result.stacks {
+        let sample: Vec<usize> = stack
+            .frames
+            .iter()
+            .map(|frame| {
+                *frame_indices.entry(frame).or_insert_with(|| {
+                    frames.push(json!({ "name": frame }));
+                    frames.len() - 1
+                })
+            })
+            .collect();
+        samples.push(sample);
+        weights.push(stack.cycles);
+    }
+
+    json!({
+        "$schema": "https://www.speedscope.app/file-format-schema.json",
+        "name": name,
+        "exporter": format!("pico-debug {}", env!("CARGO_PKG_VERSION")),
+        "activeProfileIndex": 0,
+        "shared": { "frames": frames },
+        "profiles": [{
+            "type": "sampled",
+            "name": name,
+            "unit": "none",
+            "startValue": 0,
+            "endValue": result.total_cycles,
+            "samples": samples,
+            "weights": weights,
+        }],
+    })
+}
+
+/// An uncompressed pprof `Profile` protobuf with one `cycles/count` value per
+/// stack. Each function gets a single location, so `pprof -top` and
+/// `pprof -http` group by function.
+pub fn pprof(result: &ProfilerResult) -> Vec<u8> {
+    let mut strings = StringTable::default();
+    let cycles = strings.index("cycles");
+    let count = strings.index("count");
+
+    let mut function_ids: HashMap<&str, u64> = HashMap::new();
+    let mut functions = Vec::new();
+    let mut samples = Vec::new();
+    for stack in &result.stacks {
+        // pprof lists locations leaf first.
+        let location_ids: Vec<u64> = stack
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                *function_ids.entry(frame).or_insert_with(|| {
+                    functions.push(strings.index(frame));
+                    functions.len() as u64
+                })
+            })
+            .collect();
+        let mut sample = Vec::new();
+        packed_field(&mut sample, 1, &location_ids);
+        packed_field(&mut sample, 2, &[stack.cycles]);
+        samples.push(sample);
+    }
+
+    let mut value_type = Vec::new();
+    varint_field(&mut value_type, 1, cycles);
+    varint_field(&mut value_type, 2, count);
+
+    let mut profile = Vec::new();
+    bytes_field(&mut profile, 1, &value_type);
+    for sample in &samples {
+        bytes_field(&mut profile, 2, sample);
+    }
+    for (index, &name) in functions.iter().enumerate() {
+        let id = index as u64 + 1;
+        // Location { id, line: [Line { function_id }] }
+        let mut line = Vec::new();
+        varint_field(&mut line, 1, id);
+        let mut location = Vec::new();
+        varint_field(&mut location, 1, id);
+        bytes_field(&mut location, 4, &line);
+        bytes_field(&mut profile, 4, &location);
+        // Function { id, name, system_name }
+        let mut function = Vec::new();
+        varint_field(&mut function, 1, id);
+        varint_field(&mut function, 2, name);
+        varint_field(&mut function, 3, name);
+        bytes_field(&mut profile, 5, &function);
+    }
+    for string in &strings.strings {
+        bytes_field(&mut profile, 6, string.as_bytes());
+    }
+    varint_field(&mut profile, 10, result.execution_time.as_nanos() as u64);
+    bytes_field(&mut profile, 11, &value_type);
+    varint_field(&mut profile, 12, 1);
+    profile
+}
+
+/// pprof string table; index 0 must be the empty string.
+struct StringTable {
+    strings: Vec<String>,
+    indices: HashMap<String, u64>,
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        Self {
+            strings: vec![String::new()],
+            indices: HashMap::from([(String::new(), 0)]),
+        }
+    }
+}
+
+impl StringTable {
+    fn index(&mut self, string: &str) -> u64 {
+        if let Some(&index) = self.indices.get(string) {
+            return index;
+        }
+        let index = self.strings.len() as u64;
+        self.strings.push(string.to_string());
+        self.indices.insert(string.to_string(), index);
+        index
+    }
+}
+
+fn varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn varint_field(output: &mut Vec<u8>, field: u32, value: u64) {
+    varint(output, (field as u64) << 3);
+    varint(output, value);
+}
+
+fn bytes_field(output: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    varint(output, ((field as u64) << 3) | 2);
+    varint(output, bytes.len() as u64);
+    output.extend_from_slice(bytes);
+}
+
+fn packed_field(output: &mut Vec<u8>, field: u32, values: &[u64]) {
+    let mut packed = Vec::new();
+    for &value in values {
+        varint(&mut packed, value);
+    }
+    bytes_field(output, field, &packed);
+}
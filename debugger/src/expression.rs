@@ -0,0 +1,584 @@
+// Debugger expressions
+// A small C-like language over registers, guest memory and the cycle count, used
+// by breakpoint conditions and `print`
+
+use crate::emulator::Emulator;
+use crate::riscv::register_index;
+use std::fmt;
+
+/// The type of a value, with C's `int`, `unsigned`, `long long` and `unsigned long long`
+/// sizes on a 32-bit target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    I32,
+    U32,
+    I64,
+    U64,
+}
+
+impl Type {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "i32" => Some(Type::I32),
+            "u32" => Some(Type::U32),
+            "i64" => Some(Type::I64),
+            "u64" => Some(Type::U64),
+            _ => None,
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(self, Type::I32 | Type::I64)
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            Type::I32 | Type::U32 => 32,
+            Type::I64 | Type::U64 => 64,
+        }
+    }
+
+    /// The type both operands of an arithmetic or comparison operator convert to: the
+    /// wider one, or unsigned if either is when they are the same width.
+    fn common(self, other: Type) -> Type {
+        match self.bits().cmp(&other.bits()) {
+            std::cmp::Ordering::Greater => self,
+            std::cmp::Ordering::Less => other,
+            std::cmp::Ordering::Equal if self.is_signed() => other,
+            std::cmp::Ordering::Equal => self,
+        }
+    }
+}
+
+/// A typed value. The bits are kept truncated to the type's width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Value {
+    ty: Type,
+    bits: u64,
+}
+
+impl Value {
+    pub fn new(ty: Type, bits: u64) -> Self {
+        let bits = if ty.bits() == 32 { bits & 0xffff_ffff } else { bits };
+        Self { ty, bits }
+    }
+
+    /// An integer literal gets the narrowest of i32, u32, i64 and u64 that holds it.
+    pub fn literal(value: u64) -> Self {
+        let ty = if value <= i32::MAX as u64 {
+            Type::I32
+        } else if value <= u32::MAX as u64 {
+            Type::U32
+        } else if value <= i64::MAX as u64 {
+            Type::I64
+        } else {
+            Type::U64
+        };
+        Self::new(ty, value)
+    }
+
+    fn boolean(value: bool) -> Self {
+        Self::new(Type::I32, value as u64)
+    }
+
+    pub fn ty(self) -> Type {
+        self.ty
+    }
+
+    /// The value's bits zero-extended, which is what a register or memory write stores.
+    pub fn bits(self) -> u64 {
+        self.bits
+    }
+
+    pub fn is_true(self) -> bool {
+        self.bits != 0
+    }
+
+    /// Whether storing the low `bytes` bytes keeps the value: the bits fit, or the value
+    /// is negative and its sign extension does.
+    pub fn fits(self, bytes: u32) -> bool {
+        let width = 8 * bytes;
+        width >= 64 || self.bits >> width == 0 || (self.ty.is_signed() && self.signed() >> (width - 1) == -1)
+    }
+
+    fn signed(self) -> i64 {
+        match self.ty.bits() {
+            32 => self.bits as u32 as i32 as i64,
+            _ => self.bits as i64,
+        }
+    }
+
+    /// Converts as C does: signed values are sign-extended when widened.
+    fn convert(self, ty: Type) -> Self {
+        let bits = if self.ty.is_signed() { self.signed() as u64 } else { self.bits };
+        Self::new(ty, bits)
+    }
+}
+
+/// The value's bits in hex, so -1 as an i32 is `ffffffff`.
+impl fmt::LowerHex for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.bits, f)
+    }
+}
+
+/// The value in decimal, negative for signed types.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ty.is_signed() {
+            write!(f, "{}", self.signed())
+        } else {
+            write!(f, "{}", self.bits)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    Complement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl BinaryOp {
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Equal
+                | BinaryOp::NotEqual
+                | BinaryOp::Less
+                | BinaryOp::LessEqual
+                | BinaryOp::Greater
+                | BinaryOp::GreaterEqual
+        )
+    }
+}
+
+/// Binary operators by precedence, loosest first.
+const BINARY_OPERATORS: &[&[(&str, BinaryOp)]] = &[
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("==", BinaryOp::Equal), ("!=", BinaryOp::NotEqual)],
+    &[
+        ("<=", BinaryOp::LessEqual),
+        (">=", BinaryOp::GreaterEqual),
+        ("<", BinaryOp::Less),
+        (">", BinaryOp::Greater),
+    ],
+    &[("<<", BinaryOp::ShiftLeft), (">>", BinaryOp::ShiftRight)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Subtract)],
+    &[("*", BinaryOp::Multiply), ("/", BinaryOp::Divide), ("%", BinaryOp::Remainder)],
+];
+
+/// Punctuation, longest first so `<<` is not read as two `<`.
+const SYMBOLS: &[&str] = &[
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "&", "|", "^", "<", ">", "!", "~", "(", ")",
+];
+
+/// A parsed expression. Values are typed as in C on the 32-bit guest: registers, pc and
+/// literals are 32-bit, so `a0 == -1` holds for 0xffffffff, while `cycle` and `u64` loads
+/// are 64-bit. Registers are unsigned; `(i32)a0 < 0` compares signed, and so do division,
+/// remainder and `>>` on signed operands. Comparisons and logical operators produce 0 or 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    Literal(Value),
+    Register(usize),
+    Pc,
+    Cycle,
+    /// `*(uN*)address` or `*(iN*)address`, reading `width` bytes little-endian and
+    /// sign-extending when `signed`. A bare `*address` reads a u32.
+    Load { width: u32, signed: bool, address: Box<Expression> },
+    /// `(i32)value` and the like.
+    Cast(Type, Box<Expression>),
+    Unary(UnaryOp, Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(u64),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+impl Expression {
+    /// Parses e.g. `a0 == 42 && *(u8*)(sp + 4) != 0 || cycle > 1000000`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let expression = parser.binary(0)?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(expression),
+            Some(token) => Err(format!("Unexpected {:?} in '{}'", token, text)),
+        }
+    }
+
+    /// Evaluates against the emulator's current state. Returns `None` on
+    /// division by zero.
+    pub fn evaluate(&self, emulator: &Emulator) -> Option<Value> {
+        Some(match self {
+            Expression::Literal(value) => *value,
+            Expression::Register(index) => Value::new(Type::U32, emulator.registers[*index] as u64),
+            Expression::Pc => Value::new(Type::U32, emulator.pc as u64),
+            Expression::Cycle => Value::new(Type::U64, emulator.cycle),
+            Expression::Load { width, signed, address } => {
+                let address = address.evaluate(emulator)?.bits() as u32;
+                let bits = (0..*width).fold(0u64, |value, i| {
+                    value | (emulator.memory.read_u8(address.wrapping_add(i)) as u64) << (8 * i)
+                });
+                let (bits, ty) = match (*signed, *width) {
+                    (true, 8) => (bits, Type::I64),
+                    (true, _) => (((bits << (64 - 8 * width)) as i64 >> (64 - 8 * width)) as u64, Type::I32),
+                    (false, 8) => (bits, Type::U64),
+                    (false, _) => (bits, Type::U32),
+                };
+                Value::new(ty, bits)
+            }
+            Expression::Cast(ty, operand) => operand.evaluate(emulator)?.convert(*ty),
+            Expression::Unary(op, operand) => {
+                let value = operand.evaluate(emulator)?;
+                match op {
+                    UnaryOp::Negate => Value::new(value.ty, value.bits.wrapping_neg()),
+                    UnaryOp::Not => Value::boolean(!value.is_true()),
+                    UnaryOp::Complement => Value::new(value.ty, !value.bits),
+                }
+            }
+            Expression::Binary(op, left, right) => {
+                let left = left.evaluate(emulator)?;
+                // Short-circuit so `a0 != 0 && 100 / a0 > 3` is safe.
+                match op {
+                    BinaryOp::And if !left.is_true() => return Some(Value::boolean(false)),
+                    BinaryOp::Or if left.is_true() => return Some(Value::boolean(true)),
+                    _ => {}
+                }
+                let right = right.evaluate(emulator)?;
+                match op {
+                    BinaryOp::Or | BinaryOp::And => Value::boolean(right.is_true()),
+                    // Shifts keep the left operand's type; the amount wraps at its width.
+                    BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                        let amount = right.bits as u32 % left.ty.bits();
+                        let bits = match op {
+                            BinaryOp::ShiftLeft => left.bits << amount,
+                            _ if left.ty.is_signed() => (left.signed() >> amount) as u64,
+                            _ => left.bits >> amount,
+                        };
+                        Value::new(left.ty, bits)
+                    }
+                    _ => {
+                        let ty = left.ty.common(right.ty);
+                        let (left, right) = (left.convert(ty), right.convert(ty));
+                        let ordering = if ty.is_signed() {
+                            left.signed().cmp(&right.signed())
+                        } else {
+                            left.bits.cmp(&right.bits)
+                        };
+                        let bits = match op {
+                            BinaryOp::Equal => return Some(Value::boolean(ordering.is_eq())),
+                            BinaryOp::NotEqual => return Some(Value::boolean(ordering.is_ne())),
+                            BinaryOp::Less => return Some(Value::boolean(ordering.is_lt())),
+                            BinaryOp::LessEqual => return Some(Value::boolean(ordering.is_le())),
+                            BinaryOp::Greater => return Some(Value::boolean(ordering.is_gt())),
+                            BinaryOp::GreaterEqual => return Some(Value::boolean(ordering.is_ge())),
+                            BinaryOp::BitOr => left.bits | right.bits,
+                            BinaryOp::BitXor => left.bits ^ right.bits,
+                            BinaryOp::BitAnd => left.bits & right.bits,
+                            BinaryOp::Add => left.bits.wrapping_add(right.bits),
+                            BinaryOp::Subtract => left.bits.wrapping_sub(right.bits),
+                            BinaryOp::Multiply => left.bits.wrapping_mul(right.bits),
+                            _ if right.bits == 0 => return None,
+                            BinaryOp::Divide if ty.is_signed() => left.signed().wrapping_div(right.signed()) as u64,
+                            BinaryOp::Remainder if ty.is_signed() => left.signed().wrapping_rem(right.signed()) as u64,
+                            BinaryOp::Divide => left.bits / right.bits,
+                            _ => left.bits % right.bits,
+                        };
+                        Value::new(ty, bits)
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(first) = rest.chars().next() {
+        let length = if first.is_ascii_digit() {
+            let length = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            let literal = &rest[..length];
+            let value = match literal.strip_prefix("0x") {
+                Some(hex_digits) => u64::from_str_radix(hex_digits, 16),
+                None => literal.parse(),
+            }
+            .map_err(|_| format!("Invalid number '{}'", literal))?;
+            tokens.push(Token::Number(value));
+            length
+        } else if first.is_ascii_alphabetic() || first == '_' {
+            let length = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Identifier(rest[..length].to_string()));
+            length
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(*symbol))
+                .ok_or_else(|| format!("Unexpected character '{}'", first))?;
+            tokens.push(Token::Symbol(symbol));
+            symbol.len()
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.position + offset)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.tokens.get(self.position).cloned().ok_or("Unexpected end of expression")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.next()? {
+            Token::Symbol(found) if found == symbol => Ok(()),
+            other => Err(format!("Expected '{}', found {:?}", symbol, other)),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expression, String> {
+        let Some(operators) = BINARY_OPERATORS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(&(_, op)) = operators
+            .iter()
+            .find(|(symbol, _)| self.peek(0) == Some(&Token::Symbol(symbol)))
+        {
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            left = Expression::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        match self.next()? {
+            Token::Symbol("-") => Ok(Expression::Unary(UnaryOp::Negate, Box::new(self.unary()?))),
+            Token::Symbol("!") => Ok(Expression::Unary(UnaryOp::Not, Box::new(self.unary()?))),
+            Token::Symbol("~") => Ok(Expression::Unary(UnaryOp::Complement, Box::new(self.unary()?))),
+            Token::Symbol("*") => {
+                let (width, signed) = self.pointer_cast()?.unwrap_or((4, false));
+                Ok(Expression::Load {
+                    width,
+                    signed,
+                    address: Box::new(self.unary()?),
+                })
+            }
+            Token::Symbol("(") => {
+                if let (Some(Token::Identifier(name)), Some(Token::Symbol(")"))) = (self.peek(0), self.peek(1)) {
+                    if let Some(ty) = Type::from_name(name) {
+                        self.position += 2;
+                        return Ok(Expression::Cast(ty, Box::new(self.unary()?)));
+                    }
+                }
+                let expression = self.binary(0)?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            Token::Number(value) => Ok(Expression::Literal(Value::literal(value))),
+            Token::Identifier(name) => match name.as_str() {
+                "pc" => Ok(Expression::Pc),
+                "cycle" => Ok(Expression::Cycle),
+                _ => register_index(&name)
+                    .map(Expression::Register)
+                    .ok_or_else(|| format!("Unknown register '{}'", name)),
+            },
+            other => Err(format!("Unexpected {:?}", other)),
+        }
+    }
+
+    /// Consumes a `(uN*)` or `(iN*)` cast after `*` and returns its width in bytes
+    /// and whether it is signed.
+    fn pointer_cast(&mut self) -> Result<Option<(u32, bool)>, String> {
+        let target = match (self.peek(0), self.peek(1), self.peek(2), self.peek(3)) {
+            (
+                Some(Token::Symbol("(")),
+                Some(Token::Identifier(ty)),
+                Some(Token::Symbol("*")),
+                Some(Token::Symbol(")")),
+            ) => match ty.as_str() {
+                "u8" => (1, false),
+                "u16" => (2, false),
+                "u32" => (4, false),
+                "u64" => (8, false),
+                "i8" => (1, true),
+                "i16" => (2, true),
+                "i32" => (4, true),
+                "i64" => (8, true),
+                other => return Err(format!("Unsupported type '{}', use u8-u64 or i8-i64", other)),
+            },
+            _ => return Ok(None),
+        };
+        self.position += 4;
+        Ok(Some(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::Program;
+
+    fn emulator() -> Emulator {
+        let program = Program {
+            entry: 0x1000,
+            segments: Vec::new(),
+            elf: Vec::new(),
+        };
+        let mut emulator = Emulator::new(&program, Vec::new());
+        emulator.registers[10] = u32::MAX; // a0 = -1
+        emulator.registers[11] = 7; // a1
+        emulator.registers[2] = 0x2000; // sp
+        emulator.memory.write_bytes(0x2000, &[0xfe, 0xff, 0xff, 0xff, 0x80, 0, 0, 0]);
+        emulator.cycle = 5_000_000_000;
+        emulator
+    }
+
+    fn evaluate(text: &str) -> Value {
+        Expression::parse(text).unwrap().evaluate(&emulator()).unwrap()
+    }
+
+    #[test]
+    fn parses_precedence_and_casts() {
+        let literal = |value| Box::new(Expression::Literal(Value::literal(value)));
+        assert_eq!(
+            Expression::parse("1 + 2 * 3").unwrap(),
+            Expression::Binary(
+                BinaryOp::Add,
+                literal(1),
+                Box::new(Expression::Binary(BinaryOp::Multiply, literal(2), literal(3))),
+            )
+        );
+        assert_eq!(
+            Expression::parse("-1").unwrap(),
+            Expression::Unary(UnaryOp::Negate, literal(1))
+        );
+        assert_eq!(
+            Expression::parse("(i32)a0 < 0").unwrap(),
+            Expression::Binary(
+                BinaryOp::Less,
+                Box::new(Expression::Cast(Type::I32, Box::new(Expression::Register(10)))),
+                literal(0),
+            )
+        );
+        assert_eq!(
+            Expression::parse("*(i16*)(sp + 4)").unwrap(),
+            Expression::Load {
+                width: 2,
+                signed: true,
+                address: Box::new(Expression::Binary(BinaryOp::Add, Box::new(Expression::Register(2)), literal(4))),
+            }
+        );
+        // A parenthesised register is not a cast
+        assert_eq!(Expression::parse("(a0)").unwrap(), Expression::Register(10));
+        assert!(Expression::parse("*(f32*)sp").is_err());
+        assert!(Expression::parse("a0 ==").is_err());
+        assert!(Expression::parse("x99").is_err());
+    }
+
+    #[test]
+    fn literals_take_the_narrowest_type() {
+        assert_eq!(Value::literal(5).ty(), Type::I32);
+        assert_eq!(Value::literal(0x8000_0000).ty(), Type::U32);
+        assert_eq!(Value::literal(0x1_0000_0000).ty(), Type::I64);
+        assert_eq!(Value::literal(u64::MAX).ty(), Type::U64);
+    }
+
+    #[test]
+    fn registers_compare_at_32_bits() {
+        assert!(evaluate("a0 == -1").is_true());
+        assert!(evaluate("a0 == 0xffffffff").is_true());
+        assert!(evaluate("-1 == 0xffffffff").is_true());
+        assert_eq!(evaluate("-1").bits(), 0xffff_ffff);
+        assert_eq!(evaluate("a0 + 1").bits(), 0);
+        assert_eq!(evaluate("~0").bits(), 0xffff_ffff);
+        // Registers are unsigned, so -1 is the largest value
+        assert!(evaluate("a0 > a1").is_true());
+        assert!(!evaluate("a0 < 0").is_true());
+    }
+
+    #[test]
+    fn signed_operators_follow_the_operand_types() {
+        assert!(evaluate("(i32)a0 < 0").is_true());
+        assert!(evaluate("(i32)a0 < (i32)a1").is_true());
+        assert_eq!(evaluate("(i32)a0 / 2").to_string(), "0");
+        assert_eq!(evaluate("a0 / 2").bits(), 0x7fff_ffff);
+        assert_eq!(evaluate("-7 % 3").to_string(), "-1");
+        assert_eq!(evaluate("(i32)a0 >> 4").bits(), 0xffff_ffff);
+        assert_eq!(evaluate("a0 >> 4").bits(), 0x0fff_ffff);
+        assert_eq!(evaluate("(i64)a0").bits(), 0xffff_ffff);
+        assert_eq!(evaluate("(i64)(i32)a0").bits(), u64::MAX);
+        assert_eq!(evaluate("(u64)a0").bits(), 0xffff_ffff);
+        assert_eq!(evaluate("(u32)-2").to_string(), "4294967294");
+    }
+
+    #[test]
+    fn loads_cycles_and_wide_values() {
+        assert_eq!(evaluate("*sp").bits(), 0xffff_fffe);
+        assert_eq!(evaluate("*(i32*)sp").to_string(), "-2");
+        assert_eq!(evaluate("*(i8*)sp").to_string(), "-2");
+        assert_eq!(evaluate("*(u8*)sp").to_string(), "254");
+        assert_eq!(evaluate("*(u64*)sp").bits(), 0x80_ffff_fffe);
+        assert!(evaluate("*(i32*)sp == -2").is_true());
+        assert!(evaluate("cycle > 4000000000").is_true());
+        assert_eq!(evaluate("cycle + 1").bits(), 5_000_000_001);
+        assert!(Expression::parse("a1 / (a0 + 1)").unwrap().evaluate(&emulator()).is_none());
+        // Short-circuiting skips the division
+        assert!(!evaluate("a0 + 1 != 0 && a1 / (a0 + 1)").is_true());
+    }
+
+    #[test]
+    fn values_fit_their_stores() {
+        assert!(evaluate("-1").fits(1));
+        assert!(evaluate("255").fits(1));
+        assert!(!evaluate("256").fits(1));
+        assert!(!evaluate("a0").fits(1));
+        assert!(evaluate("a0").fits(4));
+        assert!(!evaluate("(i64)-1 << 40").fits(4));
+    }
+}
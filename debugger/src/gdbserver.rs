@@ -0,0 +1,531 @@
+// GDB Remote Serial Protocol server
+// Exposes a PicoDebugger session to riscv gdb or lldb over TCP
+
+use crate::emulator::{StopReason, Trap, MAX_MEMORY_READ};
+use crate::PicoDebugger;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// Cycles executed between checks for a Ctrl-C from the client while continuing.
+const INTERRUPT_POLL_CYCLES: u64 = 100_000;
+
+/// Largest packet advertised in qSupported; an `m` reply is two hex digits per byte.
+const PACKET_SIZE: usize = 2 * MAX_MEMORY_READ;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGABRT: u8 = 6;
+const SIGBUS: u8 = 10;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>riscv:rv32</architecture>
+  <feature name="org.gnu.gdb.riscv.cpu">
+    <reg name="zero" bitsize="32" type="int" regnum="0"/>
+    <reg name="ra" bitsize="32" type="code_ptr"/>
+    <reg name="sp" bitsize="32" type="data_ptr"/>
+    <reg name="gp" bitsize="32" type="data_ptr"/>
+    <reg name="tp" bitsize="32" type="data_ptr"/>
+    <reg name="t0" bitsize="32" type="int"/>
+    <reg name="t1" bitsize="32" type="int"/>
+    <reg name="t2" bitsize="32" type="int"/>
+    <reg name="fp" bitsize="32" type="data_ptr"/>
+    <reg name="s1" bitsize="32" type="int"/>
+    <reg name="a0" bitsize="32" type="int"/>
+    <reg name="a1" bitsize="32" type="int"/>
+    <reg name="a2" bitsize="32" type="int"/>
+    <reg name="a3" bitsize="32" type="int"/>
+    <reg name="a4" bitsize="32" type="int"/>
+    <reg name="a5" bitsize="32" type="int"/>
+    <reg name="a6" bitsize="32" type="int"/>
+    <reg name="a7" bitsize="32" type="int"/>
+    <reg name="s2" bitsize="32" type="int"/>
+    <reg name="s3" bitsize="32" type="int"/>
+    <reg name="s4" bitsize="32" type="int"/>
+    <reg name="s5" bitsize="32" type="int"/>
+    <reg name="s6" bitsize="32" type="int"/>
+    <reg name="s7" bitsize="32" type="int"/>
+    <reg name="s8" bitsize="32" type="int"/>
+    <reg name="s9" bitsize="32" type="int"/>
+    <reg name="s10" bitsize="32" type="int"/>
+    <reg name="s11" bitsize="32" type="int"/>
+    <reg name="t3" bitsize="32" type="int"/>
+    <reg name="t4" bitsize="32" type="int"/>
+    <reg name="t5" bitsize="32" type="int"/>
+    <reg name="t6" bitsize="32" type="int"/>
+    <reg name="pc" bitsize="32" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// Register number gdb uses for the program counter in the riscv32 description.
+const PC_REGISTER: usize = 32;
+
+pub struct GdbServer {
+    debugger: PicoDebugger,
+    no_ack: bool,
+    /// A byte read while polling for Ctrl-C that starts the next packet.
+    pending: Option<u8>,
+}
+
+enum Resume {
+    Step,
+    Continue,
+}
+
+impl GdbServer {
+    pub fn new(debugger: PicoDebugger) -> Self {
+        Self {
+            debugger,
+            no_ack: false,
+            pending: None,
+        }
+    }
+
+    /// Listens on `port` and serves a single client until it detaches or kills the target.
+    pub fn serve(&mut self, port: u16) -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        println!("Waiting for gdb on 127.0.0.1:{}", port);
+        let (stream, peer) = listener.accept()?;
+        println!("Debugger attached from {}", peer);
+        stream.set_nodelay(true)?;
+        self.handle_connection(stream)?;
+        println!("Debugger detached");
+        Ok(())
+    }
+
+    fn handle_connection(&mut self, mut stream: TcpStream) -> Result<(), Box<dyn std::error::Error>> {
+        while let Some(packet) = self.read_packet(&mut stream)? {
+            let reply = match packet.as_bytes().first() {
+                Some(b'c') => self.resume(&mut stream, Resume::Continue, &packet[1..])?,
+                Some(b's') => self.resume(&mut stream, Resume::Step, &packet[1..])?,
+                Some(b'k') => return Ok(()),
+                Some(b'D') => {
+                    self.send_packet(&mut stream, "OK")?;
+                    return Ok(());
+                }
+                _ if packet.starts_with("vCont;") => {
+                    let action = &packet["vCont;".len()..];
+                    match action.as_bytes().first() {
+                        Some(b's') | Some(b'S') => self.resume(&mut stream, Resume::Step, "")?,
+                        _ => self.resume(&mut stream, Resume::Continue, "")?,
+                    }
+                }
+                _ => self.handle_query(&packet),
+            };
+            self.send_packet(&mut stream, &reply)?;
+        }
+        Ok(())
+    }
+
+    fn handle_query(&mut self, packet: &str) -> String {
+        let result = match packet.as_bytes().first() {
+            Some(b'?') => Ok(format!("S{:02x}", SIGTRAP)),
+            Some(b'g') => self.read_registers(),
+            Some(b'G') => self.write_registers(&packet[1..]),
+            Some(b'p') => self.read_register(&packet[1..]),
+            Some(b'P') => self.write_register(&packet[1..]),
+            Some(b'm') => self.read_memory(&packet[1..]),
+            Some(b'M') => self.write_memory(&packet[1..]),
+            Some(b'Z') => self.set_breakpoint(&packet[1..], true),
+            Some(b'z') => self.set_breakpoint(&packet[1..], false),
+            Some(b'H') | Some(b'T') => Ok("OK".to_string()),
+            _ => Ok(self.handle_general_query(packet)),
+        };
+        result.unwrap_or_else(|_| "E01".to_string())
+    }
+
+    fn handle_general_query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!(
+                "PacketSize={:x};qXfer:features:read+;swbreak+;vContSupported+;QStartNoAckMode+",
+                PACKET_SIZE
+            );
+        }
+        if packet == "QStartNoAckMode" {
+            self.no_ack = true;
+            return "OK".to_string();
+        }
+        if let Some(annex) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            return xfer_chunk(TARGET_XML, annex);
+        }
+        let triple = hex::encode(b"riscv32-unknown-unknown-elf");
+        match packet {
+            "vCont?" => "vCont;c;C;s;S".to_string(),
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            "qOffsets" => "Text=0;Data=0;Bss=0".to_string(),
+            "qSymbol::" => "OK".to_string(),
+            "qHostInfo" => format!("triple:{};endian:little;ptrsize:4;", triple),
+            "qProcessInfo" => format!("pid:1;triple:{};endian:little;ptrsize:4;", triple),
+            _ => String::new(),
+        }
+    }
+
+    fn resume(&mut self, stream: &mut TcpStream, mode: Resume, address: &str) -> Result<String, Box<dyn std::error::Error>> {
+        if !address.is_empty() {
+            self.debugger.emulator_mut()?.pc = u32::from_str_radix(address, 16)?;
+        }
+
+        let stop = match mode {
+            Resume::Step => match self.debugger.step_execution() {
+                Ok(_) => None,
+                Err(e) => match e.downcast::<Trap>() {
+                    Ok(trap) => Some(StopReason::Trapped(*trap)),
+                    Err(e) => return Err(e),
+                },
+            },
+            Resume::Continue => loop {
+                match self.debugger.run_for(Some(INTERRUPT_POLL_CYCLES))? {
+                    StopReason::CycleLimit => {
+                        if self.interrupt_requested(stream)? {
+                            return Ok(format!("T{:02x}thread:1;", SIGINT));
+                        }
+                    }
+                    other => break Some(other),
+                }
+            },
+        };
+
+        let emulator = self.debugger.emulator()?;
+        Ok(match stop {
+            Some(StopReason::Halted(code)) => format!("W{:02x}", code & 0xff),
+            Some(StopReason::Trapped(trap)) => {
+                let signal = match trap {
+                    Trap::Breakpoint { .. } => SIGTRAP,
+                    Trap::IllegalInstruction { .. }
+                    | Trap::UnsupportedSyscall { .. }
+                    | Trap::PrecompileFailed { .. }
+                    | Trap::HintLengthMismatch { .. } => SIGILL,
+                    _ => SIGBUS,
+                };
+                println!("Target stopped: {}", trap);
+                format!("T{:02x}thread:1;", signal)
+            }
+            Some(StopReason::Breakpoint(_)) => format!("T{:02x}thread:1;swbreak:;", SIGTRAP),
+            Some(StopReason::Panicked) => {
+                println!("Target panicked");
+                format!("T{:02x}thread:1;", SIGABRT)
+            }
+            _ if emulator.is_halted() => format!("W{:02x}", emulator.exit_code().unwrap_or(0) & 0xff),
+            _ => format!("T{:02x}thread:1;", SIGTRAP),
+        })
+    }
+
+    fn read_registers(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let emulator = self.debugger.emulator()?;
+        let mut reply = String::with_capacity(33 * 8);
+        for value in emulator.registers.iter().chain(std::iter::once(&emulator.pc)) {
+            reply.push_str(&hex::encode(value.to_le_bytes()));
+        }
+        Ok(reply)
+    }
+
+    fn write_registers(&mut self, data: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let bytes = hex::decode(data)?;
+        let emulator = self.debugger.emulator_mut()?;
+        for (index, word) in bytes.chunks_exact(4).enumerate().take(33) {
+            let value = u32::from_le_bytes(word.try_into()?);
+            match index {
+                0 => {}
+                PC_REGISTER => emulator.pc = value,
+                _ => emulator.registers[index] = value,
+            }
+        }
+        Ok("OK".to_string())
+    }
+
+    fn read_register(&self, data: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let index = usize::from_str_radix(data, 16)?;
+        let emulator = self.debugger.emulator()?;
+        let value = match index {
+            0..=31 => emulator.registers[index],
+            PC_REGISTER => emulator.pc,
+            _ => return Ok("E45".to_string()),
+        };
+        Ok(hex::encode(value.to_le_bytes()))
+    }
+
+    fn write_register(&mut self, data: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let (index, value) = data.split_once('=').ok_or("malformed P packet")?;
+        let index = usize::from_str_radix(index, 16)?;
+        let value = u32::from_le_bytes(hex::decode(value)?.as_slice().try_into()?);
+        let emulator = self.debugger.emulator_mut()?;
+        match index {
+            0 => {}
+            1..=31 => emulator.registers[index] = value,
+            PC_REGISTER => emulator.pc = value,
+            _ => return Ok("E45".to_string()),
+        }
+        Ok("OK".to_string())
+    }
+
+    fn read_memory(&self, data: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let (address, length) = parse_address_length(data)?;
+        if length > PACKET_SIZE / 2 {
+            return Err(format!("memory read of {} bytes exceeds the packet size", length).into());
+        }
+        let bytes = self.debugger.emulator()?.memory.read_bytes(address, length);
+        Ok(hex::encode(&bytes))
+    }
+
+    fn write_memory(&mut self, data: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let (range, payload) = data.split_once(':').ok_or("malformed M packet")?;
+        let (address, length) = parse_address_length(range)?;
+        let bytes = hex::decode(payload)?;
+        if bytes.len() != length {
+            return Err("M packet length mismatch".into());
+        }
+        self.debugger.emulator_mut()?.memory.write_bytes(address, &bytes);
+        Ok("OK".to_string())
+    }
+
+    fn set_breakpoint(&mut self, data: &str, insert: bool) -> Result<String, Box<dyn std::error::Error>> {
+        let mut fields = data.split(',');
+        let kind = fields.next().ok_or("malformed breakpoint packet")?;
+        // Only software (0) and hardware (1) execution breakpoints are supported.
+        if kind != "0" && kind != "1" {
+            return Ok(String::new());
+        }
+        let address = u64::from_str_radix(fields.next().ok_or("missing address")?, 16)?;
+        if insert {
+            self.debugger.add_breakpoint(address)?;
+        } else {
+            self.debugger.remove_breakpoint(address)?;
+        }
+        Ok("OK".to_string())
+    }
+
+    fn read_packet(&mut self, stream: &mut TcpStream) -> io::Result<Option<String>> {
+        loop {
+            loop {
+                match self.read_byte(stream)? {
+                    None => return Ok(None),
+                    // Acks and a Ctrl-C while already stopped are skipped.
+                    Some(b'$') => break,
+                    Some(_) => {}
+                }
+            }
+
+            let mut payload = Vec::new();
+            loop {
+                match self.read_byte(stream)? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => payload.push(byte),
+                }
+            }
+            let mut digits = [0u8; 2];
+            stream.read_exact(&mut digits)?;
+
+            if !self.no_ack {
+                let expected = checksum(&payload);
+                let received = std::str::from_utf8(&digits)
+                    .ok()
+                    .and_then(|text| u8::from_str_radix(text, 16).ok());
+                if received != Some(expected) {
+                    stream.write_all(b"-")?;
+                    continue;
+                }
+                stream.write_all(b"+")?;
+            }
+
+            return Ok(Some(String::from_utf8_lossy(&unescape(&payload)).into_owned()));
+        }
+    }
+
+    fn read_byte(&mut self, stream: &mut TcpStream) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.pending.take() {
+            return Ok(Some(byte));
+        }
+        let mut byte = [0u8; 1];
+        Ok((stream.read(&mut byte)? == 1).then_some(byte[0]))
+    }
+
+    /// Polls for a Ctrl-C without blocking. Any other byte is kept for `read_packet`.
+    fn interrupt_requested(&mut self, stream: &mut TcpStream) -> io::Result<bool> {
+        if self.pending.is_some() {
+            return Ok(false);
+        }
+        stream.set_nonblocking(true)?;
+        let mut byte = [0u8; 1];
+        let result = match stream.read(&mut byte) {
+            Ok(1) if byte[0] == 0x03 => Ok(true),
+            Ok(1) => {
+                self.pending = Some(byte[0]);
+                Ok(false)
+            }
+            Ok(_) => Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        };
+        stream.set_nonblocking(false)?;
+        result
+    }
+
+    fn send_packet(&mut self, stream: &mut TcpStream, payload: &str) -> io::Result<()> {
+        let frame = frame(payload);
+        loop {
+            stream.write_all(&frame)?;
+            stream.flush()?;
+            if self.no_ack {
+                return Ok(());
+            }
+            match self.read_byte(stream)? {
+                Some(b'-') => {}
+                // A packet the client sent before acking belongs to read_packet
+                Some(byte) if byte != b'+' => {
+                    self.pending = Some(byte);
+                    return Ok(());
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+fn xfer_chunk(document: &str, annex: &str) -> String {
+    let Some((offset, length)) = annex.split_once(',') else {
+        return "E00".to_string();
+    };
+    let (Ok(offset), Ok(length)) = (usize::from_str_radix(offset, 16), usize::from_str_radix(length, 16)) else {
+        return "E00".to_string();
+    };
+    let bytes = document.as_bytes();
+    if offset >= bytes.len() {
+        return "l".to_string();
+    }
+    let end = (offset + length).min(bytes.len());
+    let marker = if end == bytes.len() { 'l' } else { 'm' };
+    format!("{}{}", marker, &document[offset..end])
+}
+
+fn parse_address_length(data: &str) -> Result<(u32, usize), Box<dyn std::error::Error>> {
+    let (address, length) = data.split_once(',').ok_or("malformed memory packet")?;
+    Ok((u32::from_str_radix(address, 16)?, usize::from_str_radix(length, 16)?))
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+/// `$<escaped payload>#<checksum>`, with the checksum taken over the escaped bytes.
+fn frame(payload: &str) -> Vec<u8> {
+    let escaped = escape(payload.as_bytes());
+    let mut frame = Vec::with_capacity(escaped.len() + 4);
+    frame.push(b'$');
+    frame.extend_from_slice(&escaped);
+    frame.extend_from_slice(format!("#{:02x}", checksum(&escaped)).as_bytes());
+    frame
+}
+
+fn escape(payload: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(payload.len());
+    for &byte in payload {
+        if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+            escaped.push(b'}');
+            escaped.push(byte ^ 0x20);
+        } else {
+            escaped.push(byte);
+        }
+    }
+    escaped
+}
+
+fn unescape(payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(payload.len());
+    let mut iter = payload.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == b'}' {
+            if let Some(&next) = iter.next() {
+                bytes.push(next ^ 0x20);
+            }
+        } else {
+            bytes.push(byte);
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn connected_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (server, client)
+    }
+
+    #[test]
+    fn frames_carry_the_checksum_of_the_escaped_payload() {
+        assert_eq!(frame("OK"), b"$OK#9a");
+        assert_eq!(frame(""), b"$#00");
+        // '#' is 0x23, sent as '}' 0x03
+        assert_eq!(frame("a#b"), b"$a}\x03b#43");
+    }
+
+    #[test]
+    fn escaping_round_trips_every_reserved_byte() {
+        let payload = b"$x#y}z*w".to_vec();
+        let escaped = escape(&payload);
+        assert_eq!(escaped, b"}\x04x}\x03y}]z}\nw");
+        assert!(!escaped[1..].iter().any(|b| matches!(b, b'$' | b'#' | b'*')));
+        assert_eq!(unescape(&escaped), payload);
+    }
+
+    #[test]
+    fn read_packet_naks_a_bad_checksum_and_unescapes() {
+        let (mut server, mut client) = connected_pair();
+        client.write_all(b"+$m0,4#00").unwrap();
+        client.write_all(&frame("X0,1:}")).unwrap();
+        let mut gdb = GdbServer::new(PicoDebugger::new());
+        assert_eq!(gdb.read_packet(&mut server).unwrap().as_deref(), Some("X0,1:}"));
+
+        let mut acks = [0u8; 2];
+        client.read_exact(&mut acks).unwrap();
+        assert_eq!(&acks, b"-+");
+    }
+
+    #[test]
+    fn bytes_read_while_polling_for_an_interrupt_start_the_next_packet() {
+        let (mut server, mut client) = connected_pair();
+        let mut gdb = GdbServer::new(PicoDebugger::new());
+        client.write_all(&frame("?")).unwrap();
+        while gdb.pending.is_none() {
+            assert!(!gdb.interrupt_requested(&mut server).unwrap());
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(gdb.read_packet(&mut server).unwrap().as_deref(), Some("?"));
+
+        client.write_all(&[0x03]).unwrap();
+        while !gdb.interrupt_requested(&mut server).unwrap() {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(gdb.pending.is_none());
+    }
+
+    #[test]
+    fn memory_reads_are_capped_at_the_packet_size() {
+        let mut gdb = GdbServer::new(PicoDebugger::new());
+        assert!(gdb.handle_query("qSupported:multiprocess+").starts_with("PacketSize=4000;"));
+        assert!(gdb.read_memory("0,2001").unwrap_err().to_string().contains("exceeds the packet size"));
+        assert_eq!(gdb.handle_query("m0,2001"), "E01");
+        assert!(parse_address_length("10,2000").is_ok_and(|range| range == (0x10, 0x2000)));
+        assert!(parse_address_length("10").is_err());
+    }
+
+    #[test]
+    fn xfer_chunks_mark_the_last_piece() {
+        assert_eq!(xfer_chunk("abcdef", "0,4"), "mabcd");
+        assert_eq!(xfer_chunk("abcdef", "4,4"), "lef");
+        assert_eq!(xfer_chunk("abcdef", "6,4"), "l");
+        assert_eq!(xfer_chunk("abcdef", "zz"), "E00");
+    }
+}
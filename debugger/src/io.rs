@@ -0,0 +1,354 @@
+// Guest IO log
+// Records every ecall, how the hint input stream is consumed and the committed public values
+
+use crate::callstack::CallStack;
+use crate::debuginfo::{self, DebugInfo};
+use crate::emulator::{Memory, StepEvent, Trap};
+use crate::syscalls;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyscallRecord {
+    pub cycle: u64,
+    pub code: u32,
+    pub arguments: [u32; 3],
+    pub result: Option<u32>,
+    /// The ecall's PC followed by the call site of each enclosing frame.
+    pub frames: Vec<u32>,
+}
+
+/// One `hint_read` of an input chunk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputRead {
+    pub cycle: u64,
+    pub pc: u32,
+    pub chunk: usize,
+    /// Position of the chunk in the concatenated input stream.
+    pub offset: u64,
+    /// Bytes the guest asked for.
+    pub requested: u32,
+    /// Bytes the host actually supplied in this chunk.
+    pub available: u32,
+}
+
+impl InputRead {
+    /// The guest asked for a different length than the host wrote, the usual
+    /// sign of a host encoding that disagrees with the guest's decoding.
+    pub fn is_mismatch(&self) -> bool {
+        self.requested != self.available
+    }
+}
+
+/// One write to the public values file descriptor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicValuesWrite {
+    pub cycle: u64,
+    /// Offset of these bytes in the committed public-values stream.
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+    pub frames: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IoLog {
+    pub syscalls: Vec<SyscallRecord>,
+    pub input_reads: Vec<InputRead>,
+    pub public_values: Vec<PublicValuesWrite>,
+    /// Lengths of input chunks not yet read, mirroring the emulator's queue.
+    pending_chunks: VecDeque<u32>,
+    chunks_read: usize,
+    input_offset: u64,
+    public_values_length: usize,
+    /// Input and output position on entering an unconstrained block, which the emulator rolls back to on exit.
+    unconstrained: Option<UnconstrainedLog>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UnconstrainedLog {
+    pending_chunks: VecDeque<u32>,
+    chunks_read: usize,
+    input_offset: u64,
+    public_values_writes: usize,
+    public_values_length: usize,
+    /// Hint chunks written inside the block; they stay queued after it.
+    hints_written: Vec<u32>,
+}
+
+impl IoLog {
+    /// Starts a log for a run whose hint queue initially holds `inputs`.
+    pub fn new(inputs: &[Vec<u8>]) -> Self {
+        Self {
+            pending_chunks: inputs.iter().map(|chunk| chunk.len() as u32).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Records `event` if it was an ecall. `memory` must be the state right
+    /// after the step and `call_stack` must already include it.
+    pub fn observe(&mut self, event: &StepEvent, memory: &Memory, call_stack: &CallStack) {
+        let Some(syscall) = &event.syscall else {
+            return;
+        };
+        let [a0, a1, a2] = syscall.arguments;
+        let frames = call_stack.frame_addresses(event.pc);
+
+        match syscall.code {
+            syscalls::HINT_READ => {
+                let available = self.pending_chunks.pop_front().unwrap_or(0);
+                self.input_reads.push(InputRead {
+                    cycle: event.cycle,
+                    pc: event.pc,
+                    chunk: self.chunks_read,
+                    offset: self.input_offset,
+                    requested: a1,
+                    available,
+                });
+                self.chunks_read += 1;
+                self.input_offset += available as u64;
+            }
+            syscalls::WRITE if a0 == syscalls::FD_HINT => {
+                self.pending_chunks.push_back(a2);
+                if let Some(state) = &mut self.unconstrained {
+                    state.hints_written.push(a2);
+                }
+            }
+            syscalls::WRITE if a0 == syscalls::FD_PUBLIC_VALUES => {
+                self.public_values.push(PublicValuesWrite {
+                    cycle: event.cycle,
+                    offset: self.public_values_length,
+                    bytes: memory.read_bytes(a1, a2 as usize),
+                    frames: frames.clone(),
+                });
+                self.public_values_length += a2 as usize;
+            }
+            syscalls::ENTER_UNCONSTRAINED => {
+                self.unconstrained = Some(UnconstrainedLog {
+                    pending_chunks: self.pending_chunks.clone(),
+                    chunks_read: self.chunks_read,
+                    input_offset: self.input_offset,
+                    public_values_writes: self.public_values.len(),
+                    public_values_length: self.public_values_length,
+                    hints_written: Vec::new(),
+                });
+            }
+            syscalls::EXIT_UNCONSTRAINED => {
+                // Reads inside the block stay in the log; the chunks they took are read again
+                if let Some(state) = self.unconstrained.take() {
+                    self.pending_chunks = state.pending_chunks;
+                    self.pending_chunks.extend(state.hints_written);
+                    self.chunks_read = state.chunks_read;
+                    self.input_offset = state.input_offset;
+                    self.public_values.truncate(state.public_values_writes);
+                    self.public_values_length = state.public_values_length;
+                }
+            }
+            _ => {}
+        }
+
+        self.syscalls.push(SyscallRecord {
+            cycle: event.cycle,
+            code: syscall.code,
+            arguments: syscall.arguments,
+            result: syscall.result,
+            frames,
+        });
+    }
+
+    /// Records a `hint_read` the emulator refused because its length did not
+    /// match the next chunk, so the mismatch shows up in the input log.
+    pub fn observe_trap(&mut self, trap: &Trap, cycle: u64) {
+        let Trap::HintLengthMismatch { pc, requested, available } = *trap else {
+            return;
+        };
+        // Resuming re-executes the same ecall and traps again
+        if self.input_reads.last().is_some_and(|read| read.cycle == cycle && read.pc == pc) {
+            return;
+        }
+        self.input_reads.push(InputRead {
+            cycle,
+            pc,
+            chunk: self.chunks_read,
+            offset: self.input_offset,
+            requested,
+            available: available.unwrap_or(0),
+        });
+    }
+
+    /// The public values exactly as committed, in order.
+    pub fn public_values_stream(&self) -> Vec<u8> {
+        self.public_values.iter().flat_map(|write| write.bytes.iter().copied()).collect()
+    }
+
+    /// One line per ecall: cycle, decoded call, result and enclosing function.
+    pub fn syscall_lines(&self, debug_info: Option<&DebugInfo>, last: Option<usize>) -> Vec<String> {
+        let skip = last.map(|last| self.syscalls.len().saturating_sub(last)).unwrap_or(0);
+        self.syscalls[skip..]
+            .iter()
+            .map(|record| {
+                let mut line = format!(
+                    "{:>10}  {}",
+                    record.cycle,
+                    syscalls::describe(record.code, record.arguments)
+                );
+                if let Some(result) = record.result {
+                    line.push_str(&format!(" -> {}", result));
+                }
+                line.push_str(&format!("  at {}", debuginfo::describe_address(debug_info, record.frames[0])));
+                line
+            })
+            .collect()
+    }
+
+    /// How the input stream was consumed, flagging reads whose length does
+    /// not match the chunk the host supplied.
+    pub fn input_lines(&self, debug_info: Option<&DebugInfo>) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .input_reads
+            .iter()
+            .map(|read| {
+                let mut line = format!(
+                    "chunk {:<3} offset {:<8} length {:<8} cycle {:<10} at {}",
+                    read.chunk,
+                    read.offset,
+                    read.requested,
+                    read.cycle,
+                    debuginfo::describe_address(debug_info, read.pc)
+                );
+                if read.is_mismatch() {
+                    line.push_str(&format!(
+                        "  MISMATCH: guest read {} bytes, host supplied {}",
+                        read.requested, read.available
+                    ));
+                }
+                line
+            })
+            .collect();
+        for (index, length) in self.pending_chunks.iter().enumerate() {
+            lines.push(format!(
+                "chunk {:<3} offset {:<8} length {:<8} never read",
+                self.chunks_read + index,
+                self.input_offset + self.pending_chunks.iter().take(index).map(|&length| length as u64).sum::<u64>(),
+                length
+            ));
+        }
+        lines
+    }
+
+    /// Every public-values write with its bytes and the call stack that made it,
+    /// followed by the complete committed stream.
+    pub fn public_values_lines(&self, debug_info: Option<&DebugInfo>) -> Vec<String> {
+        let mut lines = Vec::new();
+        for write in &self.public_values {
+            lines.push(format!(
+                "offset {:<6} {} bytes at cycle {}: 0x{}",
+                write.offset,
+                write.bytes.len(),
+                write.cycle,
+                hex::encode(&write.bytes)
+            ));
+            for (index, &address) in write.frames.iter().enumerate() {
+                lines.push(format!("    #{:<2} {}", index, debuginfo::describe_address(debug_info, address)));
+            }
+        }
+        let stream = self.public_values_stream();
+        lines.push(format!("committed stream ({} bytes): 0x{}", stream.len(), hex::encode(&stream)));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{Emulator, Program, Segment};
+
+    const ECALL: u32 = 0x0000_0073;
+
+    /// Runs `code` at address 0 until it halts or traps, logging every step.
+    fn run(code: &[u32], inputs: Vec<Vec<u8>>, setup: impl FnOnce(&mut Emulator)) -> (Emulator, IoLog, Option<Trap>) {
+        let data: Vec<u8> = code.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let program = Program {
+            entry: 0,
+            segments: vec![Segment { vaddr: 0, mem_size: data.len() as u32, data, flags: 5 }],
+            elf: Vec::new(),
+        };
+        let mut emulator = Emulator::new(&program, inputs.clone());
+        setup(&mut emulator);
+        let mut log = IoLog::new(&inputs);
+        let mut call_stack = CallStack::default();
+        while !emulator.is_halted() {
+            match emulator.step() {
+                Ok(event) => {
+                    call_stack.observe(event.pc, &event.instruction, event.next_pc, &emulator.registers);
+                    log.observe(&event, &emulator.memory, &call_stack);
+                }
+                Err(trap) => {
+                    log.observe_trap(&trap, emulator.cycle);
+                    return (emulator, log, Some(trap));
+                }
+            }
+        }
+        (emulator, log, None)
+    }
+
+    #[test]
+    fn a_read_of_the_wrong_length_is_flagged_as_a_mismatch() {
+        let code = [
+            0x1000_0513, 0x0040_0593, 0x0f10_0293, ECALL, // hint_read 4 bytes to 0x100
+            0x2000_0513, 0x0040_0593, 0x0f10_0293, ECALL, // hint_read 4 bytes to 0x200
+        ];
+        let (mut emulator, mut log, trap) = run(&code, vec![vec![1, 2, 3, 4], vec![5, 6, 7, 8, 9, 10]], |_| {});
+        let trap = trap.unwrap();
+        assert_eq!(trap, Trap::HintLengthMismatch { pc: 28, requested: 4, available: Some(6) });
+
+        assert_eq!(log.input_reads.len(), 2);
+        assert!(!log.input_reads[0].is_mismatch());
+        let read = &log.input_reads[1];
+        assert_eq!((read.pc, read.chunk, read.offset, read.requested, read.available), (28, 1, 4, 4, 6));
+        assert!(read.is_mismatch());
+
+        // Resuming traps on the same ecall without logging it twice
+        assert_eq!(emulator.step().unwrap_err(), trap);
+        log.observe_trap(&trap, emulator.cycle);
+        assert_eq!(log.input_reads.len(), 2);
+
+        let lines = log.input_lines(None);
+        assert!(!lines[0].contains("MISMATCH"));
+        assert!(lines[1].starts_with("chunk 1   offset 4 "));
+        assert!(lines[1].ends_with("MISMATCH: guest read 4 bytes, host supplied 6"));
+        // The refused chunk was never consumed
+        assert!(lines[2].starts_with("chunk 1   offset 4        length 6        never read"));
+    }
+
+    #[test]
+    fn public_values_offsets_follow_the_committed_stream() {
+        let code = [
+            0x0000_25b7, 0x0030_0513, 0x0040_0613, 0x0020_0293, ECALL, // commit 4 bytes from 0x2000
+            0x0030_0293, ECALL, 0x0202_8863, // enter_unconstrained; skip the block once it returns 0 in t0
+            0x0000_25b7, 0x0030_0513, 0x0030_0613, 0x0020_0293, ECALL, // commit 3 bytes, rolled back
+            0x0000_3537, 0x0020_0593, 0x0f10_0293, ECALL, // hint_read 2 bytes, read again below
+            0x0040_0293, ECALL, // exit_unconstrained
+            0x0000_25b7, 0x0045_8593, 0x0030_0513, 0x0020_0613, 0x0020_0293, ECALL, // commit 2 bytes from 0x2004
+            0x0000_3537, 0x0020_0593, 0x0f10_0293, ECALL, // hint_read 2 bytes
+            0x0000_0293, 0x0000_0513, ECALL, // halt
+        ];
+        let (emulator, log, trap) = run(&code, vec![vec![7, 8]], |emulator| emulator.memory.write_bytes(0x2000, b"abcdef"));
+        assert_eq!(trap, None);
+        assert_eq!(emulator.exit_code(), Some(0));
+
+        let offsets: Vec<usize> = log.public_values.iter().map(|write| write.offset).collect();
+        assert_eq!(offsets, [0, 4]);
+        assert_eq!(log.public_values[1].bytes, b"ef");
+        assert_eq!(log.public_values_stream(), b"abcdef");
+        assert_eq!(log.public_values_stream(), emulator.public_values);
+
+        let lines = log.public_values_lines(None);
+        assert!(lines.iter().any(|line| line.starts_with("offset 4      2 bytes at cycle ") && line.ends_with(": 0x6566")));
+        assert_eq!(lines.last().unwrap(), "committed stream (6 bytes): 0x616263646566");
+
+        // Both reads took the first chunk, and nothing is left unread
+        let reads: Vec<(usize, u64)> = log.input_reads.iter().map(|read| (read.chunk, read.offset)).collect();
+        assert_eq!(reads, [(0, 0), (0, 0)]);
+        assert!(!log.input_lines(None).iter().any(|line| line.contains("never read")));
+    }
+}
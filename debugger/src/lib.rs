@@ -1,21 +1,77 @@
 // Pico Debugger/Profiler Library
 // Provides debugging and profiling capabilities for Pico zkVM programs
 
+pub mod callstack;
+pub mod emulator;
+pub mod commands;
+pub mod dap;
+pub mod debuginfo;
+pub mod diff;
+pub mod export;
+pub mod expression;
+pub mod gdbserver;
+pub mod io;
+pub mod memory;
+pub mod panic;
+pub mod profile_diff;
+pub mod riscv;
+pub mod script;
+pub mod sessions;
+pub mod syscalls;
+pub mod trace;
+pub mod ui;
+
+use callstack::CallStack;
+use io::IoLog;
+use panic::PanicMonitor;
+use debuginfo::{DebugInfo, SourceLocation};
+use emulator::{Emulator, Program, StopReason};
+use expression::Expression;
+use riscv::{Instruction, LoadOp, StoreOp, REGISTER_NAMES};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DebugSession {
     pub program_path: PathBuf,
-    pub input_data: Vec<u8>,
-    pub breakpoints: Vec<u64>,
+    /// SHA-256 of the ELF, so a resumed session can tell the program changed.
+    pub program_hash: String,
+    /// Input chunks, in the order the guest reads them.
+    pub inputs: Vec<Vec<u8>>,
+    pub breakpoints: Vec<Breakpoint>,
     pub watch_variables: Vec<String>,
     pub session_id: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// An instruction breakpoint. A hit is a visit to `address` where `condition`
+/// holds; the first `ignore_count` hits do not stop.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Breakpoint {
+    pub address: u64,
+    /// Source of an [`Expression`], checked on every visit.
+    pub condition: Option<String>,
+    pub ignore_count: u64,
+    pub hit_count: u64,
+    /// Debugger commands run when the breakpoint stops execution.
+    pub commands: Vec<String>,
+}
+
+impl Breakpoint {
+    pub fn new(address: u64) -> Self {
+        Self {
+            address,
+            condition: None,
+            ignore_count: 0,
+            hit_count: 0,
+            commands: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionTrace {
     pub cycles: Vec<CycleInfo>,
@@ -30,6 +86,8 @@ pub struct CycleInfo {
     pub instruction: String,
     pub pc: u64,
     pub timestamp: Duration,
+    pub function: Option<String>,
+    pub source_location: Option<SourceLocation>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,7 +98,7 @@ pub struct MemoryAccess {
     pub cycle: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MemoryAccessType {
     Read,
     Write,
@@ -55,10 +113,56 @@ pub struct RegisterState {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CallFrame {
     pub function_name: String,
+    /// Current PC for the innermost frame, the call instruction for callers.
+    pub pc: u64,
     pub return_address: u64,
+    pub source_location: Option<SourceLocation>,
     pub local_variables: HashMap<String, u64>,
 }
 
+/// Where a step or continue ends besides breakpoints, panics, halts and traps.
+/// It is fixed when the step starts, so a run split into slices by
+/// [`PicoDebugger::run_to`] keeps the original target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunGoal {
+    /// Nothing else stops the run.
+    Continue,
+    /// One instruction.
+    Step,
+    /// `address` with the call stack at most `depth` deep, e.g. after a stepped-over call.
+    ReturnTo { address: u32, depth: usize },
+    /// `address` with sp at or above `stack_pointer`: the return from a frame whose caller's sp is known.
+    ReturnAbove { address: u32, stack_pointer: u32 },
+    /// A source line other than `location`. With a `depth`, calls deeper than it
+    /// run to completion; returning below it stops.
+    NextLine { location: SourceLocation, depth: Option<usize> },
+}
+
+impl RunGoal {
+    fn reached(&self, emulator: &Emulator, call_stack: &CallStack, debug_info: Option<&DebugInfo>) -> bool {
+        match self {
+            RunGoal::Continue => false,
+            RunGoal::Step => true,
+            RunGoal::ReturnTo { address, depth } => emulator.pc == *address && call_stack.depth() <= *depth,
+            RunGoal::ReturnAbove { address, stack_pointer } => {
+                emulator.pc == *address && emulator.registers[2] >= *stack_pointer
+            }
+            RunGoal::NextLine { location, depth } => {
+                if depth.is_some_and(|depth| call_stack.depth() < depth) {
+                    return true;
+                }
+                if depth.is_some_and(|depth| call_stack.depth() > depth) {
+                    return false;
+                }
+                // Line 0 marks compiler-generated code that belongs to no line.
+                debug_info
+                    .and_then(|info| info.location(emulator.pc))
+                    .is_some_and(|here| here.line != 0 && (here.line != location.line || here.file != location.file))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProfilerResult {
     pub total_cycles: u64,
@@ -66,22 +170,35 @@ pub struct ProfilerResult {
     pub memory_usage: MemoryProfile,
     pub performance_metrics: PerformanceMetrics,
     pub hot_spots: Vec<HotSpot>,
+    /// Every function that executed, by inclusive cycles.
+    pub functions: Vec<FunctionProfile>,
+    /// Cycles spent in each distinct call stack, outermost frame first.
+    pub stacks: Vec<StackSample>,
+    /// Every syscall the guest made, by charged cycles.
+    pub syscalls: Vec<SyscallProfile>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryProfile {
+    /// Bytes in the 4 KB pages the guest touched (fetched, read or written).
     pub peak_memory: u64,
+    /// Touched bytes averaged over every cycle of the run.
     pub average_memory: u64,
     pub memory_accesses: u64,
-    pub cache_hits: u64,
-    pub cache_misses: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceMetrics {
     pub cycles_per_second: f64,
+    /// Average bytes of guest memory read or written per cycle.
     pub memory_bandwidth: f64,
     pub instruction_mix: HashMap<String, u64>,
+    /// Invocations of each syscall, by name.
+    pub syscall_mix: HashMap<String, u64>,
+    /// Executor cycles charged to all syscalls, see [`SyscallProfile::cycles`].
+    pub syscall_cycles: u64,
+    /// The part of `syscall_cycles` spent in precompiles.
+    pub precompile_cycles: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -89,12 +206,207 @@ pub struct HotSpot {
     pub function_name: String,
     pub cycles_spent: u64,
     pub percentage: f64,
+    /// Source lines that executed, hottest first.
     pub line_numbers: Vec<u32>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionProfile {
+    pub function_name: String,
+    /// Cycles spent in the function and everything it called.
+    pub inclusive_cycles: u64,
+    /// Cycles spent in the function's own instructions.
+    pub exclusive_cycles: u64,
+    pub calls: u64,
+}
+
+impl FunctionProfile {
+    fn new(function_name: &str) -> Self {
+        Self {
+            function_name: function_name.to_string(),
+            inclusive_cycles: 0,
+            exclusive_cycles: 0,
+            calls: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyscallProfile {
+    pub name: String,
+    pub code: u32,
+    pub precompile: bool,
+    pub invocations: u64,
+    /// Cycles the Pico executor charges: one for the ecall plus the extra
+    /// cycles encoded in the syscall code, per invocation.
+    pub cycles: u64,
+    /// Guest memory read by the syscall.
+    pub bytes_read: u64,
+    /// Guest memory written by the syscall.
+    pub bytes_written: u64,
+}
+
+impl SyscallProfile {
+    fn new(code: u32) -> Self {
+        Self {
+            name: syscalls::syscall_name(code),
+            code,
+            precompile: syscalls::is_precompile(code),
+            invocations: 0,
+            cycles: 0,
+            bytes_read: 0,
+            bytes_written: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackSample {
+    pub frames: Vec<String>,
+    pub cycles: u64,
+}
+
+/// Interprets a command-line input argument: an existing file is read as raw bytes,
+/// a `0x` prefixed string is decoded as hex, anything else is used verbatim.
+pub fn parse_input(input: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let path = std::path::Path::new(input);
+    if path.is_file() {
+        return Ok(std::fs::read(path)?);
+    }
+    if let Some(hex_data) = input.strip_prefix("0x") {
+        return Ok(hex::decode(hex_data)?);
+    }
+    Ok(input.as_bytes().to_vec())
+}
+
+/// Interprets each `--input` argument as one input chunk. The SDK's stdin builder
+/// writes one chunk per `write` call and the guest consumes one per `read_as`,
+/// so a guest that reads several values needs one argument per read.
+pub fn parse_inputs(inputs: &[String]) -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error>> {
+    inputs.iter().map(|input| parse_input(input)).collect()
+}
+
+/// Everything the debugger derives from watching each step.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Observers {
+    call_stack: CallStack,
+    io_log: IoLog,
+    panic_monitor: PanicMonitor,
+}
+
+impl Observers {
+    /// Updates every observer after the emulator executed `event`. Returns true
+    /// if the step entered the guest's panic handler.
+    fn observe(&mut self, event: &emulator::StepEvent, emulator: &Emulator, debug_info: Option<&DebugInfo>) -> bool {
+        self.call_stack.observe(event.pc, &event.instruction, event.next_pc, &emulator.registers);
+        self.io_log.observe(event, &emulator.memory, &self.call_stack);
+        self.panic_monitor.observe(event, emulator, &self.call_stack, debug_info)
+    }
+
+    /// Updates the observers that care about a step the emulator refused.
+    fn observe_trap(&mut self, trap: &emulator::Trap, emulator: &Emulator) {
+        self.io_log.observe_trap(trap, emulator.cycle);
+    }
+}
+
+/// A snapshot of the whole VM plus the debugger's view of it (call stack, IO
+/// log and panic state), so restoring it resumes exactly where it was taken.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub name: String,
+    pub program_path: PathBuf,
+    /// SHA-256 of the ELF the checkpoint was taken from.
+    pub program_hash: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    emulator: Emulator,
+    observers: Observers,
+}
+
+impl Checkpoint {
+    pub fn cycle(&self) -> u64 {
+        self.emulator.cycle
+    }
+
+    pub fn pc(&self) -> u32 {
+        self.emulator.pc
+    }
+
+    /// Writes the checkpoint as zstd-compressed JSON.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_vec(self)?;
+        std::fs::write(path, zstd::encode_all(json.as_slice(), CHECKPOINT_COMPRESSION_LEVEL)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = zstd::decode_all(std::fs::File::open(path)?)?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+/// zstd level for checkpoint files; guest memory compresses well even at low levels.
+const CHECKPOINT_COMPRESSION_LEVEL: i32 = 3;
+
+/// PC, return address and registers of one frame during unwinding.
+struct FrameState {
+    pc: u32,
+    return_address: u32,
+    registers: [u32; 32],
+}
+
+/// Most frames a CFI unwind walks, in case a corrupt stack never reaches the outermost frame.
+const MAX_UNWIND_FRAMES: usize = 1024;
+
+/// Walks the stack with the call frame information. `None` if the innermost frame has
+/// none; the walk ends at a zero return address, a frame without CFI or a caller whose
+/// stack pointer is not above its callee's.
+fn unwind_frames(debug_info: &DebugInfo, emulator: &Emulator) -> Option<Vec<FrameState>> {
+    let mut states = Vec::new();
+    let mut pc = emulator.pc;
+    let mut registers = emulator.registers;
+    while states.len() < MAX_UNWIND_FRAMES {
+        let Some(caller) = debug_info.unwind(pc, &registers, &emulator.memory) else {
+            if states.is_empty() {
+                return None;
+            }
+            states.push(FrameState {
+                pc,
+                return_address: 0,
+                registers,
+            });
+            break;
+        };
+        // Only the innermost frame can be a leaf that has not moved sp yet.
+        let outermost = caller[1] == 0 || caller[2] < registers[2] || (caller[2] == registers[2] && !states.is_empty());
+        let return_address = if outermost { 0 } else { caller[1] };
+        states.push(FrameState {
+            pc,
+            return_address,
+            registers,
+        });
+        if outermost {
+            break;
+        }
+        // Callers are shown at their call instruction.
+        pc = return_address.wrapping_sub(4);
+        registers = caller;
+    }
+    Some(states)
+}
+
 pub struct PicoDebugger {
     session: Option<DebugSession>,
     trace: Option<ExecutionTrace>,
+    emulator: Option<Emulator>,
+    debug_info: Option<DebugInfo>,
+    observers: Observers,
+    checkpoints: BTreeMap<String, Checkpoint>,
+    /// Frame that `locals`, `list` and `finish` act on; 0 is the innermost.
+    selected_frame: usize,
+    /// Cycle at which every run stops, so an unattended run cannot hang on a looping guest.
+    cycle_limit: Option<u64>,
+    /// Where `session save` writes: the directory the session was resumed from, if any.
+    sessions_dir: PathBuf,
 }
 
 impl PicoDebugger {
@@ -102,15 +414,54 @@ impl PicoDebugger {
         Self {
             session: None,
             trace: None,
+            emulator: None,
+            debug_info: None,
+            observers: Observers::default(),
+            checkpoints: BTreeMap::new(),
+            selected_frame: 0,
+            cycle_limit: None,
+            sessions_dir: PathBuf::from(sessions::DEFAULT_SESSIONS_DIR),
         }
     }
 
-    pub fn start_session(&mut self, program_path: PathBuf, input_data: Vec<u8>) -> Result<String, Box<dyn std::error::Error>> {
+    /// Stops every later run at `cycle` with [`StopReason::CycleLimit`].
+    pub fn set_cycle_limit(&mut self, cycle: Option<u64>) {
+        self.cycle_limit = cycle;
+    }
+
+    pub fn set_sessions_dir(&mut self, directory: impl Into<PathBuf>) {
+        self.sessions_dir = directory.into();
+    }
+
+    pub fn sessions_dir(&self) -> &Path {
+        &self.sessions_dir
+    }
+
+    /// Loads the program with `inputs` queued as input chunks, see [`parse_inputs`].
+    pub fn start_session(&mut self, program_path: PathBuf, inputs: Vec<Vec<u8>>) -> Result<String, Box<dyn std::error::Error>> {
         let session_id = format!("debug_{}", chrono::Utc::now().timestamp_millis());
+        let program = Program::load(&program_path)?;
         
+        self.emulator = Some(Emulator::new(&program, inputs.clone()));
+        // Missing or malformed debug info only costs symbolisation, not the session.
+        self.debug_info = DebugInfo::load(&program.elf).ok();
+        self.observers = Observers {
+            call_stack: CallStack::default(),
+            io_log: IoLog::new(&inputs),
+            panic_monitor: PanicMonitor::new(self.debug_info.as_ref()),
+        };
+        self.checkpoints.clear();
+        self.selected_frame = 0;
+        self.trace = Some(ExecutionTrace {
+            cycles: Vec::new(),
+            memory_accesses: Vec::new(),
+            register_states: Vec::new(),
+            call_stack: Vec::new(),
+        });
         self.session = Some(DebugSession {
             program_path,
-            input_data,
+            program_hash: hex::encode(Sha256::digest(&program.elf)),
+            inputs,
             breakpoints: Vec::new(),
             watch_variables: Vec::new(),
             session_id: session_id.clone(),
@@ -120,10 +471,61 @@ impl PicoDebugger {
         Ok(session_id)
     }
 
+    /// Starts `session` again from cycle 0 with its breakpoints, watches, id
+    /// and creation time. Fails if the ELF no longer matches the session's hash.
+    pub fn resume_session(&mut self, session: DebugSession) -> Result<(), Box<dyn std::error::Error>> {
+        self.start_session(session.program_path.clone(), session.inputs.clone())?;
+        let program_hash = &self.session.as_ref().ok_or("No active debugging session")?.program_hash;
+        if *program_hash != session.program_hash {
+            return Err(format!(
+                "{} has changed since session {} was saved (sha256 {} != {})",
+                session.program_path.display(),
+                session.session_id,
+                program_hash,
+                session.program_hash
+            )
+            .into());
+        }
+        self.session = Some(session);
+        Ok(())
+    }
+
+    pub fn session(&self) -> Option<&DebugSession> {
+        self.session.as_ref()
+    }
+
+    /// Adds an expression to show with `watches`.
+    pub fn add_watch(&mut self, expression: &str) -> Result<(), Box<dyn std::error::Error>> {
+        Expression::parse(expression)?;
+        let session = self.session.as_mut().ok_or("No active debugging session")?;
+        if !session.watch_variables.iter().any(|watch| watch == expression) {
+            session.watch_variables.push(expression.to_string());
+        }
+        Ok(())
+    }
+
+    pub fn remove_watch(&mut self, expression: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let session = self.session.as_mut().ok_or("No active debugging session")?;
+        session.watch_variables.retain(|watch| watch != expression);
+        Ok(())
+    }
+
+    pub fn debug_info(&self) -> Option<&DebugInfo> {
+        self.debug_info.as_ref()
+    }
+
+    pub fn emulator(&self) -> Result<&Emulator, Box<dyn std::error::Error>> {
+        self.emulator.as_ref().ok_or_else(|| "No active debugging session".into())
+    }
+
+    pub fn emulator_mut(&mut self) -> Result<&mut Emulator, Box<dyn std::error::Error>> {
+        self.emulator.as_mut().ok_or_else(|| "No active debugging session".into())
+    }
+
     pub fn add_breakpoint(&mut self, address: u64) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(session) = &mut self.session {
-            if !session.breakpoints.contains(&address) {
-                session.breakpoints.push(address);
+            if !session.breakpoints.iter().any(|breakpoint| breakpoint.address == address) {
+                session.breakpoints.push(Breakpoint::new(address));
             }
         }
         Ok(())
@@ -131,53 +533,463 @@ impl PicoDebugger {
 
     pub fn remove_breakpoint(&mut self, address: u64) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(session) = &mut self.session {
-            session.breakpoints.retain(|&x| x != address);
+            session.breakpoints.retain(|breakpoint| breakpoint.address != address);
         }
         Ok(())
     }
 
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        self.session.as_ref().map(|session| session.breakpoints.as_slice()).unwrap_or_default()
+    }
+
+    pub fn breakpoint(&self, address: u64) -> Option<&Breakpoint> {
+        self.breakpoints().iter().find(|breakpoint| breakpoint.address == address)
+    }
+
+    fn breakpoint_mut(&mut self, address: u64) -> Result<&mut Breakpoint, Box<dyn std::error::Error>> {
+        let session = self.session.as_mut().ok_or("No active debugging session")?;
+        session
+            .breakpoints
+            .iter_mut()
+            .find(|breakpoint| breakpoint.address == address)
+            .ok_or_else(|| format!("No breakpoint at 0x{:x}", address).into())
+    }
+
+    /// Sets or, with `None`, clears the condition of the breakpoint at `address`.
+    pub fn set_breakpoint_condition(&mut self, address: u64, condition: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(condition) = condition {
+            Expression::parse(condition)?;
+        }
+        self.breakpoint_mut(address)?.condition = condition.map(str::to_string);
+        Ok(())
+    }
+
+    /// Lets the next `count` hits of the breakpoint at `address` pass without stopping.
+    pub fn set_breakpoint_ignore_count(&mut self, address: u64, count: u64) -> Result<(), Box<dyn std::error::Error>> {
+        self.breakpoint_mut(address)?.ignore_count = count;
+        Ok(())
+    }
+
+    pub fn set_breakpoint_commands(&mut self, address: u64, commands: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+        self.breakpoint_mut(address)?.commands = commands;
+        Ok(())
+    }
+
     pub fn step_execution(&mut self) -> Result<CycleInfo, Box<dyn std::error::Error>> {
-        // Placeholder implementation
-        let cycle_info = CycleInfo {
-            cycle: 0,
-            instruction: "nop".to_string(),
-            pc: 0,
-            timestamp: Duration::from_millis(0),
-        };
-        Ok(cycle_info)
+        let started = Instant::now();
+        let emulator = self.emulator.as_mut().ok_or("No active debugging session")?;
+        let event = emulator.step().inspect_err(|trap| self.observers.observe_trap(trap, emulator))?;
+        self.observers.observe(&event, emulator, self.debug_info.as_ref());
+        self.selected_frame = 0;
+        let debug_info = self.debug_info.as_ref();
+        Ok(CycleInfo {
+            cycle: event.cycle,
+            instruction: event.instruction.disassemble(event.pc),
+            pc: event.pc as u64,
+            timestamp: started.elapsed(),
+            function: debug_info.and_then(|info| info.function_at(event.pc)),
+            source_location: debug_info.and_then(|info| info.location(event.pc)),
+        })
+    }
+
+    /// Every ecall, input read and public-values write since the session started.
+    pub fn io_log(&self) -> &IoLog {
+        &self.observers.io_log
+    }
+
+    /// Panic handler arguments and recent instruction history.
+    pub fn panic_monitor(&self) -> &PanicMonitor {
+        &self.observers.panic_monitor
+    }
+
+    /// Active frames, innermost first, see [`frame_states`](Self::frame_states).
+    /// Local variables are only filled in by [`frame`](Self::frame).
+    pub fn backtrace(&self) -> Result<Vec<CallFrame>, Box<dyn std::error::Error>> {
+        Ok(self
+            .frame_states()?
+            .into_iter()
+            .map(|state| self.describe_frame(state.pc, state.return_address))
+            .collect())
+    }
+
+    /// Frame `index` of the backtrace, with the values of any locals whose
+    /// location DWARF describes.
+    pub fn frame(&self, index: usize) -> Result<CallFrame, Box<dyn std::error::Error>> {
+        let state = self
+            .frame_states()?
+            .into_iter()
+            .nth(index)
+            .ok_or_else(|| format!("No frame #{}", index))?;
+        let mut frame = self.describe_frame(state.pc, state.return_address);
+        if let Some(debug_info) = &self.debug_info {
+            let memory = &self.emulator()?.memory;
+            for variable in debug_info.local_variables(state.pc, &state.registers, memory) {
+                if let Some(value) = variable.value(&state.registers, memory) {
+                    frame.local_variables.insert(variable.name, value);
+                }
+            }
+        }
+        Ok(frame)
     }
 
-    pub fn continue_execution(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // Placeholder implementation
-        println!("Continuing execution...");
+    /// The selected frame, see [`select_frame`](Self::select_frame).
+    pub fn current_frame(&self) -> Result<CallFrame, Box<dyn std::error::Error>> {
+        self.frame(self.selected_frame)
+    }
+
+    pub fn selected_frame(&self) -> usize {
+        self.selected_frame
+    }
+
+    /// Selects the frame that `current_frame` and `step_out` act on. Resuming
+    /// execution selects the innermost frame again.
+    pub fn select_frame(&mut self, index: usize) -> Result<(), Box<dyn std::error::Error>> {
+        let frames = self.frame_states()?.len();
+        if index >= frames {
+            return Err(format!("No frame #{}, the stack has {} frames", index, frames).into());
+        }
+        self.selected_frame = index;
         Ok(())
     }
 
+    /// The execution trace with its call stack brought up to date.
+    pub fn execution_trace(&mut self) -> Result<&ExecutionTrace, Box<dyn std::error::Error>> {
+        let call_stack = self.backtrace()?;
+        let trace = self.trace.as_mut().ok_or("No active debugging session")?;
+        trace.call_stack = call_stack;
+        Ok(trace)
+    }
+
+    /// Every frame, innermost first. Frames are unwound from sp with the call frame
+    /// information when the code at the PC has some; otherwise they come from the
+    /// shadow call stack, which is stale after the PC or registers are set by hand.
+    fn frame_states(&self) -> Result<Vec<FrameState>, Box<dyn std::error::Error>> {
+        let emulator = self.emulator()?;
+        if let Some(states) = self.debug_info.as_ref().and_then(|info| unwind_frames(info, emulator)) {
+            return Ok(states);
+        }
+        let mut states = Vec::with_capacity(self.observers.call_stack.depth() + 1);
+        let mut pc = emulator.pc;
+        let mut registers = emulator.registers;
+        for entry in self.observers.call_stack.entries().iter().rev() {
+            states.push(FrameState {
+                pc,
+                return_address: entry.return_address,
+                registers,
+            });
+            pc = entry.call_site;
+            registers = entry.registers;
+        }
+        // The outermost frame was entered before any call we observed, so its
+        // return address is unknown.
+        states.push(FrameState {
+            pc,
+            return_address: 0,
+            registers,
+        });
+        Ok(states)
+    }
+
+    fn describe_frame(&self, pc: u32, return_address: u32) -> CallFrame {
+        let debug_info = self.debug_info.as_ref();
+        CallFrame {
+            function_name: debug_info
+                .and_then(|info| info.function_at(pc))
+                .unwrap_or_else(|| format!("0x{:08x}", pc)),
+            pc: pc as u64,
+            return_address: return_address as u64,
+            source_location: debug_info.and_then(|info| info.location(pc)),
+            local_variables: HashMap::new(),
+        }
+    }
+
+    /// Runs until a breakpoint is reached, the guest enters its panic handler,
+    /// or the program halts or traps.
+    pub fn continue_execution(&mut self) -> Result<StopReason, Box<dyn std::error::Error>> {
+        self.run_for(None)
+    }
+
+    /// Like [`continue_execution`](Self::continue_execution) but also stops after
+    /// `max_cycles`, so callers can stay responsive during long runs.
+    pub fn run_for(&mut self, max_cycles: Option<u64>) -> Result<StopReason, Box<dyn std::error::Error>> {
+        self.run_to(&RunGoal::Continue, max_cycles)
+    }
+
+    /// Executes one instruction, running any call it makes to completion.
+    pub fn step_over(&mut self) -> Result<StopReason, Box<dyn std::error::Error>> {
+        let goal = self.step_over_goal()?;
+        self.run_to(&goal, None)
+    }
+
+    /// Runs until the selected frame returns to its caller.
+    pub fn step_out(&mut self) -> Result<StopReason, Box<dyn std::error::Error>> {
+        let goal = self.step_out_goal()?;
+        self.run_to(&goal, None)
+    }
+
+    /// Where [`step_over`](Self::step_over) from the current PC stops.
+    pub fn step_over_goal(&self) -> Result<RunGoal, Box<dyn std::error::Error>> {
+        let emulator = self.emulator()?;
+        let pc = emulator.pc;
+        if !Instruction::decode(emulator.memory.read_u32(pc)).is_call() {
+            return Ok(RunGoal::Step);
+        }
+        Ok(RunGoal::ReturnTo {
+            address: pc.wrapping_add(4),
+            depth: self.observers.call_stack.depth(),
+        })
+    }
+
+    /// Where [`step_out`](Self::step_out) of the selected frame stops.
+    pub fn step_out_goal(&self) -> Result<RunGoal, Box<dyn std::error::Error>> {
+        let states = self.frame_states()?;
+        if let (Some(frame), Some(caller)) = (states.get(self.selected_frame), states.get(self.selected_frame + 1)) {
+            // Recursive calls return to the same address, but with sp below the caller's.
+            return Ok(RunGoal::ReturnAbove {
+                address: frame.return_address,
+                stack_pointer: caller.registers[2],
+            });
+        }
+        if self.selected_frame > 0 {
+            return Err("\"finish\" is not meaningful in the outermost frame".into());
+        }
+        // No call into the current function was observed, so fall back to the
+        // link register.
+        let emulator = self.emulator()?;
+        Ok(RunGoal::ReturnAbove {
+            address: emulator.registers[1],
+            stack_pointer: emulator.registers[2],
+        })
+    }
+
+    /// Where a source-level step from the current PC stops: at the next line, stepping
+    /// into calls when `into_calls` is set. Without line information for the PC this is
+    /// an instruction step (over calls unless `into_calls`).
+    pub fn step_line_goal(&self, into_calls: bool) -> Result<RunGoal, Box<dyn std::error::Error>> {
+        let pc = self.emulator()?.pc;
+        match self.debug_info.as_ref().and_then(|info| info.location(pc)).filter(|location| location.line != 0) {
+            Some(location) => Ok(RunGoal::NextLine {
+                location,
+                depth: (!into_calls).then(|| self.observers.call_stack.depth()),
+            }),
+            None if into_calls => Ok(RunGoal::Step),
+            None => self.step_over_goal(),
+        }
+    }
+
+    /// Executes a single instruction and reports how the step ended.
+    pub fn step_instruction(&mut self) -> Result<StopReason, Box<dyn std::error::Error>> {
+        let emulator = self.emulator.as_mut().ok_or("No active debugging session")?;
+        self.selected_frame = 0;
+        if let Some(code) = emulator.exit_code() {
+            return Ok(StopReason::Halted(code));
+        }
+        Ok(match emulator.step() {
+            Ok(event) => {
+                let panicked = self.observers.observe(&event, emulator, self.debug_info.as_ref());
+                match emulator.exit_code() {
+                    Some(code) => StopReason::Halted(code),
+                    None if panicked => StopReason::Panicked,
+                    None => StopReason::Stepped,
+                }
+            }
+            Err(trap) => {
+                self.observers.observe_trap(&trap, emulator);
+                StopReason::Trapped(trap)
+            }
+        })
+    }
+
+    /// Runs until a breakpoint, panic, halt or trap, or until `goal` is reached
+    /// (returned as [`StopReason::Stepped`]). Stopping at `max_cycles` or the
+    /// [cycle limit](Self::set_cycle_limit) returns [`StopReason::CycleLimit`],
+    /// and calling again with the same goal resumes the run.
+    pub fn run_to(&mut self, goal: &RunGoal, max_cycles: Option<u64>) -> Result<StopReason, Box<dyn std::error::Error>> {
+        // Breakpoint index and parsed condition by address.
+        let mut breakpoints: HashMap<u32, (usize, Option<Expression>)> = HashMap::new();
+        for (index, breakpoint) in self.session.iter().flat_map(|session| session.breakpoints.iter().enumerate()) {
+            let condition = breakpoint.condition.as_deref().map(Expression::parse).transpose()?;
+            breakpoints.insert(breakpoint.address as u32, (index, condition));
+        }
+        let emulator = self.emulator.as_mut().ok_or("No active debugging session")?;
+        let observers = &mut self.observers;
+        let debug_info = self.debug_info.as_ref();
+        self.selected_frame = 0;
+        let limit = max_cycles.map(|max| emulator.cycle.saturating_add(max)).into_iter().chain(self.cycle_limit).min();
+
+        // The instruction under the PC always executes, so continuing from a
+        // breakpoint does not stop on it again.
+        let mut first = true;
+        loop {
+            if let Some(code) = emulator.exit_code() {
+                return Ok(StopReason::Halted(code));
+            }
+            if !first && goal.reached(emulator, &observers.call_stack, debug_info) {
+                return Ok(StopReason::Stepped);
+            }
+            if let Some((index, condition)) = breakpoints.get(&emulator.pc).filter(|_| !first) {
+                // A condition that cannot be evaluated stops, so the problem is visible.
+                let holds = condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.evaluate(emulator).is_none_or(|value| value.is_true()));
+                if let Some(breakpoint) = self.session.as_mut().map(|session| &mut session.breakpoints[*index]).filter(|_| holds) {
+                    breakpoint.hit_count += 1;
+                    if breakpoint.ignore_count > 0 {
+                        breakpoint.ignore_count -= 1;
+                    } else {
+                        return Ok(StopReason::Breakpoint(emulator.pc));
+                    }
+                }
+            }
+            if limit.is_some_and(|limit| emulator.cycle >= limit) {
+                return Ok(StopReason::CycleLimit);
+            }
+            first = false;
+            match emulator.step() {
+                Ok(event) => {
+                    if observers.observe(&event, emulator, debug_info) && !emulator.is_halted() {
+                        return Ok(StopReason::Panicked);
+                    }
+                }
+                Err(trap) => {
+                    observers.observe_trap(&trap, emulator);
+                    return Ok(StopReason::Trapped(trap));
+                }
+            }
+        }
+    }
+
     pub fn get_register_state(&self) -> Result<HashMap<String, u64>, Box<dyn std::error::Error>> {
-        // Placeholder implementation
+        let emulator = self.emulator()?;
         let mut registers = HashMap::new();
-        registers.insert("x1".to_string(), 0);
-        registers.insert("x2".to_string(), 0);
-        registers.insert("pc".to_string(), 0);
+        for (name, value) in REGISTER_NAMES.iter().zip(emulator.registers) {
+            registers.insert(name.to_string(), value as u64);
+        }
+        registers.insert("pc".to_string(), emulator.pc as u64);
         Ok(registers)
     }
 
-    pub fn get_memory_value(&self, _address: u64) -> Result<u64, Box<dyn std::error::Error>> {
-        // Placeholder implementation
-        Ok(0)
+    pub fn get_memory_value(&self, address: u64) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(self.emulator()?.memory.read_u32(address as u32) as u64)
     }
 
+    /// Writes the 32-bit word at `address`.
     pub fn set_memory_value(&mut self, address: u64, value: u64) -> Result<(), Box<dyn std::error::Error>> {
-        // Placeholder implementation
-        println!("Setting memory[{}] = {}", address, value);
+        let value = u32::try_from(value).map_err(|_| format!("Value 0x{:x} does not fit in a memory word", value))?;
+        self.emulator_mut()?.memory.write_u32(address as u32, value);
         Ok(())
     }
+
+    /// Writes a general-purpose register, by ABI or `x` name, or `pc`.
+    pub fn set_register_value(&mut self, register: &str, value: u64) -> Result<(), Box<dyn std::error::Error>> {
+        let value = u32::try_from(value).map_err(|_| format!("Value 0x{:x} does not fit in a register", value))?;
+        let emulator = self.emulator_mut()?;
+        if register == "pc" {
+            emulator.pc = value;
+            return Ok(());
+        }
+        match riscv::register_index(register) {
+            Some(0) => Err("Register zero is hard-wired to 0".into()),
+            Some(index) => {
+                emulator.registers[index] = value;
+                Ok(())
+            }
+            None => Err(format!("Unknown register '{}'", register).into()),
+        }
+    }
+
+    /// Snapshots the current state under `name`, replacing any checkpoint of that name.
+    pub fn save_checkpoint(&mut self, name: &str) -> Result<&Checkpoint, Box<dyn std::error::Error>> {
+        let session = self.session.as_ref().ok_or("No active debugging session")?;
+        let checkpoint = Checkpoint {
+            name: name.to_string(),
+            program_path: session.program_path.clone(),
+            program_hash: session.program_hash.clone(),
+            created_at: chrono::Utc::now(),
+            emulator: self.emulator()?.clone(),
+            observers: self.observers.clone(),
+        };
+        self.checkpoints.insert(name.to_string(), checkpoint);
+        Ok(&self.checkpoints[name])
+    }
+
+    /// Rolls the VM back to checkpoint `name`. Breakpoints are left as they are.
+    pub fn restore_checkpoint(&mut self, name: &str) -> Result<&Checkpoint, Box<dyn std::error::Error>> {
+        let checkpoint = self.checkpoints.get(name).ok_or_else(|| format!("No checkpoint named '{}'", name))?;
+        self.emulator = Some(checkpoint.emulator.clone());
+        self.observers = checkpoint.observers.clone();
+        self.selected_frame = 0;
+        Ok(checkpoint)
+    }
+
+    pub fn delete_checkpoint(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.checkpoints
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| format!("No checkpoint named '{}'", name).into())
+    }
+
+    pub fn checkpoints(&self) -> impl Iterator<Item = &Checkpoint> {
+        self.checkpoints.values()
+    }
+
+    pub fn checkpoint(&self, name: &str) -> Option<&Checkpoint> {
+        self.checkpoints.get(name)
+    }
+
+    /// Adds a checkpoint read from disk. It must come from the same ELF as the
+    /// program being debugged, wherever that ELF now lives.
+    pub fn import_checkpoint(&mut self, checkpoint: Checkpoint) -> Result<&Checkpoint, Box<dyn std::error::Error>> {
+        let session = self.session.as_ref().ok_or("No active debugging session")?;
+        if checkpoint.program_hash != session.program_hash {
+            return Err(format!(
+                "Checkpoint '{}' was taken from a different program than {} ({} has sha256 {}, not {})",
+                checkpoint.name,
+                session.program_path.display(),
+                checkpoint.program_path.display(),
+                checkpoint.program_hash,
+                session.program_hash
+            )
+            .into());
+        }
+        let name = checkpoint.name.clone();
+        self.checkpoints.insert(name.clone(), checkpoint);
+        Ok(&self.checkpoints[&name])
+    }
 }
 
+/// Number of functions reported as hot spots.
+const HOT_SPOT_COUNT: usize = 10;
+
+/// A node of the profiler's call tree: one function reached through one
+/// particular chain of calls.
+struct CallNode {
+    function: u32,
+    children: HashMap<u32, usize>,
+    parent: Option<usize>,
+    cycles: u64,
+    calls: u64,
+}
+
+/// Attributes every executed cycle to the symbolised call stack it ran under.
+/// Feed it each [`StepEvent`](emulator::StepEvent) between `start_profiling`
+/// and `stop_profiling`.
 pub struct PicoProfiler {
     start_time: Option<Instant>,
     cycle_count: u64,
-    memory_usage: u64,
+    debug_info: Option<DebugInfo>,
+    call_stack: CallStack,
+    nodes: Vec<CallNode>,
+    /// Call tree node for each frame of `call_stack`, plus the root.
+    active_nodes: Vec<usize>,
+    pc_cycles: HashMap<u32, u64>,
+    instruction_mix: HashMap<&'static str, u64>,
+    memory_accesses: u64,
+    memory_bytes: u64,
+    touched_pages: HashSet<u32>,
+    last_code_page: Option<u32>,
+    working_set_sum: u64,
+    syscalls: HashMap<u32, SyscallProfile>,
 }
 
 impl PicoProfiler {
@@ -185,69 +997,360 @@ impl PicoProfiler {
         Self {
             start_time: None,
             cycle_count: 0,
-            memory_usage: 0,
+            debug_info: None,
+            call_stack: CallStack::default(),
+            nodes: Vec::new(),
+            active_nodes: Vec::new(),
+            pc_cycles: HashMap::new(),
+            instruction_mix: HashMap::new(),
+            memory_accesses: 0,
+            memory_bytes: 0,
+            touched_pages: HashSet::new(),
+            last_code_page: None,
+            working_set_sum: 0,
+            syscalls: HashMap::new(),
         }
     }
 
+    /// Supplies the guest's debug info so functions and hot spots get names
+    /// and source lines instead of bare addresses.
+    pub fn set_debug_info(&mut self, debug_info: DebugInfo) {
+        self.debug_info = Some(debug_info);
+    }
+
     pub fn start_profiling(&mut self) {
+        let debug_info = self.debug_info.take();
+        *self = Self::new();
+        self.debug_info = debug_info;
         self.start_time = Some(Instant::now());
-        self.cycle_count = 0;
-        self.memory_usage = 0;
+    }
+
+    /// Accounts for one executed instruction.
+    pub fn record_step(&mut self, event: &emulator::StepEvent) {
+        if self.nodes.is_empty() {
+            let root = self.function_start(event.pc);
+            self.nodes.push(CallNode {
+                function: root,
+                children: HashMap::new(),
+                parent: None,
+                cycles: 0,
+                calls: 1,
+            });
+            self.active_nodes.push(0);
+        }
+
+        self.cycle_count += 1;
+        let current = *self.active_nodes.last().unwrap_or(&0);
+        self.nodes[current].cycles += 1;
+        *self.pc_cycles.entry(event.pc).or_insert(0) += 1;
+        *self.instruction_mix.entry(event.instruction.mnemonic()).or_insert(0) += 1;
+
+        let code_page = event.pc / emulator::PAGE_SIZE;
+        if self.last_code_page != Some(code_page) {
+            self.touched_pages.insert(code_page);
+            self.last_code_page = Some(code_page);
+        }
+        let width = access_width(&event.instruction);
+        for access in &event.memory {
+            self.memory_accesses += 1;
+            self.memory_bytes += width;
+            self.touched_pages.insert(access.address as u32 / emulator::PAGE_SIZE);
+        }
+        self.working_set_sum += self.touched_pages.len() as u64;
+
+        if let Some(syscall) = &event.syscall {
+            let profile = self.syscalls.entry(syscall.code).or_insert_with(|| SyscallProfile::new(syscall.code));
+            profile.invocations += 1;
+            profile.cycles += 1 + syscalls::extra_cycles(syscall.code) as u64;
+            let (read, written) = syscall_bytes(syscall, &event.memory);
+            profile.bytes_read += read;
+            profile.bytes_written += written;
+        }
+
+        let registers = [0u32; 32];
+        self.call_stack.observe(event.pc, &event.instruction, event.next_pc, &registers);
+        let depth = self.call_stack.depth();
+        if depth + 1 > self.active_nodes.len() {
+            let target = self.call_stack.entries()[depth - 1].target;
+            let child = self.child_node(current, target);
+            self.nodes[child].calls += 1;
+            self.active_nodes.push(child);
+        } else {
+            self.active_nodes.truncate(depth + 1);
+        }
     }
 
     pub fn stop_profiling(&mut self) -> Result<ProfilerResult, Box<dyn std::error::Error>> {
         let execution_time = self.start_time
             .map(|start| start.elapsed())
             .unwrap_or(Duration::from_secs(0));
+        let total_cycles = self.cycle_count;
 
         let memory_profile = MemoryProfile {
-            peak_memory: self.memory_usage,
-            average_memory: self.memory_usage / 2,
-            memory_accesses: 1000, // Placeholder
-            cache_hits: 800,       // Placeholder
-            cache_misses: 200,     // Placeholder
+            peak_memory: self.touched_pages.len() as u64 * emulator::PAGE_SIZE as u64,
+            average_memory: (self.working_set_sum / total_cycles.max(1)) * emulator::PAGE_SIZE as u64,
+            memory_accesses: self.memory_accesses,
         };
 
-        let mut instruction_mix = HashMap::new();
-        instruction_mix.insert("add".to_string(), 100);
-        instruction_mix.insert("mul".to_string(), 50);
-        instruction_mix.insert("load".to_string(), 200);
+        let mut syscall_profiles: Vec<SyscallProfile> = self.syscalls.values().cloned().collect();
+        syscall_profiles.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.code.cmp(&b.code)));
 
         let performance_metrics = PerformanceMetrics {
-            cycles_per_second: self.cycle_count as f64 / execution_time.as_secs_f64(),
-            memory_bandwidth: 1.0, // Placeholder
-            instruction_mix,
+            cycles_per_second: total_cycles as f64 / execution_time.as_secs_f64().max(f64::EPSILON),
+            memory_bandwidth: self.memory_bytes as f64 / total_cycles.max(1) as f64,
+            instruction_mix: self
+                .instruction_mix
+                .iter()
+                .map(|(mnemonic, &count)| (mnemonic.to_string(), count))
+                .collect(),
+            syscall_mix: syscall_profiles
+                .iter()
+                .map(|syscall| (syscall.name.clone(), syscall.invocations))
+                .collect(),
+            syscall_cycles: syscall_profiles.iter().map(|syscall| syscall.cycles).sum(),
+            precompile_cycles: syscall_profiles
+                .iter()
+                .filter(|syscall| syscall.precompile)
+                .map(|syscall| syscall.cycles)
+                .sum(),
         };
 
-        let hot_spots = vec![
-            HotSpot {
-                function_name: "main".to_string(),
-                cycles_spent: self.cycle_count / 2,
-                percentage: 50.0,
-                line_numbers: vec![10, 15, 20],
-            },
-            HotSpot {
-                function_name: "compute".to_string(),
-                cycles_spent: self.cycle_count / 4,
-                percentage: 25.0,
-                line_numbers: vec![5, 8, 12],
-            },
-        ];
+        let names: Vec<String> = self.nodes.iter().map(|node| self.function_name(node.function)).collect();
+        let mut stacks = Vec::new();
+        let mut functions: HashMap<&str, FunctionProfile> = HashMap::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut path = Vec::new();
+            let mut cursor = Some(index);
+            while let Some(current) = cursor {
+                path.push(names[current].as_str());
+                cursor = self.nodes[current].parent;
+            }
+            path.reverse();
+
+            let profile = functions.entry(&names[index]).or_insert_with(|| FunctionProfile::new(&names[index]));
+            profile.exclusive_cycles += node.cycles;
+            profile.calls += node.calls;
+            // Recursive functions appear several times on one stack but only
+            // count once towards its inclusive total.
+            let mut seen = HashSet::new();
+            for &name in &path {
+                if seen.insert(name) {
+                    let profile = functions.entry(name).or_insert_with(|| FunctionProfile::new(name));
+                    profile.inclusive_cycles += node.cycles;
+                }
+            }
+            if node.cycles > 0 {
+                stacks.push(StackSample {
+                    frames: path.into_iter().map(String::from).collect(),
+                    cycles: node.cycles,
+                });
+            }
+        }
+        let mut functions: Vec<FunctionProfile> = functions.into_values().collect();
+        functions.sort_by(|a, b| {
+            b.inclusive_cycles
+                .cmp(&a.inclusive_cycles)
+                .then(b.exclusive_cycles.cmp(&a.exclusive_cycles))
+                .then(a.function_name.cmp(&b.function_name))
+        });
+
+        let mut by_exclusive: Vec<&FunctionProfile> = functions.iter().filter(|f| f.exclusive_cycles > 0).collect();
+        by_exclusive.sort_by_key(|function| std::cmp::Reverse(function.exclusive_cycles));
+        let hot_spots = by_exclusive
+            .into_iter()
+            .take(HOT_SPOT_COUNT)
+            .map(|function| HotSpot {
+                function_name: function.function_name.clone(),
+                cycles_spent: function.exclusive_cycles,
+                percentage: function.exclusive_cycles as f64 * 100.0 / total_cycles.max(1) as f64,
+                line_numbers: self.hot_lines(&function.function_name),
+            })
+            .collect();
 
         Ok(ProfilerResult {
-            total_cycles: self.cycle_count,
+            total_cycles,
             execution_time,
             memory_usage: memory_profile,
             performance_metrics,
             hot_spots,
+            functions,
+            stacks,
+            syscalls: syscall_profiles,
         })
     }
 
-    pub fn record_cycle(&mut self) {
-        self.cycle_count += 1;
+    fn child_node(&mut self, parent: usize, function: u32) -> usize {
+        if let Some(&child) = self.nodes[parent].children.get(&function) {
+            return child;
+        }
+        let child = self.nodes.len();
+        self.nodes.push(CallNode {
+            function,
+            children: HashMap::new(),
+            parent: Some(parent),
+            cycles: 0,
+            calls: 0,
+        });
+        self.nodes[parent].children.insert(function, child);
+        child
+    }
+
+    /// Start address of the function containing `pc`, so the root frame is
+    /// keyed like called functions are.
+    fn function_start(&self, pc: u32) -> u32 {
+        self.debug_info
+            .as_ref()
+            .and_then(|debug_info| debug_info.symbol_at(pc))
+            .map(|symbol| symbol.address)
+            .unwrap_or(pc)
     }
 
-    pub fn record_memory_access(&mut self, size: u64) {
-        self.memory_usage += size;
+    fn function_name(&self, address: u32) -> String {
+        let debug_info = self.debug_info.as_ref();
+        debug_info
+            .and_then(|info| info.symbol_at(address).map(|symbol| symbol.name.clone()))
+            .or_else(|| debug_info.and_then(|info| info.function_at(address)))
+            .unwrap_or_else(|| format!("0x{:08x}", address))
+    }
+
+    /// Source lines of `function` that executed, hottest first.
+    fn hot_lines(&self, function: &str) -> Vec<u32> {
+        let Some(debug_info) = &self.debug_info else {
+            return Vec::new();
+        };
+        let mut line_cycles: HashMap<u32, u64> = HashMap::new();
+        for (&pc, &cycles) in &self.pc_cycles {
+            if debug_info.symbol_at(pc).is_some_and(|symbol| symbol.name == function) {
+                if let Some(location) = debug_info.location(pc) {
+                    *line_cycles.entry(location.line).or_insert(0) += cycles;
+                }
+            }
+        }
+        let mut lines: Vec<(u32, u64)> = line_cycles.into_iter().collect();
+        lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        lines.into_iter().map(|(line, _)| line).collect()
+    }
+}
+
+/// Bytes of guest memory a syscall read and wrote. Precompiles access whole
+/// words; WRITE and HINT_READ move the byte counts they were given.
+fn syscall_bytes(syscall: &emulator::SyscallEvent, accesses: &[MemoryAccess]) -> (u64, u64) {
+    let [_, a1, a2] = syscall.arguments;
+    match syscall.code {
+        syscalls::WRITE => (a2 as u64, 0),
+        syscalls::HINT_READ => (0, a1 as u64),
+        _ => {
+            let reads = accesses.iter().filter(|access| access.access_type == MemoryAccessType::Read).count();
+            ((reads * 4) as u64, ((accesses.len() - reads) * 4) as u64)
+        }
+    }
+}
+
+/// Bytes moved by each memory access of `instruction`. Syscalls move whole words.
+pub(crate) fn access_width(instruction: &Instruction) -> u64 {
+    match instruction {
+        Instruction::Load { op: LoadOp::Lb | LoadOp::Lbu, .. } | Instruction::Store { op: StoreOp::Sb, .. } => 1,
+        Instruction::Load { op: LoadOp::Lh | LoadOp::Lhu, .. } | Instruction::Store { op: StoreOp::Sh, .. } => 2,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `tests/fixtures/frames.s`: _start -> outer -> inner -> leaf, with CFI for outer and inner.
+    fn frames_session() -> PicoDebugger {
+        let mut debugger = PicoDebugger::new();
+        let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/frames.elf");
+        debugger.start_session(program, Vec::new()).unwrap();
+        debugger
+    }
+
+    fn stop_in(debugger: &mut PicoDebugger, function: &str) {
+        let address = debugger.debug_info().unwrap().symbol_address(function).unwrap();
+        debugger.add_breakpoint(address as u64).unwrap();
+        assert!(matches!(debugger.continue_execution().unwrap(), StopReason::Breakpoint(pc) if pc == address));
+        debugger.remove_breakpoint(address as u64).unwrap();
+    }
+
+    fn function_names(debugger: &PicoDebugger) -> Vec<String> {
+        debugger.backtrace().unwrap().into_iter().map(|frame| frame.function_name).collect()
+    }
+
+    #[test]
+    fn backtrace_unwinds_with_cfi() {
+        let mut debugger = frames_session();
+        stop_in(&mut debugger, "leaf");
+        assert_eq!(function_names(&debugger), ["leaf", "inner", "outer", "_start"]);
+
+        let states = debugger.frame_states().unwrap();
+        let pcs: Vec<u32> = states.iter().map(|state| state.pc).collect();
+        assert_eq!(pcs, [0x20086c, 0x200858, 0x200834, 0x200808]);
+        // inner saved the caller's s0 before overwriting it
+        assert_eq!(states[1].registers[8], 9);
+        assert_eq!(states[2].registers[8], 5);
+        assert_eq!(states[3].return_address, 0);
+    }
+
+    #[test]
+    fn backtrace_follows_a_pc_set_by_hand() {
+        let mut debugger = frames_session();
+        stop_in(&mut debugger, "leaf");
+        // Skip the rest of leaf: the shadow stack still has leaf's call, the stack does not.
+        let emulator = debugger.emulator_mut().unwrap();
+        emulator.pc = emulator.registers[1];
+        assert_eq!(function_names(&debugger), ["inner", "outer", "_start"]);
+        assert!(debugger.select_frame(3).is_err());
+    }
+
+    #[test]
+    fn finish_returns_from_the_selected_frame() {
+        let mut debugger = frames_session();
+        stop_in(&mut debugger, "leaf");
+        debugger.select_frame(1).unwrap();
+        assert!(matches!(debugger.step_out().unwrap(), StopReason::Stepped));
+        assert_eq!(debugger.emulator().unwrap().pc, 0x200838);
+        assert_eq!(function_names(&debugger), ["outer", "_start"]);
+    }
+
+    #[test]
+    fn next_steps_over_calls_by_source_line() {
+        let mut debugger = frames_session();
+        let line = |debugger: &PicoDebugger| debugger.current_frame().unwrap().source_location.unwrap().line;
+        assert_eq!(line(&debugger), 11);
+        for expected in [12, 13, 15] {
+            let goal = debugger.step_line_goal(false).unwrap();
+            assert!(matches!(debugger.run_to(&goal, None).unwrap(), StopReason::Stepped));
+            assert_eq!(line(&debugger), expected);
+        }
+        let goal = debugger.step_line_goal(true).unwrap();
+        debugger.run_to(&goal, None).unwrap();
+        assert_eq!(line(&debugger), 16);
+    }
+
+    #[test]
+    fn checkpoints_import_by_program_hash() {
+        let mut debugger = frames_session();
+        stop_in(&mut debugger, "leaf");
+        let path = std::env::temp_dir().join(format!("pico-debugger-checkpoint-{}.ckpt", std::process::id()));
+        debugger.save_checkpoint("leaf").unwrap().save(&path).unwrap();
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The same ELF under another path is accepted.
+        let copy = std::env::temp_dir().join(format!("pico-debugger-frames-{}.elf", std::process::id()));
+        std::fs::copy(&debugger.session().unwrap().program_path, &copy).unwrap();
+        let mut other = PicoDebugger::new();
+        other.start_session(copy.clone(), Vec::new()).unwrap();
+        std::fs::remove_file(&copy).unwrap();
+        other.import_checkpoint(checkpoint.clone()).unwrap();
+        other.restore_checkpoint("leaf").unwrap();
+        assert_eq!(other.emulator().unwrap().pc, 0x20086c);
+
+        let mut rebuilt = checkpoint;
+        rebuilt.program_hash = "0".repeat(64);
+        let error = other.import_checkpoint(rebuilt).unwrap_err().to_string();
+        assert!(error.contains("different program"), "{}", error);
     }
 }
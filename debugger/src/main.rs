@@ -1,5 +1,19 @@
 use clap::{Parser, Subcommand};
-use pico_debugger::{PicoDebugger, PicoProfiler};
+use pico_debugger::commands::{self, parse_address};
+use pico_debugger::dap::DapServer;
+use pico_debugger::debuginfo::DebugInfo;
+use pico_debugger::diff::{self, DiffOutcome, DiffRun};
+use pico_debugger::export;
+use pico_debugger::memory::{MemoryHeatmap, MemoryLayout, MemoryStats};
+use pico_debugger::emulator::{Emulator, Program, StopReason};
+use pico_debugger::gdbserver::GdbServer;
+use pico_debugger::profile_diff::ProfileDiff;
+use pico_debugger::script;
+use pico_debugger::sessions::{self, SessionStore};
+use pico_debugger::trace::{TraceFilter, TraceReader, TraceWriter};
+use pico_debugger::ui::DebuggerUi;
+use pico_debugger::{parse_inputs, MemoryAccessType, PicoDebugger, PicoProfiler};
+use std::collections::HashSet;
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -19,13 +33,39 @@ enum Commands {
         #[arg(short, long)]
         program: PathBuf,
         
-        /// Input data for the program
+        /// Input chunk for the program (file path, 0x-prefixed hex or literal string); repeat for each read
         #[arg(short, long)]
-        input: String,
+        input: Vec<String>,
         
         /// Breakpoints to set (addresses)
         #[arg(short, long)]
         breakpoints: Vec<u64>,
+        
+        /// Run until the guest panics, traps or halts and print a diagnosis
+        #[arg(long)]
+        until_panic: bool,
+        
+        /// Executed instructions to list in the diagnosis
+        #[arg(long, default_value_t = commands::DEFAULT_HISTORY)]
+        history: usize,
+        
+        /// Expressions to watch (e.g. "a0", "*(u32*)0x300000")
+        #[arg(short, long)]
+        watch: Vec<String>,
+        
+        /// Directory to save the session in
+        #[arg(long, default_value = sessions::DEFAULT_SESSIONS_DIR)]
+        sessions_dir: PathBuf,
+    },
+    
+    /// List, resume or delete saved debugging sessions
+    Sessions {
+        /// Directory the sessions are saved in
+        #[arg(long, default_value = sessions::DEFAULT_SESSIONS_DIR)]
+        dir: PathBuf,
+        
+        #[command(subcommand)]
+        action: SessionAction,
     },
     
     /// Profile program execution
@@ -34,13 +74,50 @@ enum Commands {
         #[arg(short, long)]
         program: PathBuf,
         
-        /// Input data for the program
+        /// Input chunk for the program (file path, 0x-prefixed hex or literal string); repeat for each read
         #[arg(short, long)]
-        input: String,
+        input: Vec<String>,
         
         /// Output file for profile results
         #[arg(short, long)]
         output: Option<PathBuf>,
+        
+        /// Write collapsed stacks for flamegraph.pl or inferno
+        #[arg(long)]
+        collapsed: Option<PathBuf>,
+        
+        /// Write a speedscope JSON profile
+        #[arg(long)]
+        speedscope: Option<PathBuf>,
+        
+        /// Write a pprof protobuf profile
+        #[arg(long)]
+        pprof: Option<PathBuf>,
+        
+        /// Stop profiling after this many cycles
+        #[arg(long)]
+        max_cycles: Option<u64>,
+        
+        /// Number of functions to list
+        #[arg(long, default_value = "20")]
+        top: usize,
+    },
+    
+    /// Compare two profiles saved with `profile --output`
+    ProfileDiff {
+        /// Baseline profile JSON
+        base: PathBuf,
+        
+        /// Profile JSON to compare against the baseline
+        new: PathBuf,
+        
+        /// Exit with status 1 if total cycles grew by more than this percentage
+        #[arg(long)]
+        threshold: Option<f64>,
+        
+        /// Number of changed functions to list
+        #[arg(long, default_value = "20")]
+        top: usize,
     },
     
     /// Interactive debugging mode
@@ -49,9 +126,111 @@ enum Commands {
         #[arg(short, long)]
         program: PathBuf,
         
-        /// Input data for the program
+        /// Input chunk for the program (file path, 0x-prefixed hex or literal string); repeat for each read
+        #[arg(short, long)]
+        input: Vec<String>,
+    },
+    
+    /// Serve the program to gdb or lldb over the GDB remote protocol
+    Gdbserver {
+        /// Path to the ELF program
+        #[arg(short, long)]
+        program: PathBuf,
+        
+        /// Input chunk for the program (file path, 0x-prefixed hex or literal string); repeat for each read
+        #[arg(short, long)]
+        input: Vec<String>,
+        
+        /// TCP port to listen on
+        #[arg(long, default_value = "1234")]
+        port: u16,
+    },
+    
+    /// Serve the Debug Adapter Protocol over stdio for editor integration
+    Dap,
+    
+    /// Record a compact binary execution trace
+    Trace {
+        /// Path to the ELF program
+        #[arg(short, long)]
+        program: PathBuf,
+        
+        /// Input chunk for the program (file path, 0x-prefixed hex or literal string); repeat for each read
+        #[arg(short, long)]
+        input: Vec<String>,
+        
+        /// Output trace file
+        #[arg(short, long)]
+        output: PathBuf,
+        
+        /// Stop recording after this many cycles
+        #[arg(long)]
+        max_cycles: Option<u64>,
+    },
+    
+    /// Log every ecall, input read and public-values write of a run
+    Io {
+        /// Path to the ELF program
+        #[arg(short, long)]
+        program: PathBuf,
+        
+        /// Input chunk for the program (file path, 0x-prefixed hex or literal string); repeat for each read
+        #[arg(short, long)]
+        input: Vec<String>,
+        
+        /// Stop after this many cycles
+        #[arg(long)]
+        max_cycles: Option<u64>,
+    },
+    
+    /// Run debugger commands from a file and report the results as JSON
+    RunScript {
+        /// Path to the ELF program
         #[arg(short, long)]
-        input: String,
+        program: PathBuf,
+        
+        /// Input chunk for the program (file path, 0x-prefixed hex or literal string); repeat for each read
+        #[arg(short, long)]
+        input: Vec<String>,
+        
+        /// File with one debugger command or assertion per line
+        #[arg(short, long)]
+        script: PathBuf,
+        
+        /// Write the JSON report here instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Fail the script once the guest has run this many cycles
+        #[arg(long, default_value_t = script::DEFAULT_MAX_CYCLES)]
+        max_cycles: u64,
+    },
+    
+    /// Run two programs or inputs in lockstep and report the first divergence
+    Diff {
+        /// First ELF program
+        #[arg(long)]
+        a: PathBuf,
+        
+        /// Input chunk for the first program (file path, 0x-prefixed hex or literal string); repeat for each read
+        #[arg(long)]
+        input1: Vec<String>,
+        
+        /// Second ELF program (defaults to the first)
+        #[arg(long)]
+        b: Option<PathBuf>,
+        
+        /// Input chunk for the second program; repeat for each read (defaults to the first input)
+        #[arg(long)]
+        input2: Vec<String>,
+        
+        /// Stop comparing after this many cycles
+        #[arg(long)]
+        max_cycles: Option<u64>,
+        
+        /// Instructions of context to show for each run
+        #[arg(long, default_value = "8")]
+        context: usize,
     },
     
     /// Analyze execution trace
@@ -60,9 +239,29 @@ enum Commands {
         #[arg(short, long)]
         trace: PathBuf,
         
-        /// Output format (json, text, html)
+        /// Output format (json, text)
         #[arg(short, long, default_value = "text")]
         format: String,
+        
+        /// First cycle to include
+        #[arg(long)]
+        from_cycle: Option<u64>,
+        
+        /// Cycle to stop at (exclusive)
+        #[arg(long)]
+        to_cycle: Option<u64>,
+        
+        /// Only include instructions in this PC range (e.g. 0x200000-0x200100)
+        #[arg(long)]
+        pc_range: Option<String>,
+        
+        /// Print the first N matching instructions
+        #[arg(long, default_value = "0")]
+        show: usize,
+        
+        /// ELF program the trace was recorded from, used to symbolize instructions
+        #[arg(short, long)]
+        program: Option<PathBuf>,
     },
     
     /// Memory analysis
@@ -71,13 +270,55 @@ enum Commands {
         #[arg(short, long)]
         program: PathBuf,
         
+        /// Input chunk for the program (file path, 0x-prefixed hex or literal string); repeat for each read
+        #[arg(short, long)]
+        input: Vec<String>,
+        
         /// Show memory layout
         #[arg(long)]
         layout: bool,
         
-        /// Show memory usage statistics
+        /// Run the program and show memory usage statistics
         #[arg(long)]
         stats: bool,
+        
+        /// Stop the run after this many cycles
+        #[arg(long)]
+        max_cycles: Option<u64>,
+        
+        /// Trace recorded with `trace`, to build an access heatmap from
+        #[arg(long)]
+        trace: Option<PathBuf>,
+        
+        /// Write the heatmap as an HTML report (needs --trace)
+        #[arg(long, requires = "trace")]
+        html: Option<PathBuf>,
+        
+        /// Number of pages and symbols to list in the heatmap
+        #[arg(long, default_value = "20")]
+        top: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum SessionAction {
+    /// List saved sessions, most recent first
+    List,
+    
+    /// Reopen a saved session in the interactive debugger
+    Resume {
+        /// Session id
+        id: String,
+        
+        /// Program path to use instead of the saved one, e.g. for a session shared by a teammate
+        #[arg(short, long)]
+        program: Option<PathBuf>,
+    },
+    
+    /// Delete a saved session and its checkpoint
+    Delete {
+        /// Session id
+        id: String,
     },
 }
 
@@ -87,9 +328,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
     
     match cli.command {
-        Commands::Debug { program, input, breakpoints } => {
+        Commands::Debug { program, input, breakpoints, until_panic, history, watch, sessions_dir } => {
             let mut debugger = PicoDebugger::new();
-            let session_id = debugger.start_session(program, input.into_bytes())?;
+            debugger.set_sessions_dir(&sessions_dir);
+            let session_id = debugger.start_session(program, parse_inputs(&input)?)?;
             
             println!("Started debugging session: {}", session_id);
             
@@ -98,32 +340,107 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("Added breakpoint at address: 0x{:x}", breakpoint);
             }
             
-            println!("Debugging session ready. Use interactive mode for step-by-step debugging.");
+            for expression in watch {
+                debugger.add_watch(&expression)?;
+                println!("Watching {}", expression);
+            }
+            
+            if until_panic {
+                let stop = loop {
+                    match debugger.continue_execution()? {
+                        StopReason::Breakpoint(_) => continue,
+                        other => break other,
+                    }
+                };
+                println!("{}", commands::describe_stop(&debugger, &stop)?);
+                if commands::is_failure(&stop) {
+                    for line in commands::diagnosis(&debugger, history)? {
+                        println!("{}", line);
+                    }
+                }
+                return Ok(());
+            }
+            
+            let path = SessionStore::new(debugger.sessions_dir()).save(&debugger, None)?;
+            println!("Saved session to {}", path.display());
+            println!("Resume with: pico-debug sessions resume {}", session_id);
         }
         
-        Commands::Profile { program, input, output } => {
+        Commands::Profile { program: program_path, input, output, collapsed, speedscope, pprof, max_cycles, top } => {
+            let program = Program::load(&program_path)?;
+            let mut emulator = Emulator::new(&program, parse_inputs(&input)?);
             let mut profiler = PicoProfiler::new();
+            // Unreadable debug info only costs names; the profile falls back to raw addresses.
+            match DebugInfo::load(&program.elf) {
+                Ok(debug_info) => profiler.set_debug_info(debug_info),
+                Err(e) => eprintln!("Warning: no debug info ({}), reporting raw addresses", e),
+            }
             profiler.start_profiling();
             
-            println!("Profiling program: {:?}", program);
-            println!("Input: {}", input);
-            
-            // Simulate some profiling
-            for i in 0..1000 {
-                profiler.record_cycle();
-                if i % 100 == 0 {
-                    profiler.record_memory_access(64);
-                }
-            }
+            println!("Profiling program: {:?}", program_path);
             
+            let stop = emulator.run(max_cycles, |event| {
+                profiler.record_step(event);
+                Ok::<(), std::convert::Infallible>(())
+            })?;
             let result = profiler.stop_profiling()?;
             
+            match stop {
+                StopReason::Halted(code) => println!("Program halted with exit code {}", code),
+                StopReason::Trapped(trap) => println!("Program trapped: {}", trap),
+                StopReason::CycleLimit => println!("Stopped at cycle limit"),
+                StopReason::Breakpoint(_) | StopReason::Stepped | StopReason::Panicked => {}
+            }
             println!("Profiling Results:");
             println!("  Total cycles: {}", result.total_cycles);
             println!("  Execution time: {:?}", result.execution_time);
             println!("  Peak memory: {} bytes", result.memory_usage.peak_memory);
+            println!("  Memory accesses: {}", result.memory_usage.memory_accesses);
             println!("  Cycles per second: {:.2}", result.performance_metrics.cycles_per_second);
             
+            println!("Functions:");
+            println!("  {:>12} {:>7} {:>12} {:>7} {:>9}  function", "inclusive", "%", "exclusive", "%", "calls");
+            let percent = |cycles: u64| cycles as f64 * 100.0 / result.total_cycles.max(1) as f64;
+            for function in result.functions.iter().take(top) {
+                println!("  {:>12} {:>6.2}% {:>12} {:>6.2}% {:>9}  {}", 
+                    function.inclusive_cycles, 
+                    percent(function.inclusive_cycles), 
+                    function.exclusive_cycles, 
+                    percent(function.exclusive_cycles), 
+                    function.calls, 
+                    function.function_name
+                );
+            }
+            
+            let metrics = &result.performance_metrics;
+            if !result.syscalls.is_empty() {
+                // Extra syscall cycles are charged on top of the instruction count.
+                let extra_cycles: u64 = result.syscalls.iter().map(|syscall| syscall.cycles - syscall.invocations).sum();
+                let executor_cycles = result.total_cycles + extra_cycles;
+                let percent_of_executor = |cycles: u64| cycles as f64 * 100.0 / executor_cycles.max(1) as f64;
+                println!("Syscalls:");
+                println!("  {:<24} {:>10} {:>12} {:>12} {:>14}", "syscall", "calls", "cycles", "bytes read", "bytes written");
+                for syscall in &result.syscalls {
+                    println!("  {:<24} {:>10} {:>12} {:>12} {:>14}", 
+                        format!("{}{}", syscall.name, if syscall.precompile { " *" } else { "" }), 
+                        syscall.invocations, 
+                        syscall.cycles, 
+                        syscall.bytes_read, 
+                        syscall.bytes_written
+                    );
+                }
+                println!("  (* precompile)");
+                println!("  Syscall cycles: {} ({:.2}% of {} executor cycles)", 
+                    metrics.syscall_cycles, 
+                    percent_of_executor(metrics.syscall_cycles), 
+                    executor_cycles
+                );
+                println!("  Precompile cycles: {} ({:.2}%)", 
+                    metrics.precompile_cycles, 
+                    percent_of_executor(metrics.precompile_cycles)
+                );
+            }
+            
             println!("Hot spots:");
             for hotspot in &result.hot_spots {
                 println!("  {}: {} cycles ({:.1}%)", 
@@ -131,6 +448,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     hotspot.cycles_spent, 
                     hotspot.percentage
                 );
+                if !hotspot.line_numbers.is_empty() {
+                    let lines: Vec<String> = hotspot.line_numbers.iter().map(|line| line.to_string()).collect();
+                    println!("    lines: {}", lines.join(", "));
+                }
             }
             
             if let Some(output_path) = output {
@@ -138,106 +459,318 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 std::fs::write(&output_path, json)?;
                 println!("Profile results saved to: {:?}", output_path);
             }
+            if let Some(path) = collapsed {
+                std::fs::write(&path, export::collapsed_stacks(&result))?;
+                println!("Collapsed stacks saved to: {:?}", path);
+            }
+            if let Some(path) = speedscope {
+                let name = program_path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                std::fs::write(&path, serde_json::to_string(&export::speedscope(&result, &name))?)?;
+                println!("Speedscope profile saved to: {:?}", path);
+            }
+            if let Some(path) = pprof {
+                std::fs::write(&path, export::pprof(&result))?;
+                println!("pprof profile saved to: {:?}", path);
+            }
+        }
+        
+        Commands::ProfileDiff { base, new, threshold, top } => {
+            let diff = ProfileDiff::load(&base, &new)?;
+            println!("Comparing {} (base) with {} (new)", base.display(), new.display());
+            for line in diff.lines(top) {
+                println!("{}", line);
+            }
+            if let Some(threshold) = threshold {
+                if diff.exceeds(threshold) {
+                    println!("Regression of {:.2}% exceeds the {:.2}% threshold", diff.change_percent(), threshold);
+                    std::process::exit(1);
+                }
+            }
         }
         
         Commands::Interactive { program, input } => {
-            println!("Starting interactive debugging session...");
-            println!("Program: {:?}", program);
-            println!("Input: {}", input);
-            println!();
-            println!("Available commands:");
-            println!("  step, s     - Execute one instruction");
-            println!("  continue, c - Continue execution");
-            println!("  break, b    - Set breakpoint");
-            println!("  registers, r- Show register state");
-            println!("  memory, m   - Show memory at address");
-            println!("  quit, q     - Exit debugger");
-            println!();
-            
             let mut debugger = PicoDebugger::new();
-            debugger.start_session(program, input.into_bytes())?;
-            
-            // Simple interactive loop
-            loop {
-                print!("(pico-debug) ");
-                std::io::Write::flush(&mut std::io::stdout())?;
-                
-                let mut input = String::new();
-                std::io::stdin().read_line(&mut input)?;
-                let command = input.trim();
-                
-                match command {
-                    "step" | "s" => {
-                        let cycle_info = debugger.step_execution()?;
-                        println!("Cycle {}: {} at PC 0x{:x}", 
-                            cycle_info.cycle, 
-                            cycle_info.instruction, 
-                            cycle_info.pc
-                        );
-                    }
-                    "continue" | "c" => {
-                        debugger.continue_execution()?;
+            debugger.start_session(program, parse_inputs(&input)?)?;
+            
+            DebuggerUi::new(debugger).run()?;
+        }
+        
+        Commands::Sessions { dir, action } => {
+            let store = SessionStore::new(&dir);
+            match action {
+                SessionAction::List => {
+                    let (saved_sessions, warnings) = store.list()?;
+                    for warning in warnings {
+                        eprintln!("Warning: {}", warning);
                     }
-                    "registers" | "r" => {
-                        let registers = debugger.get_register_state()?;
-                        println!("Registers:");
-                        for (name, value) in registers {
-                            println!("  {}: 0x{:x}", name, value);
-                        }
+                    if saved_sessions.is_empty() {
+                        println!("No saved sessions in {}", dir.display());
                     }
-                    "memory" | "m" => {
-                        print!("Enter memory address: ");
-                        std::io::Write::flush(&mut std::io::stdout())?;
-                        let mut addr_input = String::new();
-                        std::io::stdin().read_line(&mut addr_input)?;
-                        if let Ok(addr) = u64::from_str_radix(addr_input.trim(), 16) {
-                            let value = debugger.get_memory_value(addr)?;
-                            println!("Memory[0x{:x}] = 0x{:x}", addr, value);
-                        }
+                    for saved in saved_sessions {
+                        let session = &saved.session;
+                        println!(
+                            "{}  {}  saved {}  {} breakpoints, {} watches{}",
+                            session.session_id,
+                            session.program_path.display(),
+                            saved.saved_at.format("%Y-%m-%d %H:%M:%S"),
+                            session.breakpoints.len(),
+                            session.watch_variables.len(),
+                            saved.checkpoint.map(|name| format!(", checkpoint '{}'", name)).unwrap_or_default()
+                        );
                     }
-                    "quit" | "q" => {
-                        println!("Exiting debugger...");
-                        break;
+                }
+                SessionAction::Resume { id, program } => {
+                    let debugger = store.resume(&id, program)?;
+                    DebuggerUi::new(debugger).run()?;
+                }
+                SessionAction::Delete { id } => {
+                    store.delete(&id)?;
+                    println!("Deleted session {}", id);
+                }
+            }
+        }
+        
+        Commands::Gdbserver { program, input, port } => {
+            let mut debugger = PicoDebugger::new();
+            debugger.start_session(program, parse_inputs(&input)?)?;
+            
+            GdbServer::new(debugger).serve(port)?;
+        }
+        
+        Commands::Dap => {
+            DapServer::stdio().run()?;
+        }
+        
+        Commands::Trace { program, input, output, max_cycles } => {
+            let program = Program::load(&program)?;
+            let mut emulator = Emulator::new(&program, parse_inputs(&input)?);
+            let mut writer = TraceWriter::create(&output, emulator.registers)?;
+            
+            let stop = emulator.run(max_cycles, |event| writer.record(event))?;
+            let summary = writer.finish()?;
+            
+            println!("Recorded {} cycles in {} chunks ({} bytes)", 
+                summary.total_cycles, 
+                summary.chunks, 
+                summary.bytes_written
+            );
+            match stop {
+                StopReason::Halted(code) => println!("Program halted with exit code {}", code),
+                StopReason::Trapped(trap) => println!("Program trapped: {}", trap),
+                StopReason::CycleLimit => println!("Stopped at cycle limit"),
+                StopReason::Breakpoint(_) | StopReason::Stepped | StopReason::Panicked => {}
+            }
+            println!("Trace saved to: {:?}", output);
+        }
+        
+        Commands::Io { program, input, max_cycles } => {
+            let mut debugger = PicoDebugger::new();
+            debugger.start_session(program, parse_inputs(&input)?)?;
+            let stop = debugger.run_for(max_cycles)?;
+            
+            let io_log = debugger.io_log();
+            let debug_info = debugger.debug_info();
+            println!("Syscalls ({}):", io_log.syscalls.len());
+            for line in io_log.syscall_lines(debug_info, None) {
+                println!("  {}", line);
+            }
+            println!("Input reads:");
+            for line in io_log.input_lines(debug_info) {
+                println!("  {}", line);
+            }
+            println!("Public values:");
+            for line in io_log.public_values_lines(debug_info) {
+                println!("  {}", line);
+            }
+            println!("{}", commands::describe_stop(&debugger, &stop)?);
+        }
+        
+        Commands::RunScript { program, input, script, output, max_cycles } => {
+            let mut debugger = PicoDebugger::new();
+            debugger.start_session(program, parse_inputs(&input)?)?;
+            let report = script::run_script(&mut debugger, &std::fs::read_to_string(&script)?, max_cycles);
+            let json = serde_json::to_string_pretty(&report)?;
+            match output {
+                Some(path) => std::fs::write(path, json)?,
+                None => println!("{}", json),
+            }
+            if !report.success {
+                std::process::exit(1);
+            }
+        }
+        
+        Commands::Diff { a, input1, b, input2, max_cycles, context } => {
+            let b = b.unwrap_or_else(|| a.clone());
+            let input2 = if input2.is_empty() { input1.clone() } else { input2 };
+            let mut run_a = DiffRun::new(format!("a ({})", a.display()), &Program::load(&a)?, parse_inputs(&input1)?);
+            let mut run_b = DiffRun::new(format!("b ({})", b.display()), &Program::load(&b)?, parse_inputs(&input2)?);
+            
+            match diff::run_lockstep(&mut run_a, &mut run_b, max_cycles) {
+                DiffOutcome::Diverged(divergence) => {
+                    let lines = divergence
+                        .lines()
+                        .into_iter()
+                        .chain(run_a.context_lines(context))
+                        .chain(run_b.context_lines(context));
+                    for line in lines {
+                        println!("{}", line);
                     }
-                    _ => {
-                        println!("Unknown command: {}", command);
+                }
+                DiffOutcome::Identical(stop) => {
+                    let cycles = run_a.emulator.cycle;
+                    match stop {
+                        StopReason::Halted(code) => println!("No divergence: both runs halted with exit code {} after {} cycles", code, cycles),
+                        StopReason::Trapped(trap) => println!("No divergence: both runs trapped after {} cycles: {}", cycles, trap),
+                        _ => println!("No divergence in the first {} cycles", cycles),
                     }
                 }
             }
         }
         
-        Commands::Analyze { trace, format } => {
-            println!("Analyzing trace file: {:?}", trace);
-            println!("Output format: {}", format);
+        Commands::Analyze { trace, format, from_cycle, to_cycle, pc_range, show, program } => {
+            let debug_info = program.map(|path| DebugInfo::load(&std::fs::read(path)?)).transpose()?;
+            let mut reader = TraceReader::open(&trace)?;
+            let total_cycles = reader.total_cycles();
+            let filter = TraceFilter {
+                from_cycle,
+                to_cycle,
+                pc_range: pc_range.as_deref().map(parse_pc_range).transpose()?,
+            };
+            
+            let mut instructions = 0u64;
+            let mut memory_reads = 0u64;
+            let mut memory_writes = 0u64;
+            let mut function_calls = 0u64;
+            let mut syscalls = 0u64;
+            let mut unique_pcs = HashSet::new();
+            let mut shown = Vec::new();
+            
+            for record in reader.records(filter) {
+                let record = record?;
+                instructions += 1;
+                unique_pcs.insert(record.pc);
+                for (access_type, _, _) in &record.memory {
+                    match access_type {
+                        MemoryAccessType::Read => memory_reads += 1,
+                        MemoryAccessType::Write => memory_writes += 1,
+                    }
+                }
+                if record.instruction().is_call() {
+                    function_calls += 1;
+                }
+                if record.syscall.is_some() {
+                    syscalls += 1;
+                }
+                if shown.len() < show {
+                    shown.push(record.cycle_info(debug_info.as_ref()));
+                }
+            }
             
-            // Placeholder analysis
-            println!("Trace analysis complete!");
-            println!("  Total instructions: 1000");
-            println!("  Memory accesses: 500");
-            println!("  Function calls: 50");
-            println!("  Average cycles per instruction: 1.2");
+            match format.as_str() {
+                "json" => {
+                    let report = serde_json::json!({
+                        "trace_cycles": total_cycles,
+                        "instructions": instructions,
+                        "memory_reads": memory_reads,
+                        "memory_writes": memory_writes,
+                        "function_calls": function_calls,
+                        "syscalls": syscalls,
+                        "unique_pcs": unique_pcs.len(),
+                        "cycles": shown,
+                    });
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                "text" => {
+                    println!("Analyzing trace file: {:?}", trace);
+                    for cycle_info in &shown {
+                        let mut line = format!("  {:>10}  0x{:08x}  {:<28}", cycle_info.cycle, cycle_info.pc, cycle_info.instruction);
+                        if let Some(function) = &cycle_info.function {
+                            line.push_str(&format!("  {}", function));
+                        }
+                        if let Some(location) = &cycle_info.source_location {
+                            line.push_str(&format!(" ({})", location));
+                        }
+                        println!("{}", line.trim_end());
+                    }
+                    println!("Trace analysis complete!");
+                    println!("  Trace cycles: {}", total_cycles);
+                    println!("  Matching instructions: {}", instructions);
+                    println!("  Memory accesses: {} ({} reads, {} writes)", 
+                        memory_reads + memory_writes, 
+                        memory_reads, 
+                        memory_writes
+                    );
+                    println!("  Function calls: {}", function_calls);
+                    println!("  Syscalls: {}", syscalls);
+                    println!("  Unique PCs: {}", unique_pcs.len());
+                }
+                other => return Err(format!("Unsupported output format: {}", other).into()),
+            }
         }
         
-        Commands::Memory { program, layout, stats } => {
-            println!("Memory analysis for: {:?}", program);
+        Commands::Memory { program: program_path, input, layout, stats, max_cycles, trace, html, top } => {
+            let program = Program::load(&program_path)?;
+            let debug_info = DebugInfo::load(&program.elf).ok();
+            let memory_layout = MemoryLayout::new(&program, debug_info.as_ref());
+            println!("Memory analysis for: {}", program_path.display());
             
             if layout {
                 println!("Memory Layout:");
-                println!("  .text:   0x10000 - 0x15000 (20KB)");
-                println!("  .data:   0x15000 - 0x16000 (4KB)");
-                println!("  .bss:    0x16000 - 0x17000 (4KB)");
-                println!("  .stack:  0x17000 - 0x18000 (4KB)");
+                for line in memory_layout.lines() {
+                    println!("  {}", line);
+                }
             }
             
             if stats {
-                println!("Memory Statistics:");
-                println!("  Total memory used: 32KB");
-                println!("  Peak stack usage: 2KB");
-                println!("  Heap allocations: 10");
-                println!("  Memory fragmentation: 5%");
+                let mut memory_stats = MemoryStats::new(memory_layout.clone());
+                let mut emulator = Emulator::new(&program, parse_inputs(&input)?);
+                let stop = emulator.run(max_cycles, |event| {
+                    memory_stats.observe(event);
+                    Ok::<_, std::convert::Infallible>(())
+                })?;
+                match stop {
+                    StopReason::Halted(code) => println!("Memory Statistics ({} cycles, exit code {}):", emulator.cycle, code),
+                    StopReason::Trapped(trap) => println!("Memory Statistics ({} cycles, trapped: {}):", emulator.cycle, trap),
+                    _ => println!("Memory Statistics (first {} cycles):", emulator.cycle),
+                }
+                for line in memory_stats.lines(debug_info.as_ref()) {
+                    println!("  {}", line);
+                }
+            }
+            
+            if let Some(trace) = trace {
+                let mut heatmap = MemoryHeatmap::new();
+                let mut reader = TraceReader::open(&trace)?;
+                for record in reader.records(TraceFilter::default()) {
+                    heatmap.record(&record?, Some(&memory_layout), debug_info.as_ref());
+                }
+                println!("Memory Heatmap ({}):", trace.display());
+                for line in heatmap.grid_lines() {
+                    println!("  {}", line);
+                }
+                println!("Hottest pages:");
+                for line in heatmap.page_lines(top) {
+                    println!("  {}", line);
+                }
+                println!("Hottest symbols:");
+                for line in heatmap.symbol_lines(top) {
+                    println!("  {}", line);
+                }
+                if let Some(path) = html {
+                    let title = format!("Memory heatmap for {}", program_path.display());
+                    std::fs::write(&path, heatmap.html(&title, top))?;
+                    println!("HTML report saved to: {}", path.display());
+                }
             }
         }
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+fn parse_pc_range(range: &str) -> Result<(u32, u32), Box<dyn std::error::Error>> {
+    let (start, end) = range
+        .split_once('-')
+        .ok_or_else(|| format!("Invalid PC range '{}', expected START-END", range))?;
+    Ok((parse_address(start)?, parse_address(end)?))
+}
@@ -0,0 +1,752 @@
+// Guest memory analysis
+// Derives the memory layout from the ELF program headers and the Pico memory map, measures how a run
+// used it and builds access heatmaps from recorded traces
+
+use crate::debuginfo::DebugInfo;
+use crate::emulator::{Program, StepEvent, PAGE_SIZE, STACK_TOP};
+use crate::trace::TraceRecord;
+use crate::{access_width, syscalls, MemoryAccessType};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Highest address the guest allocator hands out.
+pub const HEAP_LIMIT: u32 = 0x7800_0000;
+
+/// The allocator's bump pointer, a static in the SDK's `sys_alloc_aligned`.
+const HEAP_POINTER_SYMBOL: &str = "HEAP_POS";
+
+/// Linker symbol marking the end of the program image, where the heap starts.
+const IMAGE_END_SYMBOL: &str = "_end";
+
+/// Data symbols listed as never read by [`MemoryStats::lines`].
+const UNREAD_SYMBOL_COUNT: usize = 10;
+
+/// Pages per row of the heatmap grid.
+const HEATMAP_ROW_PAGES: u32 = 64;
+
+/// Heatmap cells from cold to hot.
+const HEAT_SHADES: [char; 5] = ['.', '░', '▒', '▓', '█'];
+
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    Stack,
+    Text,
+    ReadOnly,
+    Data,
+    Bss,
+    Heap,
+}
+
+impl RegionKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            RegionKind::Stack => "stack",
+            RegionKind::Text => ".text",
+            RegionKind::ReadOnly => ".rodata",
+            RegionKind::Data => ".data",
+            RegionKind::Bss => ".bss",
+            RegionKind::Heap => "heap",
+        }
+    }
+
+    fn permissions(self) -> &'static str {
+        match self {
+            RegionKind::Text => "r-x",
+            RegionKind::ReadOnly => "r--",
+            _ => "rw-",
+        }
+    }
+}
+
+/// A half-open address range `[start, end)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub kind: RegionKind,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl MemoryRegion {
+    pub fn contains(&self, address: u32) -> bool {
+        (self.start..self.end).contains(&address)
+    }
+
+    pub fn size(&self) -> u32 {
+        self.end - self.start
+    }
+}
+
+/// Where everything lives in a guest's address space: the stack growing down
+/// from [`STACK_TOP`], the loadable segments split by permissions and file
+/// contents, and the heap from the end of the image up to [`HEAP_LIMIT`].
+#[derive(Debug, Clone)]
+pub struct MemoryLayout {
+    pub regions: Vec<MemoryRegion>,
+    /// Address of the allocator's bump pointer, if the ELF has the symbol.
+    pub heap_pointer: Option<u32>,
+}
+
+impl MemoryLayout {
+    pub fn new(program: &Program, debug_info: Option<&DebugInfo>) -> Self {
+        let mut regions = vec![MemoryRegion {
+            kind: RegionKind::Stack,
+            start: 0,
+            end: STACK_TOP,
+        }];
+        for segment in program.segments.iter().filter(|segment| segment.mem_size != 0) {
+            let start = segment.vaddr;
+            let end = segment.vaddr.saturating_add(segment.mem_size);
+            let file_end = start.saturating_add(segment.data.len() as u32).min(end);
+            if segment.flags & PF_X != 0 {
+                regions.push(MemoryRegion { kind: RegionKind::Text, start, end });
+            } else if segment.flags & PF_W == 0 {
+                regions.push(MemoryRegion { kind: RegionKind::ReadOnly, start, end });
+            } else {
+                if file_end > start {
+                    regions.push(MemoryRegion { kind: RegionKind::Data, start, end: file_end });
+                }
+                if end > file_end {
+                    regions.push(MemoryRegion { kind: RegionKind::Bss, start: file_end, end });
+                }
+            }
+        }
+
+        let image_end = regions.iter().map(|region| region.end).max().unwrap_or(STACK_TOP);
+        let heap_start = debug_info
+            .and_then(|info| info.data_symbol_address(IMAGE_END_SYMBOL))
+            .unwrap_or(image_end);
+        if heap_start < HEAP_LIMIT {
+            regions.push(MemoryRegion {
+                kind: RegionKind::Heap,
+                start: heap_start,
+                end: HEAP_LIMIT,
+            });
+        }
+        regions.sort_by_key(|region| region.start);
+
+        Self {
+            regions,
+            heap_pointer: debug_info.and_then(|info| info.data_symbol_address(HEAP_POINTER_SYMBOL)),
+        }
+    }
+
+    pub fn region_at(&self, address: u32) -> Option<&MemoryRegion> {
+        self.regions.iter().find(|region| region.contains(address))
+    }
+
+    pub fn heap(&self) -> Option<&MemoryRegion> {
+        self.regions.iter().find(|region| region.kind == RegionKind::Heap)
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.regions
+            .iter()
+            .map(|region| {
+                let note = match region.kind {
+                    RegionKind::Stack => format!("  grows down from 0x{:08x}", STACK_TOP),
+                    RegionKind::Heap => match self.heap_pointer {
+                        Some(address) => format!("  bump pointer at 0x{:08x}", address),
+                        None => "  no allocator bump pointer symbol".to_string(),
+                    },
+                    _ => String::new(),
+                };
+                format!(
+                    "{:<8} 0x{:08x} - 0x{:08x}  {:>10}  {}{}",
+                    region.kind.name(),
+                    region.start,
+                    region.end,
+                    format_size(region.size() as u64),
+                    region.kind.permissions(),
+                    note
+                )
+            })
+            .collect()
+    }
+}
+
+/// Initialised bytes of one writable or read-only data region, with whether each was read.
+#[derive(Debug, Clone)]
+struct InitialisedData {
+    start: u32,
+    read: Vec<bool>,
+}
+
+/// What a run did with guest memory. Feed it every step with [`observe`](Self::observe).
+#[derive(Debug, Clone)]
+pub struct MemoryStats {
+    layout: MemoryLayout,
+    touched_pages: BTreeSet<u32>,
+    /// Pages touched within each region of the layout, by region index.
+    region_pages: Vec<BTreeSet<u32>>,
+    lowest_stack_pointer: u32,
+    heap_pointer_writes: u64,
+    heap_end: Option<u32>,
+    initialised: Vec<InitialisedData>,
+}
+
+impl MemoryStats {
+    pub fn new(layout: MemoryLayout) -> Self {
+        let initialised = layout
+            .regions
+            .iter()
+            .filter(|region| matches!(region.kind, RegionKind::ReadOnly | RegionKind::Data))
+            .map(|region| InitialisedData {
+                start: region.start,
+                read: vec![false; region.size() as usize],
+            })
+            .collect();
+        Self {
+            region_pages: vec![BTreeSet::new(); layout.regions.len()],
+            layout,
+            touched_pages: BTreeSet::new(),
+            lowest_stack_pointer: STACK_TOP,
+            heap_pointer_writes: 0,
+            heap_end: None,
+            initialised,
+        }
+    }
+
+    pub fn observe(&mut self, event: &StepEvent) {
+        self.touch(event.pc);
+        if let Some((2, value)) = event.register_write {
+            self.lowest_stack_pointer = self.lowest_stack_pointer.min(value);
+        }
+
+        let width = access_width(&event.instruction) as u32;
+        for access in &event.memory {
+            let address = access.address as u32;
+            self.touch(address);
+            match access.access_type {
+                MemoryAccessType::Read => self.mark_read(address, width),
+                MemoryAccessType::Write if Some(address) == self.layout.heap_pointer => {
+                    self.heap_pointer_writes += 1;
+                    self.heap_end = Some(self.heap_end.unwrap_or(0).max(access.value as u32));
+                }
+                MemoryAccessType::Write => {}
+            }
+        }
+
+        // `write` reads its buffer directly rather than through loads.
+        if let Some(syscall) = event.syscall.as_ref().filter(|syscall| syscall.code == syscalls::WRITE) {
+            let [_, buffer, length] = syscall.arguments;
+            for page in buffer / PAGE_SIZE..=buffer.saturating_add(length.max(1) - 1) / PAGE_SIZE {
+                self.touch(buffer.max(page * PAGE_SIZE));
+            }
+            self.mark_read(buffer, length);
+        }
+    }
+
+    fn touch(&mut self, address: u32) {
+        let page = address / PAGE_SIZE;
+        self.touched_pages.insert(page);
+        if let Some(index) = self.layout.regions.iter().position(|region| region.contains(address)) {
+            self.region_pages[index].insert(page);
+        }
+    }
+
+    fn mark_read(&mut self, address: u32, length: u32) {
+        for data in &mut self.initialised {
+            let end = data.start as u64 + data.read.len() as u64;
+            let first = (address as u64).max(data.start as u64);
+            let last = (address as u64 + length as u64).min(end);
+            for offset in first..last {
+                data.read[(offset - data.start as u64) as usize] = true;
+            }
+        }
+    }
+
+    pub fn touched_pages(&self) -> &BTreeSet<u32> {
+        &self.touched_pages
+    }
+
+    /// Deepest the stack grew below [`STACK_TOP`], in bytes.
+    pub fn stack_high_water_mark(&self) -> u32 {
+        STACK_TOP.saturating_sub(self.lowest_stack_pointer)
+    }
+
+    /// Bytes the bump pointer moved past the start of the heap, or None if the
+    /// allocator was never used or its pointer could not be found.
+    pub fn heap_growth(&self) -> Option<u32> {
+        let heap = self.layout.heap()?;
+        Some(self.heap_end?.saturating_sub(heap.start))
+    }
+
+    /// Initialised data bytes the guest never read.
+    pub fn unread_bytes(&self) -> u64 {
+        self.initialised
+            .iter()
+            .map(|data| data.read.iter().filter(|read| !**read).count() as u64)
+            .sum()
+    }
+
+    fn is_unread(&self, start: u32, size: u32) -> bool {
+        (start..start.saturating_add(size)).all(|address| {
+            self.initialised.iter().any(|data| {
+                address >= data.start
+                    && ((address - data.start) as usize) < data.read.len()
+                    && !data.read[(address - data.start) as usize]
+            })
+        })
+    }
+
+    pub fn lines(&self, debug_info: Option<&DebugInfo>) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Touched pages: {} ({})",
+            self.touched_pages.len(),
+            format_size(self.touched_pages.len() as u64 * PAGE_SIZE as u64)
+        )];
+        for (region, pages) in self.layout.regions.iter().zip(&self.region_pages) {
+            if !pages.is_empty() {
+                lines.push(format!("  {:<8} {} pages", region.kind.name(), pages.len()));
+            }
+        }
+
+        lines.push(format!(
+            "Stack high-water mark: {} (lowest sp 0x{:08x})",
+            format_size(self.stack_high_water_mark() as u64),
+            self.lowest_stack_pointer
+        ));
+
+        match (self.layout.heap_pointer, self.heap_growth()) {
+            (None, _) => lines.push(format!("Heap: no `{}` symbol, allocator usage unknown", HEAP_POINTER_SYMBOL)),
+            (Some(_), None) => lines.push("Heap: never allocated".to_string()),
+            (Some(_), Some(growth)) => lines.push(format!(
+                "Heap growth: {} (bump pointer reached 0x{:08x} after {} updates)",
+                format_size(growth as u64),
+                self.heap_end.unwrap_or_default(),
+                self.heap_pointer_writes
+            )),
+        }
+
+        let initialised: u64 = self.initialised.iter().map(|data| data.read.len() as u64).sum();
+        lines.push(format!(
+            "Initialised but never read: {} of {}",
+            format_size(self.unread_bytes()),
+            format_size(initialised)
+        ));
+        if let Some(info) = debug_info {
+            let mut unread: Vec<_> = info
+                .data_symbols()
+                .iter()
+                .filter(|symbol| symbol.size != 0 && self.is_unread(symbol.address, symbol.size))
+                .collect();
+            unread.sort_by_key(|symbol| std::cmp::Reverse(symbol.size));
+            for symbol in unread.iter().take(UNREAD_SYMBOL_COUNT) {
+                lines.push(format!("  {:>10}  0x{:08x}  {}", format_size(symbol.size as u64), symbol.address, symbol.name));
+            }
+        }
+        lines
+    }
+}
+
+/// Reads and writes of one page or symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessCounts {
+    pub reads: u64,
+    pub writes: u64,
+    pub first_cycle: u64,
+    pub last_cycle: u64,
+}
+
+impl AccessCounts {
+    fn new(cycle: u64) -> Self {
+        Self {
+            reads: 0,
+            writes: 0,
+            first_cycle: cycle,
+            last_cycle: cycle,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.reads + self.writes
+    }
+
+    fn record(&mut self, access_type: MemoryAccessType, cycle: u64) {
+        match access_type {
+            MemoryAccessType::Read => self.reads += 1,
+            MemoryAccessType::Write => self.writes += 1,
+        }
+        self.first_cycle = self.first_cycle.min(cycle);
+        self.last_cycle = self.last_cycle.max(cycle);
+    }
+}
+
+/// Accesses to one data symbol, or to the rest of a region for addresses no symbol covers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SymbolAccesses {
+    pub name: String,
+    pub address: u32,
+    pub size: u32,
+    pub counts: AccessCounts,
+}
+
+impl SymbolAccesses {
+    /// Accesses per byte, which singles out small buffers that are hammered.
+    pub fn density(&self) -> f64 {
+        self.counts.total() as f64 / self.size.max(1) as f64
+    }
+}
+
+/// Per-page and per-symbol access counts aggregated from a trace's memory records.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryHeatmap {
+    pub pages: BTreeMap<u32, AccessCounts>,
+    /// Keyed by symbol address, or by region start for unsymbolised accesses.
+    pub symbols: BTreeMap<u32, SymbolAccesses>,
+}
+
+impl MemoryHeatmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the memory accesses of one traced cycle. With `debug_info` they are
+    /// attributed to the data symbol they fall in; otherwise, or when no symbol
+    /// covers them, to the region of `layout`.
+    pub fn record(&mut self, record: &TraceRecord, layout: Option<&MemoryLayout>, debug_info: Option<&DebugInfo>) {
+        for &(access_type, address, _) in &record.memory {
+            self.pages
+                .entry(address / PAGE_SIZE)
+                .or_insert_with(|| AccessCounts::new(record.cycle))
+                .record(access_type, record.cycle);
+
+            let owner = match debug_info.and_then(|info| info.data_symbol_at(address)) {
+                Some(symbol) => Some((symbol.address, symbol.name.clone(), symbol.size)),
+                None => layout
+                    .and_then(|layout| layout.region_at(address))
+                    .map(|region| (region.start, format!("[{}]", region.kind.name()), region.size())),
+            };
+            if let Some((start, name, size)) = owner {
+                self.symbols
+                    .entry(start)
+                    .or_insert_with(|| SymbolAccesses {
+                        name,
+                        address: start,
+                        size,
+                        counts: AccessCounts::new(record.cycle),
+                    })
+                    .counts
+                    .record(access_type, record.cycle);
+            }
+        }
+    }
+
+    /// Symbols by total accesses, busiest first.
+    pub fn hottest_symbols(&self) -> Vec<&SymbolAccesses> {
+        let mut symbols: Vec<_> = self.symbols.values().collect();
+        symbols.sort_by_key(|symbol| std::cmp::Reverse(symbol.counts.total()));
+        symbols
+    }
+
+    /// Pages by total accesses, busiest first.
+    pub fn hottest_pages(&self) -> Vec<(u32, &AccessCounts)> {
+        let mut pages: Vec<_> = self.pages.iter().map(|(&page, counts)| (page, counts)).collect();
+        pages.sort_by_key(|(_, counts)| std::cmp::Reverse(counts.total()));
+        pages
+    }
+
+    fn max_page_accesses(&self) -> u64 {
+        self.pages.values().map(AccessCounts::total).max().unwrap_or(0)
+    }
+
+    /// Heat of `accesses` from 0 to 1 on a log scale, so one hot page does
+    /// not wash out the rest.
+    fn heat(&self, accesses: u64) -> f64 {
+        let max = self.max_page_accesses();
+        if accesses == 0 || max == 0 {
+            return 0.0;
+        }
+        ((accesses as f64).ln_1p() / (max as f64).ln_1p()).clamp(0.0, 1.0)
+    }
+
+    /// One row per [`HEATMAP_ROW_PAGES`] pages that saw any access, one shaded
+    /// cell per page. Runs of untouched rows are collapsed.
+    pub fn grid_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "One cell per {} page, blank if untouched, {} coldest to {} hottest ({} accesses)",
+            format_size(PAGE_SIZE as u64),
+            HEAT_SHADES[0],
+            HEAT_SHADES[HEAT_SHADES.len() - 1],
+            self.max_page_accesses()
+        )];
+        let rows: BTreeSet<u32> = self.pages.keys().map(|page| page / HEATMAP_ROW_PAGES).collect();
+        let mut previous_row = None;
+        for row in rows {
+            if previous_row.is_some_and(|previous| row > previous + 1) {
+                lines.push("           ...".to_string());
+            }
+            previous_row = Some(row);
+            let cells: String = (0..HEATMAP_ROW_PAGES)
+                .map(|column| match self.pages.get(&(row * HEATMAP_ROW_PAGES + column)) {
+                    Some(counts) => {
+                        let shade = (self.heat(counts.total()) * (HEAT_SHADES.len() - 1) as f64).round() as usize;
+                        HEAT_SHADES[shade]
+                    }
+                    None => ' ',
+                })
+                .collect();
+            lines.push(format!("0x{:08x} |{}|", row * HEATMAP_ROW_PAGES * PAGE_SIZE, cells));
+        }
+        lines
+    }
+
+    pub fn page_lines(&self, count: usize) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:<10}  {:>12}  {:>12}  {:>12}  {:>12}",
+            "page", "reads", "writes", "first cycle", "last cycle"
+        )];
+        for (page, counts) in self.hottest_pages().into_iter().take(count) {
+            lines.push(format!(
+                "0x{:08x}  {:>12}  {:>12}  {:>12}  {:>12}",
+                page * PAGE_SIZE,
+                counts.reads,
+                counts.writes,
+                counts.first_cycle,
+                counts.last_cycle
+            ));
+        }
+        lines
+    }
+
+    pub fn symbol_lines(&self, count: usize) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:>12}  {:>12}  {:>10}  {:>10}  {:>12}  {:>12}  symbol",
+            "reads", "writes", "size", "per byte", "first cycle", "last cycle"
+        )];
+        for symbol in self.hottest_symbols().into_iter().take(count) {
+            lines.push(format!(
+                "{:>12}  {:>12}  {:>10}  {:>10.1}  {:>12}  {:>12}  {} (0x{:08x})",
+                symbol.counts.reads,
+                symbol.counts.writes,
+                format_size(symbol.size as u64),
+                symbol.density(),
+                symbol.counts.first_cycle,
+                symbol.counts.last_cycle,
+                symbol.name,
+                symbol.address
+            ));
+        }
+        lines
+    }
+
+    /// A self-contained HTML report: the page grid coloured by heat with
+    /// per-page tooltips, followed by the page and symbol tables.
+    pub fn html(&self, title: &str, count: usize) -> String {
+        let mut html = String::new();
+        html.push_str(&format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+             body {{ font-family: sans-serif; margin: 2em; }}\n\
+             table {{ border-collapse: collapse; margin-bottom: 2em; }}\n\
+             th, td {{ padding: 2px 8px; text-align: right; font-family: monospace; }}\n\
+             th {{ border-bottom: 1px solid #888; }}\n\
+             td.name {{ text-align: left; }}\n\
+             .grid td {{ width: 10px; height: 10px; padding: 0; border: 1px solid #eee; }}\n\
+             .grid th {{ border: none; padding-right: 8px; }}\n\
+             </style>\n</head>\n<body>\n<h1>{}</h1>\n",
+            escape_html(title),
+            escape_html(title)
+        ));
+
+        html.push_str(&format!(
+            "<h2>Page heatmap</h2>\n<p>One cell per {} page, {} pages touched, hottest page {} accesses.</p>\n<table class=\"grid\">\n",
+            format_size(PAGE_SIZE as u64),
+            self.pages.len(),
+            self.max_page_accesses()
+        ));
+        let rows: BTreeSet<u32> = self.pages.keys().map(|page| page / HEATMAP_ROW_PAGES).collect();
+        for row in rows {
+            html.push_str(&format!("<tr><th>0x{:08x}</th>", row * HEATMAP_ROW_PAGES * PAGE_SIZE));
+            for column in 0..HEATMAP_ROW_PAGES {
+                let page = row * HEATMAP_ROW_PAGES + column;
+                match self.pages.get(&page) {
+                    Some(counts) => html.push_str(&format!(
+                        "<td style=\"background: {}\" title=\"0x{:08x}: {} reads, {} writes, cycles {}-{}\"></td>",
+                        heat_colour(self.heat(counts.total())),
+                        page * PAGE_SIZE,
+                        counts.reads,
+                        counts.writes,
+                        counts.first_cycle,
+                        counts.last_cycle
+                    )),
+                    None => html.push_str("<td></td>"),
+                }
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Hottest pages</h2>\n<table>\n<tr><th>Page</th><th>Reads</th><th>Writes</th><th>First cycle</th><th>Last cycle</th></tr>\n");
+        for (page, counts) in self.hottest_pages().into_iter().take(count) {
+            html.push_str(&format!(
+                "<tr style=\"background: {}\"><td>0x{:08x}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                heat_colour(self.heat(counts.total())),
+                page * PAGE_SIZE,
+                counts.reads,
+                counts.writes,
+                counts.first_cycle,
+                counts.last_cycle
+            ));
+        }
+        html.push_str("</table>\n");
+
+        if !self.symbols.is_empty() {
+            html.push_str("<h2>Hottest symbols</h2>\n<table>\n<tr><th>Symbol</th><th>Address</th><th>Size</th><th>Reads</th><th>Writes</th><th>Per byte</th><th>First cycle</th><th>Last cycle</th></tr>\n");
+            for symbol in self.hottest_symbols().into_iter().take(count) {
+                html.push_str(&format!(
+                    "<tr><td class=\"name\">{}</td><td>0x{:08x}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&symbol.name),
+                    symbol.address,
+                    format_size(symbol.size as u64),
+                    symbol.counts.reads,
+                    symbol.counts.writes,
+                    symbol.density(),
+                    symbol.counts.first_cycle,
+                    symbol.counts.last_cycle
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+/// White through yellow to red as `heat` goes from 0 to 1.
+fn heat_colour(heat: f64) -> String {
+    let green = (255.0 * (1.0 - heat)).round() as u8;
+    let blue = (255.0 * (1.0 - heat).powi(2) * 0.8).round() as u8;
+    format!("#ff{:02x}{:02x}", green, blue)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// `512 B`, `4.0 KB`, `1.5 MB`.
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{Emulator, Segment};
+
+    /// Text at 0x200800, read-only data at 0x201000 and a data segment at
+    /// 0x202000 with 8 initialised bytes followed by bss.
+    fn program(code: &[u32]) -> Program {
+        let segment = |vaddr, data: Vec<u8>, mem_size, flags| Segment { vaddr, mem_size, data, flags };
+        Program {
+            entry: 0x20_0800,
+            segments: vec![
+                segment(0x20_0800, code.iter().flat_map(|word| word.to_le_bytes()).collect(), code.len() as u32 * 4, 5),
+                segment(0x20_1000, vec![1; 8], 8, 4),
+                segment(0x20_2000, vec![2; 8], 0x100, 6),
+                segment(0x20_3000, Vec::new(), 0, 6),
+            ],
+            elf: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn layout_splits_segments_by_permissions_and_contents() {
+        let layout = MemoryLayout::new(&program(&[0; 4]), None);
+        let regions: Vec<(RegionKind, u32, u32)> =
+            layout.regions.iter().map(|region| (region.kind, region.start, region.end)).collect();
+        assert_eq!(
+            regions,
+            [
+                (RegionKind::Stack, 0, STACK_TOP),
+                (RegionKind::Text, 0x20_0800, 0x20_0810),
+                (RegionKind::ReadOnly, 0x20_1000, 0x20_1008),
+                (RegionKind::Data, 0x20_2000, 0x20_2008),
+                (RegionKind::Bss, 0x20_2008, 0x20_2100),
+                (RegionKind::Heap, 0x20_2100, HEAP_LIMIT),
+            ]
+        );
+        assert_eq!(layout.region_at(0x20_2008).map(|region| region.kind), Some(RegionKind::Bss));
+        assert_eq!(layout.region_at(0x20_0400), None);
+        assert_eq!(layout.region_at(HEAP_LIMIT), None);
+
+        let lines = layout.lines();
+        assert_eq!(lines[0], "stack    0x00000000 - 0x00200400      2.0 MB  rw-  grows down from 0x00200400");
+        assert_eq!(lines[1], ".text    0x00200800 - 0x00200810        16 B  r-x");
+        assert!(lines[5].ends_with("no allocator bump pointer symbol"));
+    }
+
+    #[test]
+    fn stats_count_pages_stack_heap_and_unread_data() {
+        let code = [
+            0x0020_1537, 0x0005_2583, // lui a0, 0x201; lw a1, 0(a0)
+            0xfe01_0113, // addi sp, sp, -32
+            0x0020_2637, 0x1806_0693, 0x00d6_2023, // lui a2, 0x202; addi a3, a2, 0x180; sw a3, 0(a2)
+            0x0000_0293, 0x0000_0513, 0x0000_0073, // halt
+        ];
+        let program = program(&code);
+        let mut layout = MemoryLayout::new(&program, None);
+        // Treat the first data word as the allocator's bump pointer
+        layout.heap_pointer = Some(0x20_2000);
+        let mut stats = MemoryStats::new(layout);
+        let mut emulator = Emulator::new(&program, Vec::new());
+        emulator
+            .run(None, |event| {
+                stats.observe(event);
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+
+        assert_eq!(stats.touched_pages().iter().copied().collect::<Vec<_>>(), [0x200, 0x201, 0x202]);
+        assert_eq!(stats.stack_high_water_mark(), 32);
+        assert_eq!(stats.heap_growth(), Some(0x80));
+        // Half the read-only data was loaded; the data segment was only written
+        assert_eq!(stats.unread_bytes(), 4 + 8);
+
+        let lines = stats.lines(None);
+        assert_eq!(lines[0], "Touched pages: 3 (12.0 KB)");
+        assert!(lines.contains(&"Stack high-water mark: 32 B (lowest sp 0x002003e0)".to_string()));
+        assert!(lines.contains(&"Heap growth: 128 B (bump pointer reached 0x00202180 after 1 updates)".to_string()));
+        assert_eq!(lines.last().unwrap(), "Initialised but never read: 12 B of 16 B");
+    }
+
+    fn record(cycle: u64, memory: Vec<(MemoryAccessType, u32, u32)>) -> TraceRecord {
+        TraceRecord { cycle, pc: 0, word: 0, register_write: None, memory, syscall: None }
+    }
+
+    #[test]
+    fn heatmap_buckets_accesses_by_page_and_region() {
+        let layout = MemoryLayout::new(&program(&[0; 4]), None);
+        let mut heatmap = MemoryHeatmap::new();
+        heatmap.record(&record(1, vec![(MemoryAccessType::Write, 0x0ffc, 0)]), Some(&layout), None);
+        for cycle in 2..102 {
+            heatmap.record(&record(cycle, vec![(MemoryAccessType::Read, 0x1000, 0)]), Some(&layout), None);
+        }
+        heatmap.record(&record(200, vec![(MemoryAccessType::Read, 0x20_2004, 0)]), Some(&layout), None);
+
+        assert_eq!(heatmap.pages.len(), 3);
+        let (page, counts) = heatmap.hottest_pages()[0];
+        assert_eq!((page, counts.reads, counts.writes, counts.first_cycle, counts.last_cycle), (1, 100, 0, 2, 101));
+
+        // Both stack pages fold into one region entry; the data read gets its own
+        let symbols = heatmap.hottest_symbols();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!((symbols[0].name.as_str(), symbols[0].counts.total()), ("[stack]", 101));
+        assert_eq!((symbols[1].name.as_str(), symbols[1].address), ("[.data]", 0x20_2000));
+
+        // The busiest page is the hottest shade and a single access is on the
+        // log scale's first step; rows without accesses in between are elided
+        let grid = heatmap.grid_lines();
+        assert!(grid[0].ends_with("(100 accesses)"));
+        assert!(grid[1].starts_with("0x00000000 |░█ "));
+        assert_eq!(grid[2], "           ...");
+        assert!(grid[3].starts_with("0x00200000 |  ░"));
+        assert_eq!(grid.len(), 4);
+    }
+}
@@ -0,0 +1,340 @@
+// Guest panic diagnosis
+// Recovers the panic message and location from the arguments of the guest's panic handler
+
+use crate::callstack::CallStack;
+use crate::debuginfo::{DebugInfo, SourceLocation};
+use crate::emulator::{Emulator, Memory, StepEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Instructions remembered for the "last instructions executed" listing.
+pub const HISTORY_LENGTH: usize = 256;
+
+/// Longest string read from guest memory; anything longer is assumed to be a
+/// misread pointer rather than a panic message or file name.
+const MAX_STRING_LENGTH: u32 = 4096;
+
+/// Longest `fmt::Arguments` slice worth decoding.
+const MAX_PIECES: u32 = 64;
+
+/// `core::panicking::panic_fmt(fmt: Arguments, location: &Location)` is where
+/// every Rust panic starts; `fmt` is passed by reference because it does not fit
+/// in two registers. `rust_begin_unwind(info: &PanicInfo)` is the fallback for
+/// programs where only the `#[panic_handler]` symbol survived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Handler {
+    PanicFmt,
+    BeginUnwind,
+}
+
+const HANDLERS: &[(&str, Handler)] = &[
+    ("core::panicking::panic_fmt", Handler::PanicFmt),
+    ("rust_begin_unwind", Handler::BeginUnwind),
+];
+
+/// What the guest's panic handler was told when it was entered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PanicReport {
+    pub cycle: u64,
+    /// The formatted message, with `{..}` for arguments that could not be decoded.
+    pub message: Option<String>,
+    pub location: Option<SourceLocation>,
+    /// Handler entry followed by the call site of each enclosing frame.
+    pub frames: Vec<u32>,
+}
+
+/// Formats like the standard library's panic hook: `panicked at file:line:column: message`.
+impl fmt::Display for PanicReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "panicked")?;
+        if let Some(location) = &self.location {
+            write!(f, " at {}:{}:{}", location.file, location.line, location.column.unwrap_or(0))?;
+        }
+        write!(f, ": {}", self.message.as_deref().unwrap_or("<message not recovered>"))
+    }
+}
+
+/// One executed instruction in the history ring.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ExecutedInstruction {
+    pub cycle: u64,
+    pub pc: u32,
+    pub word: u32,
+}
+
+/// Watches execution for entry into the panic handler and keeps the most
+/// recently executed instructions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PanicMonitor {
+    handlers: Vec<(u32, Handler)>,
+    history: VecDeque<ExecutedInstruction>,
+    report: Option<PanicReport>,
+}
+
+impl PanicMonitor {
+    /// Looks up the panic handler entry points in the program's symbols.
+    pub fn new(debug_info: Option<&DebugInfo>) -> Self {
+        let handlers = HANDLERS
+            .iter()
+            .filter_map(|&(name, handler)| Some((debug_info?.symbol_address(name)?, handler)))
+            .collect();
+        Self {
+            handlers,
+            history: VecDeque::with_capacity(HISTORY_LENGTH),
+            report: None,
+        }
+    }
+
+    /// Records `event` and returns true if it entered the panic handler for
+    /// the first time. `call_stack` must already include the step.
+    pub fn observe(
+        &mut self,
+        event: &StepEvent,
+        emulator: &Emulator,
+        call_stack: &CallStack,
+        debug_info: Option<&DebugInfo>,
+    ) -> bool {
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(ExecutedInstruction {
+            cycle: event.cycle,
+            pc: event.pc,
+            word: event.word,
+        });
+
+        if self.report.is_some() {
+            return false;
+        }
+        let Some(&(_, handler)) = self.handlers.iter().find(|&&(address, _)| address == event.next_pc) else {
+            return false;
+        };
+        let memory = &emulator.memory;
+        let [a0, a1] = [emulator.registers[10], emulator.registers[11]];
+        let (message, location) = match handler {
+            Handler::PanicFmt => (read_arguments(memory, a0, debug_info), read_location(memory, a1)),
+            Handler::BeginUnwind => {
+                // PanicInfo's field order is up to rustc, so take the first words
+                // that decode as a message and a location.
+                let fields: Vec<u32> = (0..4).map(|i| memory.read_u32(a0.wrapping_add(i * 4))).collect();
+                (
+                    fields.iter().find_map(|&field| read_arguments(memory, field, debug_info)),
+                    fields.iter().find_map(|&field| read_location(memory, field)),
+                )
+            }
+        };
+        self.report = Some(PanicReport {
+            cycle: event.cycle,
+            message,
+            location,
+            frames: call_stack.frame_addresses(event.next_pc),
+        });
+        true
+    }
+
+    pub fn report(&self) -> Option<&PanicReport> {
+        self.report.as_ref()
+    }
+
+    /// Up to `count` of the most recently executed instructions, oldest first.
+    pub fn recent_instructions(&self, count: usize) -> impl Iterator<Item = &ExecutedInstruction> {
+        self.history.iter().skip(self.history.len().saturating_sub(count))
+    }
+}
+
+fn read_str(memory: &Memory, address: u32, length: u32) -> Option<String> {
+    if address == 0 || length > MAX_STRING_LENGTH {
+        return None;
+    }
+    String::from_utf8(memory.read_bytes(address, length as usize)).ok()
+}
+
+/// `core::panic::Location { file: &str, line: u32, col: u32 }`.
+fn read_location(memory: &Memory, address: u32) -> Option<SourceLocation> {
+    if address == 0 {
+        return None;
+    }
+    let word = |offset: u32| memory.read_u32(address.wrapping_add(offset));
+    let file = read_str(memory, word(0), word(4)).filter(|file| !file.is_empty())?;
+    let line = word(8);
+    if line == 0 {
+        return None;
+    }
+    Some(SourceLocation {
+        file,
+        line,
+        column: Some(word(12)),
+    })
+}
+
+/// Renders a `fmt::Arguments`: the literal pieces interleaved with whatever
+/// arguments can be decoded from their formatter function.
+///
+/// The struct holds three slices: `pieces: &[&str]`, `fmt: Option<&[Placeholder]>`
+/// and `args: &[Argument]`. rustc may lay them out in any order, so the pieces
+/// are the slice whose elements all read as strings and the arguments are the
+/// slice whose elements pair a pointer with a known function.
+fn read_arguments(memory: &Memory, address: u32, debug_info: Option<&DebugInfo>) -> Option<String> {
+    if address == 0 {
+        return None;
+    }
+    let slices: Vec<(u32, u32)> = (0..3)
+        .map(|i| {
+            let field = address.wrapping_add(i * 8);
+            (memory.read_u32(field), memory.read_u32(field.wrapping_add(4)))
+        })
+        .collect();
+    let elements = |(pointer, length): (u32, u32)| -> Option<Vec<(u32, u32)>> {
+        if pointer == 0 || length > MAX_PIECES {
+            return None;
+        }
+        Some(
+            (0..length)
+                .map(|i| {
+                    let element = pointer.wrapping_add(i * 8);
+                    (memory.read_u32(element), memory.read_u32(element.wrapping_add(4)))
+                })
+                .collect(),
+        )
+    };
+
+    let (pieces_index, pieces) = slices.iter().enumerate().find_map(|(index, &slice)| {
+        let pieces = elements(slice)
+            .filter(|pieces| !pieces.is_empty())?
+            .into_iter()
+            .map(|(pointer, length)| read_str(memory, pointer, length))
+            .collect::<Option<Vec<String>>>()?;
+        Some((index, pieces))
+    })?;
+    let arguments = slices
+        .iter()
+        .enumerate()
+        .filter(|&(index, _)| index != pieces_index)
+        .filter_map(|(_, &slice)| elements(slice))
+        .find(|arguments| {
+            !arguments.is_empty()
+                && arguments
+                    .iter()
+                    .all(|&(_, formatter)| debug_info.and_then(|info| info.symbol_at(formatter)).is_some())
+        })
+        .unwrap_or_default();
+
+    let mut message = String::new();
+    for (index, piece) in pieces.iter().enumerate() {
+        message.push_str(piece);
+        if let Some(&(value, formatter)) = arguments.get(index) {
+            message.push_str(&format_argument(memory, value, formatter, debug_info));
+        }
+    }
+    Some(message)
+}
+
+/// Decodes a `fmt::Argument` for the formatters panic messages use most:
+/// integers and string slices.
+fn format_argument(memory: &Memory, value: u32, formatter: u32, debug_info: Option<&DebugInfo>) -> String {
+    let Some(name) = debug_info.and_then(|info| info.symbol_at(formatter)).map(|symbol| symbol.name.as_str()) else {
+        return "{..}".to_string();
+    };
+    let word = memory.read_u32(value);
+    let long = || word as u64 | (memory.read_u32(value.wrapping_add(4)) as u64) << 32;
+    let decoded = ["Display", "Debug"].iter().find_map(|format| {
+        let ty = name.split(&format!("{} for ", format)).nth(1)?.split('>').next()?;
+        Some(match ty {
+            "u8" => (word as u8).to_string(),
+            "i8" => (word as i8).to_string(),
+            "u16" => (word as u16).to_string(),
+            "i16" => (word as i16).to_string(),
+            "u32" | "usize" => word.to_string(),
+            "i32" | "isize" => (word as i32).to_string(),
+            "u64" => long().to_string(),
+            "i64" => (long() as i64).to_string(),
+            "str" => read_str(memory, word, memory.read_u32(value.wrapping_add(4)))?,
+            _ => return None,
+        })
+    });
+    if let Some(text) = decoded {
+        return text;
+    }
+    // `<&T as Display>::fmt` is how `{}` of a `&str` usually reaches the formatter.
+    if name.starts_with("<&T as core::fmt::Display>") {
+        if let Some(text) = read_str(memory, word, memory.read_u32(value.wrapping_add(4))) {
+            return text;
+        }
+    }
+    "{..}".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{Program, Segment};
+
+    const HANDLER: u32 = 0x40;
+
+    /// An emulator whose first instruction, `jal ra, 0x40`, calls the handler
+    /// with a0 and a1 set to `arguments`.
+    fn calling_handler(arguments: [u32; 2]) -> Emulator {
+        let data = 0x0400_00ef_u32.to_le_bytes().to_vec();
+        let program = Program {
+            entry: 0,
+            segments: vec![Segment { vaddr: 0, mem_size: 0x80, data, flags: 5 }],
+            elf: Vec::new(),
+        };
+        let mut emulator = Emulator::new(&program, Vec::new());
+        emulator.registers[10] = arguments[0];
+        emulator.registers[11] = arguments[1];
+
+        // Location { file: "src/main.rs", line: 42, col: 9 }
+        emulator.memory.write_bytes(0x4100, b"src/main.rs");
+        for (i, word) in [0x4100, 11, 42, 9].into_iter().enumerate() {
+            emulator.memory.write_u32(0x4000 + i as u32 * 4, word);
+        }
+        // Arguments { fmt: None, pieces: &["attempt to ", "divide by zero"], args: &[] }
+        emulator.memory.write_bytes(0x3200, b"attempt to divide by zero");
+        for (i, word) in [0, 0, 0x3100, 2, 0x3300, 0].into_iter().enumerate() {
+            emulator.memory.write_u32(0x3000 + i as u32 * 4, word);
+        }
+        for (i, word) in [0x3200, 11, 0x320b, 14].into_iter().enumerate() {
+            emulator.memory.write_u32(0x3100 + i as u32 * 4, word);
+        }
+        emulator
+    }
+
+    fn enter_handler(handler: Handler, emulator: &mut Emulator) -> PanicMonitor {
+        let mut monitor = PanicMonitor {
+            handlers: vec![(HANDLER, handler)],
+            ..Default::default()
+        };
+        let mut call_stack = CallStack::default();
+        let event = emulator.step().unwrap();
+        call_stack.observe(event.pc, &event.instruction, event.next_pc, &emulator.registers);
+        assert!(monitor.observe(&event, emulator, &call_stack, None));
+        monitor
+    }
+
+    #[test]
+    fn panic_fmt_arguments_give_the_message_and_location() {
+        let mut emulator = calling_handler([0x3000, 0x4000]);
+        let monitor = enter_handler(Handler::PanicFmt, &mut emulator);
+        let report = monitor.report().unwrap();
+        assert_eq!(report.message.as_deref(), Some("attempt to divide by zero"));
+        let location = report.location.as_ref().unwrap();
+        assert_eq!((location.file.as_str(), location.line, location.column), ("src/main.rs", 42, Some(9)));
+        assert_eq!(report.frames, [HANDLER, 0]);
+        assert_eq!(report.to_string(), "panicked at src/main.rs:42:9: attempt to divide by zero");
+    }
+
+    #[test]
+    fn panic_info_fields_are_found_in_any_order() {
+        let mut emulator = calling_handler([0x5000, 0]);
+        // PanicInfo { location, message, .. }
+        emulator.memory.write_u32(0x5000, 0x4000);
+        emulator.memory.write_u32(0x5004, 0x3000);
+        let monitor = enter_handler(Handler::BeginUnwind, &mut emulator);
+        assert_eq!(
+            monitor.report().unwrap().to_string(),
+            "panicked at src/main.rs:42:9: attempt to divide by zero"
+        );
+    }
+}
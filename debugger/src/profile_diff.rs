@@ -0,0 +1,276 @@
+// Profile comparison
+// Compares two saved ProfilerResult files function by function and gives a verdict on the change
+
+use crate::{FunctionProfile, ProfilerResult};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::path::Path;
+
+/// How one function changed between the base and new profile.
+#[derive(Debug, Clone)]
+pub struct FunctionDelta {
+    pub function_name: String,
+    pub base: Option<FunctionProfile>,
+    pub new: Option<FunctionProfile>,
+}
+
+impl FunctionDelta {
+    pub fn base_inclusive(&self) -> u64 {
+        self.base.as_ref().map_or(0, |profile| profile.inclusive_cycles)
+    }
+
+    pub fn new_inclusive(&self) -> u64 {
+        self.new.as_ref().map_or(0, |profile| profile.inclusive_cycles)
+    }
+
+    pub fn inclusive_delta(&self) -> i64 {
+        self.new_inclusive() as i64 - self.base_inclusive() as i64
+    }
+
+    pub fn exclusive_delta(&self) -> i64 {
+        let exclusive = |profile: &Option<FunctionProfile>| profile.as_ref().map_or(0, |profile| profile.exclusive_cycles);
+        exclusive(&self.new) as i64 - exclusive(&self.base) as i64
+    }
+
+    fn status(&self) -> &'static str {
+        match (&self.base, &self.new) {
+            (None, Some(_)) => "new",
+            (Some(_), None) => "gone",
+            _ => "",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Improved,
+    Unchanged,
+    Regressed,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Improved => write!(f, "improved"),
+            Verdict::Unchanged => write!(f, "unchanged"),
+            Verdict::Regressed => write!(f, "regressed"),
+        }
+    }
+}
+
+pub struct ProfileDiff {
+    pub base_cycles: u64,
+    pub new_cycles: u64,
+    /// Every function in either profile, largest exclusive change first.
+    pub functions: Vec<FunctionDelta>,
+    /// Hot spots of the new profile that the base did not have.
+    pub new_hot_spots: Vec<String>,
+    /// Hot spots of the base profile that the new one no longer has.
+    pub disappeared_hot_spots: Vec<String>,
+}
+
+impl ProfileDiff {
+    pub fn new(base: &ProfilerResult, new: &ProfilerResult) -> Self {
+        let mut deltas: HashMap<&str, FunctionDelta> = HashMap::new();
+        for (profile, is_base) in base.functions.iter().map(|f| (f, true)).chain(new.functions.iter().map(|f| (f, false))) {
+            let delta = deltas.entry(&profile.function_name).or_insert_with(|| FunctionDelta {
+                function_name: profile.function_name.clone(),
+                base: None,
+                new: None,
+            });
+            if is_base {
+                delta.base = Some(profile.clone());
+            } else {
+                delta.new = Some(profile.clone());
+            }
+        }
+        let mut functions: Vec<FunctionDelta> = deltas.into_values().collect();
+        functions.sort_by(|a, b| {
+            b.exclusive_delta()
+                .unsigned_abs()
+                .cmp(&a.exclusive_delta().unsigned_abs())
+                .then_with(|| a.function_name.cmp(&b.function_name))
+        });
+
+        let hot_spots = |result: &ProfilerResult| -> BTreeSet<String> {
+            result.hot_spots.iter().map(|hot_spot| hot_spot.function_name.clone()).collect()
+        };
+        let (base_hot, new_hot) = (hot_spots(base), hot_spots(new));
+
+        Self {
+            base_cycles: base.total_cycles,
+            new_cycles: new.total_cycles,
+            functions,
+            new_hot_spots: new_hot.difference(&base_hot).cloned().collect(),
+            disappeared_hot_spots: base_hot.difference(&new_hot).cloned().collect(),
+        }
+    }
+
+    pub fn load(base: &Path, new: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let read = |path: &Path| -> Result<ProfilerResult, Box<dyn std::error::Error>> {
+            let json = std::fs::read_to_string(path).map_err(|e| format!("failed to read profile {}: {}", path.display(), e))?;
+            serde_json::from_str(&json).map_err(|e| format!("{} is not a profile written by `profile --output`: {}", path.display(), e).into())
+        };
+        Ok(Self::new(&read(base)?, &read(new)?))
+    }
+
+    /// Change in total cycles as a percentage of the base.
+    pub fn change_percent(&self) -> f64 {
+        percent_change(self.base_cycles, self.new_cycles)
+    }
+
+    /// Cycle counts are deterministic, so any change at all is real.
+    pub fn verdict(&self) -> Verdict {
+        match self.new_cycles.cmp(&self.base_cycles) {
+            std::cmp::Ordering::Less => Verdict::Improved,
+            std::cmp::Ordering::Equal => Verdict::Unchanged,
+            std::cmp::Ordering::Greater => Verdict::Regressed,
+        }
+    }
+
+    /// True when total cycles grew by more than `threshold` percent.
+    pub fn exceeds(&self, threshold: f64) -> bool {
+        self.verdict() == Verdict::Regressed && self.change_percent() > threshold
+    }
+
+    pub fn lines(&self, count: usize) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "Total cycles: {} -> {} ({:+} cycles, {:+.2}%)",
+                self.base_cycles,
+                self.new_cycles,
+                self.new_cycles as i64 - self.base_cycles as i64,
+                self.change_percent()
+            ),
+            "Functions by change in exclusive cycles:".to_string(),
+            format!(
+                "  {:>12} {:>12} {:>12} {:>9} {:>12}  function",
+                "base", "new", "incl delta", "%", "excl delta"
+            ),
+        ];
+        let changed = self.functions.iter().filter(|delta| delta.inclusive_delta() != 0 || delta.exclusive_delta() != 0);
+        for delta in changed.take(count) {
+            let percent = match (&delta.base, &delta.new) {
+                (Some(_), Some(_)) => format!("{:+.2}%", percent_change(delta.base_inclusive(), delta.new_inclusive())),
+                _ => delta.status().to_string(),
+            };
+            lines.push(format!(
+                "  {:>12} {:>12} {:>+12} {:>9} {:>+12}  {}",
+                delta.base_inclusive(),
+                delta.new_inclusive(),
+                delta.inclusive_delta(),
+                percent,
+                delta.exclusive_delta(),
+                delta.function_name
+            ));
+        }
+        if self.functions.iter().all(|delta| delta.inclusive_delta() == 0 && delta.exclusive_delta() == 0) {
+            lines.push("  (no function changed)".to_string());
+        }
+
+        if !self.new_hot_spots.is_empty() {
+            lines.push(format!("New hot spots: {}", self.new_hot_spots.join(", ")));
+        }
+        if !self.disappeared_hot_spots.is_empty() {
+            lines.push(format!("Disappeared hot spots: {}", self.disappeared_hot_spots.join(", ")));
+        }
+        lines.push(format!("Verdict: {} ({:+.2}%)", self.verdict(), self.change_percent()));
+        lines
+    }
+}
+
+fn percent_change(base: u64, new: u64) -> f64 {
+    if base == 0 {
+        return if new == 0 { 0.0 } else { 100.0 };
+    }
+    (new as f64 - base as f64) * 100.0 / base as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HotSpot, MemoryProfile, PerformanceMetrics};
+    use std::time::Duration;
+
+    /// A profile of `total_cycles` with (name, inclusive, exclusive) functions.
+    fn profile(total_cycles: u64, functions: &[(&str, u64, u64)], hot_spots: &[&str]) -> ProfilerResult {
+        ProfilerResult {
+            total_cycles,
+            execution_time: Duration::ZERO,
+            memory_usage: MemoryProfile { peak_memory: 0, average_memory: 0, memory_accesses: 0 },
+            performance_metrics: PerformanceMetrics {
+                cycles_per_second: 0.0,
+                memory_bandwidth: 0.0,
+                instruction_mix: HashMap::new(),
+                syscall_mix: HashMap::new(),
+                syscall_cycles: 0,
+                precompile_cycles: 0,
+            },
+            hot_spots: hot_spots
+                .iter()
+                .map(|name| HotSpot { function_name: name.to_string(), cycles_spent: 0, percentage: 0.0, line_numbers: Vec::new() })
+                .collect(),
+            functions: functions
+                .iter()
+                .map(|&(name, inclusive_cycles, exclusive_cycles)| FunctionProfile {
+                    function_name: name.to_string(),
+                    inclusive_cycles,
+                    exclusive_cycles,
+                    calls: 1,
+                })
+                .collect(),
+            stacks: Vec::new(),
+            syscalls: Vec::new(),
+        }
+    }
+
+    fn diff(base_cycles: u64, new_cycles: u64) -> ProfileDiff {
+        ProfileDiff::new(&profile(base_cycles, &[], &[]), &profile(new_cycles, &[], &[]))
+    }
+
+    #[test]
+    fn the_threshold_is_exclusive() {
+        let regression = diff(1000, 1100);
+        assert_eq!(regression.change_percent(), 10.0);
+        assert!(!regression.exceeds(10.0));
+        assert!(regression.exceeds(9.99));
+        // Only a regression can exceed a threshold, however low
+        assert!(!diff(1000, 1000).exceeds(0.0));
+        assert!(!diff(1000, 900).exceeds(-50.0));
+    }
+
+    #[test]
+    fn any_change_in_total_cycles_decides_the_verdict() {
+        assert_eq!(diff(1000, 999).verdict(), Verdict::Improved);
+        assert_eq!(diff(1000, 1000).verdict(), Verdict::Unchanged);
+        assert_eq!(diff(1000, 1001).verdict(), Verdict::Regressed);
+        assert_eq!(diff(0, 5).change_percent(), 100.0);
+
+        let lines = diff(1000, 1100).lines(10);
+        assert_eq!(lines[0], "Total cycles: 1000 -> 1100 (+100 cycles, +10.00%)");
+        assert!(lines.contains(&"  (no function changed)".to_string()));
+        assert_eq!(lines.last().unwrap(), "Verdict: regressed (+10.00%)");
+        assert_eq!(diff(1000, 750).lines(10).last().unwrap(), "Verdict: improved (-25.00%)");
+    }
+
+    #[test]
+    fn functions_and_hot_spots_that_come_and_go_are_listed() {
+        let base = profile(1000, &[("main", 1000, 100), ("parse", 600, 600), ("hash", 300, 300)], &["parse", "hash"]);
+        let new = profile(900, &[("main", 900, 100), ("hash", 500, 500), ("verify", 300, 300)], &["hash", "verify"]);
+        let diff = ProfileDiff::new(&base, &new);
+        assert_eq!(diff.new_hot_spots, ["verify"]);
+        assert_eq!(diff.disappeared_hot_spots, ["parse"]);
+
+        let order: Vec<(&str, i64)> = diff.functions.iter().map(|delta| (delta.function_name.as_str(), delta.exclusive_delta())).collect();
+        assert_eq!(order, [("parse", -600), ("verify", 300), ("hash", 200), ("main", 0)]);
+
+        let lines = diff.lines(10);
+        let row = |name: &str| lines.iter().find(|line| line.ends_with(&format!("  {}", name))).cloned().unwrap();
+        assert!(row("parse").contains(" gone "), "{}", row("parse"));
+        assert!(row("verify").contains(" new "), "{}", row("verify"));
+        assert!(row("hash").contains("+66.67%"), "{}", row("hash"));
+        assert!(lines.contains(&"New hot spots: verify".to_string()));
+        assert!(lines.contains(&"Disappeared hot spots: parse".to_string()));
+        assert_eq!(lines.last().unwrap(), "Verdict: improved (-10.00%)");
+    }
+}
@@ -0,0 +1,438 @@
+// RISC-V RV32IM instruction decoding and disassembly
+// Shared by the emulator, the trace format and every view that prints instructions
+
+use std::fmt;
+
+/// ABI names of the 32 integer registers, indexed by register number.
+pub const REGISTER_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+/// Looks up a register by ABI name (`a0`), numeric name (`x10`) or the `fp` alias.
+pub fn register_index(name: &str) -> Option<usize> {
+    let name = name.trim().to_lowercase();
+    if name == "fp" {
+        return Some(8);
+    }
+    if let Some(number) = name.strip_prefix('x') {
+        if let Ok(index) = number.parse::<usize>() {
+            return (index < 32).then_some(index);
+        }
+    }
+    REGISTER_NAMES.iter().position(|&n| n == name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchOp {
+    Beq,
+    Bne,
+    Blt,
+    Bge,
+    Bltu,
+    Bgeu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadOp {
+    Lb,
+    Lh,
+    Lw,
+    Lbu,
+    Lhu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreOp {
+    Sb,
+    Sh,
+    Sw,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Sub,
+    Sll,
+    Slt,
+    Sltu,
+    Xor,
+    Srl,
+    Sra,
+    Or,
+    And,
+    Mul,
+    Mulh,
+    Mulhsu,
+    Mulhu,
+    Div,
+    Divu,
+    Rem,
+    Remu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Lui { rd: u8, imm: u32 },
+    Auipc { rd: u8, imm: u32 },
+    Jal { rd: u8, offset: i32 },
+    Jalr { rd: u8, rs1: u8, offset: i32 },
+    Branch { op: BranchOp, rs1: u8, rs2: u8, offset: i32 },
+    Load { op: LoadOp, rd: u8, rs1: u8, offset: i32 },
+    Store { op: StoreOp, rs1: u8, rs2: u8, offset: i32 },
+    OpImm { op: AluOp, rd: u8, rs1: u8, imm: i32 },
+    Op { op: AluOp, rd: u8, rs1: u8, rs2: u8 },
+    Fence,
+    Ecall,
+    Ebreak,
+    Unknown(u32),
+}
+
+impl Instruction {
+    pub fn decode(word: u32) -> Self {
+        let opcode = word & 0x7f;
+        let rd = ((word >> 7) & 0x1f) as u8;
+        let funct3 = (word >> 12) & 0x7;
+        let rs1 = ((word >> 15) & 0x1f) as u8;
+        let rs2 = ((word >> 20) & 0x1f) as u8;
+        let funct7 = word >> 25;
+
+        let imm_i = (word as i32) >> 20;
+        let imm_s = (((word & 0xfe00_0000) as i32) >> 20) | ((word >> 7) & 0x1f) as i32;
+        let imm_b = (((word & 0x8000_0000) as i32) >> 19)
+            | ((word & 0x80) << 4) as i32
+            | ((word >> 20) & 0x7e0) as i32
+            | ((word >> 7) & 0x1e) as i32;
+        let imm_j = (((word & 0x8000_0000) as i32) >> 11)
+            | (word & 0xf_f000) as i32
+            | ((word >> 9) & 0x800) as i32
+            | ((word >> 20) & 0x7fe) as i32;
+
+        match opcode {
+            0x37 => Instruction::Lui { rd, imm: word & 0xffff_f000 },
+            0x17 => Instruction::Auipc { rd, imm: word & 0xffff_f000 },
+            0x6f => Instruction::Jal { rd, offset: imm_j },
+            0x67 if funct3 == 0 => Instruction::Jalr { rd, rs1, offset: imm_i },
+            0x63 => {
+                let op = match funct3 {
+                    0 => BranchOp::Beq,
+                    1 => BranchOp::Bne,
+                    4 => BranchOp::Blt,
+                    5 => BranchOp::Bge,
+                    6 => BranchOp::Bltu,
+                    7 => BranchOp::Bgeu,
+                    _ => return Instruction::Unknown(word),
+                };
+                Instruction::Branch { op, rs1, rs2, offset: imm_b }
+            }
+            0x03 => {
+                let op = match funct3 {
+                    0 => LoadOp::Lb,
+                    1 => LoadOp::Lh,
+                    2 => LoadOp::Lw,
+                    4 => LoadOp::Lbu,
+                    5 => LoadOp::Lhu,
+                    _ => return Instruction::Unknown(word),
+                };
+                Instruction::Load { op, rd, rs1, offset: imm_i }
+            }
+            0x23 => {
+                let op = match funct3 {
+                    0 => StoreOp::Sb,
+                    1 => StoreOp::Sh,
+                    2 => StoreOp::Sw,
+                    _ => return Instruction::Unknown(word),
+                };
+                Instruction::Store { op, rs1, rs2, offset: imm_s }
+            }
+            0x13 => {
+                let (op, imm) = match funct3 {
+                    0 => (AluOp::Add, imm_i),
+                    2 => (AluOp::Slt, imm_i),
+                    3 => (AluOp::Sltu, imm_i),
+                    4 => (AluOp::Xor, imm_i),
+                    6 => (AluOp::Or, imm_i),
+                    7 => (AluOp::And, imm_i),
+                    1 if funct7 == 0 => (AluOp::Sll, rs2 as i32),
+                    5 if funct7 == 0 => (AluOp::Srl, rs2 as i32),
+                    5 if funct7 == 0x20 => (AluOp::Sra, rs2 as i32),
+                    _ => return Instruction::Unknown(word),
+                };
+                Instruction::OpImm { op, rd, rs1, imm }
+            }
+            0x33 => {
+                let op = match (funct7, funct3) {
+                    (0x00, 0) => AluOp::Add,
+                    (0x20, 0) => AluOp::Sub,
+                    (0x00, 1) => AluOp::Sll,
+                    (0x00, 2) => AluOp::Slt,
+                    (0x00, 3) => AluOp::Sltu,
+                    (0x00, 4) => AluOp::Xor,
+                    (0x00, 5) => AluOp::Srl,
+                    (0x20, 5) => AluOp::Sra,
+                    (0x00, 6) => AluOp::Or,
+                    (0x00, 7) => AluOp::And,
+                    (0x01, 0) => AluOp::Mul,
+                    (0x01, 1) => AluOp::Mulh,
+                    (0x01, 2) => AluOp::Mulhsu,
+                    (0x01, 3) => AluOp::Mulhu,
+                    (0x01, 4) => AluOp::Div,
+                    (0x01, 5) => AluOp::Divu,
+                    (0x01, 6) => AluOp::Rem,
+                    (0x01, 7) => AluOp::Remu,
+                    _ => return Instruction::Unknown(word),
+                };
+                Instruction::Op { op, rd, rs1, rs2 }
+            }
+            0x0f => Instruction::Fence,
+            0x73 if word == 0x0000_0073 => Instruction::Ecall,
+            0x73 if word == 0x0010_0073 => Instruction::Ebreak,
+            _ => Instruction::Unknown(word),
+        }
+    }
+
+    /// Short mnemonic used for instruction-mix statistics.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Lui { .. } => "lui",
+            Instruction::Auipc { .. } => "auipc",
+            Instruction::Jal { .. } => "jal",
+            Instruction::Jalr { .. } => "jalr",
+            Instruction::Branch { op, .. } => match op {
+                BranchOp::Beq => "beq",
+                BranchOp::Bne => "bne",
+                BranchOp::Blt => "blt",
+                BranchOp::Bge => "bge",
+                BranchOp::Bltu => "bltu",
+                BranchOp::Bgeu => "bgeu",
+            },
+            Instruction::Load { op, .. } => match op {
+                LoadOp::Lb => "lb",
+                LoadOp::Lh => "lh",
+                LoadOp::Lw => "lw",
+                LoadOp::Lbu => "lbu",
+                LoadOp::Lhu => "lhu",
+            },
+            Instruction::Store { op, .. } => match op {
+                StoreOp::Sb => "sb",
+                StoreOp::Sh => "sh",
+                StoreOp::Sw => "sw",
+            },
+            Instruction::OpImm { op, .. } => match op {
+                AluOp::Add => "addi",
+                AluOp::Slt => "slti",
+                AluOp::Sltu => "sltiu",
+                AluOp::Xor => "xori",
+                AluOp::Or => "ori",
+                AluOp::And => "andi",
+                AluOp::Sll => "slli",
+                AluOp::Srl => "srli",
+                AluOp::Sra => "srai",
+                _ => "unknown",
+            },
+            Instruction::Op { op, .. } => alu_name(*op),
+            Instruction::Fence => "fence",
+            Instruction::Ecall => "ecall",
+            Instruction::Ebreak => "ebreak",
+            Instruction::Unknown(_) => "unknown",
+        }
+    }
+
+    /// Returns true for `jal`/`jalr` that write the return address, i.e. calls.
+    pub fn is_call(&self) -> bool {
+        matches!(self, Instruction::Jal { rd: 1, .. } | Instruction::Jalr { rd: 1, .. })
+    }
+
+    /// Returns true for `jalr zero, 0(ra)`, the canonical `ret`.
+    pub fn is_return(&self) -> bool {
+        matches!(self, Instruction::Jalr { rd: 0, rs1: 1, offset: 0 })
+    }
+
+    /// Disassembles the instruction, resolving pc-relative targets against `pc`.
+    pub fn disassemble(&self, pc: u32) -> String {
+        let r = |index: u8| REGISTER_NAMES[index as usize];
+        let target = |offset: i32| pc.wrapping_add(offset as u32);
+        match *self {
+            Instruction::Lui { rd, imm } => format!("lui {}, 0x{:x}", r(rd), imm >> 12),
+            Instruction::Auipc { rd, imm } => format!("auipc {}, 0x{:x}", r(rd), imm >> 12),
+            Instruction::Jal { rd: 0, offset } => format!("j 0x{:x}", target(offset)),
+            Instruction::Jal { rd, offset } => format!("jal {}, 0x{:x}", r(rd), target(offset)),
+            Instruction::Jalr { rd: 0, rs1: 1, offset: 0 } => "ret".to_string(),
+            Instruction::Jalr { rd, rs1, offset } => {
+                format!("jalr {}, {}({})", r(rd), offset, r(rs1))
+            }
+            Instruction::Branch { rs1, rs2, offset, .. } => format!(
+                "{} {}, {}, 0x{:x}",
+                self.mnemonic(),
+                r(rs1),
+                r(rs2),
+                target(offset)
+            ),
+            Instruction::Load { rd, rs1, offset, .. } => {
+                format!("{} {}, {}({})", self.mnemonic(), r(rd), offset, r(rs1))
+            }
+            Instruction::Store { rs1, rs2, offset, .. } => {
+                format!("{} {}, {}({})", self.mnemonic(), r(rs2), offset, r(rs1))
+            }
+            Instruction::OpImm { op: AluOp::Add, rd: 0, rs1: 0, imm: 0 } => "nop".to_string(),
+            Instruction::OpImm { op: AluOp::Add, rd, rs1: 0, imm } => format!("li {}, {}", r(rd), imm),
+            Instruction::OpImm { op: AluOp::Add, rd, rs1, imm: 0 } => format!("mv {}, {}", r(rd), r(rs1)),
+            Instruction::OpImm { rd, rs1, imm, .. } => {
+                format!("{} {}, {}, {}", self.mnemonic(), r(rd), r(rs1), imm)
+            }
+            Instruction::Op { rd, rs1, rs2, .. } => {
+                format!("{} {}, {}, {}", self.mnemonic(), r(rd), r(rs1), r(rs2))
+            }
+            Instruction::Fence => "fence".to_string(),
+            Instruction::Ecall => "ecall".to_string(),
+            Instruction::Ebreak => "ebreak".to_string(),
+            Instruction::Unknown(word) => format!(".word 0x{:08x}", word),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.disassemble(0))
+    }
+}
+
+fn alu_name(op: AluOp) -> &'static str {
+    match op {
+        AluOp::Add => "add",
+        AluOp::Sub => "sub",
+        AluOp::Sll => "sll",
+        AluOp::Slt => "slt",
+        AluOp::Sltu => "sltu",
+        AluOp::Xor => "xor",
+        AluOp::Srl => "srl",
+        AluOp::Sra => "sra",
+        AluOp::Or => "or",
+        AluOp::And => "and",
+        AluOp::Mul => "mul",
+        AluOp::Mulh => "mulh",
+        AluOp::Mulhsu => "mulhsu",
+        AluOp::Mulhu => "mulhu",
+        AluOp::Div => "div",
+        AluOp::Divu => "divu",
+        AluOp::Rem => "rem",
+        AluOp::Remu => "remu",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_each_format_with_sign_extended_immediates() {
+        let cases = [
+            // U
+            (0xffff_f537, Instruction::Lui { rd: 10, imm: 0xffff_f000 }),
+            (0x0001_2197, Instruction::Auipc { rd: 3, imm: 0x12000 }),
+            // J
+            (0x801f_f0ef, Instruction::Jal { rd: 1, offset: -2048 }),
+            (0x7fff_f06f, Instruction::Jal { rd: 0, offset: 1_048_574 }),
+            // I
+            (0xfff7_8367, Instruction::Jalr { rd: 6, rs1: 15, offset: -1 }),
+            (0x8001_0503, Instruction::Load { op: LoadOp::Lb, rd: 10, rs1: 2, offset: -2048 }),
+            (0xffe5_d303, Instruction::Load { op: LoadOp::Lhu, rd: 6, rs1: 11, offset: -2 }),
+            (0xff01_0113, Instruction::OpImm { op: AluOp::Add, rd: 2, rs1: 2, imm: -16 }),
+            (0x4075_5513, Instruction::OpImm { op: AluOp::Sra, rd: 10, rs1: 10, imm: 7 }),
+            // B
+            (0xfeb5_0ce3, Instruction::Branch { op: BranchOp::Beq, rs1: 10, rs2: 11, offset: -8 }),
+            (0x7e04_1fe3, Instruction::Branch { op: BranchOp::Bne, rs1: 8, rs2: 0, offset: 4094 }),
+            (0x80b5_5063, Instruction::Branch { op: BranchOp::Bge, rs1: 10, rs2: 11, offset: -4096 }),
+            // S
+            (0xfea1_0fa3, Instruction::Store { op: StoreOp::Sb, rs1: 2, rs2: 10, offset: -1 }),
+            (0x7eb4_9fa3, Instruction::Store { op: StoreOp::Sh, rs1: 9, rs2: 11, offset: 2047 }),
+            // R, including the M extension
+            (0x4149_8933, Instruction::Op { op: AluOp::Sub, rd: 18, rs1: 19, rs2: 20 }),
+            (0x02c5_a533, Instruction::Op { op: AluOp::Mulhsu, rd: 10, rs1: 11, rs2: 12 }),
+            (0x03ad_ffb3, Instruction::Op { op: AluOp::Remu, rd: 31, rs1: 27, rs2: 26 }),
+            // System
+            (0x0ff0_000f, Instruction::Fence),
+            (0x0000_0073, Instruction::Ecall),
+            (0x0010_0073, Instruction::Ebreak),
+        ];
+        for (word, expected) in cases {
+            assert_eq!(Instruction::decode(word), expected, "0x{:08x}", word);
+        }
+    }
+
+    #[test]
+    fn disassembles_rv32im() {
+        // Encodings from llvm-mc; branch and jump targets are relative to pc 0x1000
+        let cases = [
+            (0xffff_f537, "lui a0, 0xfffff"),
+            (0x0001_2197, "auipc gp, 0x12"),
+            (0x801f_f0ef, "jal ra, 0x800"),
+            (0x7fff_f06f, "j 0x100ffe"),
+            (0xfff7_8367, "jalr t1, -1(a5)"),
+            (0x0000_8067, "ret"),
+            (0xfeb5_0ce3, "beq a0, a1, 0xff8"),
+            (0x7e04_1fe3, "bne s0, zero, 0x1ffe"),
+            (0x0062_c863, "blt t0, t1, 0x1010"),
+            (0x80b5_5063, "bge a0, a1, 0x0"),
+            (0x00d6_6463, "bltu a2, a3, 0x1008"),
+            (0x00f7_7663, "bgeu a4, a5, 0x100c"),
+            (0x0024_1583, "lh a1, 2(s0)"),
+            (0x00c1_2083, "lw ra, 12(sp)"),
+            (0x0005_4283, "lbu t0, 0(a0)"),
+            (0xfe11_2e23, "sw ra, -4(sp)"),
+            (0x0000_0013, "nop"),
+            (0x0050_0293, "li t0, 5"),
+            (0x0005_8513, "mv a0, a1"),
+            (0xfff5_2293, "slti t0, a0, -1"),
+            (0x0015_3293, "sltiu t0, a0, 1"),
+            (0xfff5_4513, "xori a0, a0, -1"),
+            (0x0ff5_e593, "ori a1, a1, 255"),
+            (0x00f6_7613, "andi a2, a2, 15"),
+            (0x01f5_1513, "slli a0, a0, 31"),
+            (0x0015_5513, "srli a0, a0, 1"),
+            (0x01ee_9e33, "sll t3, t4, t5"),
+            (0x00b0_3533, "sltu a0, zero, a1"),
+            (0x40b5_5533, "sra a0, a0, a1"),
+            (0x02c5_8533, "mul a0, a1, a2"),
+            (0x02c5_9533, "mulh a0, a1, a2"),
+            (0x02c5_b533, "mulhu a0, a1, a2"),
+            (0x02c5_c533, "div a0, a1, a2"),
+            (0x02c5_d533, "divu a0, a1, a2"),
+            (0x02c5_e533, "rem a0, a1, a2"),
+            (0x0000_0073, "ecall"),
+            (0x0010_0073, "ebreak"),
+        ];
+        for (word, expected) in cases {
+            assert_eq!(Instruction::decode(word).disassemble(0x1000), expected, "0x{:08x}", word);
+        }
+    }
+
+    #[test]
+    fn reserved_encodings_are_unknown() {
+        // Branch funct3 2, load funct3 3, store funct3 3, slli with funct7 set, R-type funct7 0x02, csrrw
+        for word in [0x0000_2063, 0x0000_3003, 0x0000_3023, 0x0200_1013, 0x0400_0033, 0x3400_1073] {
+            let instruction = Instruction::decode(word);
+            assert_eq!(instruction, Instruction::Unknown(word));
+            assert_eq!(instruction.mnemonic(), "unknown");
+            assert_eq!(instruction.to_string(), format!(".word 0x{:08x}", word));
+        }
+    }
+
+    #[test]
+    fn calls_and_returns() {
+        assert!(Instruction::decode(0x801f_f0ef).is_call());
+        assert!(Instruction::decode(0x0000_80e7).is_call()); // jalr ra, 0(ra)
+        assert!(!Instruction::decode(0x7fff_f06f).is_call());
+        assert!(Instruction::decode(0x0000_8067).is_return());
+        assert!(!Instruction::decode(0xfff7_8367).is_return());
+    }
+
+    #[test]
+    fn register_names() {
+        assert_eq!(register_index("a0"), Some(10));
+        assert_eq!(register_index(" X31 "), Some(31));
+        assert_eq!(register_index("fp"), Some(8));
+        assert_eq!(register_index("x32"), None);
+        assert_eq!(register_index("pc"), None);
+    }
+}
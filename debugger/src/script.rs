@@ -0,0 +1,199 @@
+// Batch debugging scripts
+// Runs debugger commands from a file and checks assertions, producing a JSON report for CI
+
+use crate::commands;
+use crate::expression::Expression;
+use crate::PicoDebugger;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Ok,
+    Error,
+    Passed,
+    Failed,
+}
+
+/// One executed script line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptStep {
+    pub line: usize,
+    pub command: String,
+    pub status: StepStatus,
+    pub output: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinalState {
+    pub pc: u32,
+    pub cycle: u64,
+    pub exit_code: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptReport {
+    /// True when every command ran and every assertion held.
+    pub success: bool,
+    pub assertions_passed: usize,
+    pub assertions_failed: usize,
+    pub steps: Vec<ScriptStep>,
+    pub final_state: Option<FinalState>,
+}
+
+/// Cycles a script may run for when `run-script` is not given `--max-cycles`.
+pub const DEFAULT_MAX_CYCLES: u64 = 100_000_000;
+
+/// Runs `script` one line at a time. Blank lines and `#` comments are skipped,
+/// `assert` lines are checked, and everything else is a debugger command. A
+/// failed assertion is recorded and the script goes on; a command error stops
+/// it, since later lines would run against an unexpected state. A command that
+/// takes the guest past `max_cycles` counts as a failed assertion and also
+/// stops the script.
+pub fn run_script(debugger: &mut PicoDebugger, script: &str, max_cycles: u64) -> ScriptReport {
+    debugger.set_cycle_limit(Some(max_cycles));
+    let mut steps = Vec::new();
+    for (index, line) in script.lines().enumerate() {
+        let command = line.trim();
+        if command.is_empty() || command.starts_with('#') {
+            continue;
+        }
+        let step = match command.strip_prefix("assert ") {
+            Some(assertion) => check_assertion(debugger, assertion),
+            None => match commands::execute(debugger, command) {
+                Ok(outcome) if cycle_limit_reached(debugger, max_cycles) => (
+                    StepStatus::Failed,
+                    outcome.lines,
+                    Some(format!("Cycle limit of {} exceeded", max_cycles)),
+                ),
+                Ok(outcome) => {
+                    let quit = outcome.quit;
+                    let step = (StepStatus::Ok, outcome.lines, None);
+                    if quit {
+                        steps.push(script_step(index, command, step));
+                        break;
+                    }
+                    step
+                }
+                Err(e) => (StepStatus::Error, Vec::new(), Some(e.to_string())),
+            },
+        };
+        let stop = step.0 == StepStatus::Error || cycle_limit_reached(debugger, max_cycles);
+        steps.push(script_step(index, command, step));
+        if stop {
+            break;
+        }
+    }
+
+    let count = |status| steps.iter().filter(|step| step.status == status).count();
+    let assertions_failed = count(StepStatus::Failed);
+    ScriptReport {
+        success: assertions_failed == 0 && count(StepStatus::Error) == 0,
+        assertions_passed: count(StepStatus::Passed),
+        assertions_failed,
+        final_state: debugger.emulator().ok().map(|emulator| FinalState {
+            pc: emulator.pc,
+            cycle: emulator.cycle,
+            exit_code: emulator.exit_code(),
+        }),
+        steps,
+    }
+}
+
+/// True once a still-running guest has used up the script's cycles.
+fn cycle_limit_reached(debugger: &PicoDebugger, max_cycles: u64) -> bool {
+    debugger
+        .emulator()
+        .is_ok_and(|emulator| emulator.exit_code().is_none() && emulator.cycle >= max_cycles)
+}
+
+fn script_step(index: usize, command: &str, (status, output, error): (StepStatus, Vec<String>, Option<String>)) -> ScriptStep {
+    ScriptStep {
+        line: index + 1,
+        command: command.to_string(),
+        status,
+        output,
+        error,
+    }
+}
+
+/// Checks `reg a0 == 42`, `mem 0x300000 != 0` or any expression, which holds
+/// when it evaluates to non-zero.
+fn check_assertion(debugger: &PicoDebugger, assertion: &str) -> (StepStatus, Vec<String>, Option<String>) {
+    let mut words = assertion.split_whitespace();
+    let expression = match words.next() {
+        Some("reg") => words.collect::<Vec<_>>().join(" "),
+        Some("mem") => {
+            let address = words.next().unwrap_or_default();
+            format!("*(u32*)({}) {}", address, words.collect::<Vec<_>>().join(" "))
+        }
+        _ => assertion.to_string(),
+    };
+    let value = Expression::parse(&expression)
+        .map_err(|e| e.to_string())
+        .and_then(|expression| {
+            let emulator = debugger.emulator().map_err(|e| e.to_string())?;
+            expression.evaluate(emulator).ok_or_else(|| "Division by zero".to_string())
+        });
+    match value {
+        Ok(value) if !value.is_true() => (
+            StepStatus::Failed,
+            Vec::new(),
+            Some(format!("Assertion failed: {}{}", expression, operand_values(debugger, &expression))),
+        ),
+        Ok(_) => (StepStatus::Passed, Vec::new(), None),
+        Err(e) => (StepStatus::Error, Vec::new(), Some(e)),
+    }
+}
+
+/// ` (left = 0x.., right = 0x..)` when the assertion is a comparison, so the
+/// report shows what the guest actually had.
+fn operand_values(debugger: &PicoDebugger, expression: &str) -> String {
+    let (Ok(Expression::Binary(op, left, right)), Ok(emulator)) = (Expression::parse(expression), debugger.emulator()) else {
+        return String::new();
+    };
+    if !op.is_comparison() {
+        return String::new();
+    }
+    let value = |side: &Expression| side.evaluate(emulator).map_or("?".to_string(), |value| format!("0x{:x}", value));
+    format!(" (left = {}, right = {})", value(&left), value(&right))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// `tests/fixtures/frames.elf` spins for about two million cycles before halting.
+    fn run(script: &str, max_cycles: u64) -> ScriptReport {
+        let mut debugger = PicoDebugger::new();
+        let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/frames.elf");
+        debugger.start_session(program, Vec::new()).unwrap();
+        run_script(&mut debugger, script, max_cycles)
+    }
+
+    #[test]
+    fn assertions_are_counted_and_do_not_stop_the_script() {
+        let report = run("# comment\ncontinue\nassert reg a0 == 0\nassert s1 == 1\nassert s0 == 5\n", DEFAULT_MAX_CYCLES);
+        assert!(!report.success);
+        assert_eq!((report.assertions_passed, report.assertions_failed), (2, 1));
+        let failure = &report.steps[2];
+        assert_eq!((failure.line, failure.status), (4, StepStatus::Failed));
+        assert_eq!(failure.error.as_deref(), Some("Assertion failed: s1 == 1 (left = 0x0, right = 0x1)"));
+        assert_eq!(report.final_state.unwrap().exit_code, Some(0));
+    }
+
+    #[test]
+    fn exceeding_the_cycle_limit_fails_the_script() {
+        let report = run("continue\nassert s1 == 0\n", 1000);
+        assert!(!report.success);
+        assert_eq!((report.assertions_passed, report.assertions_failed), (0, 1));
+        assert_eq!(report.steps.len(), 1);
+        assert_eq!(report.steps[0].status, StepStatus::Failed);
+        assert_eq!(report.steps[0].error.as_deref(), Some("Cycle limit of 1000 exceeded"));
+        let state = report.final_state.unwrap();
+        assert_eq!((state.cycle, state.exit_code), (1000, None));
+    }
+}
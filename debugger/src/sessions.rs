@@ -0,0 +1,202 @@
+// Persistent debug sessions
+// Saves sessions, with an optional checkpoint, to a directory so they can be resumed or shared
+
+use crate::{Checkpoint, DebugSession, PicoDebugger};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Where sessions are kept unless `--dir` says otherwise, relative to the working directory.
+pub const DEFAULT_SESSIONS_DIR: &str = ".pico-debug/sessions";
+
+/// The on-disk record of a session. A checkpoint, if any, lives next to it in
+/// `<session_id>.ckpt`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSession {
+    pub session: DebugSession,
+    pub saved_at: chrono::DateTime<chrono::Utc>,
+    /// Name of the checkpoint saved with the session.
+    pub checkpoint: Option<String>,
+}
+
+pub struct SessionStore {
+    directory: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    fn session_path(&self, session_id: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        check_session_id(session_id)?;
+        Ok(self.directory.join(format!("{}.json", session_id)))
+    }
+
+    fn checkpoint_path(&self, session_id: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        check_session_id(session_id)?;
+        Ok(self.directory.join(format!("{}.ckpt", session_id)))
+    }
+
+    /// Saves the debugger's session, replacing an earlier save of the same id.
+    /// With `checkpoint`, that checkpoint is stored too and restored on resume.
+    pub fn save(&self, debugger: &PicoDebugger, checkpoint: Option<&str>) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let session = debugger.session().ok_or("No active debugging session")?;
+        let checkpoint_path = self.checkpoint_path(&session.session_id)?;
+        std::fs::create_dir_all(&self.directory)?;
+        match checkpoint {
+            Some(name) => debugger
+                .checkpoint(name)
+                .ok_or_else(|| format!("No checkpoint named '{}'", name))?
+                .save(&checkpoint_path)?,
+            None if checkpoint_path.exists() => std::fs::remove_file(&checkpoint_path)?,
+            None => {}
+        }
+        let saved = SavedSession {
+            session: session.clone(),
+            saved_at: chrono::Utc::now(),
+            checkpoint: checkpoint.map(str::to_string),
+        };
+        let path = self.session_path(&session.session_id)?;
+        std::fs::write(&path, serde_json::to_string_pretty(&saved)?)?;
+        Ok(path)
+    }
+
+    /// Saved sessions, most recently saved first, and a warning for each file
+    /// that could not be read, so one corrupt file does not hide the rest.
+    pub fn list(&self) -> Result<(Vec<SavedSession>, Vec<String>), Box<dyn std::error::Error>> {
+        if !self.directory.exists() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let mut sessions = Vec::new();
+        let mut warnings = Vec::new();
+        for entry in std::fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "json") {
+                match read_session(&path) {
+                    Ok(saved) => sessions.push(saved),
+                    Err(e) => warnings.push(format!("Skipping {}", e)),
+                }
+            }
+        }
+        sessions.sort_by_key(|saved| std::cmp::Reverse(saved.saved_at));
+        Ok((sessions, warnings))
+    }
+
+    pub fn load(&self, session_id: &str) -> Result<SavedSession, Box<dyn std::error::Error>> {
+        let path = self.session_path(session_id)?;
+        if !path.exists() {
+            return Err(format!("No saved session '{}' in {}", session_id, self.directory.display()).into());
+        }
+        read_session(&path)
+    }
+
+    /// Recreates a debugger for a saved session, restoring its checkpoint if it
+    /// has one. Saving it again writes back to this store. `program` overrides the stored path, for sessions shared from
+    /// another machine; the ELF must still hash the same.
+    pub fn resume(&self, session_id: &str, program: Option<PathBuf>) -> Result<PicoDebugger, Box<dyn std::error::Error>> {
+        let mut saved = self.load(session_id)?;
+        if let Some(program) = program {
+            saved.session.program_path = program;
+        }
+        let mut debugger = PicoDebugger::new();
+        debugger.set_sessions_dir(&self.directory);
+        debugger.resume_session(saved.session)?;
+        if let Some(name) = &saved.checkpoint {
+            debugger.import_checkpoint(Checkpoint::load(&self.checkpoint_path(session_id)?)?)?;
+            debugger.restore_checkpoint(name)?;
+        }
+        Ok(debugger)
+    }
+
+    pub fn delete(&self, session_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.session_path(session_id)?;
+        if !path.exists() {
+            return Err(format!("No saved session '{}' in {}", session_id, self.directory.display()).into());
+        }
+        std::fs::remove_file(path)?;
+        let checkpoint_path = self.checkpoint_path(session_id)?;
+        if checkpoint_path.exists() {
+            std::fs::remove_file(checkpoint_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Session ids name files in the sessions directory, so they are limited to
+/// `[A-Za-z0-9_-]`; anything else could reach outside it (`../../x`).
+fn check_session_id(session_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if session_id.is_empty() || !session_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(format!("Invalid session id '{}': use only letters, digits, '_' and '-'", session_id).into());
+    }
+    Ok(())
+}
+
+fn read_session(path: &Path) -> Result<SavedSession, Box<dyn std::error::Error>> {
+    let json = std::fs::read_to_string(path)?;
+    serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_store(name: &str) -> (PathBuf, SessionStore) {
+        let directory = std::env::temp_dir().join(format!("pico-debugger-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        (directory.clone(), SessionStore::new(directory))
+    }
+
+    #[test]
+    fn session_ids_cannot_leave_the_directory() {
+        let (directory, store) = scratch_store("session-ids");
+        let outside = directory.parent().unwrap().join(format!("pico-debugger-outside-{}.json", std::process::id()));
+        std::fs::write(&outside, "{}").unwrap();
+        let escape = format!("../{}", outside.file_stem().unwrap().to_str().unwrap());
+        for id in [escape.as_str(), "", "a/b", "a.b"] {
+            let error = store.delete(id).unwrap_err().to_string();
+            assert!(error.starts_with("Invalid session id"), "{}", error);
+            assert!(store.load(id).is_err());
+        }
+        assert!(outside.exists());
+        std::fs::remove_file(outside).unwrap();
+        assert!(store.delete("debug_123-x").unwrap_err().to_string().starts_with("No saved session"));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn list_skips_corrupt_sessions() {
+        let (directory, store) = scratch_store("session-list");
+        let mut debugger = PicoDebugger::new();
+        let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/frames.elf");
+        let session_id = debugger.start_session(program, Vec::new()).unwrap();
+        store.save(&debugger, None).unwrap();
+        std::fs::write(directory.join("broken.json"), "{ not json").unwrap();
+
+        let (sessions, warnings) = store.list().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session.session_id, session_id);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("broken.json"), "{}", warnings[0]);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn a_resumed_session_saves_back_to_its_store() {
+        let (directory, store) = scratch_store("session-resume");
+        let mut debugger = PicoDebugger::new();
+        let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/frames.elf");
+        let session_id = debugger.start_session(program, Vec::new()).unwrap();
+        let path = store.save(&debugger, None).unwrap();
+
+        let mut resumed = store.resume(&session_id, None).unwrap();
+        assert_eq!(resumed.sessions_dir(), directory);
+        std::fs::remove_file(&path).unwrap();
+        let outcome = crate::commands::execute(&mut resumed, "session save").unwrap();
+        assert!(outcome.lines[0].ends_with(&path.display().to_string()), "{}", outcome.lines[0]);
+        assert!(path.exists());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,473 @@
+// Pico syscall codes and the precompiles the emulator can execute
+// Codes follow the Pico zkVM convention: the syscall id is passed in t0, arguments in a0..a2
+
+use num_bigint::BigUint;
+
+pub const HALT: u32 = 0x00_00_00_00;
+pub const WRITE: u32 = 0x00_00_00_02;
+pub const ENTER_UNCONSTRAINED: u32 = 0x00_00_00_03;
+pub const EXIT_UNCONSTRAINED: u32 = 0x00_00_00_04;
+pub const SHA_EXTEND: u32 = 0x00_30_01_05;
+pub const SHA_COMPRESS: u32 = 0x00_01_01_06;
+pub const ED_ADD: u32 = 0x00_01_01_07;
+pub const ED_DECOMPRESS: u32 = 0x00_00_01_08;
+pub const KECCAK_PERMUTE: u32 = 0x00_01_01_09;
+pub const SECP256K1_ADD: u32 = 0x00_01_01_0A;
+pub const SECP256K1_DOUBLE: u32 = 0x00_00_01_0B;
+pub const SECP256K1_DECOMPRESS: u32 = 0x00_00_01_0C;
+pub const BN254_ADD: u32 = 0x00_01_01_0E;
+pub const BN254_DOUBLE: u32 = 0x00_00_01_0F;
+pub const COMMIT: u32 = 0x00_00_00_10;
+pub const COMMIT_DEFERRED_PROOFS: u32 = 0x00_00_00_1A;
+pub const VERIFY_PICO_PROOF: u32 = 0x00_00_00_1B;
+pub const BLS12381_DECOMPRESS: u32 = 0x00_00_01_1C;
+pub const UINT256_MUL: u32 = 0x00_01_01_1D;
+pub const BLS12381_ADD: u32 = 0x00_01_01_1E;
+pub const BLS12381_DOUBLE: u32 = 0x00_00_01_1F;
+pub const HINT_LEN: u32 = 0x00_00_00_F0;
+pub const HINT_READ: u32 = 0x00_00_00_F1;
+
+/// File descriptors understood by the WRITE syscall.
+pub const FD_STDOUT: u32 = 1;
+pub const FD_STDERR: u32 = 2;
+pub const FD_PUBLIC_VALUES: u32 = 3;
+pub const FD_HINT: u32 = 4;
+
+pub fn syscall_name(code: u32) -> String {
+    let name = match code {
+        HALT => "halt",
+        WRITE => "write",
+        ENTER_UNCONSTRAINED => "enter_unconstrained",
+        EXIT_UNCONSTRAINED => "exit_unconstrained",
+        SHA_EXTEND => "sha_extend",
+        SHA_COMPRESS => "sha_compress",
+        ED_ADD => "ed_add",
+        ED_DECOMPRESS => "ed_decompress",
+        KECCAK_PERMUTE => "keccak_permute",
+        SECP256K1_ADD => "secp256k1_add",
+        SECP256K1_DOUBLE => "secp256k1_double",
+        SECP256K1_DECOMPRESS => "secp256k1_decompress",
+        BN254_ADD => "bn254_add",
+        BN254_DOUBLE => "bn254_double",
+        COMMIT => "commit",
+        COMMIT_DEFERRED_PROOFS => "commit_deferred_proofs",
+        VERIFY_PICO_PROOF => "verify_pico_proof",
+        BLS12381_DECOMPRESS => "bls12381_decompress",
+        UINT256_MUL => "uint256_mul",
+        BLS12381_ADD => "bls12381_add",
+        BLS12381_DOUBLE => "bls12381_double",
+        HINT_LEN => "hint_len",
+        HINT_READ => "hint_read",
+        _ => return format!("unknown_0x{:08x}", code),
+    };
+    name.to_string()
+}
+
+/// Renders a syscall with its arguments decoded, e.g. `write(fd=stdout, buf=0x300000, len=6)`.
+pub fn describe(code: u32, arguments: [u32; 3]) -> String {
+    let [a0, a1, a2] = arguments;
+    let name = syscall_name(code);
+    match code {
+        HALT => format!("{}(exit_code={})", name, a0),
+        WRITE => {
+            let fd = match a0 {
+                FD_STDOUT => "stdout".to_string(),
+                FD_STDERR => "stderr".to_string(),
+                FD_PUBLIC_VALUES => "public_values".to_string(),
+                FD_HINT => "hint".to_string(),
+                other => other.to_string(),
+            };
+            format!("{}(fd={}, buf=0x{:x}, len={})", name, fd, a1, a2)
+        }
+        ENTER_UNCONSTRAINED | EXIT_UNCONSTRAINED | HINT_LEN => format!("{}()", name),
+        HINT_READ => format!("{}(buf=0x{:x}, len={})", name, a0, a1),
+        COMMIT => format!("{}(index={}, word=0x{:08x})", name, a0, a1),
+        SHA_EXTEND => format!("{}(w=0x{:x})", name, a0),
+        SHA_COMPRESS => format!("{}(w=0x{:x}, h=0x{:x})", name, a0, a1),
+        KECCAK_PERMUTE => format!("{}(state=0x{:x})", name, a0),
+        ED_ADD | SECP256K1_ADD | BN254_ADD | BLS12381_ADD => format!("{}(p=0x{:x}, q=0x{:x})", name, a0, a1),
+        SECP256K1_DOUBLE | BN254_DOUBLE | BLS12381_DOUBLE => format!("{}(p=0x{:x})", name, a0),
+        ED_DECOMPRESS => format!("{}(point=0x{:x}, sign={})", name, a0, a1),
+        SECP256K1_DECOMPRESS | BLS12381_DECOMPRESS => format!("{}(point=0x{:x}, sign={})", name, a0, a1),
+        UINT256_MUL => format!("{}(x=0x{:x}, y=0x{:x})", name, a0, a1),
+        _ => format!("{}(a0=0x{:x}, a1=0x{:x}, a2=0x{:x})", name, a0, a1, a2),
+    }
+}
+
+/// Cycles the Pico executor charges for a syscall in addition to the ecall
+/// itself. The count is encoded in the third byte of the syscall code.
+pub fn extra_cycles(code: u32) -> u32 {
+    (code >> 16) & 0xff
+}
+
+/// Whether the syscall is proven by a dedicated precompile chip rather than
+/// the CPU, flagged by the second byte of the syscall code.
+pub fn is_precompile(code: u32) -> bool {
+    (code >> 8) & 0xff != 0
+}
+
+const SHA_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Fills `w[16..64]` of a SHA-256 message schedule in place.
+pub fn sha_extend(w: &mut [u32; 64]) {
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+}
+
+/// Runs the 64 SHA-256 rounds over an extended schedule and adds the result into `h`.
+pub fn sha_compress(w: &[u32; 64], h: &mut [u32; 8]) {
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let temp1 = hh
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(SHA_K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+    }
+    for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+        *state = state.wrapping_add(value);
+    }
+}
+
+const KECCAK_RC: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+const KECCAK_RHO: [u32; 24] = [1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44];
+const KECCAK_PI: [usize; 24] = [10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1];
+
+/// Applies the Keccak-f[1600] permutation to `state` in place.
+pub fn keccak_permute(state: &mut [u64; 25]) {
+    for rc in KECCAK_RC {
+        let mut c = [0u64; 5];
+        for x in 0..5 {
+            c[x] = state[x] ^ state[x + 5] ^ state[x + 10] ^ state[x + 15] ^ state[x + 20];
+        }
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                state[x + 5 * y] ^= d;
+            }
+        }
+        let mut last = state[1];
+        for i in 0..24 {
+            let j = KECCAK_PI[i];
+            let temp = state[j];
+            state[j] = last.rotate_left(KECCAK_RHO[i]);
+            last = temp;
+        }
+        for y in 0..5 {
+            let row = [state[5 * y], state[5 * y + 1], state[5 * y + 2], state[5 * y + 3], state[5 * y + 4]];
+            for x in 0..5 {
+                state[5 * y + x] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+            }
+        }
+        state[0] ^= rc;
+    }
+}
+
+/// Curves of the short Weierstrass precompiles, all of the form y² = x³ + b.
+/// Points are affine: x then y, each as little-endian words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeierstrassCurve {
+    Secp256k1,
+    Bn254,
+    Bls12381,
+}
+
+impl WeierstrassCurve {
+    /// The curve a Weierstrass add, double or decompress syscall operates on.
+    pub fn for_syscall(code: u32) -> Option<Self> {
+        match code {
+            SECP256K1_ADD | SECP256K1_DOUBLE | SECP256K1_DECOMPRESS => Some(Self::Secp256k1),
+            BN254_ADD | BN254_DOUBLE => Some(Self::Bn254),
+            BLS12381_ADD | BLS12381_DOUBLE | BLS12381_DECOMPRESS => Some(Self::Bls12381),
+            _ => None,
+        }
+    }
+
+    /// Words in one coordinate.
+    pub fn coordinate_words(self) -> usize {
+        match self {
+            Self::Secp256k1 | Self::Bn254 => 8,
+            Self::Bls12381 => 12,
+        }
+    }
+
+    fn field(self) -> Field {
+        let modulus = match self {
+            Self::Secp256k1 => "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+            Self::Bn254 => "30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47",
+            Self::Bls12381 => "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaaab",
+        };
+        Field::new(modulus, self.coordinate_words())
+    }
+
+    fn b(self) -> BigUint {
+        BigUint::from(match self {
+            Self::Secp256k1 => 7u32,
+            Self::Bn254 => 3,
+            Self::Bls12381 => 4,
+        })
+    }
+
+    /// `p + q`. The precompile has no representation for the point at infinity,
+    /// so points with the same x coordinate are rejected.
+    pub fn add(self, p: &[u32], q: &[u32]) -> Result<Vec<u32>, String> {
+        let field = self.field();
+        let (x1, y1) = field.point(p);
+        let (x2, y2) = field.point(q);
+        if x1 == x2 {
+            return Err("both points have the same x coordinate; use the double precompile for p + p".to_string());
+        }
+        let slope = field.mul(&field.sub(&y2, &y1), &field.inverse(&field.sub(&x2, &x1))?);
+        Ok(field.chord(&slope, &x1, &y1, &x2))
+    }
+
+    pub fn double(self, p: &[u32]) -> Result<Vec<u32>, String> {
+        let field = self.field();
+        let (x, y) = field.point(p);
+        if y == BigUint::ZERO {
+            return Err("the point has order two, so its double is the point at infinity".to_string());
+        }
+        let slope = field.mul(&(BigUint::from(3u32) * &x * &x), &field.inverse(&(BigUint::from(2u32) * &y))?);
+        Ok(field.chord(&slope, &x, &y, &x))
+    }
+
+    /// The y coordinate for `x`. `sign` selects the odd root for secp256k1 and,
+    /// following the BLS12-381 compressed encoding, the larger root for BLS12-381.
+    pub fn decompress(self, x: &[u32], sign: bool) -> Result<Vec<u32>, String> {
+        let field = self.field();
+        let x = field.element(x);
+        let rhs = field.reduce(&x * &x * &x + self.b());
+        // All three moduli are 3 mod 4, so a square root is rhs^((p + 1) / 4)
+        let exponent = (&field.modulus + 1u32) >> 2;
+        let mut y = rhs.modpow(&exponent, &field.modulus);
+        if field.mul(&y, &y) != rhs {
+            return Err("x is not the x coordinate of a point on the curve".to_string());
+        }
+        let negated = field.sub(&BigUint::ZERO, &y);
+        let chosen = match self {
+            Self::Bls12381 => y > negated,
+            _ => y.bit(0),
+        };
+        if chosen != sign {
+            y = negated;
+        }
+        Ok(field.words(&y))
+    }
+}
+
+/// Edwards25519 modulus 2^255 - 19.
+const ED25519_MODULUS: &str = "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffed";
+
+/// `d` of edwards25519, -121665/121666.
+fn ed25519_d(field: &Field) -> BigUint {
+    field.mul(&field.sub(&BigUint::ZERO, &BigUint::from(121_665u32)), &field.inverse(&BigUint::from(121_666u32)).unwrap())
+}
+
+/// `p + q` on edwards25519 (-x² + y² = 1 + d·x²·y²), points as 16 words.
+pub fn ed_add(p: &[u32], q: &[u32]) -> Result<Vec<u32>, String> {
+    let field = Field::new(ED25519_MODULUS, 8);
+    let (x1, y1) = field.point(p);
+    let (x2, y2) = field.point(q);
+    let t = field.mul(&ed25519_d(&field), &field.mul(&field.mul(&x1, &x2), &field.mul(&y1, &y2)));
+    let x3 = field.mul(&field.reduce(&x1 * &y2 + &y1 * &x2), &field.inverse(&field.reduce(BigUint::from(1u32) + &t))?);
+    let y3 = field.mul(&field.reduce(&y1 * &y2 + &x1 * &x2), &field.inverse(&field.sub(&BigUint::from(1u32), &t))?);
+    let mut words = field.words(&x3);
+    words.extend(field.words(&y3));
+    Ok(words)
+}
+
+/// The x coordinate for `y` on edwards25519; `sign` selects the odd root.
+pub fn ed_decompress(y: &[u32], sign: bool) -> Result<Vec<u32>, String> {
+    let field = Field::new(ED25519_MODULUS, 8);
+    let y = field.element(y);
+    let one = BigUint::from(1u32);
+    let y_squared = field.mul(&y, &y);
+    let u = field.sub(&y_squared, &one);
+    let v = field.reduce(field.mul(&ed25519_d(&field), &y_squared) + &one);
+    let x_squared = field.mul(&u, &field.inverse(&v)?);
+    // The modulus is 5 mod 8: a candidate root is (x²)^((p + 3) / 8), which is
+    // off by a factor of sqrt(-1) when its square is -x²
+    let mut x = x_squared.modpow(&((&field.modulus + 3u32) >> 3), &field.modulus);
+    if field.mul(&x, &x) != x_squared {
+        let sqrt_minus_one = BigUint::from(2u32).modpow(&((&field.modulus - 1u32) >> 2), &field.modulus);
+        x = field.mul(&x, &sqrt_minus_one);
+    }
+    if field.mul(&x, &x) != x_squared {
+        return Err("y is not the y coordinate of a point on the curve".to_string());
+    }
+    if x == BigUint::ZERO && sign {
+        return Err("x is zero, so its sign bit must be clear".to_string());
+    }
+    if x.bit(0) != sign {
+        x = field.sub(&BigUint::ZERO, &x);
+    }
+    Ok(field.words(&x))
+}
+
+/// `x * y mod modulus` on 8-word integers, where a zero modulus means 2^256.
+pub fn uint256_mul(x: &[u32], y: &[u32], modulus: &[u32]) -> Vec<u32> {
+    let mut modulus = BigUint::from_slice(modulus);
+    if modulus == BigUint::ZERO {
+        modulus = BigUint::from(1u32) << 256;
+    }
+    let product = BigUint::from_slice(x) * BigUint::from_slice(y) % modulus;
+    to_words(&product, 8)
+}
+
+/// Arithmetic modulo a prime, on values read from guest words.
+struct Field {
+    modulus: BigUint,
+    words: usize,
+}
+
+impl Field {
+    fn new(modulus: &str, words: usize) -> Self {
+        Self {
+            modulus: BigUint::parse_bytes(modulus.as_bytes(), 16).expect("valid modulus"),
+            words,
+        }
+    }
+
+    fn element(&self, words: &[u32]) -> BigUint {
+        self.reduce(BigUint::from_slice(words))
+    }
+
+    fn point(&self, words: &[u32]) -> (BigUint, BigUint) {
+        let (x, y) = words.split_at(self.words);
+        (self.element(x), self.element(y))
+    }
+
+    fn words(&self, value: &BigUint) -> Vec<u32> {
+        to_words(value, self.words)
+    }
+
+    fn reduce(&self, value: BigUint) -> BigUint {
+        value % &self.modulus
+    }
+
+    fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.reduce(a + &self.modulus - b)
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        self.reduce(a * b)
+    }
+
+    fn inverse(&self, value: &BigUint) -> Result<BigUint, String> {
+        value
+            .modinv(&self.modulus)
+            .ok_or_else(|| "division by zero in the field".to_string())
+    }
+
+    /// The third point on the line with `slope` through (x1, y1) and x2, negated:
+    /// the sum for distinct points, or the double when x2 = x1 and `slope` is the tangent.
+    fn chord(&self, slope: &BigUint, x1: &BigUint, y1: &BigUint, x2: &BigUint) -> Vec<u32> {
+        let x3 = self.sub(&self.sub(&self.mul(slope, slope), x1), x2);
+        let y3 = self.sub(&self.mul(slope, &self.sub(x1, &x3)), y1);
+        let mut words = self.words(&x3);
+        words.extend(self.words(&y3));
+        words
+    }
+}
+
+fn to_words(value: &BigUint, count: usize) -> Vec<u32> {
+    let mut words = value.to_u32_digits();
+    words.resize(count, 0);
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian words of a big-endian hex number.
+    fn words(hex: &str) -> Vec<u32> {
+        to_words(&BigUint::parse_bytes(hex.as_bytes(), 16).unwrap(), (hex.len() / 8).max(8))
+    }
+
+    #[test]
+    fn secp256k1_add_and_double_agree() {
+        let curve = WeierstrassCurve::Secp256k1;
+        let g = [
+            words("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+            words("483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"),
+        ]
+        .concat();
+        let two_g = curve.double(&g).unwrap();
+        assert_eq!(two_g[..8], words("c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5"));
+        assert_eq!(two_g[8..], words("1ae168fea63dc339a3c58419466ceaeef7f632653266d0e1236431a950cfe52a"));
+        let three_g = curve.add(&two_g, &g).unwrap();
+        assert_eq!(three_g[..8], words("f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9"));
+        assert!(curve.add(&g, &g).is_err());
+    }
+
+    #[test]
+    fn decompress_picks_the_requested_root() {
+        let x = words("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+        let y = WeierstrassCurve::Secp256k1.decompress(&x, false).unwrap();
+        assert_eq!(y, words("483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"));
+        assert_eq!(y[0] & 1, 0);
+
+        // The BLS12-381 generator's compressed encoding has the sort flag clear
+        let x = words("17f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb");
+        let y = WeierstrassCurve::Bls12381.decompress(&x, false).unwrap();
+        assert_eq!(y, words("08b3f481e3aaa0f1a09e30ed741d8ae4fcf5e095d5d00af600db18cb2c04b3edd03cc744a2888ae40caa232946c5e7e1"));
+    }
+
+    #[test]
+    fn ed25519_base_point() {
+        let y = words("6666666666666666666666666666666666666666666666666666666666666658");
+        let x = ed_decompress(&y, false).unwrap();
+        assert_eq!(x, words("216936d3cd6e53fec0a4e231fdd6dc5c692cc7609525a7b2c9562d608f25d51a"));
+        assert_eq!(ed_decompress(&y, true).unwrap()[0] & 1, 1);
+
+        // The identity (0, 1) is the neutral element
+        let base = [x, y].concat();
+        let identity = [vec![0; 8], words("01")].concat();
+        assert_eq!(ed_add(&base, &identity).unwrap(), base);
+    }
+
+    #[test]
+    fn uint256_mul_reduces_by_the_modulus() {
+        assert_eq!(uint256_mul(&words("03"), &words("05"), &words("07")), words("01"));
+        // A zero modulus wraps at 2^256
+        let half = words("8000000000000000000000000000000000000000000000000000000000000000");
+        assert_eq!(uint256_mul(&half, &words("02"), &[0; 8]), vec![0; 8]);
+    }
+}
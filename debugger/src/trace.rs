@@ -0,0 +1,690 @@
+// Compact streaming binary trace format
+//
+// A trace file is a header followed by independently compressed chunks of
+// consecutive cycles and a trailing index:
+//
+//   header:  "PICOTRC1" | version: u32 | chunk_cycles: u32
+//   chunk:   zstd(keyframe | records...)
+//   index:   per chunk: offset u64 | first_cycle u64 | cycles u32 | min_pc u32 | max_pc u32 | length u32
+//   footer:  index_offset u64 | chunk_count u32 | total_cycles u64 | "PICOIDX1"
+//
+// Every chunk starts with a keyframe (pc and full register file) so it can be
+// decoded on its own. Records are delta encoded against the previous record:
+// the pc is stored only when it is not the fall-through address, registers
+// only when written, and memory addresses as zigzag deltas. The writer holds a
+// single chunk in memory, so trace size on disk is the only limit on run length.
+
+use crate::debuginfo::DebugInfo;
+use crate::emulator::StepEvent;
+use crate::riscv::Instruction;
+use crate::{CycleInfo, MemoryAccess, MemoryAccessType};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const FILE_MAGIC: &[u8; 8] = b"PICOTRC1";
+const INDEX_MAGIC: &[u8; 8] = b"PICOIDX1";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: u64 = 16;
+const FOOTER_LEN: i64 = 28;
+const INDEX_ENTRY_LEN: usize = 32;
+const COMPRESSION_LEVEL: i32 = 3;
+
+pub const DEFAULT_CHUNK_CYCLES: u32 = 65_536;
+
+const FLAG_PC_JUMP: u8 = 1 << 0;
+const FLAG_REGISTER_WRITE: u8 = 1 << 1;
+const FLAG_MEMORY: u8 = 1 << 2;
+const FLAG_SYSCALL: u8 = 1 << 3;
+
+/// One decoded cycle from a trace file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub pc: u32,
+    pub word: u32,
+    pub register_write: Option<(u8, u32)>,
+    pub memory: Vec<(MemoryAccessType, u32, u32)>,
+    pub syscall: Option<u32>,
+}
+
+impl TraceRecord {
+    pub fn instruction(&self) -> Instruction {
+        Instruction::decode(self.word)
+    }
+
+    /// Converts the record for reporting; traces do not store timing, and
+    /// function and source fields are only filled when `debug_info` is given.
+    pub fn cycle_info(&self, debug_info: Option<&DebugInfo>) -> CycleInfo {
+        CycleInfo {
+            cycle: self.cycle,
+            instruction: self.instruction().disassemble(self.pc),
+            pc: self.pc as u64,
+            timestamp: Duration::ZERO,
+            function: debug_info.and_then(|info| info.function_at(self.pc)),
+            source_location: debug_info.and_then(|info| info.location(self.pc)),
+        }
+    }
+
+    pub fn memory_accesses(&self) -> Vec<MemoryAccess> {
+        self.memory
+            .iter()
+            .map(|(access_type, address, value)| MemoryAccess {
+                address: *address as u64,
+                value: *value as u64,
+                access_type: *access_type,
+                cycle: self.cycle,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkIndexEntry {
+    pub offset: u64,
+    pub first_cycle: u64,
+    pub cycles: u32,
+    pub min_pc: u32,
+    pub max_pc: u32,
+    pub length: u32,
+}
+
+impl ChunkIndexEntry {
+    fn contains_cycle(&self, cycle: u64) -> bool {
+        cycle >= self.first_cycle && cycle < self.first_cycle + self.cycles as u64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceSummary {
+    pub total_cycles: u64,
+    pub chunks: u32,
+    pub bytes_written: u64,
+}
+
+/// Streams step events into a chunked, compressed trace file.
+pub struct TraceWriter<W: Write> {
+    inner: W,
+    chunk_cycles: u32,
+    offset: u64,
+    index: Vec<ChunkIndexEntry>,
+    registers: [u32; 32],
+    buffer: Vec<u8>,
+    chunk: Option<ChunkState>,
+    next_cycle: u64,
+}
+
+struct ChunkState {
+    first_cycle: u64,
+    cycles: u32,
+    min_pc: u32,
+    max_pc: u32,
+    expected_pc: u32,
+    last_address: u32,
+}
+
+impl TraceWriter<BufWriter<File>> {
+    pub fn create(path: &Path, registers: [u32; 32]) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Self::new(file, DEFAULT_CHUNK_CYCLES, registers)
+    }
+}
+
+impl<W: Write> TraceWriter<W> {
+    /// Starts a trace; `registers` is the register file before the first recorded cycle.
+    pub fn new(mut inner: W, chunk_cycles: u32, registers: [u32; 32]) -> io::Result<Self> {
+        inner.write_all(FILE_MAGIC)?;
+        inner.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        inner.write_all(&chunk_cycles.max(1).to_le_bytes())?;
+        Ok(Self {
+            inner,
+            chunk_cycles: chunk_cycles.max(1),
+            offset: HEADER_LEN,
+            index: Vec::new(),
+            registers,
+            buffer: Vec::new(),
+            chunk: None,
+            next_cycle: 0,
+        })
+    }
+
+    pub fn record(&mut self, event: &StepEvent) -> io::Result<()> {
+        let started = self.chunk.is_some() || !self.index.is_empty();
+        if started && event.cycle != self.next_cycle {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("trace cycles must be consecutive: expected {}, got {}", self.next_cycle, event.cycle),
+            ));
+        }
+
+        if self.chunk.is_none() {
+            self.buffer.clear();
+            self.buffer.extend_from_slice(&event.pc.to_le_bytes());
+            for register in self.registers {
+                self.buffer.extend_from_slice(&register.to_le_bytes());
+            }
+            self.chunk = Some(ChunkState {
+                first_cycle: event.cycle,
+                cycles: 0,
+                min_pc: event.pc,
+                max_pc: event.pc,
+                expected_pc: event.pc,
+                last_address: 0,
+            });
+        }
+
+        let chunk = self.chunk.as_mut().unwrap();
+        let mut flags = 0u8;
+        if event.pc != chunk.expected_pc {
+            flags |= FLAG_PC_JUMP;
+        }
+        if event.register_write.is_some() {
+            flags |= FLAG_REGISTER_WRITE;
+        }
+        if !event.memory.is_empty() {
+            flags |= FLAG_MEMORY;
+        }
+        if event.syscall.is_some() {
+            flags |= FLAG_SYSCALL;
+        }
+
+        self.buffer.push(flags);
+        self.buffer.extend_from_slice(&event.word.to_le_bytes());
+        if flags & FLAG_PC_JUMP != 0 {
+            write_varint(&mut self.buffer, zigzag(event.pc as i64 - chunk.expected_pc as i64));
+        }
+        if let Some((register, value)) = event.register_write {
+            self.buffer.push(register);
+            write_varint(&mut self.buffer, value as u64);
+            self.registers[register as usize] = value;
+        }
+        if !event.memory.is_empty() {
+            write_varint(&mut self.buffer, event.memory.len() as u64);
+            for access in &event.memory {
+                let address = access.address as u32;
+                self.buffer.push(match access.access_type {
+                    MemoryAccessType::Read => 0,
+                    MemoryAccessType::Write => 1,
+                });
+                write_varint(&mut self.buffer, zigzag(address as i64 - chunk.last_address as i64));
+                // Guest memory values are words; the reader decodes them as u32
+                write_varint(&mut self.buffer, access.value as u32 as u64);
+                chunk.last_address = address;
+            }
+        }
+        if let Some(syscall) = &event.syscall {
+            write_varint(&mut self.buffer, syscall.code as u64);
+        }
+
+        chunk.cycles += 1;
+        chunk.min_pc = chunk.min_pc.min(event.pc);
+        chunk.max_pc = chunk.max_pc.max(event.pc);
+        chunk.expected_pc = event.pc.wrapping_add(4);
+        self.next_cycle = event.cycle + 1;
+
+        if chunk.cycles >= self.chunk_cycles {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    fn flush_chunk(&mut self) -> io::Result<()> {
+        let Some(chunk) = self.chunk.take() else {
+            return Ok(());
+        };
+        let compressed = zstd::bulk::compress(&self.buffer, COMPRESSION_LEVEL)?;
+        self.inner.write_all(&compressed)?;
+        self.index.push(ChunkIndexEntry {
+            offset: self.offset,
+            first_cycle: chunk.first_cycle,
+            cycles: chunk.cycles,
+            min_pc: chunk.min_pc,
+            max_pc: chunk.max_pc,
+            length: compressed.len() as u32,
+        });
+        self.offset += compressed.len() as u64;
+        Ok(())
+    }
+
+    /// Flushes the last chunk and writes the index and footer.
+    pub fn finish(mut self) -> io::Result<TraceSummary> {
+        self.flush_chunk()?;
+        let index_offset = self.offset;
+        for entry in &self.index {
+            self.inner.write_all(&entry.offset.to_le_bytes())?;
+            self.inner.write_all(&entry.first_cycle.to_le_bytes())?;
+            self.inner.write_all(&entry.cycles.to_le_bytes())?;
+            self.inner.write_all(&entry.min_pc.to_le_bytes())?;
+            self.inner.write_all(&entry.max_pc.to_le_bytes())?;
+            self.inner.write_all(&entry.length.to_le_bytes())?;
+        }
+        let total_cycles: u64 = self.index.iter().map(|entry| entry.cycles as u64).sum();
+        self.inner.write_all(&index_offset.to_le_bytes())?;
+        self.inner.write_all(&(self.index.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&total_cycles.to_le_bytes())?;
+        self.inner.write_all(INDEX_MAGIC)?;
+        self.inner.flush()?;
+
+        Ok(TraceSummary {
+            total_cycles,
+            chunks: self.index.len() as u32,
+            bytes_written: index_offset + (self.index.len() * INDEX_ENTRY_LEN) as u64 + FOOTER_LEN as u64,
+        })
+    }
+}
+
+/// Restricts which records a [`TraceReader`] iterator yields.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceFilter {
+    pub from_cycle: Option<u64>,
+    pub to_cycle: Option<u64>,
+    /// Inclusive start and exclusive end of the PC range.
+    pub pc_range: Option<(u32, u32)>,
+}
+
+impl TraceFilter {
+    fn accepts_chunk(&self, entry: &ChunkIndexEntry) -> bool {
+        let last_cycle = entry.first_cycle + entry.cycles as u64;
+        if self.from_cycle.is_some_and(|from| last_cycle <= from) {
+            return false;
+        }
+        if self.to_cycle.is_some_and(|to| entry.first_cycle >= to) {
+            return false;
+        }
+        if let Some((start, end)) = self.pc_range {
+            if entry.max_pc < start || entry.min_pc >= end {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn accepts(&self, record: &TraceRecord) -> bool {
+        self.from_cycle.is_none_or(|from| record.cycle >= from)
+            && self.to_cycle.is_none_or(|to| record.cycle < to)
+            && self
+                .pc_range
+                .is_none_or(|(start, end)| record.pc >= start && record.pc < end)
+    }
+}
+
+/// Decoded chunk: the keyframe register file plus its records.
+#[derive(Debug, Clone)]
+pub struct TraceChunk {
+    pub registers: [u32; 32],
+    pub records: Vec<TraceRecord>,
+}
+
+/// Random-access reader for trace files written by [`TraceWriter`].
+pub struct TraceReader {
+    file: BufReader<File>,
+    chunk_cycles: u32,
+    total_cycles: u64,
+    index: Vec<ChunkIndexEntry>,
+}
+
+impl TraceReader {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut header = [0u8; HEADER_LEN as usize];
+        file.read_exact(&mut header)?;
+        if &header[..8] != FILE_MAGIC {
+            return Err(format!("{} is not a Pico trace file", path.display()).into());
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into()?);
+        if version != FORMAT_VERSION {
+            return Err(format!("unsupported trace format version {}", version).into());
+        }
+        let chunk_cycles = u32::from_le_bytes(header[12..16].try_into()?);
+
+        file.seek(SeekFrom::End(-FOOTER_LEN))?;
+        let mut footer = [0u8; FOOTER_LEN as usize];
+        file.read_exact(&mut footer)?;
+        if &footer[20..] != INDEX_MAGIC {
+            return Err("trace file is truncated: missing index (was the recording interrupted?)".into());
+        }
+        let index_offset = u64::from_le_bytes(footer[0..8].try_into()?);
+        let chunk_count = u32::from_le_bytes(footer[8..12].try_into()?) as usize;
+        let total_cycles = u64::from_le_bytes(footer[12..20].try_into()?);
+
+        file.seek(SeekFrom::Start(index_offset))?;
+        let mut raw_index = vec![0u8; chunk_count * INDEX_ENTRY_LEN];
+        file.read_exact(&mut raw_index)?;
+        let index = raw_index
+            .chunks_exact(INDEX_ENTRY_LEN)
+            .map(|entry| ChunkIndexEntry {
+                offset: u64::from_le_bytes(entry[0..8].try_into().unwrap()),
+                first_cycle: u64::from_le_bytes(entry[8..16].try_into().unwrap()),
+                cycles: u32::from_le_bytes(entry[16..20].try_into().unwrap()),
+                min_pc: u32::from_le_bytes(entry[20..24].try_into().unwrap()),
+                max_pc: u32::from_le_bytes(entry[24..28].try_into().unwrap()),
+                length: u32::from_le_bytes(entry[28..32].try_into().unwrap()),
+            })
+            .collect();
+
+        Ok(Self {
+            file,
+            chunk_cycles,
+            total_cycles,
+            index,
+        })
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.total_cycles
+    }
+
+    pub fn chunk_cycles(&self) -> u32 {
+        self.chunk_cycles
+    }
+
+    pub fn index(&self) -> &[ChunkIndexEntry] {
+        &self.index
+    }
+
+    pub fn read_chunk(&mut self, chunk: usize) -> Result<TraceChunk, Box<dyn std::error::Error>> {
+        let entry = *self
+            .index
+            .get(chunk)
+            .ok_or_else(|| format!("chunk {} out of range", chunk))?;
+        self.file.seek(SeekFrom::Start(entry.offset))?;
+        let mut compressed = vec![0u8; entry.length as usize];
+        self.file.read_exact(&mut compressed)?;
+        let raw = zstd::stream::decode_all(&compressed[..])?;
+        decode_chunk(&raw, &entry)
+    }
+
+    fn chunk_for_cycle(&self, cycle: u64) -> Option<usize> {
+        let position = self.index.partition_point(|entry| entry.first_cycle + entry.cycles as u64 <= cycle);
+        (position < self.index.len() && self.index[position].contains_cycle(cycle)).then_some(position)
+    }
+
+    /// Returns the record executed at `cycle`.
+    pub fn seek(&mut self, cycle: u64) -> Result<Option<TraceRecord>, Box<dyn std::error::Error>> {
+        let Some(chunk) = self.chunk_for_cycle(cycle) else {
+            return Ok(None);
+        };
+        let first_cycle = self.index[chunk].first_cycle;
+        let decoded = self.read_chunk(chunk)?;
+        Ok(decoded.records.into_iter().nth((cycle - first_cycle) as usize))
+    }
+
+    /// Reconstructs the register file as it was before `cycle` executed.
+    pub fn registers_at(&mut self, cycle: u64) -> Result<Option<[u32; 32]>, Box<dyn std::error::Error>> {
+        let Some(chunk) = self.chunk_for_cycle(cycle) else {
+            return Ok(None);
+        };
+        let decoded = self.read_chunk(chunk)?;
+        let mut registers = decoded.registers;
+        for record in decoded.records.iter().take_while(|record| record.cycle < cycle) {
+            if let Some((register, value)) = record.register_write {
+                registers[register as usize] = value;
+            }
+        }
+        Ok(Some(registers))
+    }
+
+    /// Iterates over matching records, decoding only chunks that can contain them.
+    pub fn records(&mut self, filter: TraceFilter) -> TraceRecords<'_> {
+        let chunks = self
+            .index
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| filter.accepts_chunk(entry))
+            .map(|(position, _)| position)
+            .collect::<Vec<_>>()
+            .into_iter();
+        TraceRecords {
+            reader: self,
+            filter,
+            chunks,
+            current: Vec::new().into_iter(),
+        }
+    }
+}
+
+pub struct TraceRecords<'a> {
+    reader: &'a mut TraceReader,
+    filter: TraceFilter,
+    chunks: std::vec::IntoIter<usize>,
+    current: std::vec::IntoIter<TraceRecord>,
+}
+
+impl Iterator for TraceRecords<'_> {
+    type Item = Result<TraceRecord, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            for record in self.current.by_ref() {
+                if self.filter.accepts(&record) {
+                    return Some(Ok(record));
+                }
+            }
+            let chunk = self.chunks.next()?;
+            match self.reader.read_chunk(chunk) {
+                Ok(decoded) => self.current = decoded.records.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+fn decode_chunk(raw: &[u8], entry: &ChunkIndexEntry) -> Result<TraceChunk, Box<dyn std::error::Error>> {
+    let mut cursor = Cursor { data: raw, position: 0 };
+    let mut expected_pc = cursor.u32()?;
+    let mut registers = [0u32; 32];
+    for register in registers.iter_mut() {
+        *register = cursor.u32()?;
+    }
+
+    let mut records = Vec::with_capacity(entry.cycles as usize);
+    let mut last_address = 0u32;
+    for i in 0..entry.cycles as u64 {
+        let flags = cursor.u8()?;
+        let word = cursor.u32()?;
+        let mut pc = expected_pc;
+        if flags & FLAG_PC_JUMP != 0 {
+            pc = (expected_pc as i64 + unzigzag(cursor.varint()?)) as u32;
+        }
+        let mut register_write = None;
+        if flags & FLAG_REGISTER_WRITE != 0 {
+            let register = cursor.u8()?;
+            if register >= 32 {
+                return Err(format!("corrupt trace: register x{} at cycle {}", register, entry.first_cycle + i).into());
+            }
+            register_write = Some((register, cursor.varint()? as u32));
+        }
+        let mut memory = Vec::new();
+        if flags & FLAG_MEMORY != 0 {
+            let count = cursor.varint()?;
+            for _ in 0..count {
+                let access_type = match cursor.u8()? {
+                    0 => MemoryAccessType::Read,
+                    _ => MemoryAccessType::Write,
+                };
+                let address = (last_address as i64 + unzigzag(cursor.varint()?)) as u32;
+                let value = cursor.varint()? as u32;
+                memory.push((access_type, address, value));
+                last_address = address;
+            }
+        }
+        let syscall = if flags & FLAG_SYSCALL != 0 {
+            Some(cursor.varint()? as u32)
+        } else {
+            None
+        };
+
+        records.push(TraceRecord {
+            cycle: entry.first_cycle + i,
+            pc,
+            word,
+            register_write,
+            memory,
+            syscall,
+        });
+        expected_pc = pc.wrapping_add(4);
+    }
+
+    Ok(TraceChunk { registers, records })
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Cursor<'_> {
+    fn u8(&mut self) -> Result<u8, Box<dyn std::error::Error>> {
+        let byte = *self.data.get(self.position).ok_or("corrupt trace: unexpected end of chunk")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + 4)
+            .ok_or("corrupt trace: unexpected end of chunk")?;
+        self.position += 4;
+        Ok(u32::from_le_bytes(bytes.try_into()?))
+    }
+
+    fn varint(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("corrupt trace: varint too long".into())
+    }
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{Emulator, Program, Segment, StopReason};
+
+    /// Counts a0 down from 5, storing and reloading it at 0x1000 on each pass,
+    /// then stores 0xffffffff at 0x1004 and halts.
+    const LOOP: [u32; 10] = [
+        0x0050_0513, // addi a0, zero, 5
+        0x0000_15b7, // lui a1, 0x1
+        0x00a5_a023, // sw a0, 0(a1)
+        0x0005_a603, // lw a2, 0(a1)
+        0xfff5_0513, // addi a0, a0, -1
+        0xfe05_1ae3, // bne a0, zero, -12
+        0xfff0_0693, // addi a3, zero, -1
+        0x00d5_a223, // sw a3, 4(a1)
+        0x0000_0293, // addi t0, zero, 0
+        0x0000_0073, // ecall
+    ];
+
+    fn run_loop() -> (Vec<StepEvent>, Vec<[u32; 32]>) {
+        let data: Vec<u8> = LOOP.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let program = Program {
+            entry: 0,
+            segments: vec![Segment { vaddr: 0, mem_size: data.len() as u32, data, flags: 5 }],
+            elf: Vec::new(),
+        };
+        let mut emulator = Emulator::new(&program, Vec::new());
+        let mut events = Vec::new();
+        let mut registers = Vec::new();
+        loop {
+            registers.push(emulator.registers);
+            match emulator.step() {
+                Ok(event) => events.push(event),
+                Err(trap) => panic!("{}", trap),
+            }
+            if emulator.is_halted() {
+                break;
+            }
+        }
+        assert_eq!(emulator.run(None, |_| Ok::<_, ()>(())), Ok(StopReason::Halted(0)));
+        (events, registers)
+    }
+
+    fn expected_record(event: &StepEvent) -> TraceRecord {
+        TraceRecord {
+            cycle: event.cycle,
+            pc: event.pc,
+            word: event.word,
+            register_write: event.register_write,
+            memory: event
+                .memory
+                .iter()
+                .map(|access| (access.access_type, access.address as u32, access.value as u32))
+                .collect(),
+            syscall: event.syscall.as_ref().map(|syscall| syscall.code),
+        }
+    }
+
+    #[test]
+    fn round_trip_across_chunks() {
+        let (events, registers) = run_loop();
+        let path = std::env::temp_dir().join(format!("pico-debugger-trace-{}.trace", std::process::id()));
+        let mut writer = TraceWriter::new(BufWriter::new(File::create(&path).unwrap()), 5, registers[0]).unwrap();
+        for event in &events {
+            writer.record(event).unwrap();
+        }
+        let summary = writer.finish().unwrap();
+        assert_eq!(summary.total_cycles, events.len() as u64);
+        assert_eq!(summary.chunks as usize, events.len().div_ceil(5));
+        assert_eq!(summary.bytes_written, std::fs::metadata(&path).unwrap().len());
+
+        let mut reader = TraceReader::open(&path).unwrap();
+        let records: Vec<TraceRecord> = reader.records(TraceFilter::default()).map(Result::unwrap).collect();
+        let expected: Vec<TraceRecord> = events.iter().map(expected_record).collect();
+        assert_eq!(records, expected);
+        // The full-width store survives the varint encoding
+        assert!(records.iter().any(|record| record.memory == [(MemoryAccessType::Write, 0x1004, u32::MAX)]));
+
+        for cycle in [0, 4, 5, 13, events.len() as u64 - 1] {
+            assert_eq!(reader.seek(cycle).unwrap(), Some(expected[cycle as usize].clone()));
+            assert_eq!(reader.registers_at(cycle).unwrap(), Some(registers[cycle as usize]));
+        }
+        assert_eq!(reader.seek(events.len() as u64).unwrap(), None);
+
+        let filter = TraceFilter { from_cycle: Some(3), to_cycle: Some(20), pc_range: Some((0x8, 0xc)) };
+        let stores: Vec<u64> = reader.records(filter).map(|record| record.unwrap().cycle).collect();
+        assert_eq!(stores, [6, 10, 14, 18]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn varints_and_zigzag_round_trip() {
+        let values = [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u32::MAX as u64, u64::MAX];
+        let mut buffer = Vec::new();
+        for value in values {
+            write_varint(&mut buffer, value);
+        }
+        let mut cursor = Cursor { data: &buffer, position: 0 };
+        for value in values {
+            assert_eq!(cursor.varint().unwrap(), value);
+        }
+        assert!(cursor.varint().is_err());
+        for delta in [0i64, 1, -1, 4, -4, i32::MAX as i64, -(u32::MAX as i64)] {
+            assert_eq!(unzigzag(zigzag(delta)), delta);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+// Full-screen terminal debugger
+// Disassembly, registers, memory, call stack and a command console on top of PicoDebugger
+
+use crate::commands::{self, CommandOutcome};
+use crate::emulator::StopReason;
+use crate::riscv::{Instruction, REGISTER_NAMES};
+use crate::PicoDebugger;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::collections::HashSet;
+use std::io;
+use std::time::Duration;
+
+/// Cycles executed between keyboard polls while continuing, so Esc can interrupt.
+const CONTINUE_SLICE_CYCLES: u64 = 200_000;
+const CONSOLE_HISTORY: usize = 500;
+const MEMORY_ROW_BYTES: u32 = 16;
+
+pub struct DebuggerUi {
+    debugger: PicoDebugger,
+    previous_registers: [u32; 32],
+    memory_address: Option<u32>,
+    console: Vec<String>,
+    input: String,
+    last_command: String,
+    quit: bool,
+}
+
+impl DebuggerUi {
+    pub fn new(debugger: PicoDebugger) -> Self {
+        Self {
+            debugger,
+            previous_registers: [0; 32],
+            memory_address: None,
+            console: vec!["Type 'help' for commands. F5 continue, F10 step over, F11 step, Shift+F11 finish, Esc interrupt.".to_string()],
+            input: String::new(),
+            last_command: String::new(),
+            quit: false,
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Ok(emulator) = self.debugger.emulator() {
+            self.previous_registers = emulator.registers;
+        }
+
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+        let result = self.event_loop(&mut terminal);
+
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        terminal.show_cursor()?;
+        result
+    }
+
+    fn event_loop(&mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<(), Box<dyn std::error::Error>> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_key(key, terminal)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_key(
+        &mut self,
+        key: KeyEvent,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match key.code {
+            KeyCode::F(5) => self.continue_interruptible(terminal)?,
+            KeyCode::F(10) => self.run_command("next"),
+            KeyCode::F(11) if key.modifiers.contains(KeyModifiers::SHIFT) => self.run_command("finish"),
+            KeyCode::F(11) => self.run_command("step"),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::PageUp => self.scroll_memory(-8),
+            KeyCode::PageDown => self.scroll_memory(8),
+            KeyCode::Enter => {
+                let line = std::mem::take(&mut self.input);
+                let line = if line.trim().is_empty() { self.last_command.clone() } else { line };
+                if line.trim().is_empty() {
+                    return Ok(());
+                }
+                self.last_command = line.clone();
+                let command = line.split_whitespace().next().unwrap_or_default();
+                if command == "continue" || command == "c" {
+                    self.log(format!("(pico-debug) {}", line));
+                    self.continue_interruptible(terminal)?;
+                } else {
+                    self.run_command(&line);
+                }
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(character) => self.input.push(character),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn run_command(&mut self, line: &str) {
+        self.log(format!("(pico-debug) {}", line));
+        let registers = self.current_registers();
+        match commands::execute(&mut self.debugger, line) {
+            Ok(CommandOutcome { lines, view_address, quit }) => {
+                for line in lines {
+                    self.log(line);
+                }
+                if view_address.is_some() {
+                    self.memory_address = view_address;
+                }
+                self.quit = quit;
+            }
+            Err(e) => self.log(format!("Error: {}", e)),
+        }
+        self.previous_registers = registers;
+    }
+
+    /// Continues in slices, redrawing and checking for Esc between them.
+    fn continue_interruptible(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let registers = self.current_registers();
+        let stop = loop {
+            match self.debugger.run_for(Some(CONTINUE_SLICE_CYCLES)) {
+                Ok(StopReason::CycleLimit) => {
+                    terminal.draw(|frame| self.draw(frame))?;
+                    if event::poll(Duration::ZERO)? {
+                        if let Event::Key(KeyEvent { code: KeyCode::Esc, .. }) = event::read()? {
+                            break StopReason::Stepped;
+                        }
+                    }
+                }
+                Ok(stop) => break stop,
+                Err(e) => {
+                    self.log(format!("Error: {}", e));
+                    return Ok(());
+                }
+            }
+        };
+        match commands::report_stop(&mut self.debugger, stop) {
+            Ok(outcome) => {
+                for line in outcome.lines {
+                    self.log(line);
+                }
+            }
+            Err(e) => self.log(format!("Error: {}", e)),
+        }
+        self.previous_registers = registers;
+        Ok(())
+    }
+
+    fn current_registers(&self) -> [u32; 32] {
+        self.debugger
+            .emulator()
+            .map(|emulator| emulator.registers)
+            .unwrap_or([0; 32])
+    }
+
+    fn scroll_memory(&mut self, rows: i32) {
+        let base = self.memory_base();
+        self.memory_address = Some(base.wrapping_add_signed(rows * MEMORY_ROW_BYTES as i32));
+    }
+
+    fn memory_base(&self) -> u32 {
+        self.memory_address
+            .or_else(|| self.debugger.emulator().ok().map(|emulator| emulator.registers[2]))
+            .unwrap_or(0)
+            & !(MEMORY_ROW_BYTES - 1)
+    }
+
+    fn log(&mut self, line: String) {
+        self.console.push(line);
+        if self.console.len() > CONSOLE_HISTORY {
+            self.console.drain(..self.console.len() - CONSOLE_HISTORY);
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(25), Constraint::Min(6)])
+            .split(frame.size());
+        let top = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(rows[0]);
+        let middle = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(rows[1]);
+
+        self.draw_disassembly(frame, top[0]);
+        self.draw_registers(frame, top[1]);
+        self.draw_memory(frame, middle[0]);
+        self.draw_call_stack(frame, middle[1]);
+        self.draw_console(frame, rows[2]);
+    }
+
+    fn draw_disassembly(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        if let Ok(emulator) = self.debugger.emulator() {
+            let breakpoints: HashSet<u64> = self.debugger.breakpoints().iter().map(|breakpoint| breakpoint.address).collect();
+            let visible = area.height.saturating_sub(2) as u32;
+            let start = emulator.pc.saturating_sub(visible / 3 * 4);
+            for i in 0..visible {
+                let address = start.wrapping_add(i * 4);
+                let instruction = Instruction::decode(emulator.memory.read_u32(address));
+                let marker = if breakpoints.contains(&(address as u64)) { "●" } else { " " };
+                let text = format!("{} 0x{:08x}  {}", marker, address, instruction.disassemble(address));
+                let style = if address == emulator.pc {
+                    Style::default().fg(Color::Black).bg(Color::Yellow)
+                } else if marker != " " {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default()
+                };
+                lines.push(Line::from(Span::styled(text, style)));
+            }
+        }
+        let title = match self.debugger.emulator() {
+            Ok(emulator) if emulator.is_halted() => {
+                format!(" Disassembly (halted, exit code {}) ", emulator.exit_code().unwrap_or(0))
+            }
+            Ok(emulator) => {
+                let debug_info = self.debugger.debug_info();
+                let function = debug_info.and_then(|info| info.function_at(emulator.pc));
+                match (function, debug_info.and_then(|info| info.location(emulator.pc))) {
+                    (Some(function), Some(location)) => {
+                        format!(" Disassembly (cycle {}) {} at {} ", emulator.cycle, function, location)
+                    }
+                    (Some(function), None) => format!(" Disassembly (cycle {}) {} ", emulator.cycle, function),
+                    _ => format!(" Disassembly (cycle {}) ", emulator.cycle),
+                }
+            }
+            Err(_) => " Disassembly ".to_string(),
+        };
+        frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)), area);
+    }
+
+    fn draw_registers(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        if let Ok(emulator) = self.debugger.emulator() {
+            lines.push(Line::from(format!("pc    0x{:08x}", emulator.pc)));
+            for row in 0..16 {
+                let mut spans = Vec::new();
+                for index in [row, row + 16] {
+                    let value = emulator.registers[index];
+                    let style = if value != self.previous_registers[index] {
+                        Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default()
+                    };
+                    spans.push(Span::styled(format!("{:<5} 0x{:08x}   ", REGISTER_NAMES[index], value), style));
+                }
+                lines.push(Line::from(spans));
+            }
+        }
+        frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Registers ")), area);
+    }
+
+    fn draw_memory(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        let base = self.memory_base();
+        if let Ok(emulator) = self.debugger.emulator() {
+            for row in 0..area.height.saturating_sub(2) as u32 {
+                let address = base.wrapping_add(row * MEMORY_ROW_BYTES);
+                let bytes = emulator.memory.read_bytes(address, MEMORY_ROW_BYTES as usize);
+                let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                let ascii: String = bytes
+                    .iter()
+                    .map(|&byte| if byte.is_ascii_graphic() { byte as char } else { '.' })
+                    .collect();
+                lines.push(Line::from(format!("0x{:08x}  {}  {}", address, hex.join(" "), ascii)));
+            }
+        }
+        let title = format!(" Memory @ 0x{:08x} (PgUp/PgDn) ", base);
+        frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)), area);
+    }
+
+    fn draw_call_stack(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        if let Ok(frames) = self.debugger.backtrace() {
+            let selected = self.debugger.selected_frame();
+            for (index, call_frame) in frames.iter().enumerate() {
+                let text = format!("#{:<2} 0x{:08x} {}", index, call_frame.pc, call_frame.function_name);
+                let style = if index == selected {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                lines.push(Line::from(Span::styled(text, style)));
+            }
+        }
+        frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Call Stack ")), area);
+    }
+
+    fn draw_console(&self, frame: &mut Frame, area: Rect) {
+        let visible = area.height.saturating_sub(3) as usize;
+        let start = self.console.len().saturating_sub(visible);
+        let mut lines: Vec<Line> = self.console[start..].iter().map(|line| Line::from(line.as_str())).collect();
+        lines.push(Line::from(Span::styled(
+            format!("(pico-debug) {}", self.input),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Console ")), area);
+        let cursor_x = area.x + 1 + "(pico-debug) ".len() as u16 + self.input.len() as u16;
+        let cursor_y = area.y + 1 + (self.console.len() - start) as u16;
+        frame.set_cursor(cursor_x.min(area.right().saturating_sub(2)), cursor_y.min(area.bottom().saturating_sub(2)));
+    }
+}
@@ -0,0 +1,62 @@
+# Guest used by the debugger's unit tests. Assembled and linked with:
+#   llvm-mc -triple=riscv32 -mattr=+m -filetype=obj -g -fdebug-compilation-dir=. tests/fixtures/frames.s -o frames.o
+#   rust-lld -flavor gnu -Ttext=0x200800 -e _start frames.o -o tests/fixtures/frames.elf
+# _start calls outer -> inner -> leaf, then counts s1 down from 1000000 and halts.
+# outer and inner save ra (and inner s0) on the stack and describe it with CFI.
+
+    .text
+    .globl _start
+    .type _start,@function
+_start:
+    li s0, 5
+    call outer
+    li s1, 1000000
+.Lspin:
+    addi s1, s1, -1
+    bne s1, zero, .Lspin
+    li a0, 0
+    li t0, 0
+    ecall
+    .size _start, .-_start
+
+    .type outer,@function
+outer:
+    .cfi_startproc
+    addi sp, sp, -16
+    .cfi_def_cfa_offset 16
+    sw ra, 12(sp)
+    .cfi_offset ra, -4
+    call inner
+    lw ra, 12(sp)
+    addi sp, sp, 16
+    .cfi_def_cfa_offset 0
+    ret
+    .cfi_endproc
+    .size outer, .-outer
+
+    .type inner,@function
+inner:
+    .cfi_startproc
+    addi sp, sp, -16
+    .cfi_def_cfa_offset 16
+    sw ra, 12(sp)
+    sw s0, 8(sp)
+    .cfi_offset ra, -4
+    .cfi_offset s0, -8
+    li s0, 9
+    call leaf
+    lw s0, 8(sp)
+    lw ra, 12(sp)
+    addi sp, sp, 16
+    .cfi_def_cfa_offset 0
+    ret
+    .cfi_endproc
+    .size inner, .-inner
+
+    .type leaf,@function
+leaf:
+    .cfi_startproc
+    addi a0, a0, 1
+    ret
+    .cfi_endproc
+    .size leaf, .-leaf
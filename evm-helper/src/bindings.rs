@@ -0,0 +1,199 @@
+// Rust contract bindings
+// Generates typed ethers bindings for verifier and application contracts, plus helpers that take a Pico proof
+
+use crate::proof::{entry_point, VERIFY_PICO_PROOF};
+use crate::EVMContract;
+use ethers::abi::{Abi, Function, ParamType};
+use ethers::contract::Abigen;
+
+/// Header of the generated file, which is meant to be its own module (`mod bindings;`).
+/// The helpers need `ethers` 2 and this crate as dependencies.
+const HEADER: &str = "// Generated by `pico-evm bindings`. Do not edit.\n// Requires the `ethers` (2.x) and `pico-evm-helper` crates.\n#![allow(clippy::all)]\n";
+
+/// Bindings for each contract, in order, in one Rust file, with a warning for each proof helper that was left out.
+pub fn generate_bindings(contracts: &[EVMContract]) -> Result<(String, Vec<String>), Box<dyn std::error::Error>> {
+    let mut code = HEADER.to_string();
+    let mut warnings = Vec::new();
+    for contract in contracts {
+        let (bindings, warning) = contract_bindings(contract)?;
+        code.push('\n');
+        code.push_str(&bindings);
+        warnings.extend(warning);
+    }
+    Ok((code, warnings))
+}
+
+/// abigen's bindings for one contract, followed by its Pico proof helper.
+/// The warning says why a contract exposing `verifyPicoProof` got no helper.
+pub fn contract_bindings(contract: &EVMContract) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
+    let abi: serde_json::Value = serde_json::from_str(&contract.abi).map_err(|e| format!("{} has an invalid ABI: {}", contract.name, e))?;
+    // With bytecode present abigen also emits the bytecode constant and a `deploy` constructor
+    let mut source = serde_json::json!({ "abi": abi });
+    if !contract.bytecode.is_empty() {
+        source["bytecode"] = serde_json::Value::String(format!("0x{}", contract.bytecode));
+    }
+    let bindings = Abigen::new(&contract.name, source.to_string())
+        .and_then(|abigen| abigen.generate())
+        .map_err(|e| format!("abigen failed for {}: {}", contract.name, e))?;
+    let mut code = bindings.to_string();
+    let (helper, warning) = proof_helper(contract)?;
+    code.push_str(&helper);
+    Ok((code, warning))
+}
+
+/// A `verify_pico_evm_proof` method calling the contract's `verifyPicoProof` with a `PicoEvmProof`.
+/// With both overloads present the verifier's is used, as `proof::entry_point` picks it.
+/// Empty for contracts without the entry point, and with a warning when the return type is unsupported.
+fn proof_helper(contract: &EVMContract) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
+    let abi: Abi = serde_json::from_str(&contract.abi)?;
+    let Some(signature) = entry_point(&abi) else {
+        return Ok((String::new(), None));
+    };
+    let selector = ethers::utils::id(signature);
+    let function = abi
+        .functions_by_name("verifyPicoProof")?
+        .iter()
+        .find(|function| function.short_signature() == selector)
+        .ok_or("entry point missing from the ABI")?;
+    let (doc, args) = if signature == VERIFY_PICO_PROOF {
+        // The verifier: proof, public values and the program's vkey hash
+        (
+            "Verifies a Pico EVM proof against the vkey hash it carries. Reverts with `ProofInvalid()` if it does not verify.",
+            "(::ethers::types::Bytes::from(proof.proof_bytes()), proof.public_values.clone(), proof.riscv_vkey.0)",
+        )
+    } else {
+        // The application contract, which holds the vkey hash itself
+        (
+            "Submits a Pico EVM proof and its public values.",
+            "(::ethers::types::Bytes::from(proof.proof_bytes()), proof.public_values.clone())",
+        )
+    };
+    let Some(output) = output_type(function) else {
+        let warning = format!(
+            "{}.{} returns a type the proof helper does not support; no verify_pico_evm_proof was generated",
+            contract.name, signature
+        );
+        return Ok((String::new(), Some(warning)));
+    };
+    // Called by selector, as abigen's own methods are, since abigen renames overloads
+    let selector = selector.iter().map(|byte| format!("0x{:02x}", byte)).collect::<Vec<_>>().join(", ");
+    let helper = format!(
+        "\nimpl<M: ::ethers::providers::Middleware> {name}<M> {{\n    /// {doc}\n    pub fn verify_pico_evm_proof(\n        &self,\n        proof: &::pico_evm_helper::proof::PicoEvmProof,\n    ) -> ::ethers::contract::builders::ContractCall<M, {output}> {{\n        self.method_hash([{selector}], {args})\n            .expect(\"{signature} is in the ABI\")\n    }}\n}}\n",
+        name = contract.name,
+    );
+    Ok((helper, None))
+}
+
+/// The Rust type abigen gives the function's return value, for the shapes generated contracts use.
+fn output_type(function: &Function) -> Option<String> {
+    match function.outputs.as_slice() {
+        [] => Some("()".to_string()),
+        [output] => match &output.kind {
+            ParamType::Bool => Some("bool".to_string()),
+            // abigen names structs after the Solidity struct, e.g. `struct Lib.Output` becomes `Output`
+            ParamType::Tuple(_) => {
+                let internal = output.internal_type.as_deref()?.strip_prefix("struct ")?;
+                Some(internal.rsplit('.').next()?.to_string())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof::APP_VERIFY_PICO_PROOF;
+
+    fn verify_function(extra: &str, outputs: &str) -> String {
+        format!(
+            r#"{{"type":"function","name":"verifyPicoProof","stateMutability":"view","inputs":[{{"name":"proof","type":"bytes"}},{{"name":"publicValues","type":"bytes"}}{}],"outputs":[{}]}}"#,
+            extra, outputs
+        )
+    }
+
+    fn contract(functions: &[String]) -> EVMContract {
+        EVMContract {
+            name: "PicoVerifier".to_string(),
+            address: None,
+            abi: format!("[{}]", functions.join(",")),
+            bytecode: String::new(),
+            constructor_args: Vec::new(),
+        }
+    }
+
+    const VKEY: &str = r#",{"name":"riscvVkey","type":"bytes32"}"#;
+    const BOOL: &str = r#"{"name":"","type":"bool"}"#;
+
+    /// The `verify_pico_evm_proof` methods in the generated file, checked to parse as Rust.
+    fn helpers(code: &str) -> Vec<&str> {
+        let file = syn::parse_file(code).expect("bindings parse as Rust");
+        let parsed = file
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Impl(block) => Some(&block.items),
+                _ => None,
+            })
+            .flatten()
+            .filter(|item| matches!(item, syn::ImplItem::Fn(method) if method.sig.ident == "verify_pico_evm_proof"))
+            .count();
+        let helpers: Vec<&str> = code
+            .match_indices("pub fn verify_pico_evm_proof")
+            .map(|(start, _)| &code[start..start + code[start..].find(".expect(").unwrap()])
+            .collect();
+        assert_eq!(helpers.len(), parsed);
+        helpers
+    }
+
+    fn method_hash(signature: &str) -> String {
+        let bytes: Vec<String> = ethers::utils::id(signature).iter().map(|byte| format!("0x{:02x}", byte)).collect();
+        format!("self.method_hash([{}]", bytes.join(", "))
+    }
+
+    #[test]
+    fn both_overloads_get_one_helper_for_the_verifier_entry_point() {
+        let contract = contract(&[verify_function("", BOOL), verify_function(VKEY, "")]);
+        let (code, warnings) = generate_bindings(&[contract]).unwrap();
+        assert!(warnings.is_empty());
+        let helpers = helpers(&code);
+        assert_eq!(helpers.len(), 1);
+        assert!(helpers[0].contains(&method_hash(VERIFY_PICO_PROOF)));
+        assert!(helpers[0].contains("proof.riscv_vkey.0"));
+        assert!(helpers[0].contains("ContractCall<M, ()>"));
+        // abigen still emits a method for each overload, under names the helper does not rely on
+        assert_eq!(code.matches("Calls the contract's `verifyPicoProof`").count(), 2);
+    }
+
+    #[test]
+    fn application_contracts_get_a_helper_without_the_vkey() {
+        let (code, warnings) = contract_bindings(&contract(&[verify_function("", BOOL)])).unwrap();
+        assert_eq!(warnings, None);
+        let helpers = helpers(&code);
+        assert_eq!(helpers.len(), 1);
+        assert!(helpers[0].contains(&method_hash(APP_VERIFY_PICO_PROOF)));
+        assert!(!helpers[0].contains("riscv_vkey"));
+        assert!(helpers[0].contains("ContractCall<M, bool>"));
+    }
+
+    #[test]
+    fn unsupported_return_types_are_reported_not_generated() {
+        let uint = r#"{"name":"","type":"uint256"}"#;
+        let contract = contract(&[verify_function(VKEY, uint)]);
+        let (code, warnings) = generate_bindings(&[contract]).unwrap();
+        assert!(helpers(&code).is_empty());
+        assert_eq!(
+            warnings,
+            vec!["PicoVerifier.verifyPicoProof(bytes,bytes,bytes32) returns a type the proof helper does not support; no verify_pico_evm_proof was generated"]
+        );
+    }
+
+    #[test]
+    fn contracts_without_the_entry_point_get_no_helper() {
+        let other = r#"{"type":"function","name":"owner","stateMutability":"view","inputs":[],"outputs":[{"name":"","type":"address"}]}"#;
+        let (code, warnings) = contract_bindings(&contract(&[other.to_string()])).unwrap();
+        assert_eq!(warnings, None);
+        assert!(!code.contains("verify_pico_evm_proof"));
+    }
+}
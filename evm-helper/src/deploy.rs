@@ -0,0 +1,257 @@
+// Contract deployment
+// Deploys compiled contracts through a JSON-RPC node and records each deployment in a manifest file
+
+use crate::{EVMContract, EVMDeployment};
+use ethers::abi::token::{LenientTokenizer, Tokenizer};
+use ethers::abi::{Abi, ParamType, Token};
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, TransactionRequest, U256, U64};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// Environment variables read when the command line and config file leave a value unset.
+pub const RPC_URL_ENV: &str = "PICO_EVM_RPC_URL";
+pub const PRIVATE_KEY_ENV: &str = "PICO_EVM_PRIVATE_KEY";
+/// A local dev node such as anvil or `hardhat node`.
+pub const DEFAULT_RPC_URL: &str = "http://127.0.0.1:8545";
+
+/// How often pending transactions are polled; dev nodes mine instantly, so this is kept short.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Deployment settings, from a TOML file and the environment.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeployConfig {
+    pub rpc_url: Option<String>,
+    /// Hex private key of the deployer. Prefer `PICO_EVM_PRIVATE_KEY` over writing it to a file.
+    pub private_key: Option<String>,
+    /// Blocks to wait for on top of the one including the deployment (default 1, the inclusion itself).
+    pub confirmations: Option<usize>,
+}
+
+impl DeployConfig {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("failed to read config {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    /// Overrides values with `PICO_EVM_RPC_URL` and `PICO_EVM_PRIVATE_KEY` where those are set.
+    pub fn with_env(mut self) -> Self {
+        if let Ok(rpc_url) = std::env::var(RPC_URL_ENV) {
+            self.rpc_url = Some(rpc_url);
+        }
+        if let Ok(private_key) = std::env::var(PRIVATE_KEY_ENV) {
+            self.private_key = Some(private_key);
+        }
+        self
+    }
+
+    pub fn rpc_url(&self) -> &str {
+        self.rpc_url.as_deref().unwrap_or(DEFAULT_RPC_URL)
+    }
+}
+
+/// A signer connected to a node. Calls block until the node answers.
+pub struct Deployer {
+    client: SignerMiddleware<Provider<Http>, LocalWallet>,
+    runtime: tokio::runtime::Runtime,
+    confirmations: usize,
+}
+
+impl Deployer {
+    pub fn connect(config: &DeployConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let private_key = config
+            .private_key
+            .as_deref()
+            .ok_or_else(|| format!("no deployer key: set {} or private_key in the config file", PRIVATE_KEY_ENV))?;
+        let wallet: LocalWallet = private_key.trim().parse().map_err(|e| format!("invalid private key: {}", e))?;
+        let provider = Provider::<Http>::try_from(config.rpc_url())?.interval(POLL_INTERVAL);
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        // Transactions are signed for the node's chain (EIP-155)
+        let chain_id = runtime
+            .block_on(provider.get_chainid())
+            .map_err(|e| format!("cannot reach {}: {}", config.rpc_url(), e))?;
+        let client = SignerMiddleware::new(provider, wallet.with_chain_id(chain_id.as_u64()));
+        Ok(Self { client, runtime, confirmations: config.confirmations.unwrap_or(1) })
+    }
+
+    pub fn address(&self) -> Address {
+        self.client.address()
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.client.signer().chain_id()
+    }
+
+    /// Sends `bytecode` followed by ABI-encoded `constructor_args` and waits for the receipt.
+    pub fn deploy(&self, contract: &EVMContract, constructor_args: &[u8]) -> Result<EVMDeployment, Box<dyn std::error::Error>> {
+        if contract.bytecode.is_empty() {
+            return Err(format!("{} has no bytecode (is it an interface or abstract contract?)", contract.name).into());
+        }
+        let mut initcode = hex::decode(&contract.bytecode)?;
+        initcode.extend_from_slice(constructor_args);
+        // No recipient makes this a contract creation; nonce, gas and gas price are filled in by the node
+        let transaction = TransactionRequest::new().data(initcode);
+        self.runtime.block_on(async {
+            let pending = self
+                .client
+                .send_transaction(transaction, None)
+                .await
+                .map_err(|e| format!("deploying {} failed: {}", contract.name, e))?;
+            let transaction_hash = pending.tx_hash();
+            let receipt = pending
+                .confirmations(self.confirmations)
+                .await?
+                .ok_or_else(|| format!("deployment transaction {:?} was dropped", transaction_hash))?;
+            if receipt.status != Some(U64::from(1)) {
+                return Err(format!("deploying {} reverted in transaction {:?}", contract.name, transaction_hash).into());
+            }
+            let address = receipt.contract_address.ok_or("receipt has no contract address")?;
+            Ok(EVMDeployment {
+                contract_name: contract.name.clone(),
+                chain_id: self.chain_id(),
+                deployed_address: ethers::utils::to_checksum(&address, None),
+                transaction_hash: format!("{:?}", transaction_hash),
+                gas_used: receipt.gas_used.unwrap_or_default().as_u64(),
+                block_number: receipt.block_number.unwrap_or_default().as_u64(),
+            })
+        })
+    }
+}
+
+/// Parses `args` with the types of the contract's constructor and ABI-encodes them.
+/// Values are written as for `cast`: `0x…` addresses and bytes, decimal or hex integers, `true`/`false`.
+pub fn encode_constructor_args(contract: &EVMContract, args: &[String]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let abi: Abi = serde_json::from_str(&contract.abi).map_err(|e| format!("{} has an invalid ABI: {}", contract.name, e))?;
+    let inputs = abi.constructor.map(|constructor| constructor.inputs).unwrap_or_default();
+    if inputs.len() != args.len() {
+        let expected: Vec<String> = inputs.iter().map(|input| format!("{} {}", input.kind, input.name)).collect();
+        return Err(format!("{} takes {} constructor arguments ({}), got {}", contract.name, inputs.len(), expected.join(", "), args.len()).into());
+    }
+    let tokens = inputs
+        .iter()
+        .zip(args)
+        .map(|(input, arg)| tokenize(&input.kind, arg).map_err(|e| format!("argument {} ({}): {}", input.name, input.kind, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ethers::abi::encode(&tokens))
+}
+
+/// `LenientTokenizer` only takes unsigned integers as decimal or as 64 hex digits, so `0x…` is parsed here.
+fn tokenize(kind: &ParamType, arg: &str) -> Result<Token, Box<dyn std::error::Error>> {
+    match (kind, arg.trim().strip_prefix("0x")) {
+        (ParamType::Uint(_), Some(digits)) => Ok(Token::Uint(U256::from_str_radix(digits, 16)?)),
+        _ => Ok(LenientTokenizer::tokenize(kind, arg)?),
+    }
+}
+
+/// Deployments recorded in `path`; a missing file holds none.
+pub fn load_deployments(path: &Path) -> Result<Vec<EVMDeployment>, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(path)?;
+    serde_json::from_str(&text).map_err(|e| format!("{} is not a deployments manifest: {}", path.display(), e).into())
+}
+
+/// Appends `deployment` to the manifest at `path`.
+pub fn record_deployment(path: &Path, deployment: &EVMDeployment) -> Result<(), Box<dyn std::error::Error>> {
+    let mut deployments = load_deployments(path)?;
+    deployments.push(deployment.clone());
+    std::fs::write(path, serde_json::to_string_pretty(&deployments)? + "\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runtime code that returns the word 42.
+    const RUNTIME: [u8; 10] = [0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+
+    /// A contract whose constructor takes a `uint256` it ignores and deploys `RUNTIME`.
+    fn answer_contract() -> EVMContract {
+        // PUSH1 len PUSH1 12 PUSH1 0 CODECOPY PUSH1 len PUSH1 0 RETURN, then the runtime code
+        let length = RUNTIME.len() as u8;
+        let mut initcode = vec![0x60, length, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, length, 0x60, 0x00, 0xf3];
+        initcode.extend_from_slice(&RUNTIME);
+        EVMContract {
+            name: "Answer".to_string(),
+            address: None,
+            abi: r#"[{"type":"constructor","stateMutability":"nonpayable","inputs":[{"name":"seed","type":"uint256"}]}]"#.to_string(),
+            bytecode: hex::encode(initcode),
+            constructor_args: Vec::new(),
+        }
+    }
+
+    fn scratch_manifest(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("pico-evm-{}-{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn constructor_args_follow_the_abi() {
+        let contract = answer_contract();
+        let encoded = encode_constructor_args(&contract, &["0x10".to_string()]).unwrap();
+        assert_eq!(encoded, ethers::abi::encode(&[ethers::abi::Token::Uint(16.into())]));
+        let error = encode_constructor_args(&contract, &[]).unwrap_err().to_string();
+        assert_eq!(error, "Answer takes 1 constructor arguments (uint256 seed), got 0");
+        assert!(encode_constructor_args(&contract, &["not a number".to_string()]).is_err());
+    }
+
+    #[test]
+    fn manifest_appends_deployments() {
+        let manifest = scratch_manifest("manifest");
+        assert!(load_deployments(&manifest).unwrap().is_empty());
+        for (index, name) in ["PicoVerifier", "App"].into_iter().enumerate() {
+            let deployment = EVMDeployment {
+                contract_name: name.to_string(),
+                chain_id: 31337,
+                deployed_address: format!("0x{:040x}", index + 1),
+                transaction_hash: format!("0x{:064x}", index + 1),
+                gas_used: 21000,
+                block_number: index as u64 + 1,
+            };
+            record_deployment(&manifest, &deployment).unwrap();
+        }
+        let names: Vec<String> = load_deployments(&manifest).unwrap().into_iter().map(|deployment| deployment.contract_name).collect();
+        assert_eq!(names, ["PicoVerifier", "App"]);
+        std::fs::remove_file(manifest).unwrap();
+    }
+
+    /// Run with `anvil` listening (or `PICO_EVM_RPC_URL` pointing at another dev node that funds
+    /// anvil's first account): `cargo test -- --ignored`.
+    #[test]
+    #[ignore = "needs a dev node such as anvil on 127.0.0.1:8545"]
+    fn deploys_to_a_dev_node_and_records_the_manifest() {
+        let config = DeployConfig {
+            // anvil's first prefunded account
+            private_key: Some("0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80".to_string()),
+            ..DeployConfig::default()
+        }
+        .with_env();
+        let deployer = Deployer::connect(&config).unwrap();
+        let contract = answer_contract();
+        let args = encode_constructor_args(&contract, &["7".to_string()]).unwrap();
+        let deployment = deployer.deploy(&contract, &args).unwrap();
+        assert_eq!(deployment.contract_name, "Answer");
+        assert_eq!(deployment.chain_id, deployer.chain_id());
+        assert!(deployment.gas_used > 0);
+
+        let address: Address = deployment.deployed_address.parse().unwrap();
+        let code = deployer.runtime.block_on(deployer.client.get_code(address, None)).unwrap();
+        assert_eq!(code.to_vec(), RUNTIME);
+
+        let manifest = scratch_manifest("deployments");
+        record_deployment(&manifest, &deployment).unwrap();
+        let recorded = load_deployments(&manifest).unwrap();
+        std::fs::remove_file(manifest).unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].deployed_address, deployment.deployed_address);
+        assert_eq!(recorded[0].transaction_hash, deployment.transaction_hash);
+        assert_eq!(recorded[0].block_number, deployment.block_number);
+    }
+}
@@ -0,0 +1,210 @@
+// Foundry project scaffolding
+// Lays out a Forge project around the generated verifier and application contracts, with a proof fixture for tests
+
+use crate::proof::PicoEvmProof;
+use ethers::types::H256;
+use std::path::{Path, PathBuf};
+
+/// Where the proof fixture lives, relative to the project root. `foundry.toml` grants tests read access to it.
+pub const FIXTURE_PATH: &str = "test/fixtures/proof-fixture.json";
+
+/// A generated file, relative to the project root.
+pub struct ProjectFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+impl ProjectFile {
+    pub fn new(path: impl Into<PathBuf>, contents: String) -> Self {
+        Self { path: path.into(), contents }
+    }
+}
+
+pub fn foundry_toml() -> String {
+    r#"# Generated by `pico-evm foundry-init`
+[profile.default]
+src = "src"
+out = "out"
+libs = ["lib"]
+test = "test"
+script = "script"
+optimizer = true
+optimizer_runs = 200
+remappings = ["forge-std/=lib/forge-std/src/"]
+# Tests read the proof fixture
+fs_permissions = [{ access = "read", path = "./test/fixtures" }]
+"#
+    .to_string()
+}
+
+pub fn gitignore() -> String {
+    "out/\ncache/\nbroadcast/\nlib/\n".to_string()
+}
+
+/// The proof as the tests read it. It is also a valid `proof.json` for the other commands.
+pub fn fixture_json(proof: &PicoEvmProof) -> Result<String, Box<dyn std::error::Error>> {
+    let fixture = serde_json::json!({
+        "riscvVKey": format!("{:?}", proof.riscv_vkey),
+        "publicValues": format!("0x{}", hex::encode(&proof.public_values)),
+        "proof": format!("0x{}", hex::encode(proof.proof_bytes())),
+        "publicValuesDigest": format!("{:?}", proof.public_values_digest()),
+    });
+    Ok(serde_json::to_string_pretty(&fixture)? + "\n")
+}
+
+/// A Forge script deploying the verifier and then the application contract pointed at it.
+pub fn deploy_script(verifier_name: &str, app_name: &str, riscv_vkey: H256) -> String {
+    format!(
+        r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import {{Script, console}} from "forge-std/Script.sol";
+import {{{verifier}}} from "../src/{verifier}.sol";
+import {{{app}}} from "../src/{app}.sol";
+
+/// Deploys {verifier} and {app}:
+/// `forge script script/Deploy.s.sol --rpc-url $RPC_URL --private-key $PRIVATE_KEY --broadcast`
+contract DeployScript is Script {{
+    /// vkey hash of the program the fixture proof is for; set RISCV_VKEY to deploy for another build.
+    bytes32 constant DEFAULT_RISCV_VKEY = {vkey:?};
+
+    function run() external returns ({verifier} verifier, {app} app) {{
+        bytes32 riscvVkey = vm.envOr("RISCV_VKEY", DEFAULT_RISCV_VKEY);
+
+        vm.startBroadcast();
+        verifier = new {verifier}();
+        app = new {app}(address(verifier), riscvVkey);
+        vm.stopBroadcast();
+
+        console.log("{verifier} deployed to:", address(verifier));
+        console.log("{app} deployed to:", address(app));
+    }}
+}}
+"#,
+        verifier = verifier_name,
+        app = app_name,
+        vkey = riscv_vkey,
+    )
+}
+
+/// Writes `files` under `root`, creating directories. Existing files are only replaced with `force`,
+/// and nothing is written if any would be refused.
+pub fn write_project(root: &Path, files: &[ProjectFile], force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if !force {
+        let existing: Vec<String> = files.iter().filter(|file| root.join(&file.path).exists()).map(|file| file.path.display().to_string()).collect();
+        if !existing.is_empty() {
+            return Err(format!("{} already has {}; pass --force to overwrite", root.display(), existing.join(", ")).into());
+        }
+    }
+    for file in files {
+        let path = root.join(&file.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, &file.contents)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verifier::{ProofSystem, VerifyingKey};
+    use crate::EVMIntegrationHelper;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    fn fixture_proof() -> PicoEvmProof {
+        PicoEvmProof::load(&fixture("groth16_proof.json")).unwrap()
+    }
+
+    fn project_files() -> Vec<ProjectFile> {
+        let key = VerifyingKey::load(&fixture("groth16_vk.bin"), ProofSystem::Groth16).unwrap();
+        EVMIntegrationHelper::new()
+            .create_foundry_project(&key, "groth16_vk.bin", &fixture_proof(), "FibonacciVerifier", "PicoVerifier", None)
+            .unwrap()
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("pico-evm-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn fixture_holds_the_proof_as_the_tests_read_it() {
+        let proof = fixture_proof();
+        let json = fixture_json(&proof).unwrap();
+        let fixture: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let mut keys: Vec<&str> = fixture.as_object().unwrap().keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["proof", "publicValues", "publicValuesDigest", "riscvVKey"]);
+        assert_eq!(fixture["riscvVKey"], "0x00a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f");
+        assert_eq!(fixture["publicValues"], "0x0000000a00000000000000370000000000000059");
+        // Eight words, as `bytes proof`
+        assert_eq!(fixture["proof"].as_str().unwrap().len(), 2 + 2 * 256);
+        assert_eq!(fixture["publicValuesDigest"], format!("{:?}", proof.public_values_digest()));
+
+        // It also loads back as a `proof.json`
+        let path = std::env::temp_dir().join(format!("pico-evm-fixture-{}.json", std::process::id()));
+        std::fs::write(&path, &json).unwrap();
+        assert_eq!(PicoEvmProof::load(&path).unwrap(), proof);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn project_lays_out_a_forge_project() {
+        let files = project_files();
+        let paths: Vec<String> = files.iter().map(|file| file.path.display().to_string()).collect();
+        assert_eq!(
+            paths,
+            [
+                "foundry.toml",
+                ".gitignore",
+                "src/PicoVerifier.sol",
+                "src/FibonacciVerifier.sol",
+                "script/Deploy.s.sol",
+                "test/FibonacciVerifier.t.sol",
+                FIXTURE_PATH,
+            ]
+        );
+        let contents = |path: &str| &files.iter().find(|file| file.path == Path::new(path)).unwrap().contents;
+        assert!(contents("src/PicoVerifier.sol").contains("contract PicoVerifier"));
+        assert!(contents("src/FibonacciVerifier.sol").contains("contract FibonacciVerifier"));
+        assert!(contents("test/FibonacciVerifier.t.sol").contains(FIXTURE_PATH));
+        assert!(contents("script/Deploy.s.sol").contains(&format!("{:?}", fixture_proof().riscv_vkey)));
+        assert_eq!(contents(FIXTURE_PATH), &fixture_json(&fixture_proof()).unwrap());
+    }
+
+    #[test]
+    fn existing_files_are_only_replaced_with_force() {
+        let root = scratch_dir("foundry");
+        let files = project_files();
+        write_project(&root, &files, false).unwrap();
+        for file in &files {
+            assert_eq!(std::fs::read_to_string(root.join(&file.path)).unwrap(), file.contents);
+        }
+
+        // One clash refuses the whole project and writes nothing
+        std::fs::remove_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("foundry.toml"), "# edited\n").unwrap();
+        let error = write_project(&root, &files, false).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!(
+                "{} already has foundry.toml, .gitignore, script/Deploy.s.sol, test/FibonacciVerifier.t.sol, {}; pass --force to overwrite",
+                root.display(),
+                FIXTURE_PATH
+            )
+        );
+        assert_eq!(std::fs::read_to_string(root.join("foundry.toml")).unwrap(), "# edited\n");
+        assert!(!root.join("src").exists());
+
+        write_project(&root, &files, true).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("foundry.toml")).unwrap(), foundry_toml());
+        assert!(root.join("src/PicoVerifier.sol").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
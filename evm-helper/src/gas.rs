@@ -0,0 +1,198 @@
+// Verification gas measurement
+// Deploys contracts into an in-memory EVM and measures the gas real calls use; no node or network is involved
+
+use revm::db::InMemoryDB;
+use revm::primitives::{AccountInfo, Address, ExecutionResult, Output, SpecId, TxKind, U256};
+use revm::Evm;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Gas every transaction pays before calldata and execution.
+pub const TRANSACTION_BASE_GAS: u64 = 21_000;
+/// Calldata cost per zero and non-zero byte (EIP-2028).
+pub const ZERO_BYTE_GAS: u64 = 4;
+pub const NON_ZERO_BYTE_GAS: u64 = 16;
+
+/// Block gas limit of the in-memory chain; also the gas limit of each transaction.
+const GAS_LIMIT: u64 = 30_000_000;
+const CHAIN_ID: u64 = 1;
+/// Fixed block values so repeated measurements give identical results.
+const BLOCK_NUMBER: u64 = 1;
+const BLOCK_TIMESTAMP: u64 = 1_700_000_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GasReport {
+    /// What was measured, e.g. `PicoVerifier.verifyPicoProof`.
+    pub label: String,
+    pub calldata_bytes: usize,
+    pub calldata_gas: u64,
+    pub execution_gas: u64,
+    /// Gas used by the whole transaction: base cost, calldata and execution.
+    pub total_gas: u64,
+}
+
+impl GasReport {
+    fn new(label: String, calldata: &[u8], total_gas: u64) -> Self {
+        let calldata_gas = calldata_gas(calldata);
+        Self {
+            label,
+            calldata_bytes: calldata.len(),
+            calldata_gas,
+            execution_gas: total_gas.saturating_sub(TRANSACTION_BASE_GAS + calldata_gas),
+            total_gas,
+        }
+    }
+}
+
+impl fmt::Display for GasReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.label)?;
+        writeln!(f, "  Calldata: {} bytes, {} gas", self.calldata_bytes, self.calldata_gas)?;
+        writeln!(f, "  Execution: {} gas", self.execution_gas)?;
+        writeln!(f, "  Transaction base: {} gas", TRANSACTION_BASE_GAS)?;
+        write!(f, "  Total: {} gas", self.total_gas)
+    }
+}
+
+pub fn calldata_gas(calldata: &[u8]) -> u64 {
+    calldata.iter().map(|&byte| if byte == 0 { ZERO_BYTE_GAS } else { NON_ZERO_BYTE_GAS }).sum()
+}
+
+/// A single-account chain in memory, following Cancun rules.
+pub struct GasMeter {
+    db: InMemoryDB,
+    caller: Address,
+}
+
+impl Default for GasMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GasMeter {
+    pub fn new() -> Self {
+        let caller = Address::with_last_byte(0x01);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(caller, AccountInfo { balance: U256::MAX, ..Default::default() });
+        Self { db, caller }
+    }
+
+    /// Deploys `bytecode` followed by ABI-encoded `constructor_args`, returning the address and the deployment report.
+    pub fn deploy(&mut self, label: &str, bytecode: &[u8], constructor_args: &[u8]) -> Result<(Address, GasReport), Box<dyn std::error::Error>> {
+        if bytecode.is_empty() {
+            return Err(format!("{} has no bytecode (is it an interface or abstract contract?)", label).into());
+        }
+        let mut initcode = bytecode.to_vec();
+        initcode.extend_from_slice(constructor_args);
+        match self.transact(TxKind::Create, &initcode)? {
+            ExecutionResult::Success { output: Output::Create(_, Some(address)), gas_used, .. } => {
+                Ok((address, GasReport::new(format!("{} deployment", label), &initcode, gas_used)))
+            }
+            result => Err(format!("deploying {} failed: {}", label, describe_failure(&result)).into()),
+        }
+    }
+
+    /// Calls `address` with `calldata` and reports the gas the transaction used. Reverts are errors.
+    pub fn call(&mut self, label: &str, address: Address, calldata: &[u8]) -> Result<GasReport, Box<dyn std::error::Error>> {
+        match self.transact(TxKind::Call(address), calldata)? {
+            ExecutionResult::Success { gas_used, .. } => Ok(GasReport::new(label.to_string(), calldata, gas_used)),
+            result => Err(format!("{} failed: {}", label, describe_failure(&result)).into()),
+        }
+    }
+
+    fn transact(&mut self, kind: TxKind, data: &[u8]) -> Result<ExecutionResult, Box<dyn std::error::Error>> {
+        let caller = self.caller;
+        let mut evm = Evm::builder()
+            .with_db(&mut self.db)
+            .with_spec_id(SpecId::CANCUN)
+            .modify_cfg_env(|cfg| cfg.chain_id = CHAIN_ID)
+            .modify_block_env(|block| {
+                block.number = U256::from(BLOCK_NUMBER);
+                block.timestamp = U256::from(BLOCK_TIMESTAMP);
+                block.gas_limit = U256::from(GAS_LIMIT);
+                block.basefee = U256::ZERO;
+            })
+            .modify_tx_env(|tx| {
+                tx.caller = caller;
+                tx.transact_to = kind;
+                tx.data = data.to_vec().into();
+                tx.gas_limit = GAS_LIMIT;
+                tx.gas_price = U256::ZERO;
+                tx.value = U256::ZERO;
+            })
+            .build();
+        evm.transact_commit().map_err(|e| format!("EVM error: {:?}", e).into())
+    }
+}
+
+/// Names the custom error or revert reason of a failed call where possible.
+fn describe_failure(result: &ExecutionResult) -> String {
+    match result {
+        ExecutionResult::Revert { output, .. } => {
+            const KNOWN_ERRORS: [&str; 2] = ["ProofInvalid()", "PublicInputNotInField()"];
+            if output.len() >= 4 {
+                if let Some(error) = KNOWN_ERRORS.iter().find(|error| ethers::utils::id(error) == output[..4]) {
+                    return format!("reverted with {}", error);
+                }
+                if output[..4] == ethers::utils::id("Error(string)") {
+                    if let Ok(tokens) = ethers::abi::decode(&[ethers::abi::ParamType::String], &output[4..]) {
+                        return format!("reverted: {}", tokens[0]);
+                    }
+                }
+            }
+            format!("reverted (0x{})", hex::encode(output))
+        }
+        ExecutionResult::Halt { reason, .. } => format!("halted: {:?}", reason),
+        ExecutionResult::Success { .. } => "deployment returned no address".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Initcode that deploys `runtime`: PUSH1 len PUSH1 12 PUSH1 0 CODECOPY PUSH1 len PUSH1 0 RETURN.
+    fn initcode(runtime: &[u8]) -> Vec<u8> {
+        let length = runtime.len() as u8;
+        let mut initcode = vec![0x60, length, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, length, 0x60, 0x00, 0xf3];
+        initcode.extend_from_slice(runtime);
+        initcode
+    }
+
+    #[test]
+    fn repeated_calls_use_the_same_gas() {
+        // PUSH1 42 PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+        let runtime = [0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+        let mut meter = GasMeter::new();
+        let (address, deployment) = meter.deploy("Answer", &initcode(&runtime), &[]).unwrap();
+        // Base, create, 18 non-zero and 4 zero initcode bytes, one initcode word,
+        // the constructor's 24 gas and 200 per deployed byte
+        assert_eq!(deployment.calldata_gas, 18 * 16 + 4 * 4);
+        assert_eq!(deployment.total_gas, 21_000 + 32_000 + 304 + 2 + 24 + 2_000);
+
+        let calldata = [0x00, 0x01, 0x00, 0xff];
+        let first = meter.call("Answer.first", address, &calldata).unwrap();
+        let second = meter.call("Answer.second", address, &calldata).unwrap();
+        for report in [&first, &second] {
+            assert_eq!((report.calldata_bytes, report.calldata_gas), (4, 40));
+            // Four PUSH1, MSTORE with one word of memory, RETURN
+            assert_eq!(report.execution_gas, 4 * 3 + 6);
+            assert_eq!(report.total_gas, TRANSACTION_BASE_GAS + 40 + 18);
+        }
+        assert!(second.to_string().ends_with("Total: 21058 gas"));
+    }
+
+    #[test]
+    fn reverts_name_the_verifier_error() {
+        // PUSH4 selector PUSH1 224 SHL PUSH1 0 MSTORE PUSH1 4 PUSH1 0 REVERT
+        let mut runtime = vec![0x63];
+        runtime.extend_from_slice(&ethers::utils::id("ProofInvalid()"));
+        runtime.extend_from_slice(&[0x60, 0xe0, 0x1b, 0x60, 0x00, 0x52, 0x60, 0x04, 0x60, 0x00, 0xfd]);
+        let mut meter = GasMeter::new();
+        let (address, _) = meter.deploy("Verifier", &initcode(&runtime), &[]).unwrap();
+        let error = meter.call("Verifier.verifyPicoProof", address, &[]).unwrap_err();
+        assert_eq!(error.to_string(), "Verifier.verifyPicoProof failed: reverted with ProofInvalid()");
+        assert!(meter.deploy("Interface", &[], &[]).unwrap_err().to_string().contains("has no bytecode"));
+    }
+}
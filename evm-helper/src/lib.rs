@@ -5,6 +5,22 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+pub mod bindings;
+pub mod deploy;
+pub mod foundry;
+pub mod gas;
+pub mod proof;
+pub mod schema;
+pub mod solc;
+pub mod verifier;
+
+use deploy::Deployer;
+use foundry::ProjectFile;
+use gas::{GasMeter, GasReport};
+use proof::PicoEvmProof;
+use schema::PublicValuesSchema;
+use verifier::VerifyingKey;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EVMContract {
     pub name: String,
@@ -26,6 +42,7 @@ pub struct ProofVerification {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EVMDeployment {
     pub contract_name: String,
+    pub chain_id: u64,
     pub deployed_address: String,
     pub transaction_hash: String,
     pub gas_used: u64,
@@ -65,148 +82,191 @@ impl EVMIntegrationHelper {
         self.contracts.push(contract);
     }
 
-    pub fn generate_solidity_contract(&self, _pico_program: &str, contract_name: &str) -> Result<String, Box<dyn std::error::Error>> {
-        // Generate Solidity contract that can verify Pico proofs
+    /// Generate the application contract that accepts proofs through a deployed verifier.
+    /// With a schema, the public values are decoded into its struct and returned.
+    pub fn generate_solidity_contract(&self, _pico_program: &str, contract_name: &str, schema: Option<&PublicValuesSchema>) -> Result<String, Box<dyn std::error::Error>> {
+        if let Some(schema) = schema {
+            if schema.name == contract_name || schema.library_name() == contract_name {
+                return Err(format!("contract name {} clashes with the public-values schema", contract_name).into());
+            }
+        }
+        
+        // Decoded public values when a schema is given, a plain success flag otherwise
+        let definitions = schema.map(|schema| format!("{}\n", schema.solidity_definitions())).unwrap_or_default();
+        let returns = match schema {
+            Some(schema) => format!("{} memory values", schema.name),
+            None => "bool".to_string(),
+        };
+        let result = match schema {
+            Some(schema) => format!("values = {}.decode(publicValues);", schema.library_name()),
+            None => "return true;".to_string(),
+        };
+        
+        // Generate Solidity contract that accepts Pico proofs through a deployed verifier
         let solidity_code = format!(
             r#"// SPDX-License-Identifier: MIT
-pragma solidity ^0.8.0;
+pragma solidity ^0.8.20;
 
-contract {} {{
-    // Pico proof verifier
-    address public constant PICOT_VERIFIER = 0x0000000000000000000000000000000000000000;
+/// Interface of the verifier generated by `pico-evm verifier`.
+interface IPicoVerifier {{
+    function verifyPicoProof(bytes calldata proof, bytes calldata publicValues, bytes32 riscvVkey) external view;
+}}
+
+{}contract {} {{
+    // Pico proof verifier and the vkey hash of the program whose proofs are accepted
+    IPicoVerifier public immutable verifier;
+    bytes32 public immutable programVkey;
     
     mapping(bytes32 => bool) public verifiedProofs;
     
-    event ProofVerified(bytes32 indexed proofHash, bytes32 publicInput);
+    event ProofVerified(bytes32 indexed proofHash, bytes publicValues);
+    
+    constructor(address verifierAddress, bytes32 vkey) {{
+        verifier = IPicoVerifier(verifierAddress);
+        programVkey = vkey;
+    }}
     
     function verifyPicoProof(
         bytes calldata proof,
-        bytes32 publicInput
-    ) external returns (bool) {{
+        bytes calldata publicValues
+    ) external returns ({}) {{
         bytes32 proofHash = keccak256(proof);
         require(!verifiedProofs[proofHash], "Proof already verified");
         
-        // Call Pico verifier contract
-        (bool success,) = PICOT_VERIFIER.call(abi.encodeWithSignature(
-            "verifyProof(bytes,bytes32)",
-            proof,
-            publicInput
-        ));
+        // Reverts if the proof does not verify
+        verifier.verifyPicoProof(proof, publicValues, programVkey);
         
-        if (success) {{
-            verifiedProofs[proofHash] = true;
-            emit ProofVerified(proofHash, publicInput);
-        }}
+        verifiedProofs[proofHash] = true;
+        emit ProofVerified(proofHash, publicValues);
         
-        return success;
+        {}
     }}
     
     function isProofVerified(bytes32 proofHash) external view returns (bool) {{
         return verifiedProofs[proofHash];
     }}
 }}"#,
-            contract_name
+            definitions, contract_name, returns, result
         );
         
         Ok(solidity_code)
     }
 
-    pub fn format_proof_for_evm(&self, proof: &str, format_type: ProofFormatType) -> Result<ProofFormat, Box<dyn std::error::Error>> {
-        match format_type {
+    /// Encode a Pico EVM proof for `verifyPicoProof(bytes proof, bytes publicValues, bytes32 riscvVkey)`.
+    /// The public inputs are the ones the verifier checks: the vkey hash and the digest of the committed values.
+    pub fn format_proof_for_evm(&self, proof: &PicoEvmProof, format_type: ProofFormatType) -> Result<ProofFormat, Box<dyn std::error::Error>> {
+        let public_inputs: Vec<String> = proof.public_inputs().iter().map(|input| format!("{:?}", input)).collect();
+        
+        let mut metadata = HashMap::new();
+        metadata.insert("riscvVKey".to_string(), format!("{:?}", proof.riscv_vkey));
+        metadata.insert("publicValues".to_string(), format!("0x{}", hex::encode(&proof.public_values)));
+        metadata.insert("publicValuesDigest".to_string(), format!("{:?}", proof.public_values_digest()));
+        metadata.insert("function".to_string(), proof::VERIFY_PICO_PROOF.to_string());
+        
+        let proof_data = match format_type {
             ProofFormatType::Solidity => {
-                // Format proof for Solidity contract calls
-                Ok(ProofFormat {
-                    format_type,
-                    proof_data: format!("0x{}", proof),
-                    public_inputs: vec![],
-                    metadata: HashMap::new(),
-                })
+                // Typed tuple literal for Solidity tests
+                proof.solidity_literal()
             }
             ProofFormatType::Calldata => {
-                // Format proof as calldata for direct contract calls
-                Ok(ProofFormat {
-                    format_type,
-                    proof_data: format!("0x{}", proof),
-                    public_inputs: vec![],
-                    metadata: HashMap::new(),
-                })
+                // Selector plus ABI-encoded (bytes proof, bytes publicValues, bytes32 vkey)
+                metadata.insert("selector".to_string(), format!("0x{}", hex::encode(ethers::utils::id(proof::VERIFY_PICO_PROOF))));
+                format!("0x{}", hex::encode(proof.verifier_calldata()))
             }
             ProofFormatType::JSON => {
                 // Format proof as JSON for web3 integration
-                let json_data = serde_json::json!({
-                    "proof": proof,
-                    "publicInputs": [],
-                    "verificationKey": "default"
+                let json = serde_json::json!({
+                    "proof": format!("0x{}", hex::encode(proof.proof_bytes())),
+                    "publicValues": format!("0x{}", hex::encode(&proof.public_values)),
+                    "riscvVKey": format!("{:?}", proof.riscv_vkey),
+                    "publicInputs": public_inputs,
+                    "calldata": format!("0x{}", hex::encode(proof.verifier_calldata())),
                 });
-                Ok(ProofFormat {
-                    format_type,
-                    proof_data: json_data.to_string(),
-                    public_inputs: vec![],
-                    metadata: HashMap::new(),
-                })
+                serde_json::to_string_pretty(&json)?
             }
-            ProofFormatType::Raw => {
-                Ok(ProofFormat {
-                    format_type,
-                    proof_data: proof.to_string(),
-                    public_inputs: vec![],
-                    metadata: HashMap::new(),
-                })
-            }
-        }
-    }
-
-    pub fn estimate_gas_cost(&self, proof_size: usize, public_inputs_count: usize) -> u64 {
-        // Estimate gas cost for proof verification
-        let base_cost = 100_000; // Base verification cost
-        let proof_cost = proof_size as u64 * 10; // Cost per byte of proof
-        let input_cost = public_inputs_count as u64 * 1_000; // Cost per public input
+            ProofFormatType::Raw => format!("0x{}", hex::encode(proof.proof_bytes())),
+        };
         
-        base_cost + proof_cost + input_cost
+        Ok(ProofFormat {
+            format_type,
+            proof_data,
+            public_inputs,
+            metadata,
+        })
     }
 
-    pub fn generate_verification_contract(&self, verification_key: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let contract_code = format!(
-            r#"// SPDX-License-Identifier: MIT
-pragma solidity ^0.8.0;
-
-contract PicoVerifier {{
-    // Verification key for Pico zkVM
-    bytes32 public constant VERIFICATION_KEY = 0x{};
-    
-    function verifyProof(
-        bytes calldata proof,
-        bytes32 publicInput
-    ) external pure returns (bool) {{
-        // Simplified verification logic
-        // In a real implementation, this would contain the actual verification circuit
-        
-        require(proof.length > 0, "Invalid proof");
-        require(publicInput != bytes32(0), "Invalid public input");
+    /// Measure verification gas by deploying the verifier (and optionally the integration contract
+    /// from `generate_solidity_contract`) into an in-memory EVM and calling them with the proof.
+    pub fn measure_verification_gas(&self, verifier: &EVMContract, app: Option<&EVMContract>, proof: &PicoEvmProof) -> Result<Vec<GasReport>, Box<dyn std::error::Error>> {
+        let mut meter = GasMeter::new();
+        let mut reports = Vec::new();
         
-        // Placeholder verification - always returns true for demo
-        // Real implementation would verify the zk-SNARK proof
-        return true;
-    }}
-    
-    function batchVerifyProofs(
-        bytes[] calldata proofs,
-        bytes32[] calldata publicInputs
-    ) external pure returns (bool[] memory) {{
-        require(proofs.length == publicInputs.length, "Array length mismatch");
+        let (verifier_address, deployment) = meter.deploy(&verifier.name, &hex::decode(&verifier.bytecode)?, &[])?;
+        reports.push(deployment);
+        reports.push(meter.call(&format!("{}.verifyPicoProof", verifier.name), verifier_address, &proof.verifier_calldata())?);
         
-        bool[] memory results = new bool[](proofs.length);
-        for (uint i = 0; i < proofs.length; i++) {{
-            results[i] = this.verifyProof(proofs[i], publicInputs[i]);
-        }}
+        if let Some(app) = app {
+            // The integration contract takes the verifier address and the program's vkey hash
+            let constructor_args = ethers::abi::encode(&[
+                ethers::abi::Token::Address(ethers::types::H160::from_slice(verifier_address.as_slice())),
+                ethers::abi::Token::FixedBytes(proof.riscv_vkey.as_bytes().to_vec()),
+            ]);
+            let (app_address, deployment) = meter.deploy(&app.name, &hex::decode(&app.bytecode)?, &constructor_args)?;
+            reports.push(deployment);
+            
+            reports.push(meter.call(&format!("{}.verifyPicoProof", app.name), app_address, &proof.app_calldata())?);
+        }
         
-        return results;
-    }}
-}}"#,
-            verification_key
-        );
+        Ok(reports)
+    }
+
+    /// Deploy a compiled contract through `deployer`, encoding `constructor_args` with the constructor's
+    /// ABI types, and remember where it went.
+    pub fn deploy_contract(&mut self, deployer: &Deployer, contract: &EVMContract, constructor_args: &[String]) -> Result<EVMDeployment, Box<dyn std::error::Error>> {
+        let encoded_args = deploy::encode_constructor_args(contract, constructor_args)?;
+        let deployment = deployer.deploy(contract, &encoded_args)?;
+        self.deployed_contracts.insert(contract.name.clone(), deployment.clone());
+        Ok(deployment)
+    }
+
+    pub fn get_deployment(&self, contract_name: &str) -> Option<&EVMDeployment> {
+        self.deployed_contracts.get(contract_name)
+    }
+
+    /// Generate a verifier contract for a verification key loaded with `VerifyingKey::load`.
+    /// `source` names the key file in the contract's header.
+    pub fn generate_verification_contract(&self, verification_key: &VerifyingKey, contract_name: &str, source: &str) -> Result<String, Box<dyn std::error::Error>> {
+        if contract_name.is_empty() || !contract_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("Invalid contract name: {}", contract_name).into());
+        }
+        Ok(verification_key.to_solidity(contract_name, source))
+    }
+
+    /// Lay out a Foundry project: the verifier for `verification_key`, the application contract, a deployment
+    /// script, and tests run against `proof` as a fixture.
+    pub fn create_foundry_project(
+        &self,
+        verification_key: &VerifyingKey,
+        key_source: &str,
+        proof: &PicoEvmProof,
+        app_name: &str,
+        verifier_name: &str,
+        schema: Option<&PublicValuesSchema>,
+    ) -> Result<Vec<ProjectFile>, Box<dyn std::error::Error>> {
+        // verifyPicoProof is only generated for keys with Pico's two public inputs
+        if verification_key.public_input_count() != 2 {
+            return Err(format!("the key has {} public inputs; Pico EVM proofs have 2 (vkey hash and public values digest)", verification_key.public_input_count()).into());
+        }
         
-        Ok(contract_code)
+        Ok(vec![
+            ProjectFile::new("foundry.toml", foundry::foundry_toml()),
+            ProjectFile::new(".gitignore", foundry::gitignore()),
+            ProjectFile::new(format!("src/{}.sol", verifier_name), self.generate_verification_contract(verification_key, verifier_name, key_source)?),
+            ProjectFile::new(format!("src/{}.sol", app_name), self.generate_solidity_contract("", app_name, schema)?),
+            ProjectFile::new("script/Deploy.s.sol", foundry::deploy_script(verifier_name, app_name, proof.riscv_vkey)),
+            ProjectFile::new(format!("test/{}.t.sol", app_name), self.generate_test_contract(app_name, verifier_name, foundry::FIXTURE_PATH)?),
+            ProjectFile::new(foundry::FIXTURE_PATH, foundry::fixture_json(proof)?),
+        ])
     }
 
     pub fn create_deployment_script(&self, contract_name: &str, constructor_args: &[String]) -> Result<String, Box<dyn std::error::Error>> {
@@ -248,55 +308,89 @@ main()
         Ok(script)
     }
 
-    pub fn generate_test_contract(&self, contract_name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    /// Generate a Forge test for the application contract from `generate_solidity_contract`, run against
+    /// the verifier and a proof fixture written by `foundry::fixture_json` (path relative to the project root).
+    pub fn generate_test_contract(&self, contract_name: &str, verifier_name: &str, fixture_path: &str) -> Result<String, Box<dyn std::error::Error>> {
+        for name in [contract_name, verifier_name] {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("Invalid contract name: {}", name).into());
+            }
+        }
+        if contract_name == verifier_name {
+            return Err(format!("The application and verifier contracts are both named {}", contract_name).into());
+        }
+        
         let test_code = format!(
             r#"// SPDX-License-Identifier: MIT
-pragma solidity ^0.8.0;
+pragma solidity ^0.8.20;
 
-import "forge-std/Test.sol";
-import "../src/{}.sol";
+import {{Test, stdJson}} from "forge-std/Test.sol";
+import {{{verifier}}} from "../src/{verifier}.sol";
+import {{{app}}} from "../src/{app}.sol";
 
-contract {}Test is Test {{
-    {} public contract;
+contract {app}Test is Test {{
+    using stdJson for string;
+    
+    {verifier} verifier;
+    {app} app;
+    
+    // A real proof, its public values and the program's vkey hash, from {fixture}
+    bytes32 riscvVkey;
+    bytes proof;
+    bytes publicValues;
     
     function setUp() public {{
-        contract = new {}();
+        string memory fixture = vm.readFile(string.concat(vm.projectRoot(), "/{fixture}"));
+        riscvVkey = fixture.readBytes32(".riscvVKey");
+        proof = fixture.readBytes(".proof");
+        publicValues = fixture.readBytes(".publicValues");
+        
+        verifier = new {verifier}();
+        app = new {app}(address(verifier), riscvVkey);
     }}
     
-    function testVerifyProof() public {{
-        // Test proof verification
-        bytes memory proof = hex"1234567890abcdef";
-        bytes32 publicInput = keccak256("test input");
-        
-        bool result = contract.verifyPicoProof(proof, publicInput);
-        assertTrue(result, "Proof verification should succeed");
+    function testVerifierAcceptsValidProof() public view {{
+        verifier.verifyPicoProof(proof, publicValues, riscvVkey);
+    }}
+    
+    function testValidProof() public {{
+        app.verifyPicoProof(proof, publicValues);
+        assertTrue(app.isProofVerified(keccak256(proof)), "Proof should be recorded as verified");
     }}
     
     function testDuplicateProof() public {{
-        bytes memory proof = hex"1234567890abcdef";
-        bytes32 publicInput = keccak256("test input");
+        app.verifyPicoProof(proof, publicValues);
         
-        // First verification should succeed
-        bool result1 = contract.verifyPicoProof(proof, publicInput);
-        assertTrue(result1, "First verification should succeed");
-        
-        // Second verification should fail
         vm.expectRevert("Proof already verified");
-        contract.verifyPicoProof(proof, publicInput);
+        app.verifyPicoProof(proof, publicValues);
     }}
     
-    function testInvalidProof() public {{
-        bytes memory proof = hex"";
-        bytes32 publicInput = keccak256("test input");
+    function testTamperedProof() public {{
+        bytes memory tampered = proof;
+        tampered[31] ^= 0x01;
         
-        bool result = contract.verifyPicoProof(proof, publicInput);
-        assertFalse(result, "Invalid proof should fail verification");
+        vm.expectRevert({verifier}.ProofInvalid.selector);
+        app.verifyPicoProof(tampered, publicValues);
     }}
-}}"#,
-            contract_name,
-            contract_name,
-            contract_name,
-            contract_name
+    
+    function testTamperedPublicValues() public {{
+        bytes memory tampered = bytes.concat(publicValues, hex"00");
+        
+        vm.expectRevert({verifier}.ProofInvalid.selector);
+        app.verifyPicoProof(proof, tampered);
+    }}
+    
+    function testWrongVkey() public {{
+        {app} other = new {app}(address(verifier), riscvVkey ^ bytes32(uint256(1)));
+        
+        vm.expectRevert({verifier}.ProofInvalid.selector);
+        other.verifyPicoProof(proof, publicValues);
+    }}
+}}
+"#,
+            app = contract_name,
+            verifier = verifier_name,
+            fixture = fixture_path,
         );
         
         Ok(test_code)
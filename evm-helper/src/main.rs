@@ -1,4 +1,11 @@
 use clap::{Parser, Subcommand};
+use pico_evm_helper::bindings::generate_bindings;
+use pico_evm_helper::deploy::{record_deployment, DeployConfig, Deployer};
+use pico_evm_helper::foundry::write_project;
+use pico_evm_helper::proof::{entry_point, PicoEvmProof, APP_VERIFY_PICO_PROOF, VERIFY_PICO_PROOF};
+use pico_evm_helper::schema::PublicValuesSchema;
+use pico_evm_helper::solc::{load_artifact, load_contracts, select_contract};
+use pico_evm_helper::verifier::{ProofSystem, VerifyingKey};
 use pico_evm_helper::{EVMIntegrationHelper, ProofFormatType};
 use std::path::PathBuf;
 
@@ -26,13 +33,40 @@ enum Commands {
         /// Pico program source file
         #[arg(short, long)]
         program: Option<PathBuf>,
+        
+        /// Public-values schema (.toml, or a Rust struct in a .rs file) to decode the committed values with
+        #[arg(short, long)]
+        schema: Option<PathBuf>,
+        
+        /// Struct to derive the schema from when the .rs file defines several
+        #[arg(long = "struct")]
+        struct_name: Option<String>,
+    },
+    
+    /// Generate the Rust encoder and Solidity decoder library for a public-values schema
+    Schema {
+        /// Public-values schema (.toml, or a Rust struct in a .rs file)
+        #[arg(short, long)]
+        schema: PathBuf,
+        
+        /// Struct to derive the schema from when the .rs file defines several
+        #[arg(long = "struct")]
+        struct_name: Option<String>,
+        
+        /// Output file for the Rust encoding helper
+        #[arg(short, long)]
+        rust: Option<PathBuf>,
+        
+        /// Output file for the Solidity library
+        #[arg(long)]
+        solidity: Option<PathBuf>,
     },
     
     /// Format proof for EVM consumption
     Format {
-        /// Proof data (hex string)
+        /// Pico EVM proof (proof.json)
         #[arg(short, long)]
-        proof: String,
+        proof: PathBuf,
         
         /// Output format (solidity, calldata, json, raw)
         #[arg(short, long, default_value = "solidity")]
@@ -43,61 +77,147 @@ enum Commands {
         output: Option<PathBuf>,
     },
     
-    /// Generate verification contract
+    /// Generate verification contract from the SDK's verification key file
     Verifier {
-        /// Verification key
+        /// Verification key file (gnark binary, or snarkjs JSON for groth16)
         #[arg(short, long)]
-        key: String,
+        key: PathBuf,
+        
+        /// Proof system of the key (groth16, plonk)
+        #[arg(short, long, default_value = "groth16")]
+        system: String,
+        
+        /// Name of the verifier contract
+        #[arg(short, long, default_value = "PicoVerifier")]
+        name: String,
         
         /// Output file
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
     
-    /// Generate deployment script
+    /// Deploy a compiled contract through a JSON-RPC node
     Deploy {
-        /// Contract name
+        /// Contract source (.sol) or artifact (.json); append `:Name` to pick a contract
         #[arg(short, long)]
         contract: String,
         
-        /// Constructor arguments
+        /// Constructor arguments, parsed with the constructor's ABI types
         #[arg(short, long)]
         args: Vec<String>,
         
-        /// Output file
-        #[arg(short, long)]
-        output: Option<PathBuf>,
+        /// JSON-RPC URL (overrides PICO_EVM_RPC_URL and the config file; default http://127.0.0.1:8545)
+        #[arg(long)]
+        rpc_url: Option<String>,
+        
+        /// Deployment config (TOML with rpc_url, private_key, confirmations)
+        #[arg(long)]
+        config: Option<PathBuf>,
+        
+        /// Deployments manifest the deployment is appended to
+        #[arg(short, long, default_value = "deployments.json")]
+        deployments: PathBuf,
+        
+        /// Write a Hardhat deployment script to this file instead of deploying
+        #[arg(long)]
+        hardhat_script: Option<PathBuf>,
     },
     
-    /// Generate test contract
+    /// Generate a Forge test for the application contract
     Test {
         /// Contract name to test
         #[arg(short, long)]
         contract: String,
         
+        /// Name of the verifier contract
+        #[arg(short, long, default_value = "PicoVerifier")]
+        verifier: String,
+        
+        /// Proof fixture the test reads, relative to the project root
+        #[arg(short, long, default_value = pico_evm_helper::foundry::FIXTURE_PATH)]
+        fixture: String,
+        
         /// Output file
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
     
-    /// Estimate gas cost for proof verification
+    /// Create a Foundry project with the verifier, application contract, deploy script and tests
+    FoundryInit {
+        /// Project directory
+        #[arg(short, long, default_value = ".")]
+        dir: PathBuf,
+        
+        /// Verification key file (gnark binary, or snarkjs JSON for groth16)
+        #[arg(short, long)]
+        key: PathBuf,
+        
+        /// Proof system of the key (groth16, plonk)
+        #[arg(short, long, default_value = "groth16")]
+        system: String,
+        
+        /// Pico EVM proof (proof.json) used as the test fixture
+        #[arg(short, long)]
+        proof: PathBuf,
+        
+        /// Name of the application contract
+        #[arg(short, long)]
+        name: String,
+        
+        /// Name of the verifier contract
+        #[arg(long, default_value = "PicoVerifier")]
+        verifier_name: String,
+        
+        /// Public-values schema (.toml or .rs) for the application contract
+        #[arg(long)]
+        schema: Option<PathBuf>,
+        
+        /// Struct to derive the schema from when the .rs file defines several
+        #[arg(long = "struct")]
+        struct_name: Option<String>,
+        
+        /// Overwrite existing files
+        #[arg(long)]
+        force: bool,
+    },
+    
+    /// Measure verification gas in an in-process EVM
     Gas {
-        /// Proof size in bytes
+        /// Verifier contract (.sol, compiled with solc) or compiled artifact (.json)
+        #[arg(short, long)]
+        verifier: PathBuf,
+        
+        /// Pico EVM proof (proof.json)
+        #[arg(short, long)]
+        proof: PathBuf,
+        
+        /// Integration contract from `generate` (.sol or artifact) to measure as well
         #[arg(short, long)]
-        proof_size: usize,
+        app: Option<PathBuf>,
         
-        /// Number of public inputs
-        #[arg(short, long, default_value = "1")]
-        inputs: usize,
+        /// Gas price in gwei used for the cost estimate
+        #[arg(long, default_value = "20")]
+        gas_price: f64,
     },
     
-    /// Validate EVM integration
+    /// Generate typed Rust bindings (ethers abigen) with Pico proof helpers
+    Bindings {
+        /// Contract source (.sol) or artifact/ABI (.json); append `:Name` to pick a contract. Repeatable
+        #[arg(short, long, required = true)]
+        contract: Vec<String>,
+        
+        /// Output Rust file
+        #[arg(short, long, visible_alias = "out")]
+        output: PathBuf,
+    },
+    
+    /// Check that a proof loads and that a contract ABI exposes verifyPicoProof
     Validate {
-        /// Contract ABI file
+        /// Contract ABI or artifact (.json)
         #[arg(short, long)]
         abi: PathBuf,
         
-        /// Proof file
+        /// Proof file (proof.json)
         #[arg(short, long)]
         proof: PathBuf,
     },
@@ -107,10 +227,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
     
     let cli = Cli::parse();
-    let helper = EVMIntegrationHelper::new();
+    let mut helper = EVMIntegrationHelper::new();
     
     match cli.command {
-        Commands::Generate { name, output, program } => {
+        Commands::Generate { name, output, program, schema, struct_name } => {
             println!("Generating Solidity contract: {}", name);
             
             let program_source = if let Some(program_path) = program {
@@ -119,7 +239,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "// Pico program placeholder".to_string()
             };
             
-            let contract_code = helper.generate_solidity_contract(&program_source, &name)?;
+            let schema = schema.map(|path| PublicValuesSchema::load(&path, struct_name.as_deref())).transpose()?;
+            let contract_code = helper.generate_solidity_contract(&program_source, &name, schema.as_ref())?;
             
             if let Some(output_path) = output {
                 std::fs::write(&output_path, &contract_code)?;
@@ -130,6 +251,31 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
         
+        Commands::Schema { schema, struct_name, rust, solidity } => {
+            let schema = PublicValuesSchema::load(&schema, struct_name.as_deref())?;
+            println!("Public values {}:", schema.name);
+            for field in &schema.fields {
+                println!("  {} {}", field.ty, field.name);
+            }
+            
+            match &rust {
+                Some(path) => {
+                    std::fs::write(path, schema.to_rust())?;
+                    println!("Rust encoding helper written to: {:?}", path);
+                }
+                None if solidity.is_none() => println!("\n{}", schema.to_rust()),
+                None => {}
+            }
+            match &solidity {
+                Some(path) => {
+                    std::fs::write(path, schema.to_solidity())?;
+                    println!("Solidity library written to: {:?}", path);
+                }
+                None if rust.is_none() => println!("{}", schema.to_solidity()),
+                None => {}
+            }
+        }
+        
         Commands::Format { proof, format, output } => {
             println!("Formatting proof for EVM consumption...");
             
@@ -144,26 +290,36 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             };
             
-            let formatted_proof = helper.format_proof_for_evm(&proof, format_type.clone())?;
-            
-            let output_data = match format_type {
-                ProofFormatType::JSON => formatted_proof.proof_data,
-                _ => format!("Proof: {}\nFormat: {:?}", formatted_proof.proof_data, formatted_proof.format_type),
-            };
+            let pico_proof = PicoEvmProof::load(&proof)?;
+            let formatted_proof = helper.format_proof_for_evm(&pico_proof, format_type)?;
             
             if let Some(output_path) = output {
-                std::fs::write(&output_path, &output_data)?;
+                std::fs::write(&output_path, &formatted_proof.proof_data)?;
                 println!("Formatted proof written to: {:?}", output_path);
             } else {
-                println!("Formatted Proof:");
-                println!("{}", output_data);
+                println!("Formatted Proof ({:?}):", formatted_proof.format_type);
+                println!("{}", formatted_proof.proof_data);
             }
+            println!("Public inputs:");
+            println!("  riscvVkey: {}", formatted_proof.public_inputs[0]);
+            println!("  publicValuesDigest: {}", formatted_proof.public_inputs[1]);
         }
         
-        Commands::Verifier { key, output } => {
+        Commands::Verifier { key, system, name, output } => {
             println!("Generating verification contract...");
             
-            let verifier_code = helper.generate_verification_contract(&key)?;
+            let system: ProofSystem = match system.parse() {
+                Ok(system) => system,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let verification_key = VerifyingKey::load(&key, system)?;
+            println!("Loaded {} key with {} public inputs", system, verification_key.public_input_count());
+            
+            let source = key.file_name().map_or_else(|| key.display().to_string(), |name| name.to_string_lossy().into_owned());
+            let verifier_code = helper.generate_verification_contract(&verification_key, &name, &source)?;
             
             if let Some(output_path) = output {
                 std::fs::write(&output_path, &verifier_code)?;
@@ -174,24 +330,45 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
         
-        Commands::Deploy { contract, args, output } => {
-            println!("Generating deployment script for: {}", contract);
+        Commands::Deploy { contract, args, rpc_url, config, deployments, hardhat_script } => {
+            let (path, name) = split_contract_spec(&contract);
             
-            let deploy_script = helper.create_deployment_script(&contract, &args)?;
+            if let Some(script_path) = hardhat_script {
+                let contract_name = name.map(str::to_string).unwrap_or_else(|| contract_name_from_path(&path));
+                println!("Generating deployment script for: {}", contract_name);
+                let deploy_script = helper.create_deployment_script(&contract_name, &args)?;
+                std::fs::write(&script_path, &deploy_script)?;
+                println!("Deployment script written to: {:?}", script_path);
+                return Ok(());
+            }
             
-            if let Some(output_path) = output {
-                std::fs::write(&output_path, &deploy_script)?;
-                println!("Deployment script written to: {:?}", output_path);
-            } else {
-                println!("Generated Deployment Script:");
-                println!("{}", deploy_script);
+            let selected = select_contract(load_contracts(&path)?, name, &path)?;
+            let mut deploy_config = match &config {
+                Some(config_path) => DeployConfig::load(config_path)?,
+                None => DeployConfig::default(),
+            }
+            .with_env();
+            if rpc_url.is_some() {
+                deploy_config.rpc_url = rpc_url;
             }
+            
+            let deployer = Deployer::connect(&deploy_config)?;
+            println!("Deploying {} to {} (chain {}) from {:?}...", selected.name, deploy_config.rpc_url(), deployer.chain_id(), deployer.address());
+            
+            let deployment = helper.deploy_contract(&deployer, &selected, &args)?;
+            record_deployment(&deployments, &deployment)?;
+            
+            println!("✓ {} deployed to: {}", deployment.contract_name, deployment.deployed_address);
+            println!("  Transaction: {}", deployment.transaction_hash);
+            println!("  Block: {}", deployment.block_number);
+            println!("  Gas used: {}", deployment.gas_used);
+            println!("Deployment recorded in: {:?}", deployments);
         }
         
-        Commands::Test { contract, output } => {
+        Commands::Test { contract, verifier, fixture, output } => {
             println!("Generating test contract for: {}", contract);
             
-            let test_code = helper.generate_test_contract(&contract)?;
+            let test_code = helper.generate_test_contract(&contract, &verifier, &fixture)?;
             
             if let Some(output_path) = output {
                 std::fs::write(&output_path, &test_code)?;
@@ -202,55 +379,125 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
         
-        Commands::Gas { proof_size, inputs } => {
-            println!("Estimating gas cost for proof verification...");
+        Commands::FoundryInit { dir, key, system, proof, name, verifier_name, schema, struct_name, force } => {
+            println!("Creating Foundry project in {:?}...", dir);
             
-            let gas_estimate = helper.estimate_gas_cost(proof_size, inputs);
+            let system: ProofSystem = match system.parse() {
+                Ok(system) => system,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let verification_key = VerifyingKey::load(&key, system)?;
+            let source = key.file_name().map_or_else(|| key.display().to_string(), |name| name.to_string_lossy().into_owned());
+            let pico_proof = PicoEvmProof::load(&proof)?;
+            let schema = schema.map(|path| PublicValuesSchema::load(&path, struct_name.as_deref())).transpose()?;
             
-            println!("Gas Estimation:");
-            println!("  Proof size: {} bytes", proof_size);
-            println!("  Public inputs: {}", inputs);
-            println!("  Estimated gas: {} wei", gas_estimate);
-            println!("  Estimated gas: {:.6} ETH (at 20 gwei)", gas_estimate as f64 * 20e-9 / 1e18);
+            let files = helper.create_foundry_project(&verification_key, &source, &pico_proof, &name, &verifier_name, schema.as_ref())?;
+            write_project(&dir, &files, force)?;
+            for file in &files {
+                println!("  {}", file.path.display());
+            }
+            
+            println!("Next steps:");
+            println!("  cd {}", dir.display());
+            println!("  forge install foundry-rs/forge-std");
+            println!("  forge test");
         }
         
-        Commands::Validate { abi, proof } => {
-            println!("Validating EVM integration...");
-            println!("ABI file: {:?}", abi);
-            println!("Proof file: {:?}", proof);
+        Commands::Gas { verifier, proof, app, gas_price } => {
+            println!("Measuring verification gas in an in-process EVM...");
             
-            // Basic validation checks
-            if !abi.exists() {
-                eprintln!("Error: ABI file does not exist");
-                std::process::exit(1);
-            }
+            let verifier_contract = select_contract(load_contracts(&verifier)?, None, &verifier)?;
+            let app_contract = match &app {
+                Some(app_path) => Some(select_contract(load_contracts(app_path)?, None, app_path)?),
+                None => None,
+            };
+            let pico_proof = PicoEvmProof::load(&proof)?;
             
-            if !proof.exists() {
-                eprintln!("Error: Proof file does not exist");
-                std::process::exit(1);
-            }
+            let reports = helper.measure_verification_gas(&verifier_contract, app_contract.as_ref(), &pico_proof)?;
             
-            // Read and validate files
-            let abi_content = std::fs::read_to_string(&abi)?;
-            let proof_content = std::fs::read_to_string(&proof)?;
+            println!("Gas Measurement (Cancun rules):");
+            for report in &reports {
+                println!("{}", report);
+            }
+            let verification = &reports[1];
+            println!(
+                "Verification cost: {} gas = {:.6} ETH at {} gwei",
+                verification.total_gas,
+                verification.total_gas as f64 * gas_price * 1e-9,
+                gas_price
+            );
+        }
+        
+        Commands::Bindings { contract, output } => {
+            println!("Generating Rust bindings...");
             
-            // Basic JSON validation for ABI
-            if let Err(e) = serde_json::from_str::<serde_json::Value>(&abi_content) {
-                eprintln!("Error: Invalid ABI JSON format: {}", e);
-                std::process::exit(1);
+            let mut contracts = Vec::new();
+            for spec in &contract {
+                let (path, name) = split_contract_spec(spec);
+                let selected = select_contract(load_contracts(&path)?, name, &path)?;
+                println!("  {} from {:?}", selected.name, path);
+                contracts.push(selected);
             }
             
-            // Basic hex validation for proof
-            if !proof_content.starts_with("0x") || proof_content.len() < 4 {
-                eprintln!("Error: Proof should be a valid hex string starting with 0x");
-                std::process::exit(1);
+            let (code, warnings) = generate_bindings(&contracts)?;
+            for warning in warnings {
+                eprintln!("warning: {}", warning);
             }
+            std::fs::write(&output, code)?;
+            println!("Bindings written to: {:?}", output);
+        }
+        
+        Commands::Validate { abi, proof } => {
+            println!("Validating EVM integration...");
+            println!("ABI file: {:?}", abi);
+            println!("Proof file: {:?}", proof);
             
-            println!("✓ ABI file is valid JSON");
-            println!("✓ Proof file is valid hex format");
+            let proof = PicoEvmProof::load(&proof)?;
+            println!(
+                "✓ Proof loads: {} words, {} bytes of public values, vkey {:?}",
+                proof.proof.len(),
+                proof.public_values.len(),
+                proof.riscv_vkey
+            );
+            
+            // A bare ABI array or any artifact `load_artifact` understands; every contract in it is checked
+            let mut found = false;
+            for contract in load_artifact(&abi)? {
+                let contract_abi: ethers::abi::Abi = serde_json::from_str(&contract.abi)
+                    .map_err(|e| format!("{} has an invalid ABI: {}", contract.name, e))?;
+                let Some(signature) = entry_point(&contract_abi) else {
+                    continue;
+                };
+                let calldata = if signature == VERIFY_PICO_PROOF { proof.verifier_calldata() } else { proof.app_calldata() };
+                println!("✓ {} declares {} ({} bytes of calldata for this proof)", contract.name, signature, calldata.len());
+                found = true;
+            }
+            if !found {
+                return Err(format!("{} declares neither {} nor {}", abi.display(), VERIFY_PICO_PROOF, APP_VERIFY_PICO_PROOF).into());
+            }
             println!("✓ EVM integration validation passed");
         }
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Splits `path/to/File.sol:Name` into the path and the optional contract name.
+fn split_contract_spec(spec: &str) -> (PathBuf, Option<&str>) {
+    match spec.rsplit_once(':') {
+        Some((path, name)) if !path.is_empty() && !name.is_empty() && !name.contains(['/', '\\']) => (PathBuf::from(path), Some(name)),
+        _ => (PathBuf::from(spec), None),
+    }
+}
+
+/// Contract name for a path without `:Name`: the file name up to its first dot.
+fn contract_name_from_path(path: &std::path::Path) -> String {
+    path.file_name()
+        .and_then(|file| file.to_str())
+        .and_then(|file| file.split('.').next())
+        .unwrap_or_default()
+        .to_string()
+}
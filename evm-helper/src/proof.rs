@@ -0,0 +1,239 @@
+// Pico EVM proof files
+// Loads the proof the Pico SDK writes for on-chain verification and encodes it as verifier calldata
+
+use ethers::abi::{self, Abi, Token};
+use ethers::types::{Bytes, H256, U256};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Solidity signature of the entry point every generated verifier exposes.
+pub const VERIFY_PICO_PROOF: &str = "verifyPicoProof(bytes,bytes,bytes32)";
+
+/// Solidity signature of the generated application contract's entry point, which holds the vkey hash itself.
+pub const APP_VERIFY_PICO_PROOF: &str = "verifyPicoProof(bytes,bytes)";
+
+/// The signature of the `verifyPicoProof` overload `abi` declares, preferring the verifier's.
+pub fn entry_point(abi: &Abi) -> Option<&'static str> {
+    let selectors: Vec<[u8; 4]> = abi
+        .functions_by_name("verifyPicoProof")
+        .map(|functions| functions.iter().map(|function| function.short_signature()).collect())
+        .unwrap_or_default();
+    [VERIFY_PICO_PROOF, APP_VERIFY_PICO_PROOF]
+        .into_iter()
+        .find(|signature| selectors.contains(&ethers::utils::id(signature)))
+}
+
+/// A proof as written by the Pico SDK's EVM prover (`proof.json`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PicoEvmProof {
+    /// Hash of the RISC-V program's verification key.
+    #[serde(rename = "riscvVKey")]
+    pub riscv_vkey: H256,
+    /// The values the guest committed, as raw bytes.
+    #[serde(rename = "publicValues")]
+    pub public_values: Bytes,
+    /// The wrapped proof as 32-byte words: A, B (imaginary parts first), C and any commitment words.
+    pub proof: Vec<U256>,
+}
+
+/// Field spellings the SDK and hand-written fixtures use.
+#[derive(Deserialize)]
+struct RawProof {
+    #[serde(alias = "riscvVKey", alias = "riscv_vkey", alias = "vkey")]
+    riscv_vkey: String,
+    #[serde(alias = "publicValues", alias = "public_values")]
+    public_values: String,
+    proof: serde_json::Value,
+}
+
+impl PicoEvmProof {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("failed to read proof {}: {}", path.display(), e))?;
+        Self::from_json(&json).map_err(|e| format!("{} is not a Pico EVM proof: {}", path.display(), e).into())
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let raw: RawProof = serde_json::from_str(json)?;
+        let vkey = parse_hex(&raw.riscv_vkey)?;
+        if vkey.len() != 32 {
+            return Err(format!("riscvVKey is {} bytes, expected 32", vkey.len()).into());
+        }
+        // The proof is either a list of words or one hex string of concatenated words.
+        let proof = match &raw.proof {
+            serde_json::Value::Array(words) => words
+                .iter()
+                .map(|word| match word {
+                    serde_json::Value::String(text) => parse_word(text),
+                    other => Err(format!("proof word {} is not a string", other).into()),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            serde_json::Value::String(text) => {
+                let bytes = parse_hex(text)?;
+                if bytes.len() % 32 != 0 {
+                    return Err(format!("proof is {} bytes, not a whole number of words", bytes.len()).into());
+                }
+                bytes.chunks(32).map(U256::from_big_endian).collect()
+            }
+            _ => return Err("proof must be a list of words or a hex string".into()),
+        };
+        if proof.is_empty() {
+            return Err("proof is empty".into());
+        }
+        Ok(Self {
+            riscv_vkey: H256::from_slice(&vkey),
+            public_values: parse_hex(&raw.public_values)?.into(),
+            proof,
+        })
+    }
+
+    /// The proof words concatenated, as the `bytes proof` argument of `verifyPicoProof`.
+    pub fn proof_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; self.proof.len() * 32];
+        for (word, chunk) in self.proof.iter().zip(bytes.chunks_mut(32)) {
+            word.to_big_endian(chunk);
+        }
+        bytes
+    }
+
+    /// `sha256(publicValues)` with the top three bits cleared so it fits the scalar field,
+    /// matching the verifier's `hashPublicValues`.
+    pub fn public_values_digest(&self) -> H256 {
+        let mut digest: [u8; 32] = Sha256::digest(&self.public_values).into();
+        digest[0] &= 0x1f;
+        H256(digest)
+    }
+
+    /// The verifier's public inputs: the program's vkey hash and the digest of its committed values.
+    pub fn public_inputs(&self) -> [H256; 2] {
+        [self.riscv_vkey, self.public_values_digest()]
+    }
+
+    /// The ABI-encoded `(bytes proof, bytes publicValues, bytes32 riscvVkey)` arguments.
+    pub fn encode_arguments(&self) -> Vec<u8> {
+        abi::encode(&[
+            Token::Bytes(self.proof_bytes()),
+            Token::Bytes(self.public_values.to_vec()),
+            Token::FixedBytes(self.riscv_vkey.as_bytes().to_vec()),
+        ])
+    }
+
+    /// Calldata for `verifyPicoProof(bytes proof, bytes publicValues, bytes32 riscvVkey)`.
+    pub fn verifier_calldata(&self) -> Vec<u8> {
+        let mut calldata = ethers::utils::id(VERIFY_PICO_PROOF).to_vec();
+        calldata.extend(self.encode_arguments());
+        calldata
+    }
+
+    /// Calldata for the application contract's `verifyPicoProof(bytes proof, bytes publicValues)`.
+    pub fn app_calldata(&self) -> Vec<u8> {
+        let mut calldata = ethers::utils::id(APP_VERIFY_PICO_PROOF).to_vec();
+        calldata.extend(abi::encode(&[Token::Bytes(self.proof_bytes()), Token::Bytes(self.public_values.to_vec())]));
+        calldata
+    }
+
+    /// A Solidity declaration binding the three arguments, for pasting into tests.
+    pub fn solidity_literal(&self) -> String {
+        format!(
+            "(bytes memory proof, bytes memory publicValues, bytes32 riscvVkey) = (\n    hex\"{}\",\n    hex\"{}\",\n    bytes32({:?})\n);",
+            hex::encode(self.proof_bytes()),
+            hex::encode(&self.public_values),
+            self.riscv_vkey
+        )
+    }
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let text = text.trim();
+    Ok(hex::decode(text.strip_prefix("0x").unwrap_or(text))?)
+}
+
+/// A word given as 0x-prefixed hex or as a decimal string.
+fn parse_word(text: &str) -> Result<U256, Box<dyn std::error::Error>> {
+    let text = text.trim();
+    match text.strip_prefix("0x") {
+        Some(hex) => Ok(U256::from_str_radix(hex, 16)?),
+        None => Ok(U256::from_dec_str(text)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::ParamType;
+
+    fn fixture_proof() -> PicoEvmProof {
+        PicoEvmProof::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/groth16_proof.json")).unwrap()
+    }
+
+    #[test]
+    fn verifier_calldata_encodes_the_three_arguments() {
+        let proof = fixture_proof();
+        let calldata = proof.verifier_calldata();
+        assert_eq!(hex::encode(&calldata[..4]), "780af0ac");
+
+        // Head: offsets of the two byte strings, then the vkey inline.
+        let word = |index: usize| U256::from_big_endian(&calldata[4 + 32 * index..4 + 32 * (index + 1)]);
+        assert_eq!(word(0), U256::from(0x60));
+        assert_eq!(word(1), U256::from(0x60 + 32 + 256));
+        assert_eq!(calldata[4 + 64..4 + 96], proof.riscv_vkey.0);
+        assert_eq!(word(3), U256::from(256));
+        assert_eq!(word(4), proof.proof[0]);
+        // 20 bytes of public values pad to one word.
+        assert_eq!(calldata.len(), 4 + 32 * 3 + (32 + 256) + (32 + 32));
+
+        let tokens = abi::decode(&[ParamType::Bytes, ParamType::Bytes, ParamType::FixedBytes(32)], &calldata[4..]).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::Bytes(proof.proof_bytes()),
+                Token::Bytes(proof.public_values.to_vec()),
+                Token::FixedBytes(proof.riscv_vkey.as_bytes().to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn app_calldata_leaves_out_the_vkey() {
+        let proof = fixture_proof();
+        let calldata = proof.app_calldata();
+        assert_eq!(hex::encode(&calldata[..4]), "2e450cda");
+        let tokens = abi::decode(&[ParamType::Bytes, ParamType::Bytes], &calldata[4..]).unwrap();
+        assert_eq!(tokens, [Token::Bytes(proof.proof_bytes()), Token::Bytes(proof.public_values.to_vec())]);
+    }
+
+    #[test]
+    fn public_values_digest_fits_the_scalar_field() {
+        let proof = fixture_proof();
+        let digest = proof.public_values_digest();
+        let full: [u8; 32] = Sha256::digest(&proof.public_values).into();
+        assert_eq!(digest.0[0], full[0] & 0x1f);
+        assert_eq!(digest.0[1..], full[1..]);
+        assert_eq!(proof.public_inputs(), [proof.riscv_vkey, digest]);
+    }
+
+    #[test]
+    fn entry_point_matches_either_overload() {
+        let abi = |inputs: &str| -> Abi {
+            serde_json::from_str(&format!(r#"[{{"type":"function","name":"verifyPicoProof","stateMutability":"view","inputs":[{}],"outputs":[]}}]"#, inputs)).unwrap()
+        };
+        let bytes = r#"{"name":"proof","type":"bytes"},{"name":"publicValues","type":"bytes"}"#;
+        assert_eq!(entry_point(&abi(&format!(r#"{},{{"name":"riscvVkey","type":"bytes32"}}"#, bytes))), Some(VERIFY_PICO_PROOF));
+        assert_eq!(entry_point(&abi(bytes)), Some(APP_VERIFY_PICO_PROOF));
+        assert_eq!(entry_point(&abi(r#"{"name":"proof","type":"bytes"}"#)), None);
+        assert_eq!(entry_point(&Abi::default()), None);
+    }
+
+    #[test]
+    fn proofs_load_from_words_or_one_hex_string() {
+        let proof = fixture_proof();
+        let json = format!(
+            r#"{{"vkey":"{:?}","public_values":"{}","proof":"0x{}"}}"#,
+            proof.riscv_vkey,
+            hex::encode(&proof.public_values),
+            hex::encode(proof.proof_bytes())
+        );
+        assert_eq!(PicoEvmProof::from_json(&json).unwrap(), proof);
+        assert!(PicoEvmProof::from_json(r#"{"vkey":"0x01","publicValues":"0x","proof":["0x1"]}"#).is_err());
+    }
+}
@@ -0,0 +1,489 @@
+// Public-values schemas
+// Describes the values a guest commits so they can be ABI-encoded in the guest and decoded into a struct on-chain
+
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A Solidity type a public value can have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiType {
+    Uint(u16),
+    Int(u16),
+    Bool,
+    Address,
+    FixedBytes(u8),
+    Bytes,
+    String,
+    /// A dynamic array; elements must be static types.
+    Array(Box<AbiType>),
+}
+
+impl AbiType {
+    pub fn is_dynamic(&self) -> bool {
+        matches!(self, AbiType::Bytes | AbiType::String | AbiType::Array(_))
+    }
+
+    /// The Rust type of the field in the generated encoding helper.
+    pub fn rust_type(&self) -> String {
+        match self {
+            AbiType::Uint(bits) if *bits <= 128 => format!("u{}", rust_int_bits(*bits)),
+            AbiType::Int(bits) if *bits <= 128 => format!("i{}", rust_int_bits(*bits)),
+            // Wider integers are given as 32 big-endian (two's complement) bytes
+            AbiType::Uint(_) | AbiType::Int(_) => "[u8; 32]".to_string(),
+            AbiType::Bool => "bool".to_string(),
+            AbiType::Address => "[u8; 20]".to_string(),
+            AbiType::FixedBytes(size) => format!("[u8; {}]", size),
+            AbiType::Bytes => "Vec<u8>".to_string(),
+            AbiType::String => "String".to_string(),
+            AbiType::Array(element) => format!("Vec<{}>", element.rust_type()),
+        }
+    }
+
+    /// Rust expression for the 32-byte word of a static value held in `place`.
+    fn rust_word(&self, place: &str) -> String {
+        match self {
+            AbiType::Uint(bits) if *bits <= 128 => format!("abi::uint({} as u128)", place),
+            AbiType::Int(bits) if *bits <= 128 => format!("abi::int({} as i128)", place),
+            AbiType::Uint(_) | AbiType::Int(_) => place.to_string(),
+            AbiType::Bool => format!("abi::uint({} as u128)", place),
+            AbiType::Address => format!("abi::left(&{})", place),
+            AbiType::FixedBytes(_) => format!("abi::right(&{})", place),
+            AbiType::Bytes | AbiType::String | AbiType::Array(_) => unreachable!("dynamic types have no single word"),
+        }
+    }
+}
+
+/// Smallest Rust integer width holding a Solidity integer of `bits`.
+fn rust_int_bits(bits: u16) -> u16 {
+    [8, 16, 32, 64, 128].into_iter().find(|&width| bits <= width).unwrap_or(128)
+}
+
+impl FromStr for AbiType {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if let Some(element) = text.strip_suffix("[]") {
+            let element: AbiType = element.parse()?;
+            if element.is_dynamic() {
+                return Err(format!("{}: array elements must be static types", text));
+            }
+            return Ok(AbiType::Array(Box::new(element)));
+        }
+        let sized = |prefix: &str, max: u16, step: u16| -> Option<Result<u16, String>> {
+            let size = text.strip_prefix(prefix)?;
+            if size.is_empty() {
+                return Some(Ok(max));
+            }
+            Some(match size.parse::<u16>() {
+                Ok(size) if size > 0 && size <= max && size % step == 0 => Ok(size),
+                _ => Err(format!("invalid type {}", text)),
+            })
+        };
+        match text {
+            "bool" => Ok(AbiType::Bool),
+            "address" => Ok(AbiType::Address),
+            "bytes" => Ok(AbiType::Bytes),
+            "string" => Ok(AbiType::String),
+            _ => {
+                if let Some(bits) = sized("uint", 256, 8) {
+                    Ok(AbiType::Uint(bits?))
+                } else if let Some(bits) = sized("int", 256, 8) {
+                    Ok(AbiType::Int(bits?))
+                } else if let Some(size) = text.strip_prefix("bytes") {
+                    match size.parse::<u8>() {
+                        Ok(size) if (1..=32).contains(&size) => Ok(AbiType::FixedBytes(size)),
+                        _ => Err(format!("invalid type {}", text)),
+                    }
+                } else {
+                    Err(format!("unsupported type {} (use uintN, intN, bool, address, bytesN, bytes, string or T[])", text))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for AbiType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbiType::Uint(bits) => write!(f, "uint{}", bits),
+            AbiType::Int(bits) => write!(f, "int{}", bits),
+            AbiType::Bool => write!(f, "bool"),
+            AbiType::Address => write!(f, "address"),
+            AbiType::FixedBytes(size) => write!(f, "bytes{}", size),
+            AbiType::Bytes => write!(f, "bytes"),
+            AbiType::String => write!(f, "string"),
+            AbiType::Array(element) => write!(f, "{}[]", element),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaField {
+    pub name: String,
+    pub ty: AbiType,
+}
+
+/// The committed public values of a guest, in commit order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicValuesSchema {
+    pub name: String,
+    pub fields: Vec<SchemaField>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlSchema {
+    name: String,
+    fields: Vec<TomlField>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+impl PublicValuesSchema {
+    /// Loads a `.toml` schema, or derives one from a struct in a `.rs` file.
+    /// `struct_name` picks the struct when the Rust file defines several.
+    pub fn load(path: &Path, struct_name: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("failed to read schema {}: {}", path.display(), e))?;
+        let schema = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("rs") => Self::from_rust(&text, struct_name),
+            _ => return Err(format!("{}: schema must be a .toml file or a Rust struct in a .rs file", path.display()).into()),
+        };
+        schema.map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let raw: TomlSchema = toml::from_str(text)?;
+        let fields = raw
+            .fields
+            .into_iter()
+            .map(|field| Ok(SchemaField { ty: field.ty.parse().map_err(|e| format!("field {}: {}", field.name, e))?, name: field.name }))
+            .collect::<Result<Vec<_>, String>>()?;
+        Self::new(raw.name, fields)
+    }
+
+    /// Derives a schema from a struct with named fields. Field types map as the generated helper
+    /// would declare them: `u64` is `uint64`, `Vec<u8>` is `bytes`, `[u8; 32]` is `bytes32`, and so on.
+    pub fn from_rust(source: &str, struct_name: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = syn::parse_file(source)?;
+        let structs: Vec<&syn::ItemStruct> = file
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Struct(item) => Some(item),
+                _ => None,
+            })
+            .collect();
+        let names: Vec<String> = structs.iter().map(|item| item.ident.to_string()).collect();
+        let item = match struct_name {
+            Some(name) => structs.into_iter().find(|item| item.ident == name).ok_or_else(|| format!("no struct {} (found: {})", name, names.join(", ")))?,
+            None if structs.len() == 1 => structs[0],
+            None if structs.is_empty() => return Err("no struct found".into()),
+            None => return Err(format!("several structs ({}); pick one by name", names.join(", ")).into()),
+        };
+        let syn::Fields::Named(named) = &item.fields else {
+            return Err(format!("struct {} must have named fields", item.ident).into());
+        };
+        let fields = named
+            .named
+            .iter()
+            .map(|field| {
+                let name = field.ident.as_ref().map(|ident| ident.to_string()).unwrap_or_default();
+                let ty = abi_type_of(&field.ty).map_err(|e| format!("field {}: {}", name, e))?;
+                Ok(SchemaField { name, ty })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Self::new(item.ident.to_string(), fields)
+    }
+
+    pub fn new(name: String, fields: Vec<SchemaField>) -> Result<Self, Box<dyn std::error::Error>> {
+        if !is_identifier(&name) {
+            return Err(format!("invalid schema name {:?}", name).into());
+        }
+        if fields.is_empty() {
+            return Err(format!("schema {} has no fields", name).into());
+        }
+        for (index, field) in fields.iter().enumerate() {
+            if !is_identifier(&field.name) {
+                return Err(format!("invalid field name {:?}", field.name).into());
+            }
+            if fields[..index].iter().any(|other| other.name == field.name) {
+                return Err(format!("field {} is declared twice", field.name).into());
+            }
+        }
+        Ok(Self { name, fields })
+    }
+
+    /// Name of the Solidity library holding `decode`.
+    pub fn library_name(&self) -> String {
+        format!("{}Lib", self.name)
+    }
+
+    /// A standalone Solidity file with the struct and its decoder library.
+    pub fn to_solidity(&self) -> String {
+        format!("// SPDX-License-Identifier: MIT\npragma solidity ^0.8.20;\n\n{}", self.solidity_definitions())
+    }
+
+    /// The struct and decoder library, for embedding in a contract file.
+    pub fn solidity_definitions(&self) -> String {
+        let members: String = self.fields.iter().map(|field| format!("    {} {};\n", field.ty, field.name)).collect();
+        format!(
+            r#"/// Public values committed by the guest.
+struct {name} {{
+{members}}}
+
+library {library} {{
+    /// Decodes public values the guest committed as `abi.encode(values)`.
+    function decode(bytes memory publicValues) internal pure returns ({name} memory values) {{
+        values = abi.decode(publicValues, ({name}));
+    }}
+}}
+"#,
+            name = self.name,
+            library = self.library_name(),
+            members = members,
+        )
+    }
+
+    /// A dependency-free Rust module with the struct and its ABI encoder, for use in the guest.
+    pub fn to_rust(&self) -> String {
+        let head_size = 32 * self.fields.len();
+        // A struct with dynamic members is itself dynamic, so its encoding starts with an offset
+        let (start, finish) = if self.fields.iter().any(|field| field.ty.is_dynamic()) {
+            (
+                "        let mut tail: Vec<u8> = Vec::new();\n",
+                "        let mut encoded = abi::uint(32).to_vec();\n        encoded.extend_from_slice(&head);\n        encoded.extend_from_slice(&tail);\n        encoded\n",
+            )
+        } else {
+            ("", "        head\n")
+        };
+        let members: String = self.fields.iter().map(|field| format!("    pub {}: {},\n", field.name, field.ty.rust_type())).collect();
+        let mut body = String::new();
+        for field in &self.fields {
+            let place = format!("self.{}", field.name);
+            match &field.ty {
+                AbiType::Bytes => {
+                    body.push_str(&format!("        head.extend_from_slice(&abi::uint(({} + tail.len()) as u128));\n", head_size));
+                    body.push_str(&format!("        abi::bytes(&mut tail, &{});\n", place));
+                }
+                AbiType::String => {
+                    body.push_str(&format!("        head.extend_from_slice(&abi::uint(({} + tail.len()) as u128));\n", head_size));
+                    body.push_str(&format!("        abi::bytes(&mut tail, {}.as_bytes());\n", place));
+                }
+                AbiType::Array(element) => {
+                    body.push_str(&format!("        head.extend_from_slice(&abi::uint(({} + tail.len()) as u128));\n", head_size));
+                    body.push_str(&format!("        tail.extend_from_slice(&abi::uint({}.len() as u128));\n", place));
+                    body.push_str(&format!("        for item in &{} {{\n", place));
+                    body.push_str(&format!("            tail.extend_from_slice(&{});\n", element.rust_word("*item")));
+                    body.push_str("        }\n");
+                }
+                ty => body.push_str(&format!("        head.extend_from_slice(&{});\n", ty.rust_word(&place))),
+            }
+        }
+        format!(
+            r#"// Generated by `pico-evm schema`; encodes the public values of {name}.
+// Commit them in the guest with `pico_sdk::io::commit_bytes(&values.abi_encode())`,
+// and decode them on-chain with `{library}.decode(publicValues)`.
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct {name} {{
+{members}}}
+
+impl {name} {{
+    /// The struct encoded as Solidity's `abi.encode(values)`.
+    pub fn abi_encode(&self) -> Vec<u8> {{
+        let mut head = Vec::with_capacity({head_size});
+{start}{body}{finish}    }}
+}}
+
+#[allow(dead_code)]
+mod abi {{
+    pub fn uint(value: u128) -> [u8; 32] {{
+        let mut word = [0u8; 32];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        word
+    }}
+
+    pub fn int(value: i128) -> [u8; 32] {{
+        let mut word = if value < 0 {{ [0xffu8; 32] }} else {{ [0u8; 32] }};
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        word
+    }}
+
+    /// Right-aligned, as for `address`.
+    pub fn left(bytes: &[u8]) -> [u8; 32] {{
+        let mut word = [0u8; 32];
+        word[32 - bytes.len()..].copy_from_slice(bytes);
+        word
+    }}
+
+    /// Left-aligned, as for `bytesN`.
+    pub fn right(bytes: &[u8]) -> [u8; 32] {{
+        let mut word = [0u8; 32];
+        word[..bytes.len()].copy_from_slice(bytes);
+        word
+    }}
+
+    /// Length word followed by the data, zero-padded to whole words.
+    pub fn bytes(tail: &mut Vec<u8>, data: &[u8]) {{
+        tail.extend_from_slice(&uint(data.len() as u128));
+        tail.extend_from_slice(data);
+        tail.resize(tail.len() + (32 - data.len() % 32) % 32, 0);
+    }}
+}}
+"#,
+            name = self.name,
+            library = self.library_name(),
+            members = members,
+            head_size = head_size,
+            start = start,
+            body = body,
+            finish = finish,
+        )
+    }
+}
+
+/// Maps a Rust field type to the Solidity type the generated helper would declare it as.
+fn abi_type_of(ty: &syn::Type) -> Result<AbiType, String> {
+    let unsupported = || "unsupported type (use u8..u128, i8..i128, bool, String, Vec<u8>, [u8; N] or Vec<T>)".to_string();
+    match ty {
+        syn::Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last().ok_or_else(unsupported)?;
+            match segment.ident.to_string().as_str() {
+                "bool" => Ok(AbiType::Bool),
+                "String" => Ok(AbiType::String),
+                "u8" | "u16" | "u32" | "u64" | "u128" => Ok(AbiType::Uint(segment.ident.to_string()[1..].parse().map_err(|_| unsupported())?)),
+                "i8" | "i16" | "i32" | "i64" | "i128" => Ok(AbiType::Int(segment.ident.to_string()[1..].parse().map_err(|_| unsupported())?)),
+                "Vec" => {
+                    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+                        return Err(unsupported());
+                    };
+                    let Some(syn::GenericArgument::Type(element)) = args.args.first() else {
+                        return Err(unsupported());
+                    };
+                    match abi_type_of(element)? {
+                        AbiType::Uint(8) => Ok(AbiType::Bytes),
+                        element if element.is_dynamic() => Err("array elements must be static types".to_string()),
+                        element => Ok(AbiType::Array(Box::new(element))),
+                    }
+                }
+                _ => Err(unsupported()),
+            }
+        }
+        syn::Type::Array(array) => {
+            let is_u8 = matches!(abi_type_of(&array.elem), Ok(AbiType::Uint(8)));
+            let size = match &array.len {
+                syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(size), .. }) => size.base10_parse::<u8>().ok(),
+                _ => None,
+            };
+            match size {
+                Some(size) if is_u8 && (1..=32).contains(&size) => Ok(AbiType::FixedBytes(size)),
+                _ => Err(unsupported()),
+            }
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::{self, Token};
+    use ethers::types::{H160, I256, U256};
+
+    /// The encoder `to_rust` generates for `tests/fixtures/schema.toml`, compiled into the tests.
+    mod generated {
+        include!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/schema_values.rs"));
+    }
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    #[test]
+    fn generated_encoder_is_up_to_date() {
+        let schema = PublicValuesSchema::load(&fixture("schema.toml"), None).unwrap();
+        let checked_in = std::fs::read_to_string(fixture("schema_values.rs")).unwrap();
+        assert!(checked_in == schema.to_rust(), "regenerate tests/fixtures/schema_values.rs from schema.toml");
+    }
+
+    #[test]
+    fn dynamic_struct_encodes_like_abi_encode() {
+        let mut total = [0u8; 32];
+        total[0] = 0x80;
+        total[31] = 7;
+        let values = generated::GameResult {
+            round: u32::MAX,
+            delta: -5,
+            total,
+            won: true,
+            player: [0x11; 20],
+            tag: *b"pico",
+            proof: (0..33).collect(),
+            label: "head-to-head".to_string(),
+            scores: vec![3, -1, i16::MIN],
+        };
+        let expected = abi::encode(&[Token::Tuple(vec![
+            Token::Uint(u32::MAX.into()),
+            Token::Int(I256::from(-5).into_raw()),
+            Token::Uint(U256::from_big_endian(&total)),
+            Token::Bool(true),
+            Token::Address(H160([0x11; 20])),
+            Token::FixedBytes(b"pico".to_vec()),
+            Token::Bytes((0..33).collect()),
+            Token::String("head-to-head".to_string()),
+            Token::Array([3, -1, i16::MIN].into_iter().map(|score| Token::Int(I256::from(score).into_raw())).collect()),
+        ])]);
+        assert_eq!(values.abi_encode(), expected);
+
+        let empty = generated::GameResult::default();
+        let expected = abi::encode(&[Token::Tuple(vec![
+            Token::Uint(0.into()),
+            Token::Int(0.into()),
+            Token::Uint(0.into()),
+            Token::Bool(false),
+            Token::Address(H160::zero()),
+            Token::FixedBytes(vec![0; 4]),
+            Token::Bytes(Vec::new()),
+            Token::String(String::new()),
+            Token::Array(Vec::new()),
+        ])]);
+        assert_eq!(empty.abi_encode(), expected);
+    }
+
+    #[test]
+    fn static_structs_have_no_offset() {
+        let schema = PublicValuesSchema::from_toml("name = \"Fib\"\nfields = [{ name = \"n\", type = \"uint32\" }, { name = \"result\", type = \"uint64\" }]").unwrap();
+        let rust = schema.to_rust();
+        assert!(!rust.contains("let mut tail"));
+        assert!(rust.contains("        head\n    }"));
+    }
+
+    #[test]
+    fn rust_structs_map_to_abi_types() {
+        let schema = PublicValuesSchema::from_rust(
+            "struct Other { x: u8 }\nstruct Out { n: u64, neg: i32, ok: bool, id: [u8; 32], blob: Vec<u8>, name: String, xs: Vec<u16> }",
+            Some("Out"),
+        )
+        .unwrap();
+        let types: Vec<String> = schema.fields.iter().map(|field| field.ty.to_string()).collect();
+        assert_eq!(types, ["uint64", "int32", "bool", "bytes32", "bytes", "string", "uint16[]"]);
+        assert!(PublicValuesSchema::from_rust("struct A { x: u8 }\nstruct B { y: u8 }", None).is_err());
+        assert!("string[]".parse::<AbiType>().is_err());
+        assert!("uint7".parse::<AbiType>().is_err());
+    }
+}
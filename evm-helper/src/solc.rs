@@ -0,0 +1,103 @@
+// Contract compilation
+// Compiles Solidity sources with a local solc, or reads contracts already compiled by solc, Foundry or Hardhat
+
+use crate::EVMContract;
+use std::path::Path;
+use std::process::Command;
+
+/// Environment variable naming the solc binary; `solc` on the PATH is used otherwise.
+pub const SOLC_ENV: &str = "SOLC";
+
+/// Loads every contract in `path`: `.sol` files are compiled, anything else is read as a JSON artifact.
+pub fn load_contracts(path: &Path) -> Result<Vec<EVMContract>, Box<dyn std::error::Error>> {
+    if path.extension().is_some_and(|ext| ext == "sol") {
+        compile(path)
+    } else {
+        load_artifact(path)
+    }
+}
+
+/// Picks `name` from `contracts`, or the only deployable contract when no name is given.
+/// Libraries with only internal functions (an empty ABI) are not counted as deployable;
+/// a lone contract without bytecode, such as a bare ABI, is still picked.
+pub fn select_contract(contracts: Vec<EVMContract>, name: Option<&str>, source: &Path) -> Result<EVMContract, Box<dyn std::error::Error>> {
+    let available: Vec<String> = contracts.iter().map(|contract| contract.name.clone()).collect();
+    let mut candidates: Vec<EVMContract> = match name {
+        Some(name) => contracts.into_iter().filter(|contract| contract.name == name).collect(),
+        None if contracts.len() == 1 => contracts,
+        None => contracts.into_iter().filter(|contract| !contract.bytecode.is_empty() && contract.abi.trim() != "[]").collect(),
+    };
+    match candidates.len() {
+        1 => Ok(candidates.remove(0)),
+        0 => Err(format!("no contract {}in {} (found: {})", name.map_or(String::new(), |n| format!("{} ", n)), source.display(), available.join(", ")).into()),
+        _ => Err(format!("{} holds several contracts ({}); pick one by name", source.display(), available.join(", ")).into()),
+    }
+}
+
+/// Compiles a Solidity file with solc's combined JSON output, optimiser on.
+pub fn compile(path: &Path) -> Result<Vec<EVMContract>, Box<dyn std::error::Error>> {
+    let solc = std::env::var(SOLC_ENV).unwrap_or_else(|_| "solc".to_string());
+    let output = Command::new(&solc)
+        .args(["--combined-json", "abi,bin", "--optimize", "--optimize-runs", "200"])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("failed to run {} (install solc or set {}): {}", solc, SOLC_ENV, e))?;
+    if !output.status.success() {
+        return Err(format!("solc failed on {}:\n{}", path.display(), String::from_utf8_lossy(&output.stderr)).into());
+    }
+    parse_combined_json(&serde_json::from_slice(&output.stdout)?)
+}
+
+/// Reads a Foundry (`out/X.sol/X.json`), Hardhat (`artifacts/.../X.json`) or solc `--combined-json` artifact,
+/// or a bare ABI array (named after the file, without bytecode).
+pub fn load_artifact(path: &Path) -> Result<Vec<EVMContract>, Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("failed to read artifact {}: {}", path.display(), e))?;
+    let json: serde_json::Value = serde_json::from_str(&text).map_err(|e| format!("{} is not JSON: {}", path.display(), e))?;
+    if json.get("contracts").is_some() {
+        return parse_combined_json(&json);
+    }
+    let abi = match &json {
+        serde_json::Value::Array(_) => &json,
+        _ => json.get("abi").ok_or_else(|| format!("{} has no abi", path.display()))?,
+    };
+    // Foundry nests the bytecode under "object"; Hardhat stores it directly.
+    let bytecode = match &json["bytecode"] {
+        serde_json::Value::Object(object) => object.get("object").and_then(|b| b.as_str()).unwrap_or_default(),
+        serde_json::Value::String(bytecode) => bytecode.as_str(),
+        _ => "",
+    };
+    let name = json["contractName"]
+        .as_str()
+        .map(str::to_string)
+        // `X.json` or `X.abi.json` without a contractName is named X
+        .or_else(|| path.file_name().and_then(|file| file.to_str()).and_then(|file| file.split('.').next()).map(str::to_string))
+        .unwrap_or_default();
+    Ok(vec![contract(name, abi, bytecode)])
+}
+
+fn parse_combined_json(json: &serde_json::Value) -> Result<Vec<EVMContract>, Box<dyn std::error::Error>> {
+    let contracts = json["contracts"].as_object().ok_or("solc output has no contracts")?;
+    let mut result = Vec::new();
+    for (key, entry) in contracts {
+        // Keys are "path/to/File.sol:Name".
+        let name = key.rsplit(':').next().unwrap_or(key).to_string();
+        result.push(contract(name, &entry["abi"], entry["bin"].as_str().unwrap_or_default()));
+    }
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(result)
+}
+
+fn contract(name: String, abi: &serde_json::Value, bytecode: &str) -> EVMContract {
+    // Older solc versions give the ABI as a JSON string rather than an array.
+    let abi = match abi {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    EVMContract {
+        name,
+        address: None,
+        abi,
+        bytecode: bytecode.trim_start_matches("0x").to_string(),
+        constructor_args: Vec::new(),
+    }
+}
@@ -0,0 +1,1245 @@
+// On-chain verifier generation
+// Reads the verification key written by the Pico SDK's EVM prover and emits a Solidity verifier with its constants
+
+use ark_bn254::{Fq, Fq2};
+use ark_ec::short_weierstrass::SWCurveConfig;
+use ark_ff::{BigInteger, Field, PrimeField};
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Base field modulus of BN254.
+pub const P: &str = "21888242871839275222246405745257275088696311157297823662689037894645226208583";
+/// Scalar field modulus of BN254.
+pub const R: &str = "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+
+/// Domain separation tag gnark uses to hash a Groth16 commitment into a public input.
+const GROTH16_COMMITMENT_DST: &str = "bsb22-commitment";
+/// Domain separation tag gnark uses to hash a PLONK BSB22 commitment into a public input.
+const PLONK_COMMITMENT_DST: &str = "BSB22-Plonk";
+
+/// Size of the precomputed KZG pairing lines newer gnark versions append to a PLONK key:
+/// 2 x 2 x 66 line evaluations of two Fp2 elements each.
+const PLONK_KZG_LINES_SIZE: usize = 2 * 2 * 66 * 4 * 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProofSystem {
+    Groth16,
+    Plonk,
+}
+
+impl FromStr for ProofSystem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "groth16" => Ok(ProofSystem::Groth16),
+            "plonk" => Ok(ProofSystem::Plonk),
+            _ => Err(format!("Invalid proof system: {}. Use: groth16, plonk", s)),
+        }
+    }
+}
+
+impl fmt::Display for ProofSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofSystem::Groth16 => write!(f, "groth16"),
+            ProofSystem::Plonk => write!(f, "plonk"),
+        }
+    }
+}
+
+/// Affine G1 point in the coordinates the EVM precompiles take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct G1Point {
+    pub x: U256,
+    pub y: U256,
+}
+
+/// Affine G2 point. Each coordinate is `[imaginary, real]`, the order the pairing precompile expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct G2Point {
+    pub x: [U256; 2],
+    pub y: [U256; 2],
+}
+
+impl G1Point {
+    fn from_fq(x: Fq, y: Fq) -> Self {
+        Self { x: fq_to_u256(&x), y: fq_to_u256(&y) }
+    }
+
+    /// The 64-byte uncompressed encoding gnark hashes into transcripts.
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        self.x.to_big_endian(&mut bytes[..32]);
+        self.y.to_big_endian(&mut bytes[32..]);
+        bytes
+    }
+}
+
+impl G2Point {
+    fn from_fq2(x: Fq2, y: Fq2) -> Self {
+        Self {
+            x: [fq_to_u256(&x.c1), fq_to_u256(&x.c0)],
+            y: [fq_to_u256(&y.c1), fq_to_u256(&y.c0)],
+        }
+    }
+
+    /// The point with its y coordinate negated, so a pairing check can be written as a product equal to one.
+    pub fn negate(&self) -> Self {
+        let p = U256::from_dec_str(P).expect("valid modulus");
+        let neg = |v: U256| if v.is_zero() { v } else { p - v };
+        Self { x: self.x, y: [neg(self.y[0]), neg(self.y[1])] }
+    }
+}
+
+/// Knowledge-proof key of gnark's Pedersen commitment, present when the circuit commits to witness values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PedersenKey {
+    pub g: G2Point,
+    pub g_sigma_neg: G2Point,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Groth16VerifyingKey {
+    pub alpha: G1Point,
+    pub beta: G2Point,
+    pub gamma: G2Point,
+    pub delta: G2Point,
+    /// `K[0]` is the constant term, then one point per public input, then one per commitment hash.
+    pub k: Vec<G1Point>,
+    pub commitment: Option<PedersenKey>,
+    /// 1-based indices of the public inputs hashed together with the commitment.
+    pub public_committed: Vec<u64>,
+}
+
+impl Groth16VerifyingKey {
+    pub fn public_input_count(&self) -> usize {
+        self.k.len() - 1 - usize::from(self.commitment.is_some())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlonkVerifyingKey {
+    pub size: u64,
+    pub size_inv: U256,
+    pub generator: U256,
+    pub nb_public_variables: u64,
+    pub coset_shift: U256,
+    pub s: [G1Point; 3],
+    pub ql: G1Point,
+    pub qr: G1Point,
+    pub qm: G1Point,
+    pub qo: G1Point,
+    pub qk: G1Point,
+    pub qcp: Vec<G1Point>,
+    pub kzg_g1: G1Point,
+    pub kzg_g2: [G2Point; 2],
+    pub commitment_constraint_indexes: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerifyingKey {
+    Groth16(Box<Groth16VerifyingKey>),
+    Plonk(Box<PlonkVerifyingKey>),
+}
+
+impl VerifyingKey {
+    /// Loads a verification key file. gnark's binary encoding (compressed or raw) is read for both
+    /// systems; a snarkjs `verification_key.json` is also accepted for Groth16.
+    pub fn load(path: &Path, system: ProofSystem) -> Result<Self, Box<dyn std::error::Error>> {
+        let bytes = std::fs::read(path).map_err(|e| format!("failed to read verification key {}: {}", path.display(), e))?;
+        Self::from_bytes(&bytes, system).map_err(|e| format!("{} is not a {} verification key: {}", path.display(), system, e).into())
+    }
+
+    pub fn from_bytes(bytes: &[u8], system: ProofSystem) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = bytes.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{');
+        match (system, json) {
+            (ProofSystem::Groth16, true) => Ok(VerifyingKey::Groth16(Box::new(parse_snarkjs_groth16(bytes)?))),
+            (ProofSystem::Groth16, false) => Ok(VerifyingKey::Groth16(Box::new(parse_gnark_groth16(bytes)?))),
+            (ProofSystem::Plonk, false) => Ok(VerifyingKey::Plonk(Box::new(parse_gnark_plonk(bytes)?))),
+            (ProofSystem::Plonk, true) => Err("only gnark's binary PLONK key is supported".into()),
+        }
+    }
+
+    pub fn system(&self) -> ProofSystem {
+        match self {
+            VerifyingKey::Groth16(_) => ProofSystem::Groth16,
+            VerifyingKey::Plonk(_) => ProofSystem::Plonk,
+        }
+    }
+
+    pub fn public_input_count(&self) -> usize {
+        match self {
+            VerifyingKey::Groth16(key) => key.public_input_count(),
+            VerifyingKey::Plonk(key) => key.nb_public_variables as usize,
+        }
+    }
+
+    /// Solidity source of a verifier contract with this key baked in as constants.
+    pub fn to_solidity(&self, contract_name: &str, source: &str) -> String {
+        match self {
+            VerifyingKey::Groth16(key) => groth16_solidity(key, contract_name, source),
+            VerifyingKey::Plonk(key) => plonk_solidity(key, contract_name, source),
+        }
+    }
+}
+
+fn fq_to_u256(value: &Fq) -> U256 {
+    U256::from_big_endian(&value.into_bigint().to_bytes_be())
+}
+
+fn fq_from_bytes(bytes: &[u8]) -> Result<Fq, Box<dyn std::error::Error>> {
+    let value = U256::from_big_endian(bytes);
+    if value >= U256::from_dec_str(P)? {
+        return Err("coordinate is not in the base field".into());
+    }
+    Ok(Fq::from_be_bytes_mod_order(bytes))
+}
+
+fn fq_is_largest(value: &Fq) -> bool {
+    value.into_bigint() > Fq::MODULUS_MINUS_ONE_DIV_TWO
+}
+
+fn fq2_is_largest(value: &Fq2) -> bool {
+    if value.c1 == Fq::from(0u64) {
+        fq_is_largest(&value.c0)
+    } else {
+        fq_is_largest(&value.c1)
+    }
+}
+
+/// gnark's point encoding flags, held in the top two bits of the first byte.
+const FLAG_MASK: u8 = 0b11 << 6;
+const FLAG_COMPRESSED_SMALLEST: u8 = 0b10 << 6;
+const FLAG_COMPRESSED_LARGEST: u8 = 0b11 << 6;
+
+/// Cursor over gnark's big-endian binary encoding.
+struct KeyReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> KeyReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn std::error::Error>> {
+        if self.remaining() < len {
+            return Err(format!("truncated at byte {} (needed {} more)", self.offset, len).into());
+        }
+        let slice = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn scalar(&mut self) -> Result<U256, Box<dyn std::error::Error>> {
+        let value = U256::from_big_endian(self.take(32)?);
+        if value >= U256::from_dec_str(R)? {
+            return Err("scalar is not in the scalar field".into());
+        }
+        Ok(value)
+    }
+
+    fn g1(&mut self) -> Result<G1Point, Box<dyn std::error::Error>> {
+        let flags = *self.bytes.get(self.offset).ok_or("truncated G1 point")? & FLAG_MASK;
+        let (x, y) = if flags == FLAG_COMPRESSED_SMALLEST || flags == FLAG_COMPRESSED_LARGEST {
+            let mut x_bytes = self.take(32)?.to_vec();
+            x_bytes[0] &= !FLAG_MASK;
+            let x = fq_from_bytes(&x_bytes)?;
+            let mut y = (x * x * x + ark_bn254::g1::Config::COEFF_B).sqrt().ok_or("compressed G1 point is not on the curve")?;
+            if fq_is_largest(&y) != (flags == FLAG_COMPRESSED_LARGEST) {
+                y = -y;
+            }
+            (x, y)
+        } else {
+            let bytes = self.take(64)?;
+            if flags != 0 {
+                return Err("G1 point at infinity in verification key".into());
+            }
+            (fq_from_bytes(&bytes[..32])?, fq_from_bytes(&bytes[32..])?)
+        };
+        if !ark_bn254::G1Affine::new_unchecked(x, y).is_on_curve() {
+            return Err("G1 point is not on the curve".into());
+        }
+        Ok(G1Point::from_fq(x, y))
+    }
+
+    fn g2(&mut self) -> Result<G2Point, Box<dyn std::error::Error>> {
+        let flags = *self.bytes.get(self.offset).ok_or("truncated G2 point")? & FLAG_MASK;
+        // Coordinates are written imaginary part first.
+        let read_fq2 = |bytes: &[u8]| -> Result<Fq2, Box<dyn std::error::Error>> {
+            Ok(Fq2::new(fq_from_bytes(&bytes[32..64])?, fq_from_bytes(&bytes[..32])?))
+        };
+        let (x, y) = if flags == FLAG_COMPRESSED_SMALLEST || flags == FLAG_COMPRESSED_LARGEST {
+            let mut x_bytes = self.take(64)?.to_vec();
+            x_bytes[0] &= !FLAG_MASK;
+            let x = read_fq2(&x_bytes)?;
+            let mut y = (x * x * x + ark_bn254::g2::Config::COEFF_B).sqrt().ok_or("compressed G2 point is not on the curve")?;
+            if fq2_is_largest(&y) != (flags == FLAG_COMPRESSED_LARGEST) {
+                y = -y;
+            }
+            (x, y)
+        } else {
+            let bytes = self.take(128)?;
+            if flags != 0 {
+                return Err("G2 point at infinity in verification key".into());
+            }
+            (read_fq2(&bytes[..64])?, read_fq2(&bytes[64..])?)
+        };
+        let point = ark_bn254::G2Affine::new_unchecked(x, y);
+        if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+            return Err("G2 point is not in the prime-order subgroup".into());
+        }
+        Ok(G2Point::from_fq2(x, y))
+    }
+
+    fn g1_slice(&mut self) -> Result<Vec<G1Point>, Box<dyn std::error::Error>> {
+        let len = self.u32()?;
+        (0..len).map(|_| self.g1()).collect()
+    }
+
+    fn u64_slice(&mut self) -> Result<Vec<u64>, Box<dyn std::error::Error>> {
+        let len = self.u32()?;
+        (0..len).map(|_| self.u64()).collect()
+    }
+}
+
+/// gnark `groth16.VerifyingKey` (bn254) as written by `WriteTo` or `WriteRawTo`.
+fn parse_gnark_groth16(bytes: &[u8]) -> Result<Groth16VerifyingKey, Box<dyn std::error::Error>> {
+    let mut reader = KeyReader::new(bytes);
+    let alpha = reader.g1()?;
+    let _beta_g1 = reader.g1()?;
+    let beta = reader.g2()?;
+    let gamma = reader.g2()?;
+    let _delta_g1 = reader.g1()?;
+    let delta = reader.g2()?;
+    let k = reader.g1_slice()?;
+
+    // Keys from gnark older than v0.9 end here.
+    let (mut commitments, mut public_committed) = (Vec::new(), Vec::new());
+    if reader.remaining() > 0 {
+        let committed_sets = reader.u32()?;
+        for _ in 0..committed_sets {
+            public_committed.push(reader.u64_slice()?);
+        }
+        let key_count = reader.u32()?;
+        for _ in 0..key_count {
+            commitments.push(PedersenKey { g: reader.g2()?, g_sigma_neg: reader.g2()? });
+        }
+    }
+    if commitments.len() > 1 {
+        return Err(format!("{} commitments in the key; at most one is supported", commitments.len()).into());
+    }
+    if k.len() < 1 + commitments.len() {
+        return Err("key has no constant term".into());
+    }
+    let public_committed = public_committed.into_iter().next().unwrap_or_default();
+    let public_inputs = k.len() - 1 - commitments.len();
+    // The indices count the constant wire as 0, which is never hashed with the commitment.
+    if let Some(index) = public_committed.iter().find(|&&index| index == 0 || index > public_inputs as u64) {
+        return Err(format!("committed public input {} is outside 1..={}", index, public_inputs).into());
+    }
+    Ok(Groth16VerifyingKey {
+        alpha,
+        beta,
+        gamma,
+        delta,
+        k,
+        commitment: commitments.pop(),
+        public_committed,
+    })
+}
+
+/// snarkjs `verification_key.json` for a Groth16 bn128 circuit.
+fn parse_snarkjs_groth16(bytes: &[u8]) -> Result<Groth16VerifyingKey, Box<dyn std::error::Error>> {
+    let json: serde_json::Value = serde_json::from_slice(bytes)?;
+    if json["protocol"] != "groth16" {
+        return Err(format!("protocol is {}, expected groth16", json["protocol"]).into());
+    }
+    let number = |value: &serde_json::Value| -> Result<Fq, Box<dyn std::error::Error>> {
+        let text = value.as_str().ok_or("coordinate is not a string")?;
+        let value = U256::from_dec_str(text)?;
+        let mut bytes = [0u8; 32];
+        value.to_big_endian(&mut bytes);
+        fq_from_bytes(&bytes)
+    };
+    let g1 = |value: &serde_json::Value| -> Result<G1Point, Box<dyn std::error::Error>> {
+        let (x, y) = (number(&value[0])?, number(&value[1])?);
+        if !ark_bn254::G1Affine::new_unchecked(x, y).is_on_curve() {
+            return Err("G1 point is not on the curve".into());
+        }
+        Ok(G1Point::from_fq(x, y))
+    };
+    let g2 = |value: &serde_json::Value| -> Result<G2Point, Box<dyn std::error::Error>> {
+        let x = Fq2::new(number(&value[0][0])?, number(&value[0][1])?);
+        let y = Fq2::new(number(&value[1][0])?, number(&value[1][1])?);
+        if !ark_bn254::G2Affine::new_unchecked(x, y).is_on_curve() {
+            return Err("G2 point is not on the curve".into());
+        }
+        Ok(G2Point::from_fq2(x, y))
+    };
+    let k = json["IC"].as_array().ok_or("missing IC")?.iter().map(g1).collect::<Result<Vec<_>, _>>()?;
+    if k.is_empty() {
+        return Err("IC is empty".into());
+    }
+    Ok(Groth16VerifyingKey {
+        alpha: g1(&json["vk_alpha_1"])?,
+        beta: g2(&json["vk_beta_2"])?,
+        gamma: g2(&json["vk_gamma_2"])?,
+        delta: g2(&json["vk_delta_2"])?,
+        k,
+        commitment: None,
+        public_committed: Vec::new(),
+    })
+}
+
+/// gnark `plonk.VerifyingKey` (bn254) as written by `WriteTo` or `WriteRawTo`.
+fn parse_gnark_plonk(bytes: &[u8]) -> Result<PlonkVerifyingKey, Box<dyn std::error::Error>> {
+    let mut reader = KeyReader::new(bytes);
+    let size = reader.u64()?;
+    let size_inv = reader.scalar()?;
+    let generator = reader.scalar()?;
+    let nb_public_variables = reader.u64()?;
+    let coset_shift = reader.scalar()?;
+    let s = [reader.g1()?, reader.g1()?, reader.g1()?];
+    let (ql, qr, qm, qo, qk) = (reader.g1()?, reader.g1()?, reader.g1()?, reader.g1()?, reader.g1()?);
+    let qcp = reader.g1_slice()?;
+    let kzg_g1 = reader.g1()?;
+    let kzg_g2 = [reader.g2()?, reader.g2()?];
+
+    // gnark v0.10 and later store precomputed pairing lines here; they are not needed on-chain.
+    let index_bytes = |count: usize| 4 + 8 * count;
+    if reader.remaining() >= PLONK_KZG_LINES_SIZE + index_bytes(0) && reader.remaining() != index_bytes(qcp.len()) {
+        reader.take(PLONK_KZG_LINES_SIZE)?;
+    }
+    let commitment_constraint_indexes = reader.u64_slice()?;
+    if reader.remaining() != 0 {
+        return Err(format!("{} unexpected trailing bytes", reader.remaining()).into());
+    }
+    if commitment_constraint_indexes.len() != qcp.len() {
+        return Err("commitment indexes do not match the custom gate selectors".into());
+    }
+    if !size.is_power_of_two() {
+        return Err(format!("domain size {} is not a power of two", size).into());
+    }
+    Ok(PlonkVerifyingKey {
+        size,
+        size_inv,
+        generator,
+        nb_public_variables,
+        coset_shift,
+        s,
+        ql,
+        qr,
+        qm,
+        qo,
+        qk,
+        qcp,
+        kzg_g1,
+        kzg_g2,
+        commitment_constraint_indexes,
+    })
+}
+
+fn g1_constants(out: &mut String, name: &str, point: &G1Point) {
+    out.push_str(&format!("    uint256 constant {}_X = {};\n", name, point.x));
+    out.push_str(&format!("    uint256 constant {}_Y = {};\n", name, point.y));
+}
+
+fn g2_constants(out: &mut String, name: &str, point: &G2Point) {
+    out.push_str(&format!("    uint256 constant {}_X_0 = {};\n", name, point.x[0]));
+    out.push_str(&format!("    uint256 constant {}_X_1 = {};\n", name, point.x[1]));
+    out.push_str(&format!("    uint256 constant {}_Y_0 = {};\n", name, point.y[0]));
+    out.push_str(&format!("    uint256 constant {}_Y_1 = {};\n", name, point.y[1]));
+}
+
+fn header(out: &mut String, contract_name: &str, description: &str, source: &str) {
+    out.push_str(&format!(
+        r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+/// @title {}
+/// @notice {} verifier over BN254 for Pico EVM proofs.
+/// @dev Generated by pico-evm from {}. The constants are the verification key; regenerate rather than edit.
+contract {} {{
+    /// @notice The proof does not verify against this key.
+    error ProofInvalid();
+    /// @notice A public input is not a canonical element of the scalar field.
+    error PublicInputNotInField();
+
+    uint256 constant P = {};
+    uint256 constant R = {};
+
+"#,
+        contract_name, description, source, contract_name, P, R
+    ));
+}
+
+/// Precompile wrappers shared by both verifiers. Every failure reverts, so a verifier never returns on a bad proof.
+const PRECOMPILE_HELPERS: &str = r#"
+    function ecAdd(uint256[2] memory a, uint256[2] memory b) internal view returns (uint256[2] memory result) {
+        uint256[4] memory input = [a[0], a[1], b[0], b[1]];
+        bool ok;
+        assembly {
+            ok := staticcall(gas(), 0x06, input, 0x80, result, 0x40)
+        }
+        if (!ok) revert ProofInvalid();
+    }
+
+    function ecMul(uint256[2] memory point, uint256 scalar) internal view returns (uint256[2] memory result) {
+        uint256[3] memory input = [point[0], point[1], scalar];
+        bool ok;
+        assembly {
+            ok := staticcall(gas(), 0x07, input, 0x60, result, 0x40)
+        }
+        if (!ok) revert ProofInvalid();
+    }
+
+    function negate(uint256[2] memory point) internal pure returns (uint256[2] memory) {
+        return [point[0], (P - point[1]) % P];
+    }
+
+    function pairing(uint256[] memory input) internal view returns (bool) {
+        uint256[1] memory output;
+        bool ok;
+        assembly {
+            ok := staticcall(gas(), 0x08, add(input, 0x20), mul(mload(input), 0x20), output, 0x20)
+        }
+        return ok && output[0] == 1;
+    }
+
+    /// @dev hash_to_field from RFC 9380 with expand_message_xmd over SHA-256, as gnark hashes commitments.
+    function hashToField(bytes memory message, bytes memory dst) internal pure returns (uint256) {
+        bytes memory dstPrime = abi.encodePacked(dst, uint8(dst.length));
+        bytes32 b0 = sha256(abi.encodePacked(new bytes(64), message, uint16(48), uint8(0), dstPrime));
+        bytes32 b1 = sha256(abi.encodePacked(b0, uint8(1), dstPrime));
+        bytes32 b2 = sha256(abi.encodePacked(b0 ^ b1, uint8(2), dstPrime));
+        // The 48 uniform bytes are b1 followed by the first half of b2, read big-endian.
+        return addmod(mulmod(uint256(b1), 1 << 128, R), uint256(b2) >> 128, R);
+    }
+"#;
+
+/// Pico's wrapped proof has two public inputs: the RISC-V program's vkey hash and the digest of its committed values.
+const PICO_PUBLIC_VALUES_HASH: &str = r#"
+    /// @notice The digest of a program's committed values that Pico uses as its second public input.
+    function hashPublicValues(bytes calldata publicValues) public pure returns (bytes32) {
+        return sha256(publicValues) & bytes32(uint256((1 << 253) - 1));
+    }
+"#;
+
+fn groth16_solidity(key: &Groth16VerifyingKey, contract_name: &str, source: &str) -> String {
+    let inputs = key.public_input_count();
+    let mut out = String::new();
+    header(&mut out, contract_name, "Groth16", source);
+    out.push_str(&format!("    uint256 constant NUM_PUBLIC_INPUTS = {};\n", inputs));
+    // A, B and C, then the commitment and its knowledge proof, as 32-byte words.
+    let proof_words = if key.commitment.is_some() { 12 } else { 8 };
+    out.push_str(&format!("    uint256 constant PROOF_SIZE = {};\n\n", proof_words * 32));
+    out.push_str("    // Verification key. beta, gamma and delta are negated so the check is one product of pairings.\n");
+    g1_constants(&mut out, "ALPHA", &key.alpha);
+    g2_constants(&mut out, "BETA_NEG", &key.beta.negate());
+    g2_constants(&mut out, "GAMMA_NEG", &key.gamma.negate());
+    g2_constants(&mut out, "DELTA_NEG", &key.delta.negate());
+    g1_constants(&mut out, "CONSTANT", &key.k[0]);
+    for (i, point) in key.k[1..].iter().enumerate() {
+        g1_constants(&mut out, &format!("PUB_{}", i), point);
+    }
+    if let Some(commitment) = &key.commitment {
+        out.push_str("\n    // Pedersen commitment knowledge-proof key.\n");
+        g2_constants(&mut out, "PEDERSEN_G", &commitment.g);
+        g2_constants(&mut out, "PEDERSEN_G_SIGMA_NEG", &commitment.g_sigma_neg);
+    }
+
+    let commitment_params = if key.commitment.is_some() { ", uint256[2] calldata commitment, uint256[2] calldata commitmentPok" } else { "" };
+    let commitment_args = if key.commitment.is_some() { "commitment, commitmentPok, " } else { "" };
+    let commitment_memory = if key.commitment.is_some() { "uint256[2] memory commitment, uint256[2] memory commitmentPok, " } else { "" };
+    // Solidity rejects `uint256[0]`, so a key without public inputs takes no input array.
+    let (notice, input_param, copy_inputs) = if inputs > 0 {
+        (
+            "is valid for `input`",
+            format!(", uint256[{}] calldata input", inputs),
+            "\n        for (uint256 i = 0; i < NUM_PUBLIC_INPUTS; i++) {\n            inputs[i] = input[i];\n        }",
+        )
+    } else {
+        ("is valid", String::new(), "")
+    };
+    out.push_str(&format!(
+        r#"
+    /// @notice Reverts unless `proof` {}.
+    /// @param proof A, B and C with B's coordinates imaginary part first.
+    function verifyProof(uint256[8] calldata proof{}{}) public view {{
+        uint256[] memory inputs = new uint256[](NUM_PUBLIC_INPUTS);{}
+        verify(proof, {}inputs);
+    }}
+"#,
+        notice, commitment_params, input_param, copy_inputs, commitment_args
+    ));
+
+    if inputs == 2 {
+        let (decode, call) = if key.commitment.is_some() {
+            (
+                "(uint256[8] memory words, uint256[2] memory commitment, uint256[2] memory commitmentPok) =\n            abi.decode(proof, (uint256[8], uint256[2], uint256[2]));",
+                "verify(words, commitment, commitmentPok, input);",
+            )
+        } else {
+            ("uint256[8] memory words = abi.decode(proof, (uint256[8]));", "verify(words, input);")
+        };
+        out.push_str(&format!(
+            r#"
+    /// @notice Reverts unless `proof` shows the program with `riscvVkey` committed `publicValues`.
+    function verifyPicoProof(bytes calldata proof, bytes calldata publicValues, bytes32 riscvVkey) external view {{
+        if (proof.length != PROOF_SIZE) revert ProofInvalid();
+        {}
+        uint256[] memory input = new uint256[](2);
+        input[0] = uint256(riscvVkey);
+        input[1] = uint256(hashPublicValues(publicValues));
+        {}
+    }}
+"#,
+            decode, call
+        ));
+        out.push_str(PICO_PUBLIC_VALUES_HASH);
+    }
+
+    // vk_x = K[0] + sum(input[i] * K[i + 1]) (+ commitment hash * K[n + 1] + commitment)
+    let mut msm = String::new();
+    for i in 0..inputs {
+        msm.push_str(&format!("        x = ecAdd(x, ecMul([PUB_{}_X, PUB_{}_Y], input[{}]));\n", i, i, i));
+    }
+    let mut commitment_check = String::new();
+    if key.commitment.is_some() {
+        let committed: String = key.public_committed.iter().map(|index| format!(", input[{}]", index - 1)).collect();
+        msm.push_str(&format!(
+            "        uint256 commitmentHash = hashToField(abi.encodePacked(commitment[0], commitment[1]{}), \"{}\");\n",
+            committed, GROTH16_COMMITMENT_DST
+        ));
+        msm.push_str(&format!("        x = ecAdd(x, ecMul([PUB_{}_X, PUB_{}_Y], commitmentHash));\n", inputs, inputs));
+        msm.push_str("        x = ecAdd(x, commitment);\n");
+        commitment_check.push_str(
+            r#"
+        // e(commitment, G) * e(commitmentPok, -sigma * G) == 1
+        uint256[] memory pokInput = new uint256[](12);
+        pokInput[0] = commitment[0];
+        pokInput[1] = commitment[1];
+        pokInput[2] = PEDERSEN_G_X_0;
+        pokInput[3] = PEDERSEN_G_X_1;
+        pokInput[4] = PEDERSEN_G_Y_0;
+        pokInput[5] = PEDERSEN_G_Y_1;
+        pokInput[6] = commitmentPok[0];
+        pokInput[7] = commitmentPok[1];
+        pokInput[8] = PEDERSEN_G_SIGMA_NEG_X_0;
+        pokInput[9] = PEDERSEN_G_SIGMA_NEG_X_1;
+        pokInput[10] = PEDERSEN_G_SIGMA_NEG_Y_0;
+        pokInput[11] = PEDERSEN_G_SIGMA_NEG_Y_1;
+        if (!pairing(pokInput)) revert ProofInvalid();
+"#,
+        );
+    }
+
+    out.push_str(&format!(
+        r#"
+    function verify(uint256[8] memory proof, {}uint256[] memory input) internal view {{
+        for (uint256 i = 0; i < input.length; i++) {{
+            if (input[i] >= R) revert PublicInputNotInField();
+        }}
+        uint256[2] memory x = [CONSTANT_X, CONSTANT_Y];
+{}{}
+        // e(A, B) * e(C, -delta) * e(alpha, -beta) * e(vk_x, -gamma) == 1
+        uint256[] memory pairingInput = new uint256[](24);
+        for (uint256 i = 0; i < 6; i++) {{
+            pairingInput[i] = proof[i];
+        }}
+        pairingInput[6] = proof[6];
+        pairingInput[7] = proof[7];
+        pairingInput[8] = DELTA_NEG_X_0;
+        pairingInput[9] = DELTA_NEG_X_1;
+        pairingInput[10] = DELTA_NEG_Y_0;
+        pairingInput[11] = DELTA_NEG_Y_1;
+        pairingInput[12] = ALPHA_X;
+        pairingInput[13] = ALPHA_Y;
+        pairingInput[14] = BETA_NEG_X_0;
+        pairingInput[15] = BETA_NEG_X_1;
+        pairingInput[16] = BETA_NEG_Y_0;
+        pairingInput[17] = BETA_NEG_Y_1;
+        pairingInput[18] = x[0];
+        pairingInput[19] = x[1];
+        pairingInput[20] = GAMMA_NEG_X_0;
+        pairingInput[21] = GAMMA_NEG_X_1;
+        pairingInput[22] = GAMMA_NEG_Y_0;
+        pairingInput[23] = GAMMA_NEG_Y_1;
+        if (!pairing(pairingInput)) revert ProofInvalid();
+    }}
+"#,
+        commitment_memory, msm, commitment_check
+    ));
+    out.push_str(PRECOMPILE_HELPERS);
+    out.push_str("}\n");
+    out
+}
+
+fn plonk_solidity(key: &PlonkVerifyingKey, contract_name: &str, source: &str) -> String {
+    let gates = key.qcp.len();
+    let mut out = String::new();
+    header(&mut out, contract_name, "PLONK", source);
+    out.push_str(&format!("    uint256 constant VK_DOMAIN_SIZE = {};\n", key.size));
+    out.push_str(&format!("    uint256 constant VK_INV_DOMAIN_SIZE = {};\n", key.size_inv));
+    out.push_str(&format!("    uint256 constant VK_OMEGA = {};\n", key.generator));
+    out.push_str(&format!("    uint256 constant VK_COSET_SHIFT = {};\n", key.coset_shift));
+    out.push_str(&format!("    uint256 constant NUM_PUBLIC_INPUTS = {};\n", key.nb_public_variables));
+    out.push_str(&format!("    uint256 constant NUM_CUSTOM_GATES = {};\n", gates));
+    out.push_str("    // 24 words of commitments and openings, then one opening and one commitment per custom gate.\n");
+    out.push_str(&format!("    uint256 constant PROOF_SIZE = {};\n\n", (24 + 3 * gates) * 32));
+    for (name, point) in [("VK_S1", &key.s[0]), ("VK_S2", &key.s[1]), ("VK_S3", &key.s[2])] {
+        g1_constants(&mut out, name, point);
+    }
+    for (name, point) in [("VK_QL", &key.ql), ("VK_QR", &key.qr), ("VK_QM", &key.qm), ("VK_QO", &key.qo), ("VK_QK", &key.qk)] {
+        g1_constants(&mut out, name, point);
+    }
+    for (i, point) in key.qcp.iter().enumerate() {
+        g1_constants(&mut out, &format!("VK_QCP_{}", i), point);
+        out.push_str(&format!("    uint256 constant VK_INDEX_COMMIT_API_{} = {};\n", i, key.commitment_constraint_indexes[i]));
+    }
+    g1_constants(&mut out, "KZG_G1", &key.kzg_g1);
+    g2_constants(&mut out, "KZG_G2_0", &key.kzg_g2[0]);
+    g2_constants(&mut out, "KZG_G2_1", &key.kzg_g2[1]);
+
+    // The key's commitments in the order the first challenge binds them.
+    let mut transcript = Vec::new();
+    for point in key.s.iter().chain([&key.ql, &key.qr, &key.qm, &key.qo, &key.qk]).chain(key.qcp.iter()) {
+        transcript.extend_from_slice(&point.to_bytes());
+    }
+    out.push_str(&format!(
+        "\n    /// @dev S1..S3, Ql, Qr, Qm, Qo, Qk and the custom gate selectors, as the first challenge binds them.\n    function vkTranscript() internal pure returns (bytes memory) {{\n        return hex\"{}\";\n    }}\n",
+        hex::encode(transcript)
+    ));
+
+    out.push_str(
+        r#"
+    struct Proof {
+        uint256[2] l;
+        uint256[2] r;
+        uint256[2] o;
+        uint256[2] h0;
+        uint256[2] h1;
+        uint256[2] h2;
+        uint256 lAtZeta;
+        uint256 rAtZeta;
+        uint256 oAtZeta;
+        uint256 s1AtZeta;
+        uint256 s2AtZeta;
+        uint256[2] z;
+        uint256 zAtZetaOmega;
+        uint256[2] batchOpening;
+        uint256[2] zOpening;
+        uint256[] qcpAtZeta;
+        uint256[2][] bsb22;
+    }
+
+    struct Challenges {
+        uint256 gamma;
+        uint256 beta;
+        uint256 alpha;
+        uint256 zeta;
+        uint256 zetaPowerN;
+        uint256 zh;
+        uint256 lagrangeOne;
+        uint256 alphaSquareLagrangeOne;
+        uint256 pi;
+        uint256 linOpening;
+    }
+
+    /// @notice Reverts unless `proof` is valid for `publicInputs`.
+    /// @param proof The proof in gnark's `MarshalSolidity` layout.
+    function verifyProof(bytes calldata proof, uint256[] calldata publicInputs) public view {
+        verify(proof, publicInputs);
+    }
+"#,
+    );
+    if key.nb_public_variables == 2 {
+        out.push_str(
+            r#"
+    /// @notice Reverts unless `proof` shows the program with `riscvVkey` committed `publicValues`.
+    function verifyPicoProof(bytes calldata proof, bytes calldata publicValues, bytes32 riscvVkey) external view {
+        uint256[] memory input = new uint256[](2);
+        input[0] = uint256(riscvVkey);
+        input[1] = uint256(hashPublicValues(publicValues));
+        verify(proof, input);
+    }
+"#,
+        );
+        out.push_str(PICO_PUBLIC_VALUES_HASH);
+    }
+
+    out.push_str(
+        r#"
+    function verify(bytes calldata proof, uint256[] memory publicInputs) internal view {
+        if (proof.length != PROOF_SIZE || publicInputs.length != NUM_PUBLIC_INPUTS) revert ProofInvalid();
+        for (uint256 i = 0; i < publicInputs.length; i++) {
+            if (publicInputs[i] >= R) revert PublicInputNotInField();
+        }
+        Proof memory p = decodeProof(proof);
+        Challenges memory c = deriveChallenges(p, publicInputs);
+        computePublicInputs(p, c, publicInputs);
+        computeLinearisedOpening(p, c);
+        batchVerify(p, c, linearisedDigest(p, c));
+    }
+
+    function readWord(bytes calldata data, uint256 index) internal pure returns (uint256 value) {
+        value = uint256(bytes32(data[index * 32:index * 32 + 32]));
+        if (value >= P) revert ProofInvalid();
+    }
+
+    function readPoint(bytes calldata data, uint256 index) internal pure returns (uint256[2] memory) {
+        return [readWord(data, index), readWord(data, index + 1)];
+    }
+
+    function readScalar(bytes calldata data, uint256 index) internal pure returns (uint256 value) {
+        value = readWord(data, index);
+        if (value >= R) revert ProofInvalid();
+    }
+
+    function decodeProof(bytes calldata proof) internal pure returns (Proof memory p) {
+        p.l = readPoint(proof, 0);
+        p.r = readPoint(proof, 2);
+        p.o = readPoint(proof, 4);
+        p.h0 = readPoint(proof, 6);
+        p.h1 = readPoint(proof, 8);
+        p.h2 = readPoint(proof, 10);
+        p.lAtZeta = readScalar(proof, 12);
+        p.rAtZeta = readScalar(proof, 13);
+        p.oAtZeta = readScalar(proof, 14);
+        p.s1AtZeta = readScalar(proof, 15);
+        p.s2AtZeta = readScalar(proof, 16);
+        p.z = readPoint(proof, 17);
+        p.zAtZetaOmega = readScalar(proof, 19);
+        p.batchOpening = readPoint(proof, 20);
+        p.zOpening = readPoint(proof, 22);
+        p.qcpAtZeta = new uint256[](NUM_CUSTOM_GATES);
+        p.bsb22 = new uint256[2][](NUM_CUSTOM_GATES);
+        for (uint256 i = 0; i < NUM_CUSTOM_GATES; i++) {
+            p.qcpAtZeta[i] = readScalar(proof, 24 + i);
+            p.bsb22[i] = readPoint(proof, 24 + NUM_CUSTOM_GATES + 2 * i);
+        }
+    }
+
+    /// @dev gnark's Fiat-Shamir transcript: each challenge hashes its name, the previous unreduced hash and its bindings.
+    function deriveChallenges(Proof memory p, uint256[] memory publicInputs) internal pure returns (Challenges memory c) {
+        bytes32 gammaHash = sha256(abi.encodePacked("gamma", vkTranscript(), publicInputs, p.l, p.r, p.o));
+        bytes32 betaHash = sha256(abi.encodePacked("beta", gammaHash));
+        bytes memory commitments;
+        for (uint256 i = 0; i < NUM_CUSTOM_GATES; i++) {
+            commitments = abi.encodePacked(commitments, p.bsb22[i]);
+        }
+        bytes32 alphaHash = sha256(abi.encodePacked("alpha", betaHash, commitments, p.z));
+        bytes32 zetaHash = sha256(abi.encodePacked("zeta", alphaHash, p.h0, p.h1, p.h2));
+        c.gamma = uint256(gammaHash) % R;
+        c.beta = uint256(betaHash) % R;
+        c.alpha = uint256(alphaHash) % R;
+        c.zeta = uint256(zetaHash) % R;
+    }
+
+    function expmod(uint256 base, uint256 exponent) internal view returns (uint256) {
+        uint256[6] memory input = [uint256(32), 32, 32, base, exponent, R];
+        uint256[1] memory output;
+        bool ok;
+        assembly {
+            ok := staticcall(gas(), 0x05, input, 0xc0, output, 0x20)
+        }
+        if (!ok) revert ProofInvalid();
+        return output[0];
+    }
+
+    /// @dev L_i(zeta) = omega^i / n * (zeta^n - 1) / (zeta - omega^i), given omega^i and (zeta^n - 1) / n.
+    function lagrange(uint256 zeta, uint256 omegaPower, uint256 zhOverN) internal view returns (uint256) {
+        return mulmod(mulmod(omegaPower, zhOverN, R), expmod(addmod(zeta, R - omegaPower, R), R - 2), R);
+    }
+
+"#,
+    );
+    // The proof is only needed here for its BSB22 commitments.
+    out.push_str(&format!(
+        r#"    /// @dev PI(zeta), including the hashed BSB22 commitments at their constraint indexes.
+    function computePublicInputs(Proof memory{}, Challenges memory c, uint256[] memory publicInputs) internal view {{
+"#,
+        if gates > 0 { " p" } else { "" }
+    ));
+    out.push_str(
+        r#"        c.zetaPowerN = expmod(c.zeta, VK_DOMAIN_SIZE);
+        c.zh = addmod(c.zetaPowerN, R - 1, R);
+        if (c.zh == 0) revert ProofInvalid();
+        uint256 zhOverN = mulmod(c.zh, VK_INV_DOMAIN_SIZE, R);
+        c.lagrangeOne = lagrange(c.zeta, 1, zhOverN);
+        uint256 pi = 0;
+        uint256 omegaPower = 1;
+        for (uint256 i = 0; i < publicInputs.length; i++) {
+            pi = addmod(pi, mulmod(publicInputs[i], lagrange(c.zeta, omegaPower, zhOverN), R), R);
+            omegaPower = mulmod(omegaPower, VK_OMEGA, R);
+        }
+"#,
+    );
+    if gates > 0 {
+        let indexes: Vec<String> = (0..gates).map(|i| format!("VK_INDEX_COMMIT_API_{}", i)).collect();
+        out.push_str(&format!(
+            r#"        uint256[{}] memory commitIndexes = [uint256({})];
+        for (uint256 i = 0; i < NUM_CUSTOM_GATES; i++) {{
+            uint256 hashed = hashToField(abi.encodePacked(p.bsb22[i]), "{}");
+            uint256 omegaIndex = expmod(VK_OMEGA, NUM_PUBLIC_INPUTS + commitIndexes[i]);
+            pi = addmod(pi, mulmod(hashed, lagrange(c.zeta, omegaIndex, zhOverN), R), R);
+        }}
+"#,
+            gates,
+            indexes.join(", "),
+            PLONK_COMMITMENT_DST
+        ));
+    }
+    out.push_str(
+        r#"        c.pi = pi;
+    }
+
+    /// @dev The linearised polynomial's opening is not sent; it is -(PI + alpha * (l + beta * s1 + gamma)
+    /// * (r + beta * s2 + gamma) * (o + gamma) * z(omega * zeta) - alpha^2 * L_1(zeta)).
+    function computeLinearisedOpening(Proof memory p, Challenges memory c) internal pure {
+        uint256 s1 = addmod(addmod(mulmod(p.s1AtZeta, c.beta, R), p.lAtZeta, R), c.gamma, R);
+        uint256 s2 = addmod(addmod(mulmod(p.s2AtZeta, c.beta, R), p.rAtZeta, R), c.gamma, R);
+        uint256 t = mulmod(mulmod(s1, s2, R), addmod(p.oAtZeta, c.gamma, R), R);
+        t = mulmod(mulmod(t, c.alpha, R), p.zAtZetaOmega, R);
+        c.alphaSquareLagrangeOne = mulmod(mulmod(c.alpha, c.alpha, R), c.lagrangeOne, R);
+        uint256 constLin = addmod(addmod(c.pi, t, R), R - c.alphaSquareLagrangeOne, R);
+        c.linOpening = (R - constLin) % R;
+    }
+
+    /// @dev [lin] = sum(qcp_i(zeta) * [Bsb22_i]) + l*[Ql] + r*[Qr] + l*r*[Qm] + o*[Qo] + [Qk] + s1'*[S3] + coeffZ*[Z]
+    /// - Z_H(zeta) * ([H0] + zeta^(n+2) * [H1] + zeta^(2(n+2)) * [H2]).
+    function linearisedDigest(Proof memory p, Challenges memory c) internal view returns (uint256[2] memory acc) {
+        // s1' = alpha * (l + beta * s1 + gamma) * (r + beta * s2 + gamma) * beta * z(omega * zeta)
+        uint256 s1 = mulmod(
+            addmod(addmod(mulmod(c.beta, p.s1AtZeta, R), p.lAtZeta, R), c.gamma, R),
+            addmod(addmod(mulmod(c.beta, p.s2AtZeta, R), p.rAtZeta, R), c.gamma, R),
+            R
+        );
+        s1 = mulmod(mulmod(mulmod(s1, c.beta, R), c.alpha, R), p.zAtZetaOmega, R);
+        // coeffZ = alpha^2 * L_1(zeta) - alpha * (l + beta*zeta + gamma) * (r + beta*u*zeta + gamma) * (o + beta*u^2*zeta + gamma)
+        uint256 betaZeta = mulmod(c.beta, c.zeta, R);
+        uint256 s2 = addmod(addmod(betaZeta, p.lAtZeta, R), c.gamma, R);
+        betaZeta = mulmod(betaZeta, VK_COSET_SHIFT, R);
+        s2 = mulmod(s2, addmod(addmod(betaZeta, p.rAtZeta, R), c.gamma, R), R);
+        betaZeta = mulmod(betaZeta, VK_COSET_SHIFT, R);
+        s2 = mulmod(s2, addmod(addmod(betaZeta, p.oAtZeta, R), c.gamma, R), R);
+        uint256 coeffZ = addmod(c.alphaSquareLagrangeOne, (R - mulmod(s2, c.alpha, R)) % R, R);
+
+        acc = [VK_QK_X, VK_QK_Y];
+        for (uint256 i = 0; i < NUM_CUSTOM_GATES; i++) {
+            acc = ecAdd(acc, ecMul(p.bsb22[i], p.qcpAtZeta[i]));
+        }
+        acc = ecAdd(acc, ecMul([VK_QL_X, VK_QL_Y], p.lAtZeta));
+        acc = ecAdd(acc, ecMul([VK_QR_X, VK_QR_Y], p.rAtZeta));
+        acc = ecAdd(acc, ecMul([VK_QM_X, VK_QM_Y], mulmod(p.lAtZeta, p.rAtZeta, R)));
+        acc = ecAdd(acc, ecMul([VK_QO_X, VK_QO_Y], p.oAtZeta));
+        acc = ecAdd(acc, ecMul([VK_S3_X, VK_S3_Y], s1));
+        acc = ecAdd(acc, ecMul(p.z, coeffZ));
+
+        uint256 negZh = R - c.zh;
+        uint256 zetaNPlusTwo = mulmod(c.zetaPowerN, mulmod(c.zeta, c.zeta, R), R);
+        acc = ecAdd(acc, ecMul(p.h0, negZh));
+        negZh = mulmod(negZh, zetaNPlusTwo, R);
+        acc = ecAdd(acc, ecMul(p.h1, negZh));
+        negZh = mulmod(negZh, zetaNPlusTwo, R);
+        acc = ecAdd(acc, ecMul(p.h2, negZh));
+    }
+
+    /// @dev Folds the openings at zeta with gamma_kzg, then checks them together with the opening of z at omega * zeta.
+    function batchVerify(Proof memory p, Challenges memory c, uint256[2] memory linDigest) internal view {
+        (uint256[2] memory digest, uint256 claim) = foldOpenings(p, c, linDigest);
+        checkOpenings(p, c, digest, claim);
+    }
+
+    function foldOpenings(Proof memory p, Challenges memory c, uint256[2] memory linDigest)
+        internal
+        view
+        returns (uint256[2] memory digest, uint256 claim)
+    {
+        uint256[2][] memory digests = new uint256[2][](6 + NUM_CUSTOM_GATES);
+        uint256[] memory claims = new uint256[](6 + NUM_CUSTOM_GATES);
+        digests[0] = linDigest;
+        digests[1] = p.l;
+        digests[2] = p.r;
+        digests[3] = p.o;
+        digests[4] = [VK_S1_X, VK_S1_Y];
+        digests[5] = [VK_S2_X, VK_S2_Y];
+"#,
+    );
+    for i in 0..gates {
+        out.push_str(&format!("        digests[{}] = [VK_QCP_{}_X, VK_QCP_{}_Y];\n", 6 + i, i, i));
+    }
+    out.push_str(
+        r#"        claims[0] = c.linOpening;
+        claims[1] = p.lAtZeta;
+        claims[2] = p.rAtZeta;
+        claims[3] = p.oAtZeta;
+        claims[4] = p.s1AtZeta;
+        claims[5] = p.s2AtZeta;
+        for (uint256 i = 0; i < NUM_CUSTOM_GATES; i++) {
+            claims[6 + i] = p.qcpAtZeta[i];
+        }
+
+        bytes memory transcript = abi.encodePacked("gamma", c.zeta);
+        for (uint256 i = 0; i < digests.length; i++) {
+            transcript = abi.encodePacked(transcript, digests[i]);
+        }
+        uint256 gammaKzg = uint256(sha256(abi.encodePacked(transcript, claims, p.zAtZetaOmega))) % R;
+
+        uint256 power = 1;
+        for (uint256 i = 0; i < digests.length; i++) {
+            digest = ecAdd(digest, ecMul(digests[i], power));
+            claim = addmod(claim, mulmod(claims[i], power, R), R);
+            power = mulmod(power, gammaKzg, R);
+        }
+    }
+
+    function checkOpenings(Proof memory p, Challenges memory c, uint256[2] memory digest, uint256 claim) internal view {
+        // Combine the two opening claims with a challenge bound to both.
+        uint256 lambda = uint256(
+            sha256(abi.encodePacked(c.zeta, digest, claim, p.batchOpening, p.z, p.zAtZetaOmega, p.zOpening))
+        ) % R;
+        uint256[2] memory quotients = ecAdd(p.batchOpening, ecMul(p.zOpening, lambda));
+        uint256[2] memory lhs = ecAdd(digest, ecMul(p.z, lambda));
+        uint256 evaluations = addmod(claim, mulmod(p.zAtZetaOmega, lambda, R), R);
+        lhs = ecAdd(lhs, ecMul([KZG_G1_X, KZG_G1_Y], (R - evaluations) % R));
+        lhs = ecAdd(lhs, ecMul(p.batchOpening, c.zeta));
+        lhs = ecAdd(lhs, ecMul(p.zOpening, mulmod(lambda, mulmod(c.zeta, VK_OMEGA, R), R)));
+        quotients = negate(quotients);
+
+        // e(lhs, [1]) * e(-quotients, [tau]) == 1
+        uint256[] memory pairingInput = new uint256[](12);
+        pairingInput[0] = lhs[0];
+        pairingInput[1] = lhs[1];
+        pairingInput[2] = KZG_G2_0_X_0;
+        pairingInput[3] = KZG_G2_0_X_1;
+        pairingInput[4] = KZG_G2_0_Y_0;
+        pairingInput[5] = KZG_G2_0_Y_1;
+        pairingInput[6] = quotients[0];
+        pairingInput[7] = quotients[1];
+        pairingInput[8] = KZG_G2_1_X_0;
+        pairingInput[9] = KZG_G2_1_X_1;
+        pairingInput[10] = KZG_G2_1_Y_0;
+        pairingInput[11] = KZG_G2_1_Y_1;
+        if (!pairing(pairingInput)) revert ProofInvalid();
+    }
+"#,
+    );
+    out.push_str(PRECOMPILE_HELPERS);
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gas::GasMeter;
+    use crate::proof::PicoEvmProof;
+    use revm::primitives::Address;
+    use ark_bn254::{Bn254, Fr, G1Affine, G2Affine};
+    use ark_ec::pairing::Pairing;
+    use ark_ec::{AffineRepr, CurveGroup};
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    /// `tests/fixtures/groth16_vk.bin`: a compressed gnark Groth16 key with Pico's two public inputs.
+    fn fixture_key() -> Groth16VerifyingKey {
+        match VerifyingKey::load(&fixture("groth16_vk.bin"), ProofSystem::Groth16).unwrap() {
+            VerifyingKey::Groth16(key) => *key,
+            VerifyingKey::Plonk(_) => unreachable!(),
+        }
+    }
+
+    fn fq(word: U256) -> Fq {
+        let mut bytes = [0u8; 32];
+        word.to_big_endian(&mut bytes);
+        Fq::from_be_bytes_mod_order(&bytes)
+    }
+
+    fn g1(x: U256, y: U256) -> G1Affine {
+        G1Affine::new(fq(x), fq(y))
+    }
+
+    fn g2(x: [U256; 2], y: [U256; 2]) -> G2Affine {
+        G2Affine::new(Fq2::new(fq(x[1]), fq(x[0])), Fq2::new(fq(y[1]), fq(y[0])))
+    }
+
+    /// The Groth16 equation e(A, B) = e(alpha, beta) e(vk_x, gamma) e(C, delta), as the generated contract checks it.
+    fn verifies(key: &Groth16VerifyingKey, proof: &PicoEvmProof) -> bool {
+        let mut vk_x = g1(key.k[0].x, key.k[0].y).into_group();
+        for (point, input) in key.k[1..].iter().zip(proof.public_inputs()) {
+            vk_x += g1(point.x, point.y) * Fr::from_be_bytes_mod_order(input.as_bytes());
+        }
+        let words = &proof.proof;
+        let lhs = Bn254::pairing(g1(words[0], words[1]), g2([words[2], words[3]], [words[4], words[5]]));
+        let rhs = Bn254::pairing(g1(key.alpha.x, key.alpha.y), g2(key.beta.x, key.beta.y))
+            + Bn254::pairing(vk_x.into_affine(), g2(key.gamma.x, key.gamma.y))
+            + Bn254::pairing(g1(words[6], words[7]), g2(key.delta.x, key.delta.y));
+        lhs == rhs
+    }
+
+    #[test]
+    fn gnark_groth16_key_verifies_its_proof() {
+        let key = fixture_key();
+        assert_eq!(key.public_input_count(), 2);
+        assert_eq!(key.commitment, None);
+        assert!(g1(key.alpha.x, key.alpha.y).is_on_curve());
+        assert!(g2(key.delta.x, key.delta.y).is_in_correct_subgroup_assuming_on_curve());
+
+        let proof = PicoEvmProof::load(&fixture("groth16_proof.json")).unwrap();
+        assert!(verifies(&key, &proof));
+        let mut tampered = proof.clone();
+        tampered.public_values = [proof.public_values.to_vec(), vec![0]].concat().into();
+        assert!(!verifies(&key, &tampered));
+    }
+
+    /// The fixture key with a Pedersen commitment (reusing beta as both of its
+    /// points) that hashes the public inputs at `committed`.
+    fn key_with_commitment(committed: &[u64]) -> Vec<u8> {
+        let bytes = std::fs::read(fixture("groth16_vk.bin")).unwrap();
+        // Everything up to and including K; the fixture then has no committed sets and no commitment keys
+        let mut key = bytes[..bytes.len() - 8].to_vec();
+        key.extend(1u32.to_be_bytes());
+        key.extend((committed.len() as u32).to_be_bytes());
+        for index in committed {
+            key.extend(index.to_be_bytes());
+        }
+        key.extend(1u32.to_be_bytes());
+        key.extend_from_slice(&bytes[64..128]);
+        key.extend_from_slice(&bytes[64..128]);
+        key
+    }
+
+    #[test]
+    fn committed_public_inputs_are_one_based() {
+        // K holds the constant, one public input and the commitment hash
+        let key = VerifyingKey::from_bytes(&key_with_commitment(&[1]), ProofSystem::Groth16).unwrap();
+        assert_eq!(key.public_input_count(), 1);
+        assert!(key.to_solidity("Committed", "vm_vk").contains("abi.encodePacked(commitment[0], commitment[1], input[0])"));
+        for index in [0, 2] {
+            let error = VerifyingKey::from_bytes(&key_with_commitment(&[index]), ProofSystem::Groth16).unwrap_err();
+            assert_eq!(error.to_string(), format!("committed public input {} is outside 1..=1", index));
+        }
+    }
+
+    /// `tests/fixtures/plonk_vk.bin`: a gnark PLONK key over a domain of 8 with
+    /// Pico's two public inputs and one BSB22 custom gate.
+    #[test]
+    fn gnark_plonk_key_parses_with_or_without_pairing_lines() {
+        let bytes = std::fs::read(fixture("plonk_vk.bin")).unwrap();
+        let VerifyingKey::Plonk(key) = VerifyingKey::from_bytes(&bytes, ProofSystem::Plonk).unwrap() else {
+            panic!("expected a PLONK key");
+        };
+        assert_eq!((key.size, key.nb_public_variables, key.coset_shift), (8, 2, U256::from(5)));
+        let mut size_inv = [0u8; 32];
+        key.size_inv.to_big_endian(&mut size_inv);
+        assert_eq!(Fr::from_be_bytes_mod_order(&size_inv) * Fr::from(8u64), Fr::ONE);
+        assert_eq!(key.qcp.len(), 1);
+        assert_eq!(key.commitment_constraint_indexes, [3]);
+
+        // gnark v0.10 writes the precomputed KZG lines before the commitment indexes
+        let split = bytes.len() - 12;
+        let with_lines = [&bytes[..split], &vec![0; PLONK_KZG_LINES_SIZE], &bytes[split..]].concat();
+        assert_eq!(VerifyingKey::from_bytes(&with_lines, ProofSystem::Plonk).unwrap(), VerifyingKey::Plonk(key.clone()));
+        assert!(VerifyingKey::from_bytes(&bytes[..bytes.len() - 1], ProofSystem::Plonk).is_err());
+
+        let solidity = VerifyingKey::Plonk(key).to_solidity("PicoPlonkVerifier", "vm_vk");
+        assert!(solidity.contains("uint256 constant PROOF_SIZE = 864;"));
+        assert!(solidity.contains("uint256 constant VK_INDEX_COMMIT_API_0 = 3;"));
+        assert!(solidity.contains("function verifyPicoProof(bytes calldata proof, bytes calldata publicValues, bytes32 riscvVkey) external view"));
+    }
+
+    /// Compiles the generated verifier for `key` and deploys it.
+    fn deploy_verifier(meter: &mut GasMeter, key: VerifyingKey, name: &str) -> Address {
+        let path = std::env::temp_dir().join(format!("{}-{}.sol", name, std::process::id()));
+        std::fs::write(&path, key.to_solidity(name, "vm_vk")).unwrap();
+        let contracts = crate::solc::compile(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let verifier = crate::solc::select_contract(contracts, Some(name), &path).unwrap();
+        meter.deploy(name, &hex::decode(&verifier.bytecode).unwrap(), &[]).unwrap().0
+    }
+
+    fn assert_proof_invalid(meter: &mut GasMeter, verifier: Address, proof: &PicoEvmProof) {
+        let error = meter.call("verifyPicoProof", verifier, &proof.verifier_calldata()).unwrap_err().to_string();
+        assert!(error.ends_with("reverted with ProofInvalid()"), "{}", error);
+    }
+
+    #[test]
+    #[ignore = "needs solc on the PATH or in SOLC"]
+    fn generated_verifiers_run_in_the_evm() {
+        let mut meter = GasMeter::new();
+        let proof = PicoEvmProof::load(&fixture("groth16_proof.json")).unwrap();
+
+        let groth16 = deploy_verifier(&mut meter, VerifyingKey::Groth16(Box::new(fixture_key())), "PicoGroth16Verifier");
+        meter.call("verifyPicoProof", groth16, &proof.verifier_calldata()).unwrap();
+        let mut tampered = proof.clone();
+        tampered.proof[0] = proof.proof[6];
+        tampered.proof[1] = proof.proof[7];
+        assert_proof_invalid(&mut meter, groth16, &tampered);
+        let mut tampered = proof.clone();
+        tampered.public_values = [proof.public_values.to_vec(), vec![0]].concat().into();
+        assert_proof_invalid(&mut meter, groth16, &tampered);
+        let mut tampered = proof.clone();
+        tampered.riscv_vkey.0[31] ^= 1;
+        assert_proof_invalid(&mut meter, groth16, &tampered);
+        let mut tampered = proof.clone();
+        tampered.proof.pop();
+        assert_proof_invalid(&mut meter, groth16, &tampered);
+
+        // There is no PLONK proof for the fixture key, so only rejection is checked
+        let key = VerifyingKey::load(&fixture("plonk_vk.bin"), ProofSystem::Plonk).unwrap();
+        let plonk = deploy_verifier(&mut meter, key, "PicoPlonkVerifier");
+        let mut garbage = proof.clone();
+        garbage.proof = vec![U256::one(); 27];
+        assert_proof_invalid(&mut meter, plonk, &garbage);
+        garbage.proof.pop();
+        assert_proof_invalid(&mut meter, plonk, &garbage);
+    }
+
+    #[test]
+    fn truncated_keys_are_rejected() {
+        let bytes = std::fs::read(fixture("groth16_vk.bin")).unwrap();
+        assert!(VerifyingKey::from_bytes(&bytes[..bytes.len() - 1], ProofSystem::Groth16).is_err());
+        assert!(VerifyingKey::from_bytes(&bytes, ProofSystem::Plonk).is_err());
+    }
+
+    #[test]
+    fn pico_entry_point_checks_the_proof_length() {
+        let solidity = VerifyingKey::Groth16(Box::new(fixture_key())).to_solidity("PicoVerifier", "vm_vk");
+        assert!(solidity.contains("uint256 constant PROOF_SIZE = 256;"));
+        let entry = solidity.find("function verifyPicoProof").unwrap();
+        assert!(solidity[entry..].contains("if (proof.length != PROOF_SIZE) revert ProofInvalid();"));
+        assert!(solidity.contains("function verifyProof(uint256[8] calldata proof, uint256[2] calldata input) public view"));
+    }
+
+    #[test]
+    fn keys_without_public_inputs_take_no_input_array() {
+        let mut key = fixture_key();
+        key.k.truncate(1);
+        let solidity = VerifyingKey::Groth16(Box::new(key)).to_solidity("NoInputs", "vm_vk");
+        assert!(!solidity.contains("uint256[0]"));
+        assert!(!solidity.contains("verifyPicoProof"));
+        assert!(solidity.contains("function verifyProof(uint256[8] calldata proof) public view"));
+        assert!(solidity.contains("uint256[] memory inputs = new uint256[](NUM_PUBLIC_INPUTS);\n        verify(proof, inputs);"));
+    }
+}
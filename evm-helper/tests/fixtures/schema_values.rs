@@ -0,0 +1,79 @@
+// Generated by `pico-evm schema`; encodes the public values of GameResult.
+// Commit them in the guest with `pico_sdk::io::commit_bytes(&values.abi_encode())`,
+// and decode them on-chain with `GameResultLib.decode(publicValues)`.
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GameResult {
+    pub round: u32,
+    pub delta: i64,
+    pub total: [u8; 32],
+    pub won: bool,
+    pub player: [u8; 20],
+    pub tag: [u8; 4],
+    pub proof: Vec<u8>,
+    pub label: String,
+    pub scores: Vec<i16>,
+}
+
+impl GameResult {
+    /// The struct encoded as Solidity's `abi.encode(values)`.
+    pub fn abi_encode(&self) -> Vec<u8> {
+        let mut head = Vec::with_capacity(288);
+        let mut tail: Vec<u8> = Vec::new();
+        head.extend_from_slice(&abi::uint(self.round as u128));
+        head.extend_from_slice(&abi::int(self.delta as i128));
+        head.extend_from_slice(&self.total);
+        head.extend_from_slice(&abi::uint(self.won as u128));
+        head.extend_from_slice(&abi::left(&self.player));
+        head.extend_from_slice(&abi::right(&self.tag));
+        head.extend_from_slice(&abi::uint((288 + tail.len()) as u128));
+        abi::bytes(&mut tail, &self.proof);
+        head.extend_from_slice(&abi::uint((288 + tail.len()) as u128));
+        abi::bytes(&mut tail, self.label.as_bytes());
+        head.extend_from_slice(&abi::uint((288 + tail.len()) as u128));
+        tail.extend_from_slice(&abi::uint(self.scores.len() as u128));
+        for item in &self.scores {
+            tail.extend_from_slice(&abi::int(*item as i128));
+        }
+        let mut encoded = abi::uint(32).to_vec();
+        encoded.extend_from_slice(&head);
+        encoded.extend_from_slice(&tail);
+        encoded
+    }
+}
+
+#[allow(dead_code)]
+mod abi {
+    pub fn uint(value: u128) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        word
+    }
+
+    pub fn int(value: i128) -> [u8; 32] {
+        let mut word = if value < 0 { [0xffu8; 32] } else { [0u8; 32] };
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        word
+    }
+
+    /// Right-aligned, as for `address`.
+    pub fn left(bytes: &[u8]) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[32 - bytes.len()..].copy_from_slice(bytes);
+        word
+    }
+
+    /// Left-aligned, as for `bytesN`.
+    pub fn right(bytes: &[u8]) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[..bytes.len()].copy_from_slice(bytes);
+        word
+    }
+
+    /// Length word followed by the data, zero-padded to whole words.
+    pub fn bytes(tail: &mut Vec<u8>, data: &[u8]) {
+        tail.extend_from_slice(&uint(data.len() as u128));
+        tail.extend_from_slice(data);
+        tail.resize(tail.len() + (32 - data.len() % 32) % 32, 0);
+    }
+}